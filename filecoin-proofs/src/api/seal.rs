@@ -507,6 +507,24 @@ pub fn compute_comm_d(sector_size: SectorSize, piece_infos: &[PieceInfo]) -> Res
     pieces::compute_comm_d(sector_size, piece_infos)
 }
 
+/// Computes a sectors's `comm_d` like `compute_comm_d`, caching it for the rest of the process.
+///
+/// # Arguments
+///
+/// * `sector_size` - the number of bytes in the sector.
+/// * `piece_infos` - the piece info (commitment and byte length) for each piece in this sector.
+pub fn compute_comm_d_cached(
+    sector_size: SectorSize,
+    piece_infos: &[PieceInfo],
+) -> Result<Commitment> {
+    pieces::compute_comm_d_cached(sector_size, piece_infos)
+}
+
+/// Drops all `comm_d`s cached by `compute_comm_d_cached`.
+pub fn clear_comm_d_cache() {
+    pieces::clear_comm_d_cache()
+}
+
 /// Verifies the output of some previously-run seal operation.
 ///
 /// # Arguments
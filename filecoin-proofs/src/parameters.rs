@@ -1,4 +1,5 @@
 use anyhow::{ensure, Result};
+use storage_proofs::drgraph::Seed;
 use storage_proofs::porep::stacked::{self, LayerChallenges, StackedDrg};
 use storage_proofs::post::election::{self, ElectionPoSt};
 use storage_proofs::proof::ProofScheme;
@@ -8,10 +9,10 @@ use crate::constants::{
 };
 use crate::types::{PaddedBytesAmount, PoStConfig};
 
-const DRG_SEED: [u8; 28] = [
+const DRG_SEED: Seed = Seed::new([
     0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25,
     26, 27,
-]; // Arbitrary, need a theory for how to vary this over time.
+]); // Arbitrary, need a theory for how to vary this over time.
 
 type PostSetupParams = election::SetupParams;
 pub type PostPublicParams = election::PublicParams;
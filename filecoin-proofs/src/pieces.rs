@@ -1,10 +1,11 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::io::Read;
 use std::io::{self, Cursor};
 use std::iter::Iterator;
 use std::sync::Mutex;
 
 use anyhow::{ensure, Context, Result};
+use blake2b_simd::Params as Blake2bParams;
 use lazy_static::lazy_static;
 use log::info;
 use storage_proofs::hasher::{HashFunction, Hasher};
@@ -29,8 +30,37 @@ pub fn verify_pieces(
     Ok(&comm_d_calculated == comm_d)
 }
 
+/// The number of `comm_d`s kept by `compute_comm_d_cached`. Once full, the oldest one is dropped.
+const COMM_D_CACHE_CAPACITY: usize = 1024;
+
 lazy_static! {
     static ref COMMITMENTS: Mutex<HashMap<SectorSize, Commitment>> = Mutex::new(HashMap::new());
+    static ref COMM_D_CACHE: Mutex<CommDCache> = Mutex::new(CommDCache::default());
+}
+
+/// The `comm_d`s of `compute_comm_d_cached`, by key, with the keys in insertion order.
+#[derive(Debug, Default)]
+struct CommDCache {
+    entries: HashMap<[u8; 32], Commitment>,
+    order: VecDeque<[u8; 32]>,
+}
+
+impl CommDCache {
+    fn insert(&mut self, key: [u8; 32], comm_d: Commitment) {
+        if self.entries.insert(key, comm_d).is_none() {
+            self.order.push_back(key);
+        }
+        while self.order.len() > COMM_D_CACHE_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
 }
 use crate::commitment_reader::CommitmentReader;
 use crate::fr32_reader::Fr32Reader;
@@ -78,8 +108,48 @@ fn empty_comm_d(sector_size: SectorSize) -> Commitment {
     })
 }
 
+/// Like `compute_comm_d`, but remembers the result, so that computing `comm_d` again for the
+/// same sector size and pieces is a lookup. The last `COMM_D_CACHE_CAPACITY` results are kept,
+/// see `clear_comm_d_cache`.
+///
+/// `comm_d` only depends on the sector size and the pieces, which are keyed by a blake2b hash
+/// of their commitments and sizes.
+pub fn compute_comm_d_cached(
+    sector_size: SectorSize,
+    piece_infos: &[PieceInfo],
+) -> Result<Commitment> {
+    let key = comm_d_cache_key(sector_size, piece_infos);
+    if let Some(comm_d) = COMM_D_CACHE.lock().unwrap().entries.get(&key) {
+        return Ok(*comm_d);
+    }
+
+    let comm_d = compute_comm_d(sector_size, piece_infos)?;
+    COMM_D_CACHE.lock().unwrap().insert(key, comm_d);
+
+    Ok(comm_d)
+}
+
+/// Drops all results remembered by `compute_comm_d_cached`.
+pub fn clear_comm_d_cache() {
+    COMM_D_CACHE.lock().unwrap().clear();
+}
+
+fn comm_d_cache_key(sector_size: SectorSize, piece_infos: &[PieceInfo]) -> [u8; 32] {
+    let mut hasher = Blake2bParams::new().hash_length(32).to_state();
+    hasher.update(&u64::from(sector_size).to_le_bytes());
+    for piece_info in piece_infos {
+        hasher.update(&piece_info.commitment);
+        hasher.update(&u64::from(piece_info.size).to_le_bytes());
+    }
+    let mut key = [0u8; 32];
+    key.copy_from_slice(hasher.finalize().as_bytes());
+
+    key
+}
+
 pub fn compute_comm_d(sector_size: SectorSize, piece_infos: &[PieceInfo]) -> Result<Commitment> {
     info!("verifying {} pieces", piece_infos.len());
+
     if piece_infos.is_empty() {
         return Ok(empty_comm_d(sector_size));
     }
@@ -385,6 +455,62 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_compute_comm_d_cached() {
+        let sector_size = SectorSize(512);
+        let pieces = |fill: u8| {
+            vec![
+                PieceInfo::new([fill; 32], UnpaddedBytesAmount(127)).unwrap(),
+                PieceInfo::new([fill + 1; 32], UnpaddedBytesAmount(2 * 127)).unwrap(),
+            ]
+        };
+
+        let expected = compute_comm_d(sector_size, &pieces(1)).unwrap();
+        assert_eq!(
+            compute_comm_d_cached(sector_size, &pieces(1)).unwrap(),
+            expected
+        );
+
+        // A planted entry is returned as is, so the second call must be a lookup.
+        let key = comm_d_cache_key(sector_size, &pieces(1));
+        COMM_D_CACHE.lock().unwrap().insert(key, [0xff; 32]);
+        assert_eq!(
+            compute_comm_d_cached(sector_size, &pieces(1)).unwrap(),
+            [0xff; 32],
+            "identical pieces must hit the cache"
+        );
+
+        let other = compute_comm_d_cached(sector_size, &pieces(3)).unwrap();
+        assert_ne!(other, expected);
+        assert_ne!(key, comm_d_cache_key(sector_size, &pieces(3)));
+    }
+
+    #[test]
+    fn test_comm_d_cache_capacity() {
+        let mut cache = CommDCache::default();
+        let key = |i: usize| {
+            let mut key = [0u8; 32];
+            key[..8].copy_from_slice(&(i as u64).to_le_bytes());
+            key
+        };
+
+        for i in 0..COMM_D_CACHE_CAPACITY + 2 {
+            cache.insert(key(i), [1; 32]);
+        }
+        // Inserting a present key does not change the order.
+        cache.insert(key(2), [2; 32]);
+
+        assert_eq!(cache.entries.len(), COMM_D_CACHE_CAPACITY);
+        assert_eq!(cache.order.len(), COMM_D_CACHE_CAPACITY);
+        assert!(!cache.entries.contains_key(&key(0)));
+        assert!(!cache.entries.contains_key(&key(1)));
+        assert_eq!(cache.entries[&key(2)], [2; 32]);
+
+        cache.clear();
+        assert!(cache.entries.is_empty());
+        assert!(cache.order.is_empty());
+    }
+
     #[test]
     fn test_get_piece_alignment() {
         let table = vec![
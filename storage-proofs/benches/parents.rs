@@ -1,5 +1,7 @@
-use criterion::{black_box, criterion_group, criterion_main, Criterion, ParameterizedBenchmark};
-use storage_proofs::drgraph::{Graph, BASE_DEGREE};
+use criterion::{
+    black_box, criterion_group, criterion_main, Criterion, ParameterizedBenchmark, Throughput,
+};
+use storage_proofs::drgraph::{BucketGraph, Graph, PrecomputedGraph, Seed, BASE_DEGREE};
 use storage_proofs::hasher::blake2s::Blake2sHasher;
 use storage_proofs::hasher::pedersen::PedersenHasher;
 use storage_proofs::hasher::sha256::Sha256Hasher;
@@ -35,10 +37,24 @@ fn stop_profile() {
 fn stop_profile() {}
 
 fn pregenerate_graph<H: Hasher>(size: usize) -> StackedBucketGraph<H> {
-    let seed = [1u8; 28];
+    let seed = Seed::new([1u8; 28]);
     StackedBucketGraph::<H>::new_stacked(size, BASE_DEGREE, EXP_DEGREE, seed).unwrap()
 }
 
+/// Loads the parent table of `graph` into a `PrecomputedGraph`, which serves the parents from
+/// memory.
+fn cache_parents<H: Hasher, G: Graph<H>>(graph: &G) -> PrecomputedGraph<H> {
+    let mut table = Vec::new();
+    graph.write_parent_table(&mut table).unwrap();
+    PrecomputedGraph::from_parent_table(
+        graph.size(),
+        graph.degree(),
+        graph.expansion_degree(),
+        &table[..],
+    )
+    .unwrap()
+}
+
 fn parents_loop<H: Hasher, G: Graph<H>>(graph: &G, parents: &mut [u32]) {
     (0..graph.size())
         .map(|node| graph.parents(node, parents).unwrap())
@@ -74,5 +90,110 @@ fn parents_loop_benchmark(cc: &mut Criterion) {
     );
 }
 
-criterion_group!(benches, parents_loop_benchmark);
+/// Calls `parents` for every node of a graph with `nodes` nodes.
+fn stacked_parents_loop<F>(nodes: usize, parents: &mut [u32], parents_fn: F)
+where
+    F: Fn(usize, &mut [u32]) -> storage_proofs::error::Result<()>,
+{
+    for node in 0..nodes {
+        parents_fn(node, parents).unwrap();
+        black_box(&parents);
+    }
+}
+
+/// Measures the throughput of the parent samplers in nodes per second, separately for the
+/// base and the expansion parents of the stacked graph. The `cold` variants include building
+/// the graph, i.e. the feistel precomputation, in every iteration. The `cached` variants serve
+/// the parents from a table in memory, as a `PrecomputedGraph`.
+///
+/// The stacked parents cache (`FIL_PROOFS_MAXIMIZE_CACHING`) is only available for 32GiB
+/// sectors, which are too large to benchmark here. It holds the same table as the `cached`
+/// variants, so their numbers are those of replicating with caching enabled.
+fn parents_throughput_benchmark(cc: &mut Criterion) {
+    #[cfg(feature = "big-sector-sizes-bench")]
+    let sizes = vec![1 << 10, 1 << 15, 1 << 20];
+    #[cfg(not(feature = "big-sector-sizes-bench"))]
+    let sizes = vec![1 << 10, 1 << 15];
+
+    cc.bench(
+        "parents throughput",
+        ParameterizedBenchmark::new(
+            "bucket",
+            |b, size| {
+                let graph =
+                    BucketGraph::<PedersenHasher>::new(*size, BASE_DEGREE, 0, Seed::new([1u8; 28]))
+                        .unwrap();
+                let mut parents = vec![0; graph.degree()];
+                b.iter(|| black_box(parents_loop::<PedersenHasher, _>(&graph, &mut parents)))
+            },
+            sizes,
+        )
+        .with_function("bucket-cold", |b, size| {
+            let mut parents = vec![0; BASE_DEGREE];
+            b.iter(|| {
+                let graph =
+                    BucketGraph::<PedersenHasher>::new(*size, BASE_DEGREE, 0, Seed::new([1u8; 28]))
+                        .unwrap();
+                black_box(parents_loop::<PedersenHasher, _>(&graph, &mut parents))
+            })
+        })
+        .with_function("bucket-cached", |b, size| {
+            let graph = cache_parents(
+                &BucketGraph::<PedersenHasher>::new(*size, BASE_DEGREE, 0, Seed::new([1u8; 28]))
+                    .unwrap(),
+            );
+            let mut parents = vec![0; graph.degree()];
+            b.iter(|| black_box(parents_loop::<PedersenHasher, _>(&graph, &mut parents)))
+        })
+        .with_function("stacked-base", |b, size| {
+            let graph = pregenerate_graph::<PedersenHasher>(*size);
+            let mut parents = vec![0; BASE_DEGREE];
+            b.iter(|| {
+                stacked_parents_loop(*size, &mut parents, |node, parents| {
+                    graph.base_parents(node, parents)
+                })
+            })
+        })
+        .with_function("stacked-expansion", |b, size| {
+            let graph = pregenerate_graph::<PedersenHasher>(*size);
+            let mut parents = vec![0; EXP_DEGREE];
+            b.iter(|| {
+                stacked_parents_loop(*size, &mut parents, |node, parents| {
+                    graph.expanded_parents(node, parents)
+                })
+            })
+        })
+        .with_function("stacked-base-cold", |b, size| {
+            let mut parents = vec![0; BASE_DEGREE];
+            b.iter(|| {
+                let graph = pregenerate_graph::<PedersenHasher>(*size);
+                stacked_parents_loop(*size, &mut parents, |node, parents| {
+                    graph.base_parents(node, parents)
+                })
+            })
+        })
+        .with_function("stacked-expansion-cold", |b, size| {
+            let mut parents = vec![0; EXP_DEGREE];
+            b.iter(|| {
+                let graph = pregenerate_graph::<PedersenHasher>(*size);
+                stacked_parents_loop(*size, &mut parents, |node, parents| {
+                    graph.expanded_parents(node, parents)
+                })
+            })
+        })
+        .with_function("stacked-cached", |b, size| {
+            let graph = cache_parents(&pregenerate_graph::<PedersenHasher>(*size));
+            let mut parents = vec![0; graph.degree()];
+            b.iter(|| black_box(parents_loop::<PedersenHasher, _>(&graph, &mut parents)))
+        })
+        .throughput(|size| Throughput::Elements(*size as u64))
+        .sample_size(20),
+    );
+}
+
+criterion_group!(
+    benches,
+    parents_loop_benchmark,
+    parents_throughput_benchmark
+);
 criterion_main!(benches);
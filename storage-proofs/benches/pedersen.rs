@@ -2,7 +2,7 @@ use bellperson::gadgets::boolean::{self, Boolean};
 use bellperson::groth16::*;
 use bellperson::{Circuit, ConstraintSystem, SynthesisError};
 use criterion::{black_box, criterion_group, criterion_main, Criterion, ParameterizedBenchmark};
-use fil_sapling_crypto::jubjub::JubjubEngine;
+use fil_sapling_crypto::jubjub::{JubjubBls12, JubjubEngine};
 use paired::bls12_381::Bls12;
 use rand::{thread_rng, Rng};
 use storage_proofs::crypto::pedersen::{self, JJ_PARAMS};
@@ -107,6 +107,29 @@ fn pedersen_md_benchmark(c: &mut Criterion) {
     );
 }
 
+fn pedersen_window_benchmark(c: &mut Criterion) {
+    let params = vec![8, 16];
+
+    c.bench(
+        "hash-pedersen-window",
+        ParameterizedBenchmark::new(
+            "non-circuit",
+            |b, window_size| {
+                let mut rng = thread_rng();
+                let data: Vec<u8> = (0..2 * 32).map(|_| rng.gen()).collect();
+                let jj_params = JubjubBls12::new_with_window_size(*window_size);
+
+                b.iter(|| {
+                    black_box(pedersen::pedersen_md_no_padding_with_params(
+                        &data, &jj_params,
+                    ))
+                })
+            },
+            params,
+        ),
+    );
+}
+
 fn pedersen_circuit_benchmark(c: &mut Criterion) {
     let mut rng1 = thread_rng();
     let groth_params = generate_random_parameters::<Bls12, _, _>(
@@ -225,6 +248,7 @@ criterion_group!(
     benches,
     pedersen_benchmark,
     pedersen_md_benchmark,
+    pedersen_window_benchmark,
     pedersen_circuit_benchmark,
 );
 criterion_main!(benches);
@@ -0,0 +1,90 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Bencher, Criterion};
+use ff::Field;
+use merkletree::store::StoreConfig;
+use paired::bls12_381::{Bls12, Fr};
+use rand::thread_rng;
+use storage_proofs::cache_key::CacheKey;
+use storage_proofs::drgraph::{new_seed, BucketGraph, BASE_DEGREE};
+use storage_proofs::fr32::fr_into_bytes;
+use storage_proofs::hasher::blake2s::Blake2sHasher;
+use storage_proofs::hasher::pedersen::PedersenHasher;
+use storage_proofs::hasher::poseidon::PoseidonHasher;
+use storage_proofs::hasher::{Domain, Hasher};
+use storage_proofs::porep::drg::{DrgParams, DrgPoRep, SetupParams};
+use storage_proofs::porep::stacked::BINARY_ARITY;
+use storage_proofs::porep::PoRep;
+use storage_proofs::proof::ProofScheme;
+
+fn replicate<H: 'static + Hasher>(b: &mut Bencher, nodes: usize) {
+    let mut rng = thread_rng();
+    let replica_id: H::Domain = H::Domain::random(&mut rng);
+    let data: Vec<u8> = (0..nodes)
+        .flat_map(|_| fr_into_bytes::<Bls12>(&Fr::random(&mut rng)))
+        .collect();
+
+    let sp = SetupParams {
+        drg: DrgParams {
+            nodes,
+            degree: BASE_DEGREE,
+            expansion_degree: 0,
+            seed: new_seed(),
+        },
+        private: false,
+        challenges_count: 1,
+    };
+    let pp = DrgPoRep::<H, BucketGraph<H>>::setup(&sp).unwrap();
+
+    // Trees and the replica are written to a temporary directory, removed after the benchmark.
+    let cache_dir = tempfile::tempdir().unwrap();
+    let config = StoreConfig::new(
+        cache_dir.path(),
+        CacheKey::CommDTree.to_string(),
+        StoreConfig::default_cached_above_base_layer(nodes, BINARY_ARITY),
+    );
+    let replica_path = cache_dir.path().join("replica-path");
+
+    b.iter_batched(
+        || data.clone(),
+        |mut data| {
+            black_box(
+                DrgPoRep::<H, BucketGraph<H>>::replicate(
+                    &pp,
+                    &replica_id,
+                    data.as_mut_slice().into(),
+                    None,
+                    config.clone(),
+                    replica_path.clone(),
+                )
+                .unwrap(),
+            )
+        },
+        BatchSize::LargeInput,
+    );
+}
+
+fn replicate_benchmark(c: &mut Criterion) {
+    #[cfg(feature = "big-sector-sizes-bench")]
+    let params = vec![128, 1024, 1048576];
+    #[cfg(not(feature = "big-sector-sizes-bench"))]
+    let params = vec![128, 1024];
+
+    let mut group = c.benchmark_group("drg-replicate");
+    group.sample_size(10);
+
+    for nodes in params {
+        group.bench_function(format!("pedersen-{}", nodes), |b| {
+            replicate::<PedersenHasher>(b, nodes)
+        });
+        group.bench_function(format!("poseidon-{}", nodes), |b| {
+            replicate::<PoseidonHasher>(b, nodes)
+        });
+        group.bench_function(format!("blake2s-{}", nodes), |b| {
+            replicate::<Blake2sHasher>(b, nodes)
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, replicate_benchmark);
+criterion_main!(benches);
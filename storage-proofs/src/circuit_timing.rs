@@ -0,0 +1,83 @@
+#[cfg(feature = "circuit-timing")]
+use std::cell::RefCell;
+use std::time::Duration;
+#[cfg(feature = "circuit-timing")]
+use std::time::Instant;
+
+use serde::Serialize;
+
+#[cfg(feature = "circuit-timing")]
+thread_local! {
+    static SYNTHESIS_TIMING: RefCell<SynthesisTiming> = RefCell::new(SynthesisTiming::default());
+}
+
+/// The sections of circuit synthesis which are timed with the `circuit-timing` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SynthesisPhase {
+    /// Inclusion proofs of the data nodes in comm_d.
+    PorData,
+    /// Inclusion proofs of the replica nodes and their parents in comm_r.
+    PorReplica,
+    /// Key derivation and decoding of the replica nodes.
+    Encoding,
+}
+
+/// The wall-clock time spent in each phase of circuit synthesis, summed over all challenges.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct SynthesisTiming {
+    pub phases: Vec<(SynthesisPhase, Duration)>,
+}
+
+impl SynthesisTiming {
+    /// Returns the time spent in `phase`, zero if it was never entered.
+    pub fn get(&self, phase: SynthesisPhase) -> Duration {
+        self.phases
+            .iter()
+            .filter(|(p, _)| *p == phase)
+            .map(|(_, duration)| *duration)
+            .sum()
+    }
+
+    #[cfg(feature = "circuit-timing")]
+    fn add(&mut self, phase: SynthesisPhase, duration: Duration) {
+        match self.phases.iter_mut().find(|(p, _)| *p == phase) {
+            Some((_, total)) => *total += duration,
+            None => self.phases.push((phase, duration)),
+        }
+    }
+}
+
+/// Runs `f`, adding its wall-clock time to `phase` in the timing report of the current thread.
+#[cfg(feature = "circuit-timing")]
+pub fn time_phase<T, F>(phase: SynthesisPhase, f: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    let start = Instant::now();
+    let x = f();
+    let elapsed = start.elapsed();
+    SYNTHESIS_TIMING.with(|timing| timing.borrow_mut().add(phase, elapsed));
+
+    x
+}
+
+#[cfg(not(feature = "circuit-timing"))]
+pub fn time_phase<T, F>(_: SynthesisPhase, f: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    f()
+}
+
+/// Returns the timing report collected on the current thread since the last call, and resets
+/// it. The report is empty unless the `circuit-timing` feature is enabled.
+#[cfg(feature = "circuit-timing")]
+pub fn take_synthesis_timing() -> SynthesisTiming {
+    SYNTHESIS_TIMING.with(|timing| timing.replace(SynthesisTiming::default()))
+}
+
+#[cfg(not(feature = "circuit-timing"))]
+pub fn take_synthesis_timing() -> SynthesisTiming {
+    SynthesisTiming::default()
+}
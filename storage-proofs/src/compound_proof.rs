@@ -1,13 +1,34 @@
+use std::fmt;
+#[cfg(not(feature = "verify-only"))]
+use std::fs::{self, File};
+#[cfg(not(feature = "verify-only"))]
+use std::io::{self, Write};
+#[cfg(not(feature = "verify-only"))]
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
 use anyhow::{ensure, Context};
 use bellperson::{groth16, Circuit};
 use fil_sapling_crypto::jubjub::JubjubEngine;
 use log::info;
-use rand::{rngs::OsRng, RngCore};
+#[cfg(not(feature = "verify-only"))]
+use paired::Engine;
+#[cfg(not(feature = "verify-only"))]
+use rand::rngs::OsRng;
+use rand::RngCore;
 use rayon::prelude::*;
+#[cfg(not(feature = "verify-only"))]
+use sha2::{Digest, Sha256};
 
 use crate::error::Result;
+#[cfg(not(feature = "verify-only"))]
+use crate::fr32::fr_into_bytes;
 use crate::multi_proof::MultiProof;
-use crate::parameter_cache::{CacheableParameters, ParameterSetMetadata};
+use crate::parameter_cache::{
+    read_versioned_verifying_key, CacheableParameters, ParameterSetMetadata,
+};
+#[cfg(not(feature = "verify-only"))]
 use crate::partitions;
 use crate::proof::ProofScheme;
 
@@ -19,6 +40,19 @@ pub struct SetupParams<'a, S: ProofScheme<'a>> {
     pub priority: bool,
 }
 
+impl<'a, S: ProofScheme<'a>> SetupParams<'a, S> {
+    /// Checks the partition count and the vanilla setup parameters, see
+    /// `ProofScheme::validate_setup_params`.
+    pub fn validate(&self) -> Result<()> {
+        ensure!(
+            self.partitions != Some(0),
+            "invalid setup params: partitions must be greater than zero when specified"
+        );
+
+        S::validate_setup_params(&self.vanilla_params)
+    }
+}
+
 #[derive(Clone)]
 pub struct PublicParams<'a, S: ProofScheme<'a>> {
     pub vanilla_params: S::PublicParams,
@@ -26,6 +60,79 @@ pub struct PublicParams<'a, S: ProofScheme<'a>> {
     pub priority: bool,
 }
 
+/// VerifyReport records how far verification of a `MultiProof` got, and why it failed if it did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// Whether the proof satisfied the verification requirements.
+    pub requirements_satisfied: bool,
+    /// Whether public inputs could be generated for every partition.
+    pub public_inputs_generated: bool,
+    /// Whether the groth16 pairing check passed.
+    pub pairing_check_passed: bool,
+    /// Human-readable reason for the first failed step, if any.
+    pub reason: Option<String>,
+}
+
+impl VerifyReport {
+    /// Returns true if every verification step succeeded.
+    pub fn is_valid(&self) -> bool {
+        self.requirements_satisfied && self.public_inputs_generated && self.pairing_check_passed
+    }
+
+    fn fail(mut self, reason: String) -> Self {
+        self.reason = Some(reason);
+        self
+    }
+}
+
+/// A flag used to abort long running work, such as verification of many partitions, from another
+/// thread. Clones share the same flag.
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    condition: Option<Arc<dyn Fn() -> bool + Send + Sync>>,
+}
+
+impl fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CancellationToken")
+            .field("cancelled", &self.cancelled)
+            .field("has_condition", &self.condition.is_some())
+            .finish()
+    }
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a token which is also cancelled whenever `condition` returns true, e.g. once a
+    /// deadline has passed. `condition` is called each time the token is checked.
+    pub fn from_fn<F>(condition: F) -> Self
+    where
+        F: Fn() -> bool + Send + Sync + 'static,
+    {
+        CancellationToken {
+            cancelled: Default::default(),
+            condition: Some(Arc::new(condition)),
+        }
+    }
+
+    /// Requests cancellation of all work observing this token.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+            || self
+                .condition
+                .as_ref()
+                .map_or(false, |condition| condition())
+    }
+}
+
 /// CircuitComponent exists so parent components can pass private inputs to their subcomponents
 /// when calling CompoundProof::circuit directly. In general, there are no internal private inputs,
 /// and a default value will be passed. CompoundProof::circuit implementations should exhibit
@@ -55,6 +162,8 @@ pub trait CompoundProof<
     where
         E::Params: Sync,
     {
+        sp.validate()?;
+
         Ok(PublicParams {
             vanilla_params: S::setup(&sp.vanilla_params)?,
             partitions: sp.partitions,
@@ -71,6 +180,7 @@ pub trait CompoundProof<
     }
 
     /// prove is equivalent to ProofScheme::prove.
+    #[cfg(not(feature = "verify-only"))]
     fn prove<'b>(
         pub_params: &PublicParams<'a, S>,
         pub_in: &S::PublicInputs,
@@ -109,8 +219,227 @@ pub trait CompoundProof<
         Ok(MultiProof::new(groth_proofs, &groth_params.vk))
     }
 
-    // verify is equivalent to ProofScheme::verify.
-    fn verify<'b>(
+    /// prove_with_pool is like prove, but runs its rayon work, i.e. the vanilla proofs and the
+    /// circuit synthesis, in `pool` instead of the global rayon pool. The groth16 proving itself
+    /// runs on the separate worker threads of bellperson, which are sized by the
+    /// `BELLMAN_NUM_CPUS` environment variable and are not confined by `pool`.
+    #[cfg(not(feature = "verify-only"))]
+    fn prove_with_pool<'b>(
+        pub_params: &PublicParams<'a, S>,
+        pub_in: &S::PublicInputs,
+        priv_in: &S::PrivateInputs,
+        groth_params: &'b groth16::MappedParameters<E>,
+        pool: &rayon::ThreadPool,
+    ) -> Result<MultiProof<'b, E>>
+    where
+        E::Params: Sync,
+        S::PrivateInputs: Sync,
+    {
+        pool.install(|| Self::prove(pub_params, pub_in, priv_in, groth_params))
+    }
+
+    /// prove_from_vanilla is like prove, but starts from the vanilla proofs of all partitions,
+    /// built elsewhere. This allows generating the SNARK without access to the private inputs.
+    #[cfg(not(feature = "verify-only"))]
+    fn prove_from_vanilla<'b>(
+        pub_params: &PublicParams<'a, S>,
+        pub_in: &S::PublicInputs,
+        vanilla_proofs: Vec<S::Proof>,
+        groth_params: &'b groth16::MappedParameters<E>,
+    ) -> Result<MultiProof<'b, E>>
+    where
+        E::Params: Sync,
+    {
+        let partition_count = Self::partition_count(pub_params);
+        ensure!(
+            vanilla_proofs.len() == partition_count,
+            "expected {} vanilla proofs, got {}",
+            partition_count,
+            vanilla_proofs.len()
+        );
+
+        let sanity_check =
+            S::verify_all_partitions(&pub_params.vanilla_params, &pub_in, &vanilla_proofs)?;
+        ensure!(sanity_check, "sanity check failed");
+
+        info!("snark_proof:start");
+        let groth_proofs = Self::circuit_proofs(
+            pub_in,
+            vanilla_proofs,
+            &pub_params.vanilla_params,
+            groth_params,
+            pub_params.priority,
+        )?;
+        info!("snark_proof:finish");
+
+        Ok(MultiProof::new(groth_proofs, &groth_params.vk))
+    }
+
+    /// validate_inputs is a dry run of prove: it generates and verifies the vanilla proofs of
+    /// all partitions and checks them against the preconditions of circuit, returning the first
+    /// problem found. Nothing is synthesized and no groth proof is generated, so mistakes in the
+    /// inputs are reported before the expensive part of proving starts.
+    #[cfg(not(feature = "verify-only"))]
+    fn validate_inputs(
+        pub_params: &PublicParams<'a, S>,
+        pub_in: &S::PublicInputs,
+        priv_in: &S::PrivateInputs,
+    ) -> Result<()> {
+        let partitions = Self::partition_count(pub_params);
+        let vanilla_proofs =
+            S::prove_all_partitions(&pub_params.vanilla_params, &pub_in, priv_in, partitions)?;
+
+        let sanity_check =
+            S::verify_all_partitions(&pub_params.vanilla_params, &pub_in, &vanilla_proofs)?;
+        ensure!(sanity_check, "sanity check failed");
+
+        for (k, vanilla_proof) in vanilla_proofs.iter().enumerate() {
+            Self::check_circuit_inputs(
+                pub_in,
+                &C::ComponentPrivateInputs::default(),
+                vanilla_proof,
+                &pub_params.vanilla_params,
+            )
+            .with_context(|| format!("invalid inputs for partition {}", k))?;
+        }
+
+        Ok(())
+    }
+
+    /// prove_resumable is like prove, but appends the groth proof of each partition to the
+    /// `checkpoint` file as soon as it is generated. If proving is interrupted, `resume_prove`
+    /// picks up from the partitions recorded in the checkpoint. Any existing checkpoint is
+    /// discarded.
+    ///
+    /// The checkpoint starts with a digest of the parameters identifier and the public inputs of
+    /// every partition, so that it cannot be resumed for another proof.
+    #[cfg(not(feature = "verify-only"))]
+    fn prove_resumable<'b>(
+        pub_params: &PublicParams<'a, S>,
+        pub_in: &S::PublicInputs,
+        priv_in: &S::PrivateInputs,
+        groth_params: &'b groth16::MappedParameters<E>,
+        checkpoint: &Path,
+    ) -> Result<MultiProof<'b, E>>
+    where
+        E::Params: Sync,
+    {
+        if checkpoint.exists() {
+            fs::remove_file(checkpoint)
+                .with_context(|| format!("could not remove checkpoint {:?}", checkpoint))?;
+        }
+
+        Self::resume_prove(pub_params, pub_in, priv_in, groth_params, checkpoint)
+    }
+
+    /// resume_prove loads the partition proofs recorded in `checkpoint` by `prove_resumable`,
+    /// generates the vanilla and groth proofs of the remaining partitions only, appending them to
+    /// the checkpoint, and assembles the full proof. A partition proof which was only partially
+    /// written before an interruption, or which does not verify against the verifying key of
+    /// `groth_params`, is generated again, together with the proofs of the partitions after it.
+    /// Fails if the checkpoint was written for other parameters or public inputs.
+    #[cfg(not(feature = "verify-only"))]
+    fn resume_prove<'b>(
+        pub_params: &PublicParams<'a, S>,
+        pub_in: &S::PublicInputs,
+        priv_in: &S::PrivateInputs,
+        groth_params: &'b groth16::MappedParameters<E>,
+        checkpoint: &Path,
+    ) -> Result<MultiProof<'b, E>>
+    where
+        E::Params: Sync,
+    {
+        let partition_count = Self::partition_count(pub_params);
+        let header = Self::checkpoint_header(pub_params, pub_in)?;
+        let mut groth_proofs = read_checkpoint::<E>(checkpoint, &header)?;
+        ensure!(
+            groth_proofs.len() <= partition_count,
+            "checkpoint holds {} partition proofs, but there are only {} partitions",
+            groth_proofs.len(),
+            partition_count
+        );
+
+        let pvk = groth16::prepare_verifying_key(&groth_params.vk);
+        let mut verified = 0;
+        for (k, groth_proof) in groth_proofs.iter().enumerate() {
+            let inputs = Self::generate_public_inputs(pub_in, &pub_params.vanilla_params, Some(k))?;
+            if !groth16::verify_proof(&pvk, groth_proof, &inputs)? {
+                info!("discarding invalid checkpointed proof of partition {}", k);
+                break;
+            }
+            verified += 1;
+        }
+        groth_proofs.truncate(verified);
+        info!(
+            "resuming with {}/{} partition proofs",
+            groth_proofs.len(),
+            partition_count
+        );
+
+        // Rewrite the checkpoint with the verified proofs only.
+        let mut file = File::create(checkpoint)
+            .with_context(|| format!("could not create checkpoint {:?}", checkpoint))?;
+        file.write_all(&header)?;
+        for groth_proof in &groth_proofs {
+            groth_proof.write(&mut file)?;
+        }
+        file.sync_data()?;
+
+        for k in groth_proofs.len()..partition_count {
+            let partition_pub_in = S::with_partition(pub_in.clone(), Some(k));
+            let vanilla_proof = S::prove(&pub_params.vanilla_params, &partition_pub_in, priv_in)?;
+            let sanity_check = S::verify(
+                &pub_params.vanilla_params,
+                &partition_pub_in,
+                &vanilla_proof,
+            )?;
+            ensure!(sanity_check, "sanity check failed for partition {}", k);
+
+            info!("snark_proof:start partition {}", k);
+            let groth_proof = Self::circuit_proofs(
+                pub_in,
+                vec![vanilla_proof],
+                &pub_params.vanilla_params,
+                groth_params,
+                pub_params.priority,
+            )?
+            .pop()
+            .context("missing partition proof")?;
+            info!("snark_proof:finish partition {}", k);
+
+            groth_proof.write(&mut file)?;
+            file.sync_data()?;
+            groth_proofs.push(groth_proof);
+        }
+
+        Ok(MultiProof::new(groth_proofs, &groth_params.vk))
+    }
+
+    /// Returns the header of a `resume_prove` checkpoint: the sha256 digest of the parameters
+    /// identifier followed by the public inputs of each partition.
+    #[cfg(not(feature = "verify-only"))]
+    fn checkpoint_header(
+        pub_params: &PublicParams<'a, S>,
+        pub_in: &S::PublicInputs,
+    ) -> Result<[u8; CHECKPOINT_HEADER_LEN]> {
+        let mut hasher = Sha256::new();
+        hasher.input(pub_params.vanilla_params.identifier().as_bytes());
+        for k in 0..Self::partition_count(pub_params) {
+            let inputs = Self::generate_public_inputs(pub_in, &pub_params.vanilla_params, Some(k))?;
+            for input in &inputs {
+                hasher.input(fr_into_bytes::<E>(input));
+            }
+        }
+
+        let mut header = [0u8; CHECKPOINT_HEADER_LEN];
+        header.copy_from_slice(&hasher.result());
+        Ok(header)
+    }
+
+    /// Runs the checks shared by the verify methods before any pairing is done. Fails if
+    /// `multi_proof` does not have one proof per partition, or if `public_inputs` do not meet
+    /// `requirements`. Returns whether the proof satisfies `requirements`.
+    fn check_verify_requirements<'b>(
         public_params: &PublicParams<'a, S>,
         public_inputs: &S::PublicInputs,
         multi_proof: &MultiProof<'b, E>,
@@ -121,21 +450,40 @@ pub trait CompoundProof<
             "Inconsistent inputs"
         );
 
-        let vanilla_public_params = &public_params.vanilla_params;
-        let pvk = groth16::prepare_batch_verifying_key(&multi_proof.verifying_key);
+        S::check_requirements(public_inputs, requirements)?;
 
-        if !<S as ProofScheme>::satisfies_requirements(
+        Ok(<S as ProofScheme>::satisfies_requirements(
             &public_params.vanilla_params,
             requirements,
             multi_proof.circuit_proofs.len(),
-        ) {
+        ))
+    }
+
+    // verify is equivalent to ProofScheme::verify.
+    fn verify<'b>(
+        public_params: &PublicParams<'a, S>,
+        public_inputs: &S::PublicInputs,
+        multi_proof: &MultiProof<'b, E>,
+        requirements: &S::Requirements,
+    ) -> Result<bool> {
+        let requirements_satisfied = Self::check_verify_requirements(
+            public_params,
+            public_inputs,
+            multi_proof,
+            requirements,
+        )?;
+        if !requirements_satisfied {
             return Ok(false);
         }
 
+        let vanilla_public_params = &public_params.vanilla_params;
+        let pvk = groth16::prepare_batch_verifying_key(&multi_proof.verifying_key);
+
         let inputs: Vec<_> = (0..multi_proof.circuit_proofs.len())
             .into_par_iter()
             .map(|k| Self::generate_public_inputs(public_inputs, vanilla_public_params, Some(k)))
-            .collect::<Result<_>>()?;
+            .collect::<Result<_>>()
+            .context("failed to generate public inputs")?;
         let proofs: Vec<_> = multi_proof.circuit_proofs.iter().collect();
 
         let res = groth16::verify_proofs_batch(&pvk, &mut rand::rngs::OsRng, &proofs, &inputs)?;
@@ -143,6 +491,168 @@ pub trait CompoundProof<
         Ok(res)
     }
 
+    /// verify_detailed is like verify, but instead of a plain bool it returns a VerifyReport
+    /// describing which step of the verification failed, and why.
+    fn verify_detailed<'b>(
+        public_params: &PublicParams<'a, S>,
+        public_inputs: &S::PublicInputs,
+        multi_proof: &MultiProof<'b, E>,
+        requirements: &S::Requirements,
+    ) -> Result<VerifyReport> {
+        let requirements_satisfied = Self::check_verify_requirements(
+            public_params,
+            public_inputs,
+            multi_proof,
+            requirements,
+        )?;
+
+        let mut report = VerifyReport::default();
+
+        let vanilla_public_params = &public_params.vanilla_params;
+        let pvk = groth16::prepare_batch_verifying_key(&multi_proof.verifying_key);
+
+        if !requirements_satisfied {
+            return Ok(report.fail("requirements not satisfied".into()));
+        }
+        report.requirements_satisfied = true;
+
+        let inputs: Vec<_> = match (0..multi_proof.circuit_proofs.len())
+            .into_par_iter()
+            .map(|k| Self::generate_public_inputs(public_inputs, vanilla_public_params, Some(k)))
+            .collect::<Result<_>>()
+        {
+            Ok(inputs) => inputs,
+            Err(err) => {
+                return Ok(report.fail(format!("failed to generate public inputs: {:#}", err)));
+            }
+        };
+        report.public_inputs_generated = true;
+
+        let proofs: Vec<_> = multi_proof.circuit_proofs.iter().collect();
+
+        report.pairing_check_passed =
+            groth16::verify_proofs_batch(&pvk, &mut rand::rngs::OsRng, &proofs, &inputs)?;
+        if !report.pairing_check_passed {
+            return Ok(report.fail("pairing check failed".into()));
+        }
+
+        Ok(report)
+    }
+
+    /// verify_with_inputs is like verify, but also returns the public inputs the proof was
+    /// verified against, so that they can be compared with an independent derivation. The inputs
+    /// of all partitions are concatenated in partition order.
+    fn verify_with_inputs<'b>(
+        public_params: &PublicParams<'a, S>,
+        public_inputs: &S::PublicInputs,
+        multi_proof: &MultiProof<'b, E>,
+        requirements: &S::Requirements,
+    ) -> Result<(bool, Vec<E::Fr>)> {
+        let requirements_satisfied = Self::check_verify_requirements(
+            public_params,
+            public_inputs,
+            multi_proof,
+            requirements,
+        )?;
+
+        let vanilla_public_params = &public_params.vanilla_params;
+        let inputs: Vec<_> = (0..multi_proof.circuit_proofs.len())
+            .into_par_iter()
+            .map(|k| Self::generate_public_inputs(public_inputs, vanilla_public_params, Some(k)))
+            .collect::<Result<_>>()?;
+
+        let verified = if requirements_satisfied {
+            let pvk = groth16::prepare_batch_verifying_key(&multi_proof.verifying_key);
+            let proofs: Vec<_> = multi_proof.circuit_proofs.iter().collect();
+
+            groth16::verify_proofs_batch(&pvk, &mut rand::rngs::OsRng, &proofs, &inputs)?
+        } else {
+            false
+        };
+
+        Ok((verified, inputs.into_iter().flatten().collect()))
+    }
+
+    /// verify_versioned is like verify, but checks `circuit_proofs` against the verifying key
+    /// cached for the parameter `version` and `parameter_set_identifier`, instead of the key of
+    /// the current parameters. This allows verifying proofs made before a circuit version bump.
+    ///
+    /// Only the verifying key is versioned: the public inputs are still generated by the current
+    /// code, so the bump must not have changed them. Fails if that verifying key is not in the
+    /// parameter cache.
+    fn verify_versioned(
+        public_params: &PublicParams<'a, S>,
+        public_inputs: &S::PublicInputs,
+        circuit_proofs: Vec<groth16::Proof<E>>,
+        requirements: &S::Requirements,
+        version: usize,
+        parameter_set_identifier: &str,
+    ) -> Result<bool> {
+        let verifying_key = read_versioned_verifying_key(version, parameter_set_identifier)?;
+        let multi_proof = MultiProof::new(circuit_proofs, &verifying_key);
+
+        Self::verify(public_params, public_inputs, &multi_proof, requirements)
+    }
+
+    /// verify_cancellable is like verify, but verifies the partitions one by one, checking
+    /// `cancel` before each of them. Returns `None` if verification was cancelled.
+    fn verify_cancellable<'b>(
+        public_params: &PublicParams<'a, S>,
+        public_inputs: &S::PublicInputs,
+        multi_proof: &MultiProof<'b, E>,
+        requirements: &S::Requirements,
+        cancel: &CancellationToken,
+    ) -> Result<Option<bool>> {
+        if !Self::check_verify_requirements(
+            public_params,
+            public_inputs,
+            multi_proof,
+            requirements,
+        )? {
+            return Ok(Some(false));
+        }
+
+        let pvk = groth16::prepare_verifying_key(&multi_proof.verifying_key);
+
+        for (k, circuit_proof) in multi_proof.circuit_proofs.iter().enumerate() {
+            if cancel.is_cancelled() {
+                info!("verification cancelled before partition {}", k);
+                return Ok(None);
+            }
+
+            let inputs = Self::generate_public_inputs(
+                public_inputs,
+                &public_params.vanilla_params,
+                Some(k),
+            )?;
+            if !groth16::verify_proof(&pvk, circuit_proof, &inputs)? {
+                return Ok(Some(false));
+            }
+        }
+
+        Ok(Some(true))
+    }
+
+    /// verify_streaming is equivalent to verify, but generates the public inputs of one partition
+    /// at a time and checks its pairing before moving on to the next, so that the memory needed
+    /// does not grow with the number of partitions.
+    fn verify_streaming<'b>(
+        public_params: &PublicParams<'a, S>,
+        public_inputs: &S::PublicInputs,
+        multi_proof: &MultiProof<'b, E>,
+        requirements: &S::Requirements,
+    ) -> Result<bool> {
+        let verified = Self::verify_cancellable(
+            public_params,
+            public_inputs,
+            multi_proof,
+            requirements,
+            &CancellationToken::new(),
+        )?;
+
+        Ok(verified == Some(true))
+    }
+
     /// Efficiently verify multiple proofs.
     fn batch_verify<'b>(
         public_params: &PublicParams<'a, S>,
@@ -161,6 +671,9 @@ pub trait CompoundProof<
             );
         }
         ensure!(!public_inputs.is_empty(), "Cannot verify empty proofs");
+        for pub_inputs in public_inputs {
+            S::check_requirements(pub_inputs, requirements)?;
+        }
 
         let vanilla_public_params = &public_params.vanilla_params;
         // just use the first one, the must be equal any way
@@ -208,6 +721,7 @@ pub trait CompoundProof<
     /// groth proof from it. It returns a groth proof.
     /// circuit_proof is used internally and should neither be called nor implemented outside of
     /// default trait methods.
+    #[cfg(not(feature = "verify-only"))]
     fn circuit_proofs(
         pub_in: &S::PublicInputs,
         vanilla_proof: Vec<S::Proof>,
@@ -270,6 +784,18 @@ pub trait CompoundProof<
         public_param: &S::PublicParams,
     ) -> Result<C>;
 
+    /// check_circuit_inputs checks the preconditions of circuit for the given inputs, without
+    /// constructing the circuit. circuit implementations should call it before anything else,
+    /// so that both agree on what is rejected.
+    fn check_circuit_inputs(
+        _public_inputs: &S::PublicInputs,
+        _component_private_inputs: &C::ComponentPrivateInputs,
+        _vanilla_proof: &S::Proof,
+        _public_param: &S::PublicParams,
+    ) -> Result<()> {
+        Ok(())
+    }
+
     fn blank_circuit(public_params: &S::PublicParams) -> C;
 
     /// If the rng option argument is set, parameters will be
@@ -296,6 +822,7 @@ pub trait CompoundProof<
         Self::get_verifying_key(rng, Self::blank_circuit(public_params), public_params)
     }
 
+    #[cfg(not(feature = "verify-only"))]
     fn circuit_for_test(
         public_parameters: &PublicParams<'a, S>,
         public_inputs: &S::PublicInputs,
@@ -338,3 +865,43 @@ pub trait CompoundProof<
         Ok((circuit, inputs))
     }
 }
+
+/// The length of the header of a checkpoint, see `CompoundProof::checkpoint_header`.
+#[cfg(not(feature = "verify-only"))]
+const CHECKPOINT_HEADER_LEN: usize = 32;
+
+/// Reads the partition proofs written to `checkpoint` by `CompoundProof::resume_prove`, in
+/// partition order, after checking that the checkpoint starts with `header`. A missing
+/// checkpoint holds no proofs, and a header or trailing proof which was only partially written
+/// is ignored.
+#[cfg(not(feature = "verify-only"))]
+fn read_checkpoint<E: Engine>(
+    checkpoint: &Path,
+    header: &[u8; CHECKPOINT_HEADER_LEN],
+) -> Result<Vec<groth16::Proof<E>>> {
+    let bytes = match fs::read(checkpoint) {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => {
+            return Err(err).with_context(|| format!("could not read checkpoint {:?}", checkpoint))
+        }
+    };
+
+    if bytes.len() < CHECKPOINT_HEADER_LEN && header.starts_with(&bytes) {
+        return Ok(Vec::new());
+    }
+    ensure!(
+        bytes.starts_with(header),
+        "checkpoint {:?} was written for other parameters or public inputs",
+        checkpoint
+    );
+
+    bytes[CHECKPOINT_HEADER_LEN..]
+        .chunks_exact(MultiProof::<E>::groth_proof_size())
+        .enumerate()
+        .map(|(k, proof)| {
+            groth16::Proof::read(proof)
+                .with_context(|| format!("invalid proof of partition {} in checkpoint", k))
+        })
+        .collect()
+}
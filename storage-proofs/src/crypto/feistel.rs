@@ -1,6 +1,9 @@
+use anyhow::ensure;
 use blake2b_simd::blake2b;
 use std::mem;
 
+use crate::error::Result;
+
 pub const FEISTEL_ROUNDS: usize = 3;
 // 3 rounds is an acceptable value for a pseudo-random permutation,
 // see https://github.com/filecoin-project/rust-proofs/issues/425
@@ -10,6 +13,11 @@ pub type Index = u64;
 
 pub type FeistelPrecomputed = (Index, Index, Index);
 
+/// Default bound on the number of times `permute_bounded` re-encodes an index which landed
+/// outside of the permitted range. At least a quarter of the encoded range is permitted, so for
+/// any real input this is never reached.
+pub const DEFAULT_MAX_RESAMPLES: usize = 4096;
+
 // Find the minimum number of even bits to represent `num_elements`
 // within a `u32` maximum. Returns the left and right masks evenly
 // distributed that together add up to that minimum number of bits.
@@ -49,6 +57,33 @@ pub fn permute(
     u
 }
 
+/// Like `permute`, but fails instead of re-encoding more than `max_resamples` times until the
+/// result lands in the `[0, num_elements)` range.
+pub fn permute_bounded(
+    num_elements: Index,
+    index: Index,
+    keys: &[Index],
+    precomputed: FeistelPrecomputed,
+    max_resamples: usize,
+) -> Result<Index> {
+    let mut u = encode(index, keys, precomputed);
+
+    let mut resamples = 0;
+    while u >= num_elements {
+        ensure!(
+            resamples < max_resamples,
+            "permutation of {} did not land in [0, {}) within {} resamples",
+            index,
+            num_elements,
+            max_resamples
+        );
+        u = encode(u, keys, precomputed);
+        resamples += 1;
+    }
+
+    Ok(u)
+}
+
 // Inverts the `permute` result to its starting value for the same `key`.
 pub fn invert_permute(
     num_elements: Index,
@@ -227,4 +262,39 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_feistel_bounded_resamples() {
+        let n = 17;
+        let keys = &[1, 2, 3, 4];
+        let precomputed = precompute(n);
+
+        // Find the index which needs the most re-encodings to land in range.
+        let (index, resamples) = (0..n)
+            .map(|i| {
+                let mut u = encode(i, keys, precomputed);
+                let mut resamples = 0;
+                while u >= n {
+                    u = encode(u, keys, precomputed);
+                    resamples += 1;
+                }
+                (i, resamples)
+            })
+            .max_by_key(|(_, resamples)| *resamples)
+            .unwrap();
+        assert!(
+            resamples >= 2,
+            "expected several resamples, got {}",
+            resamples
+        );
+
+        let expected = permute(n, index, keys, precomputed);
+        for max_resamples in &[resamples, DEFAULT_MAX_RESAMPLES] {
+            let p = permute_bounded(n, index, keys, precomputed, *max_resamples).unwrap();
+            assert_eq!(p, expected);
+        }
+
+        let err = permute_bounded(n, index, keys, precomputed, resamples - 1).unwrap_err();
+        assert!(err.to_string().contains("resamples"), "{}", err);
+    }
 }
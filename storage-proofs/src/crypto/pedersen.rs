@@ -1,6 +1,6 @@
 use anyhow::{ensure, Context};
 use ff::PrimeFieldRepr;
-use fil_sapling_crypto::jubjub::JubjubBls12;
+use fil_sapling_crypto::jubjub::{edwards, JubjubBls12, PrimeOrder};
 use fil_sapling_crypto::pedersen_hash::Personalization;
 use lazy_static::lazy_static;
 use paired::bls12_381::{Bls12, Fr, FrRepr};
@@ -9,13 +9,32 @@ use crate::error::Result;
 use crate::fr32::bytes_into_frs;
 use crate::settings;
 
+/// The default exponentiation window size of `JJ_PARAMS`.
+pub const DEFAULT_PEDERSEN_WINDOW_SIZE: u32 = 16;
+
 lazy_static! {
-    pub static ref JJ_PARAMS: JubjubBls12 = JubjubBls12::new_with_window_size(
-        settings::SETTINGS
-            .lock()
-            .unwrap()
-            .pedersen_hash_exp_window_size
-    );
+    /// The exponentiation window size of `JJ_PARAMS`, read from the
+    /// `pedersen_hash_exp_window_size` setting.
+    pub static ref PEDERSEN_WINDOW_SIZE: u32 = settings::SETTINGS
+        .lock()
+        .unwrap()
+        .pedersen_hash_exp_window_size;
+
+    /// The parameters used by `PedersenHasher`, with the window size `PEDERSEN_WINDOW_SIZE`, see
+    /// `pedersen_with_params`.
+    pub static ref JJ_PARAMS: JubjubBls12 =
+        JubjubBls12::new_with_window_size(*PEDERSEN_WINDOW_SIZE);
+}
+
+/// Returns the suffix of `PedersenHasher::name` for the exponentiation window size
+/// `window_size`, so that parameters record the window size they were set up with. It is empty
+/// for the default, so that the identifiers of existing parameters do not change.
+pub fn window_size_identifier(window_size: u32) -> String {
+    if window_size == DEFAULT_PEDERSEN_WINDOW_SIZE {
+        String::new()
+    } else {
+        format!("-window-{}", window_size)
+    }
 }
 
 pub const PEDERSEN_BLOCK_SIZE: usize = 256;
@@ -25,16 +44,31 @@ pub fn pedersen(data: &[u8]) -> Fr {
     pedersen_bits(Bits::new(data))
 }
 
+/// Like `pedersen`, but using `params` instead of `JJ_PARAMS`.
+///
+/// The exponentiation window size of the parameters, as given to
+/// `JubjubBls12::new_with_window_size`, trades the memory used by the precomputed tables for
+/// hashing speed. It does not change the generators, so hashes, and thereby commitments, are the
+/// same for every window size.
+pub fn pedersen_with_params(data: &[u8], params: &JubjubBls12) -> Fr {
+    pedersen_hash_bits(Bits::new(data), params).into_xy().0
+}
+
 pub fn pedersen_bits<'a, S: Iterator<Item = &'a [u8]>>(data: Bits<&'a [u8], S>) -> Fr {
-    let digest = if cfg!(target_arch = "x86_64") {
+    pedersen_hash_bits(data, &JJ_PARAMS).into_xy().0
+}
+
+fn pedersen_hash_bits<T>(bits: T, params: &JubjubBls12) -> edwards::Point<Bls12, PrimeOrder>
+where
+    T: IntoIterator<Item = bool>,
+{
+    if cfg!(target_arch = "x86_64") {
         use fil_sapling_crypto::pedersen_hash::pedersen_hash_bls12_381_with_precomp;
-        pedersen_hash_bls12_381_with_precomp::<_>(Personalization::None, data, &JJ_PARAMS)
+        pedersen_hash_bls12_381_with_precomp::<_>(Personalization::None, bits, params)
     } else {
         use fil_sapling_crypto::pedersen_hash::pedersen_hash;
-        pedersen_hash::<Bls12, _>(Personalization::None, data, &JJ_PARAMS)
-    };
-
-    digest.into_xy().0
+        pedersen_hash::<Bls12, _>(Personalization::None, bits, params)
+    }
 }
 
 /// Pedersen hashing for inputs that have length mulitple of the block size `256`. Based on pedersen hashes and a Merkle-Damgard construction.
@@ -42,20 +76,32 @@ pub fn pedersen_md_no_padding(data: &[u8]) -> Fr {
     pedersen_md_no_padding_bits(Bits::new(data))
 }
 
-pub fn pedersen_md_no_padding_bits<T: AsRef<[u8]>, S: Iterator<Item = T>>(
+/// Like `pedersen_md_no_padding`, but using `params` instead of `JJ_PARAMS`, see
+/// `pedersen_with_params`.
+pub fn pedersen_md_no_padding_with_params(data: &[u8], params: &JubjubBls12) -> Fr {
+    pedersen_md_no_padding_bits_with_params(Bits::new(data), params)
+}
+
+pub fn pedersen_md_no_padding_bits<T: AsRef<[u8]>, S: Iterator<Item = T>>(data: Bits<T, S>) -> Fr {
+    pedersen_md_no_padding_bits_with_params(data, &JJ_PARAMS)
+}
+
+fn pedersen_md_no_padding_bits_with_params<T: AsRef<[u8]>, S: Iterator<Item = T>>(
     mut data: Bits<T, S>,
+    params: &JubjubBls12,
 ) -> Fr {
     let mut cur = Vec::with_capacity(PEDERSEN_BLOCK_SIZE);
 
     // hash the first two blocks
-    let first = pedersen_compression_bits(data.ref_take(2 * PEDERSEN_BLOCK_SIZE));
+    let first =
+        pedersen_compression_bits_with_params(data.ref_take(2 * PEDERSEN_BLOCK_SIZE), params);
     first
         .write_le(&mut cur)
         .expect("failed to write result hash");
 
     while !data.is_done() {
         let r = data.ref_take(PEDERSEN_BLOCK_SIZE);
-        let x = pedersen_compression_bits(Bits::new(&cur).chain(r));
+        let x = pedersen_compression_bits_with_params(Bits::new(&cur).chain(r), params);
 
         cur.truncate(0);
         x.write_le(&mut cur).expect("failed to write result hash");
@@ -70,15 +116,14 @@ fn pedersen_compression_bits<T>(bits: T) -> FrRepr
 where
     T: IntoIterator<Item = bool>,
 {
-    let digest = if cfg!(target_arch = "x86_64") {
-        use fil_sapling_crypto::pedersen_hash::pedersen_hash_bls12_381_with_precomp;
-        pedersen_hash_bls12_381_with_precomp::<_>(Personalization::None, bits, &JJ_PARAMS)
-    } else {
-        use fil_sapling_crypto::pedersen_hash::pedersen_hash;
-        pedersen_hash::<Bls12, _>(Personalization::None, bits, &JJ_PARAMS)
-    };
+    pedersen_compression_bits_with_params(bits, &JJ_PARAMS)
+}
 
-    digest.into_xy().0.into()
+fn pedersen_compression_bits_with_params<T>(bits: T, params: &JubjubBls12) -> FrRepr
+where
+    T: IntoIterator<Item = bool>,
+{
+    pedersen_hash_bits(bits, params).into_xy().0.into()
 }
 
 #[derive(Debug, Clone)]
@@ -352,6 +397,40 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_pedersen_window_sizes() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        for window_size in &[8, 12] {
+            let params = JubjubBls12::new_with_window_size(*window_size);
+
+            for i in 1..4 {
+                let x: Vec<u8> = (0..i * 32).map(|_| rng.gen()).collect();
+                assert_eq!(
+                    pedersen_with_params(&x, &params),
+                    pedersen(&x),
+                    "window size {}",
+                    window_size
+                );
+                if i > 1 {
+                    assert_eq!(
+                        pedersen_md_no_padding_with_params(&x, &params),
+                        pedersen_md_no_padding(&x),
+                        "window size {}",
+                        window_size
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_pedersen_window_size_identifier() {
+        assert_eq!(window_size_identifier(DEFAULT_PEDERSEN_WINDOW_SIZE), "");
+        assert_eq!(window_size_identifier(8), "-window-8");
+        assert_ne!(window_size_identifier(8), window_size_identifier(12));
+    }
+
     #[test]
     fn test_bits_collect() {
         let bytes = b"hello";
@@ -1,19 +1,26 @@
 use std::cmp;
+use std::collections::BTreeSet;
+use std::io::{Read, Write};
 use std::marker::PhantomData;
 use std::path::PathBuf;
+use std::sync::Arc;
 
-use anyhow::ensure;
+use anyhow::{bail, ensure, Context};
+use byteorder::{ByteOrder, LittleEndian};
 use generic_array::typenum;
 use merkletree::store::StoreConfig;
-use rand::{rngs::OsRng, Rng, SeedableRng};
+use rand::{rngs::OsRng, Rng, RngCore, SeedableRng};
 use rand_chacha::ChaChaRng;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
 use crate::error::*;
 use crate::fr32::bytes_into_fr_repr_safe;
 use crate::hasher::Hasher;
 use crate::merkle::{
-    create_lcmerkle_tree, create_merkle_tree, open_lcmerkle_tree, LCMerkleTree, MerkleTree,
+    create_lcmerkle_tree, create_merkle_tree, create_merkle_tree_capped,
+    create_merkle_tree_with_leaf_fn, open_lcmerkle_tree, LCMerkleTree, MerkleTree,
 };
 use crate::parameter_cache::ParameterSetMetadata;
 use crate::util::{data_at_node_offset, NODE_SIZE};
@@ -25,6 +32,9 @@ pub const PARALLEL_MERKLE: bool = true;
 /// ordering of the graph nodes.
 pub const BASE_DEGREE: usize = 6;
 
+/// Size in bytes of a parent in a parent table, see `Graph::write_parent_table`.
+const PARENT_SIZE: usize = 4;
+
 /// A depth robust graph.
 pub trait Graph<H: Hasher>: ::std::fmt::Debug + Clone + PartialEq + Eq {
     type Key: std::fmt::Debug;
@@ -43,6 +53,32 @@ pub trait Graph<H: Hasher>: ::std::fmt::Debug + Clone + PartialEq + Eq {
         create_merkle_tree::<H, U>(config, self.size(), data)
     }
 
+    /// Builds a merkle tree based on the given data, in memory if it fits in `max_mem_bytes` and
+    /// in the disk store described by `config` otherwise, see `create_merkle_tree_capped`.
+    fn merkle_tree_capped<'a, U: typenum::Unsigned>(
+        &self,
+        config: StoreConfig,
+        data: &'a [u8],
+        max_mem_bytes: u64,
+    ) -> Result<MerkleTree<H::Domain, H::Function, U>> {
+        create_merkle_tree_capped::<H, U>(config, self.size(), data, max_mem_bytes)
+    }
+
+    /// Builds a merkle tree based on the given data, deriving each leaf with `leaf_fn` from the
+    /// index and the bytes of the node.
+    fn merkle_tree_with_leaf_fn<'a, U, F>(
+        &self,
+        config: Option<StoreConfig>,
+        data: &'a [u8],
+        leaf_fn: F,
+    ) -> Result<MerkleTree<H::Domain, H::Function, U>>
+    where
+        U: typenum::Unsigned,
+        F: Fn(usize, &[u8]) -> H::Domain + Sync,
+    {
+        create_merkle_tree_with_leaf_fn::<H, U, F>(config, self.size(), data, leaf_fn)
+    }
+
     /// Builds a merkle tree based on the given data and level cache
     /// data.
     fn lcmerkle_tree<'a, U: typenum::Unsigned>(
@@ -79,19 +115,200 @@ pub trait Graph<H: Hasher>: ::std::fmt::Debug + Clone + PartialEq + Eq {
     /// reasons, so that the vector can be allocated outside this call.
     fn parents(&self, node: usize, parents: &mut [u32]) -> Result<()>;
 
+    /// Returns the parents of every node in `[start, end)`, in node order, as `parents` would
+    /// for each node. The nodes are processed in parallel.
+    fn parents_range(&self, start: usize, end: usize) -> Result<Vec<Vec<u32>>>
+    where
+        Self: Sync,
+    {
+        ensure!(start <= end, "invalid node range {}..{}", start, end);
+        ensure!(end <= self.size(), Error::OutOfBounds(end, self.size()));
+
+        (start..end)
+            .into_par_iter()
+            .map(|node| {
+                let mut parents = vec![0; self.degree()];
+                self.parents(node, &mut parents)?;
+                Ok(parents)
+            })
+            .collect()
+    }
+
+    /// Writes the parents of every node, in node order, as little endian `u32`s. The table can
+    /// be loaded with `PrecomputedGraph::from_parent_table`.
+    fn write_parent_table<W: Write>(&self, mut writer: W) -> Result<()> {
+        let mut parents = vec![0; self.degree()];
+        let mut bytes = vec![0; self.degree() * PARENT_SIZE];
+        for node in 0..self.size() {
+            self.parents(node, &mut parents)?;
+            LittleEndian::write_u32_into(&parents, &mut bytes);
+            writer.write_all(&bytes)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the parents of every node, in node order, each as its distance to the node,
+    /// encoded as a variable length integer. Parents are mostly close to their node, so this is
+    /// much smaller than `write_parent_table`. The table can be loaded with
+    /// `PrecomputedGraph::from_parent_table_delta`.
+    ///
+    /// Expansion parents may follow their node, so their signed distance is zigzag encoded.
+    ///
+    /// Fails with `Error::InvalidParent` if a base parent follows its node.
+    fn export_parent_table_delta<W: Write>(&self, mut writer: W) -> Result<()> {
+        let base_degree = self.degree() - self.expansion_degree();
+        let mut parents = vec![0; self.degree()];
+        let mut bytes = Vec::with_capacity(self.degree() * 2);
+        for node in 0..self.size() {
+            self.parents(node, &mut parents)?;
+            let (base_parents, exp_parents) = parents.split_at(base_degree);
+            validate_parents(node, base_parents)?;
+
+            bytes.clear();
+            for parent in base_parents {
+                write_varint((node - *parent as usize) as u64, &mut bytes);
+            }
+            for parent in exp_parents {
+                let delta = node as i64 - i64::from(*parent);
+                write_varint(((delta << 1) ^ (delta >> 63)) as u64, &mut bytes);
+            }
+            writer.write_all(&bytes)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the nodes in an order in which every node comes after all of its parents, other
+    /// than itself. Replication encodes nodes in this order, so that the parents of a node are
+    /// always encoded before the node itself.
+    ///
+    /// Parents always have a smaller index than their child in a DRG, so this defaults to
+    /// ascending order.
+    fn topo_order(&self) -> Box<dyn Iterator<Item = usize> + '_> {
+        Box::new(0..self.size())
+    }
+
+    /// Calls `f` with every node together with its parents, as `parents` would, in `topo_order`.
+    /// This is the traversal of replication: the parents of each node are visited before the
+    /// node. The parents are passed in a single buffer, which is reused for every node.
+    fn for_each_scheduled<F>(&self, mut f: F) -> Result<()>
+    where
+        F: FnMut(usize, &[u32]) -> Result<()>,
+    {
+        let mut parents = vec![0; self.degree()];
+        for node in self.topo_order() {
+            self.parents(node, &mut parents)?;
+            f(node, &parents)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the number of nodes on the longest chain of dependencies in the graph, that is the
+    /// number of encoding steps which have to happen one after the other.
+    ///
+    /// This visits every edge of the graph, which is expensive for production sized graphs.
+    fn longest_path(&self) -> Result<usize> {
+        let mut depths = vec![0; self.size()];
+        let mut parents = vec![0; self.degree()];
+        let mut longest = 0;
+
+        for node in self.topo_order() {
+            self.parents(node, &mut parents)?;
+
+            let mut depth = 0;
+            for parent in parents.iter().map(|p| *p as usize).filter(|p| *p != node) {
+                ensure!(
+                    depths[parent] > 0,
+                    "parent {} of node {} comes after it in topological order",
+                    parent,
+                    node
+                );
+                depth = cmp::max(depth, depths[parent]);
+            }
+
+            depths[node] = depth + 1;
+            longest = cmp::max(longest, depth + 1);
+        }
+
+        Ok(longest)
+    }
+
+    /// Checks that every parent of every node has a smaller index than the node, which
+    /// guarantees that the graph is acyclic. A node whose parents all are the node itself, as an
+    /// indicator of having no parents, is allowed.
+    ///
+    /// Fails with `Error::InvalidParent` holding the first offending node and parent.
+    fn validate_acyclic(&self) -> Result<()> {
+        let mut parents = vec![0; self.degree()];
+        for node in 0..self.size() {
+            self.parents(node, &mut parents)?;
+            validate_parents(node, &parents)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the number of distinct replica bytes touched when proving `challenge`, that is
+    /// the challenged node together with its parents, counting repeated nodes only once.
+    fn challenge_footprint_bytes(&self, challenge: usize) -> Result<u64> {
+        ensure!(
+            challenge < self.size(),
+            Error::OutOfBounds(challenge, self.size())
+        );
+
+        let mut parents = vec![0; self.degree()];
+        self.parents(challenge, &mut parents)?;
+
+        let nodes: BTreeSet<usize> = parents
+            .iter()
+            .map(|p| *p as usize)
+            .chain(std::iter::once(challenge))
+            .collect();
+
+        Ok((nodes.len() * NODE_SIZE) as u64)
+    }
+
+    /// Returns how much the parents of `challenges` overlap, see `OverlapStats`. The parents
+    /// are derived by `parents`, as when proving.
+    fn parent_overlap(&self, challenges: &[usize]) -> Result<OverlapStats> {
+        let mut parents = vec![0; self.degree()];
+        let mut distinct = BTreeSet::new();
+        let mut parent_slots = 0;
+
+        for challenge in challenges {
+            ensure!(
+                *challenge < self.size(),
+                Error::OutOfBounds(*challenge, self.size())
+            );
+
+            self.parents(*challenge, &mut parents)?;
+            distinct.extend(parents.iter().copied());
+            parent_slots += parents.len();
+        }
+
+        Ok(OverlapStats::new(distinct.len(), parent_slots))
+    }
+
+    /// Builds the inverse of `parents`, see `ReverseIndex`.
+    fn build_reverse_index(&self) -> Result<ReverseIndex> {
+        ReverseIndex::new(self)
+    }
+
     /// Returns the size of the graph (number of nodes).
     fn size(&self) -> usize;
 
     /// Returns the number of parents of each node in the graph.
     fn degree(&self) -> usize;
 
-    fn new(
-        nodes: usize,
-        base_degree: usize,
-        expansion_degree: usize,
-        seed: [u8; 28],
-    ) -> Result<Self>;
-    fn seed(&self) -> [u8; 28];
+    /// Returns how many of the `degree` parents of each node are expansion parents.
+    fn expansion_degree(&self) -> usize {
+        0
+    }
+
+    fn new(nodes: usize, base_degree: usize, expansion_degree: usize, seed: Seed) -> Result<Self>;
+    fn seed(&self) -> Seed;
 
     /// Creates the encoding key.
     /// The algorithm for that is `Sha256(id | encodedParentNode1 | encodedParentNode1 | ...)`.
@@ -105,20 +322,180 @@ pub trait Graph<H: Hasher>: ::std::fmt::Debug + Clone + PartialEq + Eq {
     ) -> Result<Self::Key>;
 }
 
+/// The overlap of the parents of a set of challenges, as returned by `Graph::parent_overlap`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OverlapStats {
+    /// The number of distinct nodes among all parents.
+    pub distinct_parents: usize,
+    /// The number of parents summed over all challenges, counting repeated parents.
+    pub parent_slots: usize,
+    /// The fraction of parent slots which refer to a parent already counted in another slot,
+    /// `1 - distinct_parents / parent_slots`, or zero if there are no parents.
+    pub overlap_ratio: f64,
+}
+
+impl OverlapStats {
+    fn new(distinct_parents: usize, parent_slots: usize) -> Self {
+        let overlap_ratio = if parent_slots == 0 {
+            0.
+        } else {
+            1. - distinct_parents as f64 / parent_slots as f64
+        };
+
+        OverlapStats {
+            distinct_parents,
+            parent_slots,
+            overlap_ratio,
+        }
+    }
+}
+
+/// The children of every node of a graph, that is the nodes which have it as a parent.
+///
+/// The children of a node are sorted and distinct, and a node is never its own child, even when
+/// it self references as an indicator of having no parents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReverseIndex {
+    /// The children of node `i` are `children[offsets[i]..offsets[i + 1]]`.
+    offsets: Vec<usize>,
+    children: Vec<u32>,
+}
+
+impl ReverseIndex {
+    fn new<H: Hasher, G: Graph<H> + ?Sized>(graph: &G) -> Result<Self> {
+        let nodes = graph.size();
+        let mut parents = Vec::with_capacity(graph.degree());
+
+        // First count the children of each node, then place them, visiting the nodes in
+        // ascending order so that the children of each node end up sorted.
+        let mut counts = vec![0usize; nodes];
+        for node in 0..nodes {
+            for parent in distinct_parents(graph, node, &mut parents)? {
+                counts[*parent as usize] += 1;
+            }
+        }
+
+        let mut offsets = Vec::with_capacity(nodes + 1);
+        offsets.push(0);
+        for count in &counts {
+            offsets.push(offsets[offsets.len() - 1] + count);
+        }
+
+        let mut next = offsets[..nodes].to_vec();
+        let mut children = vec![0; offsets[nodes]];
+        for node in 0..nodes {
+            for parent in distinct_parents(graph, node, &mut parents)? {
+                let parent = *parent as usize;
+                children[next[parent]] = node as u32;
+                next[parent] += 1;
+            }
+        }
+
+        Ok(ReverseIndex { offsets, children })
+    }
+
+    /// Returns the children of `node`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `node` is not a node of the graph.
+    pub fn children(&self, node: usize) -> &[u32] {
+        &self.children[self.offsets[node]..self.offsets[node + 1]]
+    }
+}
+
+/// Writes the parents of `node` into `parents` and returns them sorted, without repetitions and
+/// without `node` itself.
+fn distinct_parents<'a, H: Hasher, G: Graph<H> + ?Sized>(
+    graph: &G,
+    node: usize,
+    parents: &'a mut Vec<u32>,
+) -> Result<&'a [u32]> {
+    parents.resize(graph.degree(), 0);
+    graph.parents(node, parents)?;
+    parents.sort_unstable();
+    parents.dedup();
+    parents.retain(|parent| *parent as usize != node);
+
+    Ok(parents.as_slice())
+}
+
+/// Checks that every parent of `node` precedes it, see `Graph::validate_acyclic`.
+fn validate_parents(node: usize, parents: &[u32]) -> Result<()> {
+    // A node whose parents all are the node itself has no parents.
+    if parents.iter().all(|parent| *parent as usize == node) {
+        return Ok(());
+    }
+
+    for parent in parents.iter().map(|p| *p as usize) {
+        ensure!(parent < node, Error::InvalidParent(node, parent));
+    }
+
+    Ok(())
+}
+
 pub fn graph_height<U: typenum::Unsigned>(number_of_leafs: usize) -> usize {
     merkletree::merkle::get_merkle_tree_height(number_of_leafs, U::to_usize())
 }
 
+/// Derives the random number generator from which the parents of a node are sampled.
+pub trait ParentRng:
+    ::std::fmt::Debug + Clone + Copy + PartialEq + Eq + Send + Sync + 'static
+{
+    type Rng: RngCore;
+
+    fn rng(seed: &Seed, node: usize) -> Self::Rng;
+}
+
+/// Seeds ChaCha with the graph seed followed by the little endian `u32` node index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChaChaParentRng;
+
+impl ParentRng for ChaChaParentRng {
+    type Rng = ChaChaRng;
+
+    fn rng(seed: &Seed, node: usize) -> Self::Rng {
+        // seed = self.seed | node
+        let mut chacha_seed = [0u8; 32];
+        chacha_seed[..SEED_SIZE].copy_from_slice(seed.as_bytes());
+        chacha_seed[SEED_SIZE..].copy_from_slice(&(node as u32).to_le_bytes());
+
+        ChaChaRng::from_seed(chacha_seed)
+    }
+}
+
+/// Seeds ChaCha with `Sha256(seed | node)`, the node index being a little endian `u64`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sha256ParentRng;
+
+impl ParentRng for Sha256ParentRng {
+    type Rng = ChaChaRng;
+
+    fn rng(seed: &Seed, node: usize) -> Self::Rng {
+        let mut hasher = Sha256::new();
+        hasher.input(seed.as_bytes());
+        hasher.input(&(node as u64).to_le_bytes());
+
+        let mut chacha_seed = [0u8; 32];
+        chacha_seed.copy_from_slice(hasher.result().as_ref());
+
+        ChaChaRng::from_seed(chacha_seed)
+    }
+}
+
 /// Bucket sampling algorithm.
+///
+/// The parents of each node are sampled from the rng derived by `R`.
 #[derive(Clone, Debug, PartialEq, Eq, Copy)]
-pub struct BucketGraph<H: Hasher> {
+pub struct BucketGraph<H: Hasher, R: ParentRng = ChaChaParentRng> {
     nodes: usize,
     base_degree: usize,
-    seed: [u8; 28],
+    seed: Seed,
     _h: PhantomData<H>,
+    _r: PhantomData<R>,
 }
 
-impl<H: Hasher> ParameterSetMetadata for BucketGraph<H> {
+impl<H: Hasher, R: ParentRng> ParameterSetMetadata for BucketGraph<H, R> {
     fn identifier(&self) -> String {
         // NOTE: Seed is not included because it does not influence parameter generation.
         format!(
@@ -134,7 +511,7 @@ impl<H: Hasher> ParameterSetMetadata for BucketGraph<H> {
     }
 }
 
-impl<H: Hasher> Graph<H> for BucketGraph<H> {
+impl<H: Hasher, R: ParentRng> Graph<H> for BucketGraph<H, R> {
     type Key = H::Domain;
 
     fn create_key(
@@ -145,21 +522,15 @@ impl<H: Hasher> Graph<H> for BucketGraph<H> {
         base_parents_data: &[u8],
         _exp_parents_data: Option<&[u8]>,
     ) -> Result<Self::Key> {
-        let mut hasher = Sha256::new();
-        hasher.input(AsRef::<[u8]>::as_ref(id));
-
-        // The hash is about the parents, hence skip if a node doesn't have any parents
-        if node != parents[0] as usize {
-            for parent in parents.iter() {
-                let offset = data_at_node_offset(*parent as usize);
-                hasher.input(&base_parents_data[offset..offset + NODE_SIZE]);
-            }
-        }
-
-        let hash = hasher.result();
-        Ok(bytes_into_fr_repr_safe(hash.as_ref()).into())
+        create_bucket_key::<H>(id, node, parents, self.degree(), base_parents_data)
     }
 
+    /// Each of the first `m - 1` parents is sampled from the rng of the node: a bucket `j` is
+    /// drawn, then a back distance uniformly within the bucket. A parent landing on the node
+    /// itself is replaced by the immediate predecessor, and the last parent always is the
+    /// immediate predecessor. There are no weighted choices, so nothing is ever tied: equal
+    /// draws yield duplicate parents, which are kept in draw order. The parents are never sorted
+    /// or deduplicated, as that would change the keys of existing replicas.
     #[inline]
     fn parents(&self, node: usize, parents: &mut [u32]) -> Result<()> {
         let m = self.degree();
@@ -179,37 +550,34 @@ impl<H: Hasher> Graph<H> for BucketGraph<H> {
                 // The degree `m` minus 1; the degree without the immediate predecessor node.
                 let m_prime = m - 1;
 
-                // seed = self.seed | node
-                let mut seed = [0u8; 32];
-                seed[..28].copy_from_slice(&self.seed);
-                seed[28..].copy_from_slice(&(node as u32).to_le_bytes());
-                let mut rng = ChaChaRng::from_seed(seed);
+                // All arithmetic is done on `u64`, and only `u64`s are drawn from the rng, so that
+                // the sampled parents do not depend on the width of `usize`. Parents are `u32`, so
+                // `node * m_prime + k` cannot overflow.
+                ensure!(
+                    node <= u32::max_value() as usize,
+                    Error::OutOfBounds(node, u32::max_value() as usize)
+                );
+                let meta_node = node as u64 * m_prime as u64;
+
+                let mut rng = R::rng(&self.seed, node);
 
                 for (k, parent) in parents.iter_mut().take(m_prime).enumerate() {
-                    *parent = 0;
                     // Iterate over `m_prime` number of meta nodes for the i-th real node. Simulate
                     // the edges that we would add from previous graph nodes. If any edge is added
                     // from a meta node of j-th real node then add edge (j,i).
-                   /* let logi = ((node * m_prime) as f32).log2().floor() as usize;
-                    let j = rng.gen::<usize>() % logi;
-                    let jj = cmp::min(node * m_prime + k, 1 << (j + 1));
+                    let logi = 63 - u64::from(meta_node.leading_zeros());
+                    let meta = meta_node + k as u64;
+                    let j = rng.gen::<u64>() % logi;
+                    let jj = cmp::min(meta, 1u64 << (j + 1));
                     let back_dist = rng.gen_range(cmp::max(jj >> 1, 2), jj + 1);
-                    let out = (node * m_prime + k - back_dist) / m_prime;
+                    let out = ((meta - back_dist) / m_prime as u64) as usize;
 
                     // remove self references and replace with reference to previous node
-                    if out == node {
-                        *parent = (node - 1) as u32;
-                    } else {
-                        ensure!(
-                            out <= node,
-                            "Parent node must be smaller than current node."
-                        );
-                        *parent = out as u32;
-                    }*/
+                    *parent = if out == node { node - 1 } else { out } as u32;
                 }
 
                 // Add the immediate predecessor as a parent to ensure unique topological ordering.
-                parents[m_prime] = 0;//(node - 1) as u32;
+                parents[m_prime] = (node - 1) as u32;
                 Ok(())
             }
         }
@@ -226,29 +594,341 @@ impl<H: Hasher> Graph<H> for BucketGraph<H> {
         self.base_degree
     }
 
-    fn seed(&self) -> [u8; 28] {
+    fn seed(&self) -> Seed {
         self.seed
     }
 
-    fn new(
-        nodes: usize,
-        base_degree: usize,
-        expansion_degree: usize,
-        seed: [u8; 28],
-    ) -> Result<Self> {
+    fn new(nodes: usize, base_degree: usize, expansion_degree: usize, seed: Seed) -> Result<Self> {
         ensure!(expansion_degree == 0, "Expension degree must be zero.");
+        ensure!(base_degree > 0, "Degree must be at least one.");
 
         Ok(BucketGraph {
             nodes,
             base_degree,
             seed,
             _h: PhantomData,
+            _r: PhantomData,
         })
     }
 }
 
-pub fn new_seed() -> [u8; 28] {
-    OsRng.gen()
+/// Derives the encoding key of `node` from its first `degree` parents, see `Graph::create_key`.
+fn create_bucket_key<H: Hasher>(
+    id: &H::Domain,
+    node: usize,
+    parents: &[u32],
+    degree: usize,
+    base_parents_data: &[u8],
+) -> Result<H::Domain> {
+    // The key covers exactly `degree` parents, so that changing the degree of the graph
+    // changes the key derivation accordingly, in the vanilla proofs and in the circuit.
+    ensure!(
+        parents.len() >= degree,
+        "expected {} parents for node {}, got {}",
+        degree,
+        node,
+        parents.len()
+    );
+
+    let mut hasher = Sha256::new();
+    hasher.input(AsRef::<[u8]>::as_ref(id));
+
+    // The hash is about the parents, hence skip if a node doesn't have any parents
+    if node != parents[0] as usize {
+        for parent in parents[..degree].iter() {
+            let offset = data_at_node_offset(*parent as usize);
+            hasher.input(&base_parents_data[offset..offset + NODE_SIZE]);
+        }
+    }
+
+    let hash = hasher.result();
+    Ok(bytes_into_fr_repr_safe(hash.as_ref()).into())
+}
+
+/// A graph serving the parents of its nodes from a table loaded in memory, instead of deriving
+/// them. This trades memory and disk for CPU, for deployments which precompute the table once
+/// with `Graph::write_parent_table`.
+///
+/// The encoding key is derived as for a `BucketGraph`, so a table written from a `BucketGraph`
+/// yields the same replicas and proofs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PrecomputedGraph<H: Hasher> {
+    nodes: usize,
+    degree: usize,
+    /// How many of the `degree` parents of each node are expansion parents, following the base
+    /// parents.
+    expansion_degree: usize,
+    seed: Seed,
+    /// The parents of node `i` are `parents[i * degree..(i + 1) * degree]`.
+    parents: Arc<Vec<u32>>,
+    _h: PhantomData<H>,
+}
+
+impl<H: Hasher> PrecomputedGraph<H> {
+    /// Loads a parent table written by `Graph::write_parent_table` for a graph of `nodes` nodes
+    /// of degree `degree`, the last `expansion_degree` of which are expansion parents. The table
+    /// must hold exactly `nodes * degree` parents.
+    ///
+    /// The seed the table was derived from is not part of it, so `seed` returns all zeros.
+    pub fn from_parent_table<R: Read>(
+        nodes: usize,
+        degree: usize,
+        expansion_degree: usize,
+        mut table: R,
+    ) -> Result<Self> {
+        ensure!(degree > 0, "Degree must be at least one.");
+
+        let mut bytes = Vec::new();
+        table.read_to_end(&mut bytes)?;
+
+        let expected_len = nodes * degree * PARENT_SIZE;
+        ensure!(
+            bytes.len() == expected_len,
+            "invalid parent table length {}, expected {} for {} nodes of degree {}",
+            bytes.len(),
+            expected_len,
+            nodes,
+            degree
+        );
+
+        let mut parents = vec![0; nodes * degree];
+        LittleEndian::read_u32_into(&bytes, &mut parents);
+
+        Self::from_parents(nodes, degree, expansion_degree, parents)
+    }
+
+    /// Loads a parent table written by `Graph::export_parent_table_delta` for a graph of `nodes`
+    /// nodes of degree `degree`, the last `expansion_degree` of which are expansion parents. The
+    /// table must hold exactly `nodes * degree` parents.
+    ///
+    /// The seed the table was derived from is not part of it, so `seed` returns all zeros.
+    pub fn from_parent_table_delta<R: Read>(
+        nodes: usize,
+        degree: usize,
+        expansion_degree: usize,
+        mut table: R,
+    ) -> Result<Self> {
+        ensure!(degree > 0, "Degree must be at least one.");
+
+        let mut bytes = Vec::new();
+        table.read_to_end(&mut bytes)?;
+
+        let base_degree = degree.saturating_sub(expansion_degree);
+        let mut offset = 0;
+        let mut parents = Vec::with_capacity(nodes * degree);
+        for node in 0..nodes {
+            for i in 0..degree {
+                let (delta, len) = read_varint(&bytes[offset..]).with_context(|| {
+                    format!("invalid delta parent table at node {} of {}", node, nodes)
+                })?;
+                let parent = if i < base_degree {
+                    (node as u64).checked_sub(delta)
+                } else {
+                    let signed = (delta >> 1) as i64 ^ -((delta & 1) as i64);
+                    (node as i64)
+                        .checked_sub(signed)
+                        .filter(|parent| *parent >= 0)
+                        .map(|parent| parent as u64)
+                };
+                let parent = parent
+                    .filter(|parent| *parent < nodes as u64)
+                    .with_context(|| {
+                        format!("invalid delta {} for node {} in parent table", delta, node)
+                    })?;
+
+                parents.push(parent as u32);
+                offset += len;
+            }
+        }
+        ensure!(
+            offset == bytes.len(),
+            "{} trailing bytes after the delta parent table of {} nodes of degree {}",
+            bytes.len() - offset,
+            nodes,
+            degree
+        );
+
+        Self::from_parents(nodes, degree, expansion_degree, parents)
+    }
+
+    /// Only the base parents are validated to precede their node, as expansion parents, as of a
+    /// `StackedGraph`, reference the previous layer.
+    fn from_parents(
+        nodes: usize,
+        degree: usize,
+        expansion_degree: usize,
+        parents: Vec<u32>,
+    ) -> Result<Self> {
+        ensure!(
+            expansion_degree < degree,
+            "expansion degree {} must be smaller than the degree {}",
+            expansion_degree,
+            degree
+        );
+
+        let base_degree = degree - expansion_degree;
+        for (node, parents) in parents.chunks(degree).enumerate() {
+            validate_parents(node, &parents[..base_degree])?;
+        }
+
+        Ok(PrecomputedGraph {
+            nodes,
+            degree,
+            expansion_degree,
+            seed: Seed::new([0; SEED_SIZE]),
+            parents: Arc::new(parents),
+            _h: PhantomData,
+        })
+    }
+}
+
+/// Appends `value` to `out` as an LEB128 variable length integer: 7 bits per byte, least
+/// significant first, with the high bit set on every byte but the last.
+fn write_varint(mut value: u64, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Reads an LEB128 variable length integer written by `write_varint` from the start of
+/// `bytes`, returning it and the number of bytes it took.
+fn read_varint(bytes: &[u8]) -> Result<(u64, usize)> {
+    let mut value = 0u64;
+    for (i, byte) in bytes.iter().enumerate() {
+        ensure!(i < 10, "variable length integer is too long");
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok((value, i + 1));
+        }
+    }
+
+    bail!("truncated variable length integer")
+}
+
+impl<H: Hasher> ParameterSetMetadata for PrecomputedGraph<H> {
+    fn identifier(&self) -> String {
+        format!(
+            "drgraph::PrecomputedGraph{{size: {}; degree: {}; hasher: {}}}",
+            self.nodes,
+            self.degree,
+            H::name(),
+        )
+    }
+
+    fn sector_size(&self) -> u64 {
+        (self.nodes * NODE_SIZE) as u64
+    }
+}
+
+impl<H: Hasher> Graph<H> for PrecomputedGraph<H> {
+    type Key = H::Domain;
+
+    fn create_key(
+        &self,
+        id: &H::Domain,
+        node: usize,
+        parents: &[u32],
+        base_parents_data: &[u8],
+        _exp_parents_data: Option<&[u8]>,
+    ) -> Result<Self::Key> {
+        create_bucket_key::<H>(id, node, parents, self.degree, base_parents_data)
+    }
+
+    #[inline]
+    fn parents(&self, node: usize, parents: &mut [u32]) -> Result<()> {
+        ensure!(node < self.nodes, Error::OutOfBounds(node, self.nodes));
+        ensure!(
+            parents.len() >= self.degree,
+            "expected room for {} parents, got {}",
+            self.degree,
+            parents.len()
+        );
+
+        let start = node * self.degree;
+        parents[..self.degree].copy_from_slice(&self.parents[start..start + self.degree]);
+        Ok(())
+    }
+
+    #[inline]
+    fn size(&self) -> usize {
+        self.nodes
+    }
+
+    #[inline]
+    fn degree(&self) -> usize {
+        self.degree
+    }
+
+    fn expansion_degree(&self) -> usize {
+        self.expansion_degree
+    }
+
+    fn seed(&self) -> Seed {
+        self.seed
+    }
+
+    /// Computes the table of the `BucketGraph` with the same parameters.
+    fn new(nodes: usize, base_degree: usize, expansion_degree: usize, seed: Seed) -> Result<Self> {
+        let graph = BucketGraph::<H>::new(nodes, base_degree, expansion_degree, seed)?;
+        let parents = graph.parents_range(0, nodes)?.concat();
+
+        Ok(PrecomputedGraph {
+            nodes,
+            degree: base_degree,
+            expansion_degree: 0,
+            seed,
+            parents: Arc::new(parents),
+            _h: PhantomData,
+        })
+    }
+}
+
+/// Size in bytes of a graph seed. The seed is extended with the 4 byte node index to form the
+/// 32 byte ChaCha seed used to sample the parents of that node.
+pub const SEED_SIZE: usize = 28;
+
+/// The seed from which the parents of a graph are derived.
+///
+/// The seed is 28 bytes rather than 32, because `ChaChaParentRng` fills the 32 byte ChaCha key
+/// with the seed followed by the 4 byte node index. A wider seed would change the parents of
+/// every existing graph, and so every parameter set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Seed([u8; SEED_SIZE]);
+
+impl Seed {
+    pub const fn new(bytes: [u8; SEED_SIZE]) -> Self {
+        Seed(bytes)
+    }
+
+    /// Creates a seed from an untyped byte slice, which must be exactly `SEED_SIZE` bytes long.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        ensure!(
+            bytes.len() == SEED_SIZE,
+            "invalid seed length {}, expected {}",
+            bytes.len(),
+            SEED_SIZE
+        );
+
+        let mut seed = [0u8; SEED_SIZE];
+        seed.copy_from_slice(bytes);
+        Ok(Seed(seed))
+    }
+
+    pub fn as_bytes(&self) -> &[u8; SEED_SIZE] {
+        &self.0
+    }
+}
+
+impl From<[u8; SEED_SIZE]> for Seed {
+    fn from(bytes: [u8; SEED_SIZE]) -> Self {
+        Seed(bytes)
+    }
+}
+
+pub fn new_seed() -> Seed {
+    Seed(OsRng.gen())
 }
 
 #[cfg(test)]
@@ -257,9 +937,13 @@ mod tests {
 
     use memmap::MmapMut;
     use memmap::MmapOptions;
+    use rand_xorshift::XorShiftRng;
 
     use crate::drgraph::new_seed;
-    use crate::hasher::{Blake2sHasher, PedersenHasher, PoseidonHasher, Sha256Hasher};
+    use crate::hasher::{
+        Blake2sHasher, Domain, HashFunction, PedersenHasher, PoseidonHasher, Sha256Hasher,
+    };
+    use crate::porep::stacked::{StackedBucketGraph, EXP_DEGREE};
 
     // Create and return an object of MmapMut backed by in-memory copy of data.
     pub fn mmap_from(data: &[u8]) -> MmapMut {
@@ -323,6 +1007,285 @@ mod tests {
         graph_bucket::<PedersenHasher>();
     }
 
+    #[test]
+    fn validate_acyclic() {
+        for nodes in &[1, 2, 64] {
+            let g = BucketGraph::<PedersenHasher>::new(*nodes, BASE_DEGREE, 0, new_seed()).unwrap();
+            g.validate_acyclic().unwrap();
+        }
+
+        let g = StackedBucketGraph::<PedersenHasher>::new_stacked(
+            64,
+            BASE_DEGREE,
+            EXP_DEGREE,
+            new_seed(),
+        )
+        .unwrap();
+        g.validate_acyclic().unwrap();
+
+        validate_parents(3, &[0, 1, 2, 2]).unwrap();
+        validate_parents(3, &[3, 3, 3, 3]).unwrap();
+        validate_parents(0, &[0, 0, 0, 0]).unwrap();
+
+        // A self reference among other parents is not the no-parents marker.
+        let err = validate_parents(3, &[0, 1, 2, 3]).unwrap_err();
+        match err.downcast_ref::<Error>() {
+            Some(Error::InvalidParent(node, parent)) => assert_eq!((*node, *parent), (3, 3)),
+            _ => panic!("unexpected error: {}", err),
+        }
+
+        let err = validate_parents(3, &[0, 1, 5, 2, 4]).unwrap_err();
+        match err.downcast_ref::<Error>() {
+            Some(Error::InvalidParent(node, parent)) => assert_eq!((*node, *parent), (3, 5)),
+            _ => panic!("unexpected error: {}", err),
+        }
+    }
+
+    #[test]
+    fn parent_overlap() {
+        let g = BucketGraph::<PedersenHasher>::new(8, BASE_DEGREE, 0, pinned_seed()).unwrap();
+
+        // The parents are [0, 1, 1, 1, 1, 1], [4, 4, 4, 4, 3, 4] and [5, 6, 6, 6, 4, 6], so 3
+        // challenges with 6 parent slots each share 6 distinct parents.
+        let stats = g.parent_overlap(&[2, 5, 7]).unwrap();
+        assert_eq!(stats.distinct_parents, 6);
+        assert_eq!(stats.parent_slots, 18);
+        assert!((stats.overlap_ratio - 12. / 18.).abs() < 1e-9);
+
+        let empty = g.parent_overlap(&[]).unwrap();
+        assert_eq!(empty, OverlapStats::new(0, 0));
+
+        assert!(g.parent_overlap(&[2, 8]).is_err());
+    }
+
+    fn assert_same_parents<H: Hasher, G: Graph<H>, P: Graph<H>>(graph: &G, loaded: &P) {
+        assert_eq!(loaded.size(), graph.size());
+        assert_eq!(loaded.degree(), graph.degree());
+
+        let mut expected = vec![0; graph.degree()];
+        let mut actual = vec![0; graph.degree()];
+        for node in 0..graph.size() {
+            graph.parents(node, &mut expected).unwrap();
+            loaded.parents(node, &mut actual).unwrap();
+            assert_eq!(actual, expected, "different parents for node {}", node);
+        }
+    }
+
+    #[test]
+    fn precomputed_graph_from_parent_table() {
+        let nodes = 64;
+
+        let g = BucketGraph::<PedersenHasher>::new(nodes, BASE_DEGREE, 0, new_seed()).unwrap();
+        let mut table = Vec::new();
+        g.write_parent_table(&mut table).unwrap();
+        assert_eq!(table.len(), nodes * BASE_DEGREE * 4);
+
+        let loaded = PrecomputedGraph::<PedersenHasher>::from_parent_table(
+            nodes,
+            BASE_DEGREE,
+            0,
+            &table[..],
+        )
+        .unwrap();
+        assert_same_parents(&g, &loaded);
+
+        let computed =
+            PrecomputedGraph::<PedersenHasher>::new(nodes, BASE_DEGREE, 0, g.seed()).unwrap();
+        assert_same_parents(&g, &computed);
+
+        let stacked = StackedBucketGraph::<PedersenHasher>::new_stacked(
+            nodes,
+            BASE_DEGREE,
+            EXP_DEGREE,
+            new_seed(),
+        )
+        .unwrap();
+        let mut table = Vec::new();
+        stacked.write_parent_table(&mut table).unwrap();
+        let loaded = PrecomputedGraph::<PedersenHasher>::from_parent_table(
+            nodes,
+            stacked.degree(),
+            EXP_DEGREE,
+            &table[..],
+        )
+        .unwrap();
+        assert_same_parents(&stacked, &loaded);
+        assert_eq!(loaded.expansion_degree(), EXP_DEGREE);
+
+        // Expansion parents reference the previous layer, so they may follow their node and
+        // are rejected when taken for base parents.
+        assert!(PrecomputedGraph::<PedersenHasher>::from_parent_table(
+            nodes,
+            stacked.degree(),
+            0,
+            &table[..],
+        )
+        .is_err());
+
+        let mut parents = vec![0; loaded.degree()];
+        assert!(loaded.parents(nodes, &mut parents).is_err());
+    }
+
+    #[test]
+    fn precomputed_graph_invalid_table() {
+        let nodes = 8;
+        let g = BucketGraph::<PedersenHasher>::new(nodes, BASE_DEGREE, 0, new_seed()).unwrap();
+        let mut table = Vec::new();
+        g.write_parent_table(&mut table).unwrap();
+
+        let err = PrecomputedGraph::<PedersenHasher>::from_parent_table(
+            nodes,
+            BASE_DEGREE,
+            0,
+            &table[..table.len() - 4],
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string().contains("invalid parent table length"),
+            "{}",
+            err
+        );
+        assert!(PrecomputedGraph::<PedersenHasher>::from_parent_table(
+            nodes + 1,
+            BASE_DEGREE,
+            0,
+            &table[..]
+        )
+        .is_err());
+        assert!(PrecomputedGraph::<PedersenHasher>::from_parent_table(
+            nodes,
+            BASE_DEGREE,
+            BASE_DEGREE,
+            &table[..]
+        )
+        .is_err());
+
+        // A parent following its child is rejected.
+        LittleEndian::write_u32(&mut table[2 * BASE_DEGREE * 4..], 5);
+        assert!(PrecomputedGraph::<PedersenHasher>::from_parent_table(
+            nodes,
+            BASE_DEGREE,
+            0,
+            &table[..]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn parent_table_delta_roundtrip() {
+        let nodes = 64;
+        let stacked = StackedBucketGraph::<PedersenHasher>::new_stacked(
+            nodes,
+            BASE_DEGREE,
+            EXP_DEGREE,
+            new_seed(),
+        )
+        .unwrap();
+
+        // Expansion parents reference the previous layer, so some follow their node.
+        let mut parents = vec![0; stacked.degree()];
+        assert!((0..nodes).any(|node| {
+            stacked.parents(node, &mut parents).unwrap();
+            parents[BASE_DEGREE..]
+                .iter()
+                .any(|parent| *parent as usize > node)
+        }));
+
+        let mut raw = Vec::new();
+        stacked.write_parent_table(&mut raw).unwrap();
+        let mut delta = Vec::new();
+        stacked.export_parent_table_delta(&mut delta).unwrap();
+        assert!(
+            delta.len() < raw.len() / 2,
+            "delta table of {} bytes, raw table of {} bytes",
+            delta.len(),
+            raw.len()
+        );
+
+        let loaded = PrecomputedGraph::<PedersenHasher>::from_parent_table_delta(
+            nodes,
+            stacked.degree(),
+            EXP_DEGREE,
+            &delta[..],
+        )
+        .unwrap();
+        assert_same_parents(&stacked, &loaded);
+
+        assert!(PrecomputedGraph::<PedersenHasher>::from_parent_table_delta(
+            nodes,
+            stacked.degree(),
+            EXP_DEGREE,
+            &delta[..delta.len() - 1],
+        )
+        .is_err());
+        assert!(PrecomputedGraph::<PedersenHasher>::from_parent_table_delta(
+            nodes - 1,
+            stacked.degree(),
+            EXP_DEGREE,
+            &delta[..],
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn varint_roundtrip() {
+        let values = [0, 1, 127, 128, 300, 1 << 32, u64::max_value()];
+        for value in &values {
+            let mut bytes = Vec::new();
+            write_varint(*value, &mut bytes);
+            assert_eq!(read_varint(&bytes).unwrap(), (*value, bytes.len()));
+            assert!(read_varint(&bytes[..bytes.len() - 1]).is_err());
+        }
+    }
+
+    #[test]
+    fn challenge_footprint_bytes() {
+        let g = BucketGraph::<PedersenHasher>::new(8, BASE_DEGREE, 0, pinned_seed()).unwrap();
+        let challenge = 5;
+
+        let mut parents = vec![0; g.degree()];
+        g.parents(challenge, &mut parents).unwrap();
+        let mut read: Vec<usize> = parents.iter().map(|p| *p as usize).collect();
+        read.push(challenge);
+        read.sort();
+        read.dedup();
+
+        let footprint = g.challenge_footprint_bytes(challenge).unwrap();
+        assert_eq!(footprint, (read.len() * NODE_SIZE) as u64);
+        // The parents of node 5 are [4, 4, 4, 4, 3, 4], so only three nodes are read.
+        assert_eq!(footprint, 3 * NODE_SIZE as u64);
+
+        // The first node is its own parent.
+        assert_eq!(g.challenge_footprint_bytes(0).unwrap(), NODE_SIZE as u64);
+
+        assert!(g.challenge_footprint_bytes(8).is_err());
+    }
+
+    #[test]
+    fn parents_range_matches_parents() {
+        let g = StackedBucketGraph::<PedersenHasher>::new_stacked(
+            256,
+            BASE_DEGREE,
+            EXP_DEGREE,
+            new_seed(),
+        )
+        .unwrap();
+
+        let (start, end) = (13, 200);
+        let bulk = g.parents_range(start, end).unwrap();
+        assert_eq!(bulk.len(), end - start);
+
+        let mut parents = vec![0; g.degree()];
+        for (node, bulk_parents) in (start..end).zip(bulk.iter()) {
+            g.parents(node, &mut parents).unwrap();
+            assert_eq!(bulk_parents, &parents, "parents differ for node {}", node);
+        }
+
+        assert!(g.parents_range(5, 5).unwrap().is_empty());
+        assert!(g.parents_range(6, 5).is_err());
+        assert!(g.parents_range(0, 257).is_err());
+    }
+
     fn gen_proof<H: Hasher, U: typenum::Unsigned>(config: Option<StoreConfig>) {
         let leafs = 64;
         let g = BucketGraph::<H>::new(leafs, BASE_DEGREE, 0, new_seed()).unwrap();
@@ -335,6 +1298,88 @@ mod tests {
         assert!(proof.validate::<H::Function>().expect("failed to validate"));
     }
 
+    fn merkle_tree_with_leaf_fn<H: Hasher>() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let leafs = 64;
+        let g = BucketGraph::<H>::new(leafs, BASE_DEGREE, 0, new_seed()).unwrap();
+        let data: Vec<u8> = (0..leafs)
+            .flat_map(|_| H::Domain::random(rng).into_bytes())
+            .collect();
+
+        let tree = g.merkle_tree::<typenum::U2>(None, &data).unwrap();
+
+        let identity = g
+            .merkle_tree_with_leaf_fn::<typenum::U2, _>(None, &data, |_, d| {
+                H::Domain::try_from_bytes(d).unwrap()
+            })
+            .unwrap();
+        assert_eq!(identity.root(), tree.root());
+
+        // Tag each leaf with its index, by hashing the index together with the node.
+        let tagged = g
+            .merkle_tree_with_leaf_fn::<typenum::U2, _>(None, &data, |i, d| {
+                let mut index = [0u8; NODE_SIZE];
+                index[..8].copy_from_slice(&(i as u64).to_le_bytes());
+                H::Function::hash2(
+                    &H::Domain::try_from_bytes(&index).unwrap(),
+                    &H::Domain::try_from_bytes(d).unwrap(),
+                )
+            })
+            .unwrap();
+        assert_ne!(tagged.root(), tree.root());
+        assert_eq!(tagged.leafs(), tree.leafs());
+    }
+
+    #[test]
+    fn merkle_tree_with_leaf_fn_pedersen() {
+        merkle_tree_with_leaf_fn::<PedersenHasher>();
+    }
+
+    #[test]
+    fn merkle_tree_with_leaf_fn_poseidon() {
+        merkle_tree_with_leaf_fn::<PoseidonHasher>();
+    }
+
+    fn merkle_tree_capped<H: Hasher>() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let leafs = 64;
+        let g = BucketGraph::<H>::new(leafs, BASE_DEGREE, 0, new_seed()).unwrap();
+        let data: Vec<u8> = (0..leafs)
+            .flat_map(|_| H::Domain::random(rng).into_bytes())
+            .collect();
+        let tree = g.merkle_tree::<typenum::U2>(None, &data).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = StoreConfig::new(dir.path(), "capped".to_string(), 0);
+        let data_path = StoreConfig::data_path(&config.path, &config.id);
+
+        // The whole tree of 127 nodes fits, nothing is written to the store of `config`.
+        let in_memory = g
+            .merkle_tree_capped::<typenum::U2>(config.clone(), &data, 127 * NODE_SIZE as u64)
+            .unwrap();
+        assert_eq!(in_memory.root(), tree.root());
+        assert!(!std::path::Path::new(&data_path).exists());
+
+        // A small cap spills the tree to the store of `config`.
+        let spilled = g
+            .merkle_tree_capped::<typenum::U2>(config, &data, 4 * NODE_SIZE as u64)
+            .unwrap();
+        assert_eq!(spilled.root(), tree.root());
+        assert!(std::path::Path::new(&data_path).exists());
+    }
+
+    #[test]
+    fn merkle_tree_capped_pedersen() {
+        merkle_tree_capped::<PedersenHasher>();
+    }
+
+    #[test]
+    fn merkle_tree_capped_poseidon() {
+        merkle_tree_capped::<PoseidonHasher>();
+    }
+
     #[test]
     fn gen_proof_pedersen_binary() {
         gen_proof::<PedersenHasher, typenum::U2>(None);
@@ -384,4 +1429,291 @@ mod tests {
     fn gen_proof_poseidon_oct() {
         gen_proof::<PoseidonHasher, typenum::U8>(None);
     }
+
+    #[test]
+    fn typed_seed_is_deterministic() {
+        let bytes = [7u8; SEED_SIZE];
+        let seed = Seed::from_bytes(&bytes).unwrap();
+        assert_eq!(seed, Seed::new(bytes));
+
+        let g1 = BucketGraph::<PedersenHasher>::new(256, BASE_DEGREE, 0, seed).unwrap();
+        let g2 = BucketGraph::<PedersenHasher>::new(256, BASE_DEGREE, 0, seed).unwrap();
+        assert_eq!(g1, g2);
+        assert_eq!(g1.seed(), seed);
+
+        let mut p1 = vec![0; BASE_DEGREE];
+        let mut p2 = vec![0; BASE_DEGREE];
+        for node in 0..256 {
+            g1.parents(node, &mut p1).unwrap();
+            g2.parents(node, &mut p2).unwrap();
+            assert_eq!(p1, p2, "parents differ for node {}", node);
+        }
+    }
+
+    #[test]
+    fn seed_from_bytes_rejects_wrong_length() {
+        assert!(Seed::from_bytes(&[0u8; SEED_SIZE - 1]).is_err());
+        assert!(Seed::from_bytes(&[0u8; SEED_SIZE + 1]).is_err());
+        assert!(Seed::from_bytes(&[0u8; 32]).is_err());
+        assert!(Seed::from_bytes(&[]).is_err());
+        assert!(Seed::from_bytes(&[0u8; SEED_SIZE]).is_ok());
+    }
+
+    /// The seed for which parents are hardcoded in tests.
+    fn pinned_seed() -> Seed {
+        Seed::new([
+            0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23,
+            24, 25, 26, 27,
+        ])
+    }
+
+    #[test]
+    fn bucket_graph_parents_are_pinned() {
+        // Parents must be identical on all targets, so they are hardcoded for a fixed seed.
+        let g = BucketGraph::<PedersenHasher>::new(1 << 10, BASE_DEGREE, 0, pinned_seed()).unwrap();
+
+        let expected: [(usize, [u32; BASE_DEGREE]); 6] = [
+            (0, [0, 0, 0, 0, 0, 0]),
+            (1, [0, 0, 0, 0, 0, 0]),
+            (2, [0, 1, 1, 1, 1, 1]),
+            (17, [16, 16, 16, 16, 16, 16]),
+            (512, [505, 496, 511, 511, 316, 511]),
+            (1023, [953, 1021, 776, 931, 830, 1022]),
+        ];
+
+        let mut parents = [0u32; BASE_DEGREE];
+        for (node, expected_parents) in expected.iter() {
+            g.parents(*node, &mut parents).unwrap();
+            assert_eq!(&parents, expected_parents, "node {}", node);
+        }
+    }
+
+    #[test]
+    fn bucket_graph_duplicate_parents_are_kept() {
+        // The first nodes have fewer predecessors than parents, so their parents always repeat.
+        // Duplicates must be kept in draw order rather than be deduplicated or reordered. The
+        // sampled parents of node 4 are [2, 1, 4, 4, 4], the self references being replaced by
+        // the immediate predecessor in place.
+        let g = BucketGraph::<PedersenHasher>::new(1 << 10, BASE_DEGREE, 0, pinned_seed()).unwrap();
+
+        let expected: [(usize, [u32; BASE_DEGREE]); 4] = [
+            (2, [0, 1, 1, 1, 1, 1]),
+            (3, [2, 1, 2, 2, 2, 2]),
+            (4, [2, 1, 3, 3, 3, 3]),
+            (5, [4, 4, 4, 4, 3, 4]),
+        ];
+
+        let mut parents = [0u32; BASE_DEGREE];
+        for (node, expected_parents) in expected.iter() {
+            g.parents(*node, &mut parents).unwrap();
+            assert_eq!(&parents, expected_parents, "node {}", node);
+            assert!(parents.iter().all(|parent| (*parent as usize) < *node));
+
+            let mut deduplicated = parents.to_vec();
+            deduplicated.dedup();
+            assert!(deduplicated.len() < parents.len(), "node {}", node);
+        }
+    }
+
+    fn graph_for_each_scheduled<H: Hasher, G: Graph<H>>(g: &G) {
+        let base_degree = g.degree() - g.expansion_degree();
+        let mut seen = vec![false; g.size()];
+        let mut parents = vec![0; g.degree()];
+        g.for_each_scheduled(|node, schedule_parents| {
+            assert!(!seen[node], "node {} repeated", node);
+
+            g.parents(node, &mut parents).unwrap();
+            assert_eq!(schedule_parents, &parents[..], "parents of node {}", node);
+
+            // Expansion parents are labels of the previous layer, so only the base parents have
+            // to come before their node.
+            for parent in parents[..base_degree]
+                .iter()
+                .map(|p| *p as usize)
+                .filter(|p| *p != node)
+            {
+                assert!(
+                    seen[parent],
+                    "parent {} of node {} comes after it",
+                    parent, node
+                );
+            }
+
+            seen[node] = true;
+            Ok(())
+        })
+        .unwrap();
+
+        assert!(seen.iter().all(|seen| *seen), "nodes missing from schedule");
+    }
+
+    #[test]
+    fn for_each_scheduled() {
+        let g = BucketGraph::<PedersenHasher>::new(256, BASE_DEGREE, 0, new_seed()).unwrap();
+        graph_for_each_scheduled(&g);
+
+        let stacked = StackedBucketGraph::<PedersenHasher>::new_stacked(
+            256,
+            BASE_DEGREE,
+            EXP_DEGREE,
+            new_seed(),
+        )
+        .unwrap();
+        graph_for_each_scheduled(&stacked);
+
+        // An error of the callback stops the traversal.
+        let mut visited = 0;
+        let err = g
+            .for_each_scheduled(|node, _| {
+                visited += 1;
+                ensure!(node < 10, "stop at node {}", node);
+                Ok(())
+            })
+            .unwrap_err();
+        assert_eq!(err.to_string(), "stop at node 10");
+        assert_eq!(visited, 11);
+    }
+
+    fn graph_topo_order<H: Hasher>() {
+        let g = BucketGraph::<H>::new(256, BASE_DEGREE, 0, new_seed()).unwrap();
+
+        let mut position = vec![None; g.size()];
+        for (i, node) in g.topo_order().enumerate() {
+            assert!(position[node].is_none(), "node {} repeated", node);
+            position[node] = Some(i);
+        }
+
+        let mut parents = vec![0; g.degree()];
+        for node in 0..g.size() {
+            let node_position = position[node].expect("node missing from topo order");
+            g.parents(node, &mut parents).unwrap();
+
+            for parent in parents.iter().map(|p| *p as usize).filter(|p| *p != node) {
+                assert!(
+                    position[parent].unwrap() < node_position,
+                    "parent {} of node {} comes after it",
+                    parent,
+                    node
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn graph_topo_order_pedersen() {
+        graph_topo_order::<PedersenHasher>();
+    }
+
+    #[test]
+    fn graph_topo_order_sha256() {
+        graph_topo_order::<Sha256Hasher>();
+    }
+
+    #[test]
+    fn default_parent_rng_reproduces_parents() {
+        let seed = new_seed();
+        let default = BucketGraph::<PedersenHasher>::new(64, BASE_DEGREE, 0, seed).unwrap();
+        let chacha =
+            BucketGraph::<PedersenHasher, ChaChaParentRng>::new(64, BASE_DEGREE, 0, seed).unwrap();
+
+        let mut expected = [0u32; BASE_DEGREE];
+        let mut actual = [0u32; BASE_DEGREE];
+        for node in 0..default.size() {
+            default.parents(node, &mut expected).unwrap();
+            chacha.parents(node, &mut actual).unwrap();
+            assert_eq!(actual, expected, "node {}", node);
+        }
+
+        // The rng is seeded as before the parent rng was configurable.
+        for node in &[2, 5, 63] {
+            let mut chacha_seed = [0u8; 32];
+            chacha_seed[..SEED_SIZE].copy_from_slice(seed.as_bytes());
+            chacha_seed[SEED_SIZE..].copy_from_slice(&(*node as u32).to_le_bytes());
+            let expected = ChaChaRng::from_seed(chacha_seed).gen::<[u64; 4]>();
+
+            assert_eq!(
+                ChaChaParentRng::rng(&seed, *node).gen::<[u64; 4]>(),
+                expected
+            );
+            assert_ne!(
+                Sha256ParentRng::rng(&seed, *node).gen::<[u64; 4]>(),
+                expected
+            );
+        }
+
+        // Another parent rng keeps the sampler, but yields other parents than those pinned in
+        // `bucket_graph_parents_are_pinned`.
+        let sha256 = BucketGraph::<PedersenHasher, Sha256ParentRng>::new(
+            1 << 10,
+            BASE_DEGREE,
+            0,
+            pinned_seed(),
+        )
+        .unwrap();
+        let mut parents = [0u32; BASE_DEGREE];
+        sha256.parents(512, &mut parents).unwrap();
+        assert_eq!(parents, [511, 493, 500, 511, 511, 511]);
+        sha256.parents(1023, &mut parents).unwrap();
+        assert_eq!(parents, [279, 998, 955, 1022, 1022, 1022]);
+    }
+
+    #[test]
+    fn longest_path() {
+        // Every node has its immediate predecessor as a parent, so the longest chain goes through
+        // all nodes.
+        for (nodes, expected) in &[(1, 1), (2, 2), (64, 64)] {
+            let g = BucketGraph::<PedersenHasher>::new(*nodes, BASE_DEGREE, 0, new_seed()).unwrap();
+            assert_eq!(g.longest_path().unwrap(), *expected, "{} nodes", nodes);
+        }
+    }
+
+    fn check_reverse_index<H: Hasher, G: Graph<H>>(g: &G) {
+        let index = g.build_reverse_index().unwrap();
+
+        let mut parents = vec![0; g.degree()];
+        let mut edges = 0;
+        for node in 0..g.size() {
+            g.parents(node, &mut parents).unwrap();
+            for parent in parents.iter().filter(|p| **p as usize != node) {
+                assert!(
+                    index.children(*parent as usize).contains(&(node as u32)),
+                    "node {} missing from the children of {}",
+                    node,
+                    parent
+                );
+            }
+
+            let children = index.children(node);
+            assert!(children.windows(2).all(|w| w[0] < w[1]), "node {}", node);
+            for child in children {
+                assert_ne!(*child as usize, node);
+                g.parents(*child as usize, &mut parents).unwrap();
+                assert!(
+                    parents.contains(&(node as u32)),
+                    "{} is not a parent of {}",
+                    node,
+                    child
+                );
+            }
+            edges += children.len();
+        }
+
+        assert!(edges > 0);
+    }
+
+    #[test]
+    fn reverse_index_is_inverse_of_parents() {
+        check_reverse_index(
+            &BucketGraph::<PedersenHasher>::new(64, BASE_DEGREE, 0, new_seed()).unwrap(),
+        );
+        check_reverse_index(
+            &StackedBucketGraph::<PedersenHasher>::new_stacked(
+                64,
+                BASE_DEGREE,
+                EXP_DEGREE,
+                new_seed(),
+            )
+            .unwrap(),
+        );
+    }
 }
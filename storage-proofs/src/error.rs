@@ -13,6 +13,8 @@ pub enum Error {
     BadPieceCommitment,
     #[error("Out of bounds access {} > {}", _0, _1)]
     OutOfBounds(usize, usize),
+    #[error("node {} has parent {}, which does not precede it", _0, _1)]
+    InvalidParent(usize, usize),
     #[error("mismatch of data, node_size and nodes {} != {} * {}", _0, _1, _2)]
     InvalidMerkleTreeArgs(usize, usize, usize),
     #[error("{}", _0)]
@@ -25,6 +27,10 @@ pub enum Error {
     MalformedInput,
     #[error("malformed merkle tree")]
     MalformedMerkleTree,
+    #[error("merkle tree node {} does not match the hash of its children", _0)]
+    CorruptMerkleTree(usize),
+    #[error("merkle tree leaf {} does not match the data", _0)]
+    LeafMismatch(usize),
     #[error("invalid input size")]
     InvalidInputSize,
     #[error("merkle tree generation error: {}", _0)]
@@ -0,0 +1,78 @@
+use ff::Field;
+use paired::bls12_381::Fr;
+
+use crate::hasher::Domain;
+
+/// Returns `a + b`, computed in the scalar field.
+pub fn add_domains<T: Domain>(a: T, b: T) -> T {
+    let mut result: Fr = a.into();
+    result.add_assign(&b.into());
+    result.into()
+}
+
+/// Returns `a - b`, computed in the scalar field.
+pub fn sub_domains<T: Domain>(a: T, b: T) -> T {
+    let mut result: Fr = a.into();
+    result.sub_assign(&b.into());
+    result.into()
+}
+
+/// Returns `-a`, computed in the scalar field.
+pub fn negate_domain<T: Domain>(a: T) -> T {
+    let mut result: Fr = a.into();
+    result.negate();
+    result.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::hasher::{Blake2sHasher, Hasher, PedersenHasher, PoseidonHasher, Sha256Hasher};
+
+    fn test_field_ops<H: Hasher>() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        for _ in 0..20 {
+            let a = H::Domain::random(rng);
+            let b = H::Domain::random(rng);
+
+            assert_eq!(sub_domains(add_domains(a, b), b), a);
+            assert_eq!(add_domains(sub_domains(a, b), b), a);
+            assert_eq!(add_domains(a, negate_domain(a)), Fr::zero().into());
+            assert_eq!(sub_domains(a, b), add_domains(a, negate_domain(b)));
+
+            let (a_fr, b_fr): (Fr, Fr) = (a.into(), b.into());
+            let mut sum = a_fr;
+            sum.add_assign(&b_fr);
+            let mut difference = a_fr;
+            difference.sub_assign(&b_fr);
+
+            assert_eq!(add_domains(a, b), sum.into());
+            assert_eq!(sub_domains(a, b), difference.into());
+        }
+    }
+
+    #[test]
+    fn field_ops_pedersen() {
+        test_field_ops::<PedersenHasher>();
+    }
+
+    #[test]
+    fn field_ops_poseidon() {
+        test_field_ops::<PoseidonHasher>();
+    }
+
+    #[test]
+    fn field_ops_sha256() {
+        test_field_ops::<Sha256Hasher>();
+    }
+
+    #[test]
+    fn field_ops_blake2s() {
+        test_field_ops::<Blake2sHasher>();
+    }
+}
@@ -1,7 +1,7 @@
 use bellperson::{ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
 use paired::Engine;
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 #[derive(Clone, Copy)]
 struct OrderedVariable(Variable);
@@ -66,6 +66,19 @@ impl<E: Engine> MetricCS<E> {
         self.inputs.len()
     }
 
+    /// Returns the number of constraints grouped by the top-level namespace they were
+    /// enforced in. Constraints enforced outside of any namespace are keyed by their own name.
+    pub fn constraints_by_prefix(&self) -> BTreeMap<String, usize> {
+        let mut result = BTreeMap::new();
+
+        for &(ref _a, ref _b, ref _c, ref name) in &self.constraints {
+            let prefix = name.split('/').next().unwrap_or_default();
+            *result.entry(prefix.to_string()).or_insert(0) += 1;
+        }
+
+        result
+    }
+
     pub fn pretty_print_list(&self) -> Vec<String> {
         let mut result = Vec::new();
 
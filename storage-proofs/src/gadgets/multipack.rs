@@ -5,15 +5,38 @@ use bellperson::gadgets::{
 use bellperson::{ConstraintSystem, SynthesisError};
 use ff::{Field, PrimeField, ScalarEngine};
 
-/// Takes a sequence of booleans and exposes them as a single compact Num.
-pub fn pack_bits<E, CS>(mut cs: CS, bits: &[Boolean]) -> Result<AllocatedNum<E>, SynthesisError>
+/// Takes a sequence of booleans and exposes them as a single compact Num. Only the first
+/// `CAPACITY` bits are packed, so that the packing is injective.
+pub fn pack_bits<E, CS>(cs: CS, bits: &[Boolean]) -> Result<AllocatedNum<E>, SynthesisError>
+where
+    E: ScalarEngine,
+    CS: ConstraintSystem<E>,
+{
+    pack_bits_limited(cs, bits, E::Fr::CAPACITY as usize)
+}
+
+/// Like `pack_bits`, but packs all of the bits, so that the result is their little endian value
+/// reduced modulo the field order.
+pub fn pack_all_bits<E, CS>(cs: CS, bits: &[Boolean]) -> Result<AllocatedNum<E>, SynthesisError>
+where
+    E: ScalarEngine,
+    CS: ConstraintSystem<E>,
+{
+    pack_bits_limited(cs, bits, bits.len())
+}
+
+fn pack_bits_limited<E, CS>(
+    mut cs: CS,
+    bits: &[Boolean],
+    limit: usize,
+) -> Result<AllocatedNum<E>, SynthesisError>
 where
     E: ScalarEngine,
     CS: ConstraintSystem<E>,
 {
     let mut num = Num::<E>::zero();
     let mut coeff = E::Fr::one();
-    for bit in bits.iter().take(E::Fr::CAPACITY as usize) {
+    for bit in bits.iter().take(limit) {
         num = num.add_bool_with_coeff(CS::one(), &bit, coeff);
 
         coeff.double();
@@ -1,6 +1,6 @@
 use std::marker::PhantomData;
 
-use anyhow::ensure;
+use anyhow::{ensure, Context};
 use bellperson::gadgets::boolean::{AllocatedBit, Boolean};
 use bellperson::gadgets::{multipack, num};
 use bellperson::{Circuit, ConstraintSystem, SynthesisError};
@@ -15,7 +15,7 @@ use crate::error::Result;
 use crate::gadgets::constraint;
 use crate::gadgets::insertion::insert;
 use crate::gadgets::variables::Root;
-use crate::hasher::{HashFunction, Hasher, PoseidonArity, PoseidonEngine};
+use crate::hasher::{Domain, HashFunction, Hasher, PoseidonArity, PoseidonEngine};
 use crate::parameter_cache::{CacheableParameters, ParameterSetMetadata};
 use crate::por::PoR;
 use crate::proof::ProofScheme;
@@ -159,7 +159,7 @@ where
 
         if let Some(commitment) = pub_inputs.commitment {
             ensure!(!pub_params.private, "Params must be public");
-            inputs.push(commitment.into());
+            inputs.push(commitment.try_into_fr().context("invalid commitment")?);
         } else {
             ensure!(pub_params.private, "Params must be private");
         }
@@ -5,18 +5,104 @@ use anyhow::ensure;
 use bellperson::gadgets::{blake2s as blake2s_circuit, boolean, num};
 use bellperson::{ConstraintSystem, SynthesisError};
 use blake2s_simd::{Hash as Blake2sHash, Params as Blake2s, State};
+use byteorder::{ByteOrder, LittleEndian};
 use ff::{Field, PrimeField, PrimeFieldRepr};
 use fil_sapling_crypto::jubjub::JubjubEngine;
+use lazy_static::lazy_static;
 use merkletree::hash::{Algorithm, Hashable};
 use merkletree::merkle::Element;
 use paired::bls12_381::{Bls12, Fr, FrRepr};
 use rand::RngCore;
 use serde::{Deserialize, Serialize};
+use zeroize::DefaultIsZeroes;
 
-use super::{Domain, HashFunction, Hasher};
+use super::{CostHint, Domain, HashFunction, Hasher};
 use crate::crypto::sloth;
 use crate::error::*;
 use crate::gadgets::multipack;
+use crate::settings;
+
+lazy_static! {
+    /// The packing used by `Blake2sHasher`, read from the `blake2s_packing` setting.
+    pub static ref BLAKE2S_PACKING: Blake2sPacking =
+        settings::SETTINGS.lock().unwrap().blake2s_packing;
+}
+
+/// How a 32 byte Blake2s digest, read as a little endian integer, is mapped into the field.
+///
+/// The packing changes every hash, and thereby every commitment, so trees built with one
+/// packing can only be verified with the same packing. It is part of `Blake2sHasher::name`, and
+/// so of the identifiers of the parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Blake2sPacking {
+    /// Clears the two most significant bits of the digest.
+    TruncateTopBits,
+    /// Reduces the whole digest modulo the field order.
+    ModReduce,
+}
+
+impl Default for Blake2sPacking {
+    fn default() -> Self {
+        Blake2sPacking::TruncateTopBits
+    }
+}
+
+impl Blake2sPacking {
+    /// Maps `digest` into the field.
+    pub fn pack(self, digest: &[u8]) -> Blake2sDomain {
+        let mut res = Blake2sDomain::default();
+        res.0.copy_from_slice(&digest[..32]);
+
+        match self {
+            Blake2sPacking::TruncateTopBits => res.trim_to_fr32(),
+            Blake2sPacking::ModReduce => {
+                // Both halves are below the field order, so the value is `low + high * 2^128`.
+                let low = FrRepr([
+                    LittleEndian::read_u64(&res.0[..8]),
+                    LittleEndian::read_u64(&res.0[8..16]),
+                    0,
+                    0,
+                ]);
+                let high = FrRepr([
+                    LittleEndian::read_u64(&res.0[16..24]),
+                    LittleEndian::read_u64(&res.0[24..]),
+                    0,
+                    0,
+                ]);
+                let shift = Fr::from_repr(FrRepr([0, 0, 1, 0])).expect("2^128 is in the field");
+
+                let mut value = Fr::from_repr(high).expect("high half is in the field");
+                value.mul_assign(&shift);
+                value.add_assign(&Fr::from_repr(low).expect("low half is in the field"));
+                res = value.into();
+            }
+        }
+
+        res
+    }
+
+    /// Returns the suffix of `Blake2sHasher::name` for this packing. It is empty for the
+    /// default, so that the identifiers of existing parameters do not change.
+    pub fn identifier(self) -> &'static str {
+        match self {
+            Blake2sPacking::TruncateTopBits => "",
+            Blake2sPacking::ModReduce => "-mod-reduce",
+        }
+    }
+
+    /// Packs the bits of a digest computed in a circuit, like `pack`.
+    fn pack_circuit<E: JubjubEngine, CS: ConstraintSystem<E>>(
+        self,
+        cs: CS,
+        bits: &[boolean::Boolean],
+    ) -> std::result::Result<num::AllocatedNum<E>, SynthesisError> {
+        match self {
+            Blake2sPacking::TruncateTopBits => multipack::pack_bits(cs, bits),
+            Blake2sPacking::ModReduce => multipack::pack_all_bits(cs, bits),
+        }
+    }
+}
 
 #[derive(Default, Copy, Clone, PartialEq, Eq, Debug)]
 pub struct Blake2sHasher {}
@@ -25,8 +111,16 @@ impl Hasher for Blake2sHasher {
     type Domain = Blake2sDomain;
     type Function = Blake2sFunction;
 
+    const CIRCUIT_CONSTRAINTS_PER_HASH: usize = 21_519;
+
     fn name() -> String {
-        "Blake2sHasher".into()
+        format!("Blake2sHasher{}", BLAKE2S_PACKING.identifier())
+    }
+
+    fn vanilla_cost_hint() -> CostHint {
+        CostHint {
+            nanos_per_hash: 250,
+        }
     }
 
     fn sloth_encode(key: &Self::Domain, ciphertext: &Self::Domain) -> Result<Self::Domain> {
@@ -152,6 +246,9 @@ impl From<Blake2sDomain> for Fr {
     }
 }
 
+// The default domain element is all zero bytes.
+impl DefaultIsZeroes for Blake2sDomain {}
+
 impl Domain for Blake2sDomain {
     fn serialize(&self) -> Vec<u8> {
         self.0.to_vec()
@@ -186,11 +283,7 @@ impl Domain for Blake2sDomain {
 
 impl Into<Blake2sDomain> for Blake2sHash {
     fn into(self) -> Blake2sDomain {
-        let mut res = Blake2sDomain::default();
-        res.0[..].copy_from_slice(self.as_ref());
-        res.trim_to_fr32();
-
-        res
+        BLAKE2S_PACKING.pack(self.as_ref())
     }
 }
 
@@ -263,7 +356,7 @@ impl HashFunction<Blake2sDomain> for Blake2sFunction {
         let alloc_bits =
             blake2s_circuit::blake2s(cs.namespace(|| "hash"), &bits[..], &personalization)?;
 
-        multipack::pack_bits(cs.namespace(|| "pack"), &alloc_bits)
+        BLAKE2S_PACKING.pack_circuit(cs.namespace(|| "pack"), &alloc_bits)
     }
 
     fn hash2_circuit<E, CS>(
@@ -338,3 +431,74 @@ impl From<Blake2sDomain> for [u8; 32] {
         val.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    #[test]
+    fn packing_strategies() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        for _ in 0..10 {
+            let mut digest = [0u8; 32];
+            rng.fill_bytes(&mut digest);
+
+            for packing in &[Blake2sPacking::TruncateTopBits, Blake2sPacking::ModReduce] {
+                let packed = packing.pack(&digest);
+                assert_eq!(packed, packing.pack(&digest));
+                assert!(Blake2sDomain::try_from_bytes(&packed.0).is_ok());
+            }
+        }
+
+        // A digest with the top bits set differs between the packings.
+        let digest = [0xff; 32];
+        let truncated = Blake2sPacking::TruncateTopBits.pack(&digest);
+        let reduced = Blake2sPacking::ModReduce.pack(&digest);
+        assert_eq!(truncated.0[31], 0b0011_1111);
+        assert_ne!(truncated, reduced);
+
+        // 2^256 - 1 = 2^254 - 1 + 3 * 2^254, and 2^254 = 2^254 - r mod r.
+        let mut expected = Fr::from(truncated);
+        let mut top = Fr::from_repr(FrRepr([0, 0, 0, 1 << 62])).unwrap();
+        top.mul_assign(&Fr::from_repr(FrRepr::from(3)).unwrap());
+        expected.add_assign(&top);
+        assert_eq!(Fr::from(reduced), expected);
+
+        // Below the field order both packings are the identity.
+        let small = Blake2sDomain::from(Fr::from_repr(FrRepr::from(42)).unwrap());
+        assert_eq!(Blake2sPacking::TruncateTopBits.pack(&small.0), small);
+        assert_eq!(Blake2sPacking::ModReduce.pack(&small.0), small);
+    }
+
+    #[test]
+    fn packing_circuit_matches_native() {
+        use crate::gadgets::TestConstraintSystem;
+        use bellperson::gadgets::boolean::{AllocatedBit, Boolean};
+
+        let digest = [0xfe; 32];
+        for packing in &[Blake2sPacking::TruncateTopBits, Blake2sPacking::ModReduce] {
+            let mut cs = TestConstraintSystem::<Bls12>::new();
+            let bits: Vec<Boolean> = digest
+                .iter()
+                .flat_map(|byte| (0..8).map(move |i| (byte >> i) & 1 == 1))
+                .enumerate()
+                .map(|(i, bit)| {
+                    Boolean::from(
+                        AllocatedBit::alloc(cs.namespace(|| format!("bit {}", i)), Some(bit))
+                            .unwrap(),
+                    )
+                })
+                .collect();
+
+            let packed = packing
+                .pack_circuit(cs.namespace(|| "pack"), &bits)
+                .unwrap();
+            assert!(cs.is_satisfied());
+            assert_eq!(packed.get_value().unwrap(), packing.pack(&digest).into());
+        }
+    }
+}
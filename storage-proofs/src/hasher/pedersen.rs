@@ -11,11 +11,12 @@ use merkletree::hash::{Algorithm as LightAlgorithm, Hashable};
 use merkletree::merkle::Element;
 use paired::bls12_381::{Bls12, Fr, FrRepr};
 use serde::{Deserialize, Serialize};
+use zeroize::DefaultIsZeroes;
 
 use crate::crypto::{pedersen, sloth};
 use crate::error::{Error, Result};
 use crate::gadgets::pedersen::{pedersen_compression_num, pedersen_md_no_padding};
-use crate::hasher::{Domain, HashFunction, Hasher};
+use crate::hasher::{CostHint, Domain, HashFunction, Hasher};
 
 #[derive(Default, Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct PedersenHasher {}
@@ -24,8 +25,19 @@ impl Hasher for PedersenHasher {
     type Domain = PedersenDomain;
     type Function = PedersenFunction;
 
+    const CIRCUIT_CONSTRAINTS_PER_HASH: usize = 1_371;
+
     fn name() -> String {
-        "PedersenHasher".into()
+        format!(
+            "PedersenHasher{}",
+            pedersen::window_size_identifier(*pedersen::PEDERSEN_WINDOW_SIZE)
+        )
+    }
+
+    fn vanilla_cost_hint() -> CostHint {
+        CostHint {
+            nanos_per_hash: 40_000,
+        }
     }
 
     #[inline]
@@ -138,6 +150,9 @@ fn as_ref<'a>(src: &'a [u64; 4]) -> &'a [u8] {
     }
 }
 
+// The default domain element is all zero bytes.
+impl DefaultIsZeroes for PedersenDomain {}
+
 impl Domain for PedersenDomain {
     // QUESTION: When, if ever, should serialize and into_bytes return different results?
     // The definitions here at least are equivalent.
@@ -6,7 +6,7 @@ use crate::hasher::types::{
     PoseidonArity, PoseidonEngine, PoseidonMDArity, POSEIDON_CONSTANTS_1, POSEIDON_CONSTANTS_16,
     POSEIDON_CONSTANTS_2, POSEIDON_CONSTANTS_4, POSEIDON_CONSTANTS_8, POSEIDON_MD_CONSTANTS,
 };
-use crate::hasher::{Domain, HashFunction, Hasher};
+use crate::hasher::{CostHint, Domain, HashFunction, Hasher};
 use anyhow::ensure;
 use bellperson::gadgets::{boolean, num};
 use bellperson::{ConstraintSystem, SynthesisError};
@@ -20,6 +20,7 @@ use neptune::circuit::poseidon_hash;
 use neptune::poseidon::Poseidon;
 use paired::bls12_381::{Bls12, Fr, FrRepr};
 use serde::{Deserialize, Serialize};
+use zeroize::DefaultIsZeroes;
 
 #[derive(Default, Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct PoseidonHasher {}
@@ -28,10 +29,18 @@ impl Hasher for PoseidonHasher {
     type Domain = PoseidonDomain;
     type Function = PoseidonFunction;
 
+    const CIRCUIT_CONSTRAINTS_PER_HASH: usize = 241;
+
     fn name() -> String {
         "PoseidonHasher".into()
     }
 
+    fn vanilla_cost_hint() -> CostHint {
+        CostHint {
+            nanos_per_hash: 15_000,
+        }
+    }
+
     #[inline]
     fn sloth_encode(key: &Self::Domain, ciphertext: &Self::Domain) -> Result<Self::Domain> {
         // Unrapping here is safe; `Fr` elements and hash domain elements are the same byte length.
@@ -142,6 +151,9 @@ fn as_ref<'a>(src: &'a [u64; 4]) -> &'a [u8] {
     }
 }
 
+// The default domain element is all zero bytes.
+impl DefaultIsZeroes for PoseidonDomain {}
+
 impl Domain for PoseidonDomain {
     // QUESTION: When, if ever, should serialize and into_bytes return different results?
     // The definitions here at least are equivalent.
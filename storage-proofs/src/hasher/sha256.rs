@@ -11,8 +11,9 @@ use paired::bls12_381::{Bls12, Fr, FrRepr};
 use rand::RngCore;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use zeroize::DefaultIsZeroes;
 
-use super::{Domain, HashFunction, Hasher};
+use super::{CostHint, Domain, HashFunction, Hasher};
 use crate::crypto::sloth;
 use crate::error::*;
 use crate::gadgets::multipack;
@@ -24,10 +25,18 @@ impl Hasher for Sha256Hasher {
     type Domain = Sha256Domain;
     type Function = Sha256Function;
 
+    const CIRCUIT_CONSTRAINTS_PER_HASH: usize = 45_387;
+
     fn name() -> String {
         "Sha256Hasher".into()
     }
 
+    fn vanilla_cost_hint() -> CostHint {
+        CostHint {
+            nanos_per_hash: 300,
+        }
+    }
+
     fn sloth_encode(key: &Self::Domain, ciphertext: &Self::Domain) -> Result<Self::Domain> {
         // TODO: validate this is how sloth should work in this case
         let k = (*key).into();
@@ -118,6 +127,9 @@ impl From<Sha256Domain> for Fr {
     }
 }
 
+// The default domain element is all zero bytes.
+impl DefaultIsZeroes for Sha256Domain {}
+
 impl Domain for Sha256Domain {
     fn serialize(&self) -> Vec<u8> {
         self.0.to_vec()
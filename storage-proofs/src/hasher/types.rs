@@ -1,6 +1,8 @@
+use anyhow::{ensure, Context};
 use lazy_static::lazy_static;
 
 use crate::error::Result;
+use crate::fr32::{bytes_into_fr, bytes_into_fr_repr_safe};
 use bellperson::gadgets::{boolean, num};
 use bellperson::{ConstraintSystem, SynthesisError};
 use fil_sapling_crypto::jubjub::JubjubEngine;
@@ -13,6 +15,8 @@ use paired::bls12_381::{Bls12, Fr, FrRepr};
 use paired::Engine;
 use serde::de::DeserializeOwned;
 use serde::ser::Serialize;
+use sha2::{Digest, Sha256};
+use zeroize::DefaultIsZeroes;
 
 pub type PoseidonBinaryArity = U2;
 pub type PoseidonQuadArity = U4;
@@ -139,6 +143,7 @@ pub trait Domain:
     + DeserializeOwned
     + Element
     + std::hash::Hash
+    + DefaultIsZeroes
 {
     fn serialize(&self) -> Vec<u8>;
     fn into_bytes(&self) -> Vec<u8>;
@@ -147,6 +152,29 @@ pub trait Domain:
     fn write_bytes(&self, _: &mut [u8]) -> Result<()>;
 
     fn random<R: rand::RngCore>(rng: &mut R) -> Self;
+
+    /// Converts the element into the field element it represents. Unlike `Into<Fr>`, this fails
+    /// instead of panicking if the element is not a canonical field element.
+    fn try_into_fr(&self) -> Result<Fr> {
+        bytes_into_fr::<Bls12>(&self.into_bytes())
+    }
+
+    /// Returns the element as a 32 byte big endian word, the encoding of a `uint256` in the EVM.
+    fn to_uint256_be(&self) -> [u8; 32] {
+        let mut word = [0u8; 32];
+        word.copy_from_slice(&self.into_bytes());
+        word.reverse();
+        word
+    }
+
+    /// Reads an element from a 32 byte big endian word. Fails if the word is not a canonical
+    /// field element, i.e. not smaller than the modulus.
+    fn from_uint256_be(word: &[u8; 32]) -> Result<Self> {
+        let mut bytes = *word;
+        bytes.reverse();
+
+        Ok(bytes_into_fr::<Bls12>(&bytes)?.into())
+    }
 }
 
 pub trait HashFunction<T: Domain>:
@@ -243,8 +271,308 @@ pub trait Hasher: Clone + ::std::fmt::Debug + Eq + Default + Send + Sync {
     type Domain: Domain + LightHashable<Self::Function> + AsRef<Self::Domain>;
     type Function: HashFunction<Self::Domain>;
 
+    /// The number of constraints of `hash2_circuit`, the hash of two nodes, including the
+    /// decomposition of its inputs into bits for the bit oriented hashers. Meant for estimating
+    /// circuit sizes without synthesizing them.
+    const CIRCUIT_CONSTRAINTS_PER_HASH: usize;
+
     fn sloth_encode(key: &Self::Domain, ciphertext: &Self::Domain) -> Result<Self::Domain>;
     fn sloth_decode(key: &Self::Domain, ciphertext: &Self::Domain) -> Result<Self::Domain>;
 
     fn name() -> String;
+
+    /// The approximate cost of hashing outside of the circuit, see `CostHint`.
+    fn vanilla_cost_hint() -> CostHint;
+
+    /// Hashes a message of any length into the domain, e.g. to derive replica ids. The message is
+    /// hashed with SHA-256, and the top two bits of the digest are cleared, so the result is a
+    /// valid field element for every domain.
+    fn hash_bytes(data: &[u8]) -> Self::Domain {
+        let hash = Sha256::digest(data);
+        bytes_into_fr_repr_safe(hash.as_ref()).into()
+    }
+}
+
+/// An estimate of the cost of a hasher outside of the circuit, for planning replication and
+/// proving before running anything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CostHint {
+    /// The approximate time of a single hash of two nodes on one core, in nanoseconds.
+    pub nanos_per_hash: u64,
+}
+
+impl CostHint {
+    /// Returns the estimated single core time of `hashes` hashes of two nodes.
+    pub fn duration(&self, hashes: u64) -> std::time::Duration {
+        std::time::Duration::from_nanos(self.nanos_per_hash.saturating_mul(hashes))
+    }
+}
+
+/// Converts an element of the domain of `H1` into the domain of `H2`, through the field element
+/// it represents. Fails if the element is not a valid field element, which is possible for the
+/// byte oriented domains, such as the one of `Sha256Hasher`.
+pub fn convert_domain<H1: Hasher, H2: Hasher>(domain: H1::Domain) -> Result<H2::Domain> {
+    let fr = bytes_into_fr::<Bls12>(&domain.into_bytes())?;
+
+    Ok(fr.into())
+}
+
+/// The byte order in which a commitment encodes its field element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommitmentByteOrder {
+    /// Little endian, as returned by `Domain::into_bytes`.
+    LittleEndian,
+    /// Big endian, as returned by `Domain::to_uint256_be`.
+    BigEndian,
+}
+
+/// Compares two commitments by the field elements they encode, rather than by their bytes, so
+/// that commitments in different byte orders can be compared.
+///
+/// A commitment must be exactly `Domain::byte_len()` bytes in its declared byte order. A
+/// commitment which is not a canonical field element is rejected rather than reduced.
+pub fn commitments_equal<H: Hasher>(
+    a: &[u8],
+    a_order: CommitmentByteOrder,
+    b: &[u8],
+    b_order: CommitmentByteOrder,
+) -> Result<bool> {
+    let a = parse_commitment::<H>(a, a_order).context("invalid first commitment")?;
+    let b = parse_commitment::<H>(b, b_order).context("invalid second commitment")?;
+
+    Ok(a == b)
+}
+
+/// Parses the field element `raw` encodes in `order`.
+fn parse_commitment<H: Hasher>(raw: &[u8], order: CommitmentByteOrder) -> Result<H::Domain> {
+    let len = H::Domain::byte_len();
+    ensure!(
+        raw.len() == len,
+        "commitment of {} bytes, expected {} bytes",
+        raw.len(),
+        len
+    );
+
+    let mut bytes = raw.to_vec();
+    if order == CommitmentByteOrder::BigEndian {
+        bytes.reverse();
+    }
+    let fr = bytes_into_fr::<Bls12>(&bytes).context("commitment is not a field element")?;
+
+    Ok(fr.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ff::{Field, PrimeField, PrimeFieldRepr};
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::crypto::pedersen::JJ_PARAMS;
+    use crate::gadgets::TestConstraintSystem;
+    use crate::hasher::{Blake2sHasher, PedersenHasher, PoseidonHasher, Sha256Hasher};
+
+    #[test]
+    fn convert_domain_roundtrip() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        for _ in 0..10 {
+            let fr = Fr::random(rng);
+            let pedersen: <PedersenHasher as Hasher>::Domain = fr.into();
+
+            let poseidon = convert_domain::<PedersenHasher, PoseidonHasher>(pedersen).unwrap();
+            let blake2s = convert_domain::<PoseidonHasher, Blake2sHasher>(poseidon).unwrap();
+            let sha256 = convert_domain::<Blake2sHasher, Sha256Hasher>(blake2s).unwrap();
+            let back = convert_domain::<Sha256Hasher, PedersenHasher>(sha256).unwrap();
+
+            assert_eq!(back, pedersen);
+            assert_eq!(Into::<Fr>::into(poseidon), fr);
+            assert_eq!(Into::<Fr>::into(blake2s), fr);
+            assert_eq!(Into::<Fr>::into(sha256), fr);
+        }
+    }
+
+    #[test]
+    fn convert_domain_rejects_non_field_elements() {
+        let mut bytes = [0xff; 32];
+        bytes[31] = 0x7f;
+        let blake2s = <Blake2sHasher as Hasher>::Domain::try_from_bytes(&bytes).unwrap();
+
+        assert!(convert_domain::<Blake2sHasher, PoseidonHasher>(blake2s).is_err());
+    }
+
+    #[test]
+    fn uint256_be_roundtrip() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        fn roundtrip<H: Hasher>(fr: Fr) {
+            let domain: H::Domain = fr.into();
+            let word = domain.to_uint256_be();
+
+            let mut expected = [0u8; 32];
+            fr.into_repr().write_be(&mut expected[..]).unwrap();
+            assert_eq!(word, expected);
+            assert_eq!(H::Domain::from_uint256_be(&word).unwrap(), domain);
+        }
+
+        for fr in vec![Fr::zero(), Fr::one()]
+            .into_iter()
+            .chain((0..10).map(|_| Fr::random(rng)))
+        {
+            roundtrip::<PedersenHasher>(fr);
+            roundtrip::<PoseidonHasher>(fr);
+            roundtrip::<Sha256Hasher>(fr);
+            roundtrip::<Blake2sHasher>(fr);
+        }
+    }
+
+    #[test]
+    fn uint256_be_rejects_modulus() {
+        let mut modulus = [0u8; 32];
+        Fr::char().write_be(&mut modulus[..]).unwrap();
+
+        assert!(<PoseidonHasher as Hasher>::Domain::from_uint256_be(&modulus).is_err());
+        assert!(<Sha256Hasher as Hasher>::Domain::from_uint256_be(&modulus).is_err());
+    }
+
+    fn commitments_equal_normalizes<H: Hasher>() {
+        use super::CommitmentByteOrder::{BigEndian, LittleEndian};
+
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let fr = Fr::random(rng);
+        let domain: H::Domain = fr.into();
+        let bytes = domain.into_bytes();
+        let fr_bytes = crate::fr32::fr_into_bytes::<Bls12>(&fr);
+        assert!(commitments_equal::<H>(&bytes, LittleEndian, &fr_bytes, LittleEndian).unwrap());
+
+        // The same value in big endian order.
+        let word = domain.to_uint256_be();
+        assert!(commitments_equal::<H>(&bytes, LittleEndian, &word, BigEndian).unwrap());
+        assert!(commitments_equal::<H>(&word, BigEndian, &bytes, LittleEndian).unwrap());
+        assert!(!commitments_equal::<H>(&bytes, LittleEndian, &word, LittleEndian).unwrap());
+
+        let other: H::Domain = Fr::random(rng).into();
+        let other_bytes = other.into_bytes();
+        let other_word = other.to_uint256_be();
+        assert!(!commitments_equal::<H>(&bytes, LittleEndian, &other_bytes, LittleEndian).unwrap());
+        assert!(!commitments_equal::<H>(&word, BigEndian, &other_word, BigEndian).unwrap());
+
+        // 1 and 2^248 are each other's byte reversal, but distinct commitments.
+        let mut one = [0u8; 32];
+        one[0] = 1;
+        let mut reversed_one = [0u8; 32];
+        reversed_one[31] = 1;
+        assert!(!commitments_equal::<H>(&one, LittleEndian, &reversed_one, LittleEndian).unwrap());
+        assert!(!commitments_equal::<H>(&one, BigEndian, &reversed_one, BigEndian).unwrap());
+        assert!(commitments_equal::<H>(&one, LittleEndian, &reversed_one, BigEndian).unwrap());
+
+        // Not a field element.
+        assert!(commitments_equal::<H>(&[0xff; 32], LittleEndian, &bytes, LittleEndian).is_err());
+        assert!(commitments_equal::<H>(&bytes, LittleEndian, &[0xff; 32], BigEndian).is_err());
+
+        // Commitments must have exactly the length of a domain element.
+        let mut overlong = bytes.clone();
+        overlong.push(0);
+        assert!(commitments_equal::<H>(&overlong, LittleEndian, &bytes, LittleEndian).is_err());
+        assert!(commitments_equal::<H>(&bytes[..31], LittleEndian, &bytes, LittleEndian).is_err());
+        assert!(commitments_equal::<H>(&[], LittleEndian, &bytes, LittleEndian).is_err());
+    }
+
+    #[test]
+    fn commitments_equal_normalizes_pedersen() {
+        commitments_equal_normalizes::<PedersenHasher>();
+    }
+
+    #[test]
+    fn commitments_equal_normalizes_sha256() {
+        commitments_equal_normalizes::<Sha256Hasher>();
+    }
+
+    fn hash_bytes<H: Hasher>() {
+        let message = b"prover id, sector id and ticket of arbitrary length".to_vec();
+
+        let hash = H::hash_bytes(&message);
+        assert_eq!(hash, H::hash_bytes(&message));
+        assert!(hash.try_into_fr().is_ok());
+
+        for i in 0..message.len() {
+            let mut changed = message.clone();
+            changed[i] ^= 1;
+            assert_ne!(H::hash_bytes(&changed), hash, "byte {} was ignored", i);
+        }
+        assert_ne!(H::hash_bytes(&message[1..]), hash);
+    }
+
+    #[test]
+    fn hash_bytes_pedersen() {
+        hash_bytes::<PedersenHasher>();
+    }
+
+    #[test]
+    fn hash_bytes_poseidon() {
+        hash_bytes::<PoseidonHasher>();
+    }
+
+    #[test]
+    fn hash_bytes_sha256() {
+        hash_bytes::<Sha256Hasher>();
+    }
+
+    #[test]
+    fn hash_bytes_blake2s() {
+        hash_bytes::<Blake2sHasher>();
+    }
+
+    fn circuit_constraints_per_hash<H: Hasher>() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let mut cs = TestConstraintSystem::<Bls12>::new();
+
+        let a = num::AllocatedNum::alloc(cs.namespace(|| "a"), || Ok(Fr::random(rng))).unwrap();
+        let b = num::AllocatedNum::alloc(cs.namespace(|| "b"), || Ok(Fr::random(rng))).unwrap();
+        H::Function::hash2_circuit(cs.namespace(|| "hash2"), &a, &b, &JJ_PARAMS).unwrap();
+
+        assert!(cs.is_satisfied(), "constraints not satisfied");
+        assert_eq!(cs.num_constraints(), H::CIRCUIT_CONSTRAINTS_PER_HASH);
+    }
+
+    #[test]
+    fn circuit_constraints_per_hash_pedersen() {
+        circuit_constraints_per_hash::<PedersenHasher>();
+    }
+
+    #[test]
+    fn circuit_constraints_per_hash_poseidon() {
+        circuit_constraints_per_hash::<PoseidonHasher>();
+    }
+
+    #[test]
+    fn circuit_constraints_per_hash_blake2s() {
+        circuit_constraints_per_hash::<Blake2sHasher>();
+    }
+
+    #[test]
+    fn circuit_constraints_per_hash_sha256() {
+        circuit_constraints_per_hash::<Sha256Hasher>();
+    }
+
+    #[test]
+    fn cost_hints_nonzero() {
+        for hint in &[
+            PedersenHasher::vanilla_cost_hint(),
+            PoseidonHasher::vanilla_cost_hint(),
+            Blake2sHasher::vanilla_cost_hint(),
+            Sha256Hasher::vanilla_cost_hint(),
+        ] {
+            assert!(hint.nanos_per_hash > 0);
+            assert!(hint.duration(2) > hint.duration(1));
+        }
+
+        assert!(
+            PoseidonHasher::CIRCUIT_CONSTRAINTS_PER_HASH
+                < Blake2sHasher::CIRCUIT_CONSTRAINTS_PER_HASH
+        );
+    }
 }
@@ -4,13 +4,16 @@
 
 #[macro_use]
 pub mod test_helper;
+#[cfg(not(feature = "verify-only"))]
 pub mod example_helper;
 
 pub mod cache_key;
+pub mod circuit_timing;
 pub mod compound_proof;
 pub mod crypto;
 pub mod drgraph;
 pub mod error;
+pub mod field_ops;
 pub mod fr32;
 pub mod hasher;
 pub mod measurements;
@@ -1,16 +1,16 @@
 #![allow(clippy::len_without_is_empty)]
 
 use std::marker::PhantomData;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use anyhow::ensure;
+use anyhow::{ensure, Context};
 use generic_array::typenum;
 use log::trace;
 use merkletree::hash::Algorithm;
 use merkletree::merkle;
 use merkletree::merkle::{
-    get_merkle_tree_leafs, get_merkle_tree_len, is_merkle_tree_size_valid,
-    FromIndexedParallelIterator,
+    get_merkle_tree_height, get_merkle_tree_leafs, get_merkle_tree_len, is_merkle_tree_size_valid,
+    Element, FromIndexedParallelIterator,
 };
 use merkletree::proof;
 use merkletree::store::{LevelCacheStore, StoreConfig};
@@ -93,6 +93,22 @@ impl<H: Hasher, U: typenum::Unsigned> MerkleProof<H, U> {
         }
     }
 
+    /// Creates a proof from its root, leaf and path, ordered from the leaf upwards. Each path
+    /// element holds the siblings at that level and the position of the node among them.
+    pub fn new_from_parts(
+        root: H::Domain,
+        leaf: H::Domain,
+        path: Vec<(Vec<H::Domain>, usize)>,
+    ) -> MerkleProof<H, U> {
+        MerkleProof {
+            path,
+            root,
+            leaf,
+            _h: PhantomData,
+            _u: PhantomData,
+        }
+    }
+
     pub fn new_from_proof(p: &proof::Proof<H::Domain, U>) -> MerkleProof<H, U> {
         let lemma = p.lemma();
 
@@ -218,6 +234,93 @@ impl<H: Hasher, U: typenum::Unsigned> MerkleProof<H, U> {
         out
     }
 
+    /// Returns the number of bytes `serialize` produces for this proof, without serializing it.
+    pub fn serialized_size(&self) -> usize {
+        let path_size: usize = self
+            .path
+            .iter()
+            .map(|(hashes, _)| hashes.len() * NODE_SIZE + 1)
+            .sum();
+
+        // leaf and root
+        path_size + 2 * NODE_SIZE
+    }
+
+    /// Packs the proof into bytes: the root, the leaf and all sibling hashes, followed by a
+    /// bitfield of the path positions, using `log2(arity)` bits per level.
+    pub fn compress(&self) -> Vec<u8> {
+        let bits = position_bits::<U>();
+        let mut out = Vec::with_capacity(self.compressed_size());
+
+        out.extend(Domain::serialize(self.root()));
+        out.extend(Domain::serialize(&self.leaf()));
+        for (hashes, _) in &self.path {
+            for hash in hashes {
+                out.extend(Domain::serialize(hash));
+            }
+        }
+
+        let mut positions = vec![0u8; (self.path.len() * bits + 7) / 8];
+        for (level, (_, index)) in self.path.iter().enumerate() {
+            for bit in 0..bits {
+                if (index >> bit) & 1 == 1 {
+                    let offset = level * bits + bit;
+                    positions[offset / 8] |= 1 << (offset % 8);
+                }
+            }
+        }
+        out.extend(positions);
+
+        out
+    }
+
+    /// Returns the number of bytes `compress` produces for this proof.
+    pub fn compressed_size(&self) -> usize {
+        let hashes = 2 + self.path.len() * (U::to_usize() - 1);
+        hashes * NODE_SIZE + (self.path.len() * position_bits::<U>() + 7) / 8
+    }
+
+    /// Restores a proof of `depth` levels in a tree of the given `arity` from the output of
+    /// `compress`.
+    pub fn decompress(bytes: &[u8], arity: usize, depth: usize) -> Result<Self> {
+        ensure!(
+            arity == U::to_usize(),
+            "arity mismatch: expected {}, got {}",
+            U::to_usize(),
+            arity
+        );
+
+        let bits = position_bits::<U>();
+        let hashes_len = (2 + depth * (arity - 1)) * NODE_SIZE;
+        let expected_len = hashes_len + (depth * bits + 7) / 8;
+        ensure!(bytes.len() == expected_len, Error::InvalidInputSize);
+
+        let mut hashes = bytes[..hashes_len]
+            .chunks(NODE_SIZE)
+            .map(H::Domain::try_from_bytes);
+        let mut next = || hashes.next().expect("length was checked");
+
+        let root = next()?;
+        let leaf = next()?;
+        let positions = &bytes[hashes_len..];
+
+        let path = (0..depth)
+            .map(|level| {
+                let siblings = (0..arity - 1).map(|_| next()).collect::<Result<Vec<_>>>()?;
+                let index = (0..bits)
+                    .map(|bit| {
+                        let offset = level * bits + bit;
+                        (((positions[offset / 8] >> (offset % 8)) & 1) as usize) << bit
+                    })
+                    .sum();
+
+                Ok((siblings, index))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(MerkleProof::new_from_parts(root, leaf, path))
+    }
+
     pub fn path(&self) -> &Vec<(Vec<H::Domain>, usize)> {
         &self.path
     }
@@ -236,6 +339,156 @@ impl<H: Hasher, U: typenum::Unsigned> MerkleProof<H, U> {
     }
 }
 
+/// Number of bits needed to encode a position among the children of a node.
+fn position_bits<U: typenum::Unsigned>() -> usize {
+    U::to_usize().trailing_zeros() as usize
+}
+
+/// Inclusion proof of several leaves of the same tree. Nodes shared by the paths of the leaves,
+/// and nodes which can be computed from the leaves, are only included once, respectively not at
+/// all.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MultiLeafProof<H: Hasher, U: typenum::Unsigned> {
+    /// The number of levels below the root.
+    depth: usize,
+    /// The proven leaf indices, sorted and without repetitions.
+    indices: Vec<usize>,
+    /// The nodes needed to compute the root, which cannot be computed from the leaves. They are
+    /// ordered by level, starting at the leaves, and by position within a level.
+    nodes: Vec<H::Domain>,
+
+    #[serde(skip)]
+    _h: PhantomData<H>,
+    #[serde(skip)]
+    _u: PhantomData<U>,
+}
+
+impl<H: Hasher, U: typenum::Unsigned> std::fmt::Debug for MultiLeafProof<H, U> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MultiLeafProof")
+            .field("depth", &self.depth)
+            .field("indices", &self.indices)
+            .field("nodes", &self.nodes)
+            .field("H", &H::name())
+            .field("U", &U::to_usize())
+            .finish()
+    }
+}
+
+impl<H: Hasher, U: typenum::Unsigned> MultiLeafProof<H, U> {
+    /// Generates the proof for the leaves at `indices`.
+    pub fn generate(
+        tree: &MerkleTree<H::Domain, H::Function, U>,
+        indices: &[usize],
+    ) -> Result<Self> {
+        let mut indices = indices.to_vec();
+        indices.sort_unstable();
+        indices.dedup();
+        ensure!(!indices.is_empty(), "no leaves to prove");
+
+        let last = indices[indices.len() - 1];
+        ensure!(last < tree.leafs(), Error::OutOfBounds(last, tree.leafs()));
+
+        let proofs = indices
+            .iter()
+            .map(|index| {
+                Ok(MerkleProof::<H, U>::new_from_proof(
+                    &tree.gen_proof(*index)?,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let depth = proofs[0].path.len();
+        let arity = U::to_usize();
+
+        // The positions known at the current level, with a proof passing through each of them.
+        let mut known: Vec<(usize, &MerkleProof<H, U>)> =
+            indices.iter().copied().zip(proofs.iter()).collect();
+        let mut nodes = Vec::new();
+
+        for level in 0..depth {
+            let mut next = Vec::new();
+            let mut i = 0;
+            while i < known.len() {
+                let (position, proof) = known[i];
+                let parent = position / arity;
+                let (siblings, index) = &proof.path[level];
+
+                for child in 0..arity {
+                    if i < known.len() && known[i].0 == parent * arity + child {
+                        i += 1;
+                    } else if child < *index {
+                        nodes.push(siblings[child]);
+                    } else {
+                        nodes.push(siblings[child - 1]);
+                    }
+                }
+
+                next.push((parent, proof));
+            }
+            known = next;
+        }
+
+        Ok(MultiLeafProof {
+            depth,
+            indices,
+            nodes,
+            _h: PhantomData,
+            _u: PhantomData,
+        })
+    }
+
+    /// Returns the proven leaf indices, sorted.
+    pub fn indices(&self) -> &[usize] {
+        &self.indices
+    }
+
+    /// Verifies that `leaves`, pairs of leaf index and value, are included in the tree with the
+    /// given `root`. The leaf indices must be the ones the proof was generated for.
+    pub fn verify(&self, root: &H::Domain, leaves: &[(usize, H::Domain)]) -> bool {
+        let mut known = leaves.to_vec();
+        known.sort_by_key(|(index, _)| *index);
+        if known.len() != self.indices.len()
+            || known
+                .iter()
+                .zip(self.indices.iter())
+                .any(|((index, _), expected)| index != expected)
+        {
+            return false;
+        }
+
+        let arity = U::to_usize();
+        let mut nodes = self.nodes.iter();
+        let mut hasher = H::Function::default();
+
+        for level in 0..self.depth {
+            let mut next = Vec::new();
+            let mut i = 0;
+            while i < known.len() {
+                let parent = known[i].0 / arity;
+
+                let mut children = Vec::with_capacity(arity);
+                for child in 0..arity {
+                    if i < known.len() && known[i].0 == parent * arity + child {
+                        children.push(known[i].1);
+                        i += 1;
+                    } else {
+                        match nodes.next() {
+                            Some(node) => children.push(*node),
+                            None => return false,
+                        }
+                    }
+                }
+
+                hasher.reset();
+                next.push((parent, hasher.multi_node(&children, level)));
+            }
+            known = next;
+        }
+
+        nodes.next().is_none() && known.len() == 1 && known[0] == (0, *root)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IncludedNode<H: Hasher> {
     value: H::Domain,
@@ -263,12 +516,190 @@ impl<H: Hasher> std::ops::Deref for IncludedNode<H> {
     }
 }
 
+/// Extends `StoreConfig`, which is defined by merkletree, with helpers for planning tree stores.
+pub trait StoreConfigExt {
+    /// Returns the number of bytes needed on disk to build the tree store described by this
+    /// config, with `nodes` leafs of the given arity. Level cache stores are built in full before
+    /// their lower levels are discarded, so this is the size of the complete tree.
+    fn required_bytes(&self, nodes: usize, arity: usize) -> Result<u64>;
+}
+
+impl StoreConfigExt for StoreConfig {
+    fn required_bytes(&self, nodes: usize, arity: usize) -> Result<u64> {
+        let len = match self.size {
+            Some(size) => size,
+            None => get_merkle_tree_len(nodes, arity)?,
+        };
+
+        Ok((len * NODE_SIZE) as u64)
+    }
+}
+
+/// Fails if the filesystem containing `path` has less than `required` bytes available.
+pub fn check_available_space<P: AsRef<Path>>(path: P, required: u64) -> Result<()> {
+    let path = path.as_ref();
+    let available = fs2::available_space(path)
+        .with_context(|| format!("failed to query available space at {:?}", path))?;
+    ensure!(
+        available >= required,
+        "not enough space at {:?}: {} bytes required, {} available",
+        path,
+        required,
+        available
+    );
+
+    Ok(())
+}
+
+/// Re-hashes every level of `tree` from its leaves and compares the result with the stored
+/// nodes and root, failing with `Error::CorruptMerkleTree` at the first node that does not match.
+/// This reads the whole store and is meant as an offline check of trees kept on disk.
+pub fn verify_integrity<H: Hasher, U: typenum::Unsigned>(
+    tree: &MerkleTree<H::Domain, H::Function, U>,
+) -> Result<()> {
+    let arity = U::to_usize();
+    let mut hasher = H::Function::default();
+
+    let mut level_start = 0;
+    let mut level_len = tree.leafs();
+    let mut height = 0;
+    while level_len > 1 {
+        let next_start = level_start + level_len;
+
+        for parent in 0..level_len / arity {
+            let mut children = Vec::with_capacity(arity);
+            for child in 0..arity {
+                children.push(tree.read_at(level_start + parent * arity + child)?);
+            }
+
+            hasher.reset();
+            let computed = hasher.multi_node(&children, height);
+            let index = next_start + parent;
+            ensure!(
+                computed == tree.read_at(index)?,
+                Error::CorruptMerkleTree(index)
+            );
+        }
+
+        level_start = next_start;
+        level_len /= arity;
+        height += 1;
+    }
+
+    ensure!(level_start + 1 == tree.len(), Error::MalformedMerkleTree);
+    ensure!(
+        tree.root() == tree.read_at(level_start)?,
+        Error::CorruptMerkleTree(level_start)
+    );
+
+    Ok(())
+}
+
+/// Re-derives every leaf of `tree` from the node of `data` at the same index, as
+/// `create_merkle_tree` does, and compares it with the stored leaf. Fails with
+/// `Error::LeafMismatch` holding the first leaf which does not match its data, including data
+/// which is not a valid domain element.
+pub fn verify_leaves_against_data<H: Hasher, U: typenum::Unsigned>(
+    tree: &MerkleTree<H::Domain, H::Function, U>,
+    data: &[u8],
+) -> Result<()> {
+    let leafs = tree.leafs();
+    ensure!(
+        data.len() == leafs * NODE_SIZE,
+        Error::InvalidMerkleTreeArgs(data.len(), NODE_SIZE, leafs)
+    );
+
+    for i in 0..leafs {
+        let matches = match H::Domain::try_from_bytes(data_at_node(data, i)?) {
+            Ok(leaf) => leaf == tree.read_at(i)?,
+            Err(_) => false,
+        };
+        ensure!(matches, Error::LeafMismatch(i));
+    }
+
+    Ok(())
+}
+
 /// Construct a new merkle tree.
 pub fn create_merkle_tree<H: Hasher, U: typenum::Unsigned>(
     config: Option<StoreConfig>,
     size: usize,
     data: &[u8],
 ) -> Result<MerkleTree<H::Domain, H::Function, U>> {
+    create_merkle_tree_with_leaf_fn::<H, U, _>(config, size, data, |_, d| {
+        // TODO/FIXME: This can panic. FOR NOW, let's leave this since we're experimenting with
+        // optimization paths. However, we need to ensure that bad input will not lead to a panic
+        // that isn't caught by the FPS API.
+        // Unfortunately, it's not clear how to perform this error-handling in the parallel
+        // iterator case.
+        H::Domain::try_from_bytes(d).expect("failed to convert node data to domain element")
+    })
+}
+
+/// Construct a new merkle tree, in memory if the whole tree takes at most `max_mem_bytes`, and
+/// in the disk store described by `config` otherwise. Both builds have the same root.
+///
+/// When the tree is spilled, the levels of `config`, i.e. the rows above the base which are
+/// kept once the tree is turned into a level cache, are sized so that they fit in
+/// `max_mem_bytes`, see `capped_cached_levels`.
+pub fn create_merkle_tree_capped<H: Hasher, U: typenum::Unsigned>(
+    config: StoreConfig,
+    size: usize,
+    data: &[u8],
+    max_mem_bytes: u64,
+) -> Result<MerkleTree<H::Domain, H::Function, U>> {
+    let tree_bytes = (get_merkle_tree_len(size, U::to_usize())? * NODE_SIZE) as u64;
+    trace!(
+        "create_merkle_tree_capped: {} bytes, cap {} bytes",
+        tree_bytes,
+        max_mem_bytes
+    );
+
+    let config = if tree_bytes <= max_mem_bytes {
+        None
+    } else {
+        let levels = capped_cached_levels(size, U::to_usize(), max_mem_bytes);
+        trace!("create_merkle_tree_capped: caching {} levels", levels);
+        Some(StoreConfig::new(&config.path, config.id.clone(), levels))
+    };
+
+    create_merkle_tree::<H, U>(config, size, data)
+}
+
+/// Returns the number of rows, counted from the root and excluding the base, of a tree of
+/// `leafs` leaves and the given arity whose nodes together take at most `max_mem_bytes`.
+pub fn capped_cached_levels(leafs: usize, arity: usize, max_mem_bytes: u64) -> usize {
+    let height = get_merkle_tree_height(leafs, arity);
+
+    let mut levels = 0;
+    let mut row_nodes = 1;
+    let mut cached_bytes = 0;
+    // The base row is never cached, so at most `height - 1` rows are.
+    while levels + 1 < height {
+        cached_bytes += (row_nodes * NODE_SIZE) as u64;
+        if cached_bytes > max_mem_bytes {
+            break;
+        }
+        levels += 1;
+        row_nodes *= arity;
+    }
+
+    levels
+}
+
+/// Construct a new merkle tree, whose leaves are derived by `leaf_fn` from the index and the
+/// bytes of each node of `data`, e.g. to bind the leaves to their position.
+pub fn create_merkle_tree_with_leaf_fn<H, U, F>(
+    config: Option<StoreConfig>,
+    size: usize,
+    data: &[u8],
+    leaf_fn: F,
+) -> Result<MerkleTree<H::Domain, H::Function, U>>
+where
+    H: Hasher,
+    U: typenum::Unsigned,
+    F: Fn(usize, &[u8]) -> H::Domain + Sync,
+{
     ensure!(
         data.len() == (NODE_SIZE * size) as usize,
         Error::InvalidMerkleTreeArgs(data.len(), NODE_SIZE, size)
@@ -289,12 +720,7 @@ pub fn create_merkle_tree<H: Hasher, U: typenum::Unsigned>(
     let f = |i| {
         // TODO Replace `expect()` with `context()` (problem is the parallel iterator)
         let d = data_at_node(&data, i).expect("data_at_node math failed");
-        // TODO/FIXME: This can panic. FOR NOW, let's leave this since we're experimenting with
-        // optimization paths. However, we need to ensure that bad input will not lead to a panic
-        // that isn't caught by the FPS API.
-        // Unfortunately, it's not clear how to perform this error-handling in the parallel
-        // iterator case.
-        H::Domain::try_from_bytes(d).expect("failed to convert node data to domain element")
+        leaf_fn(i, d)
     };
 
     match config {
@@ -383,16 +809,73 @@ pub fn open_lcmerkle_tree<H: Hasher, U: typenum::Unsigned>(
     LCMerkleTree::from_data_store(tree_store, size)
 }
 
+/// The shape of a merkle tree, available from the tree itself, so that verification code does
+/// not need the graph the tree was built for.
+pub trait MerkleTreeShape {
+    /// Returns the number of leaves of the tree.
+    fn leaf_count(&self) -> usize;
+
+    /// Returns the number of levels, including the leaves and the root, of a tree with `arity`
+    /// over the leaves of this tree. Matches `Graph::merkle_tree_depth` for the arity the tree
+    /// was built with.
+    fn depth(&self, arity: usize) -> usize;
+}
+
+impl<T, A, S, U> MerkleTreeShape for merkle::MerkleTree<T, A, S, U>
+where
+    T: Element,
+    A: Algorithm<T>,
+    S: Store<T>,
+    U: typenum::Unsigned,
+{
+    fn leaf_count(&self) -> usize {
+        self.leafs()
+    }
+
+    fn depth(&self, arity: usize) -> usize {
+        get_merkle_tree_height(self.leafs(), arity)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     use rand;
-    use std::io::Write;
+    use std::io::{Seek, SeekFrom, Write};
 
     use crate::drgraph::{new_seed, BucketGraph, Graph, BASE_DEGREE};
     use crate::hasher::{Blake2sHasher, PedersenHasher, PoseidonHasher, Sha256Hasher};
 
+    /// The bytes of the top `levels` rows of a tree of the given arity.
+    fn cached_bytes(levels: usize, arity: usize) -> u64 {
+        (0..levels)
+            .map(|level| (arity.pow(level as u32) * NODE_SIZE) as u64)
+            .sum()
+    }
+
+    #[test]
+    fn capped_cached_levels_fit_the_cap() {
+        for &(leafs, arity) in &[(64, 2), (1 << 10, 2), (512, 8)] {
+            let height = get_merkle_tree_height(leafs, arity);
+            let tree_bytes = (get_merkle_tree_len(leafs, arity).unwrap() * NODE_SIZE) as u64;
+
+            for max_mem_bytes in (0..tree_bytes).step_by(NODE_SIZE / 2) {
+                let levels = capped_cached_levels(leafs, arity, max_mem_bytes);
+                assert!(levels < height);
+                // The cached rows fit in the cap, and the next row above the base does not.
+                assert!(cached_bytes(levels, arity) <= max_mem_bytes);
+                if levels + 1 < height {
+                    assert!(cached_bytes(levels + 1, arity) > max_mem_bytes);
+                }
+            }
+        }
+
+        assert_eq!(capped_cached_levels(64, 2, 0), 0);
+        assert_eq!(capped_cached_levels(64, 2, 3 * NODE_SIZE as u64), 2);
+        assert_eq!(capped_cached_levels(64, 2, u64::max_value()), 6);
+    }
+
     fn merklepath<H: Hasher, U: typenum::Unsigned>() {
         let leafs = 64;
         let g = BucketGraph::<H>::new(leafs, BASE_DEGREE, 0, new_seed()).unwrap();
@@ -414,6 +897,27 @@ mod tests {
             let mp = MerkleProof::<H, U>::new_from_proof(&proof);
 
             assert_eq!(mp.len(), len, "invalid prof len");
+            assert_eq!(
+                mp.serialized_size(),
+                mp.serialize().len(),
+                "invalid serialized size"
+            );
+
+            let compressed = mp.compress();
+            assert_eq!(compressed.len(), mp.compressed_size());
+            assert!(compressed.len() < mp.serialize().len());
+            assert!(compressed.len() < serde_json::to_vec(&mp).unwrap().len());
+
+            let depth = mp.path().len();
+            let restored =
+                MerkleProof::<H, U>::decompress(&compressed, U::to_usize(), depth).unwrap();
+            assert_eq!(restored.root(), mp.root());
+            assert_eq!(restored.leaf(), mp.leaf());
+            assert_eq!(restored.as_pairs(), mp.as_pairs());
+            assert!(restored.validate(i), "failed to validate decompressed path");
+            assert!(
+                MerkleProof::<H, U>::decompress(&compressed[1..], U::to_usize(), depth).is_err()
+            );
 
             assert!(mp.validate(i), "failed to validate valid merkle path");
             let data_slice = &data[i * node_size..(i + 1) * node_size].to_vec();
@@ -473,4 +977,156 @@ mod tests {
     fn merklepath_pedersen_oct() {
         merklepath::<PedersenHasher, typenum::U8>();
     }
+
+    #[test]
+    fn required_bytes_for_config() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let config = StoreConfig::new(cache_dir.path(), "tree", 0);
+
+        // 8 + 4 + 2 + 1 nodes in a binary tree, 8 + 1 in an oct tree.
+        assert_eq!(config.required_bytes(8, 2).unwrap(), 15 * 32);
+        assert_eq!(config.required_bytes(8, 8).unwrap(), 9 * 32);
+
+        let sized = StoreConfig::from_config(&config, "sized", Some(15));
+        assert_eq!(sized.required_bytes(8, 2).unwrap(), 15 * 32);
+    }
+
+    #[test]
+    fn check_available_space_for_path() {
+        let cache_dir = tempfile::tempdir().unwrap();
+
+        check_available_space(cache_dir.path(), 15 * 32).unwrap();
+
+        let err = check_available_space(cache_dir.path(), u64::max_value()).unwrap_err();
+        assert!(err.to_string().contains("not enough space"), "{}", err);
+    }
+
+    fn multi_leaf_proof<H: Hasher, U: typenum::Unsigned>() {
+        let leafs = 64;
+        let g = BucketGraph::<H>::new(leafs, BASE_DEGREE, 0, new_seed()).unwrap();
+        let mut rng = rand::thread_rng();
+        let data: Vec<u8> = (0..leafs)
+            .flat_map(|_| H::Domain::random(&mut rng).into_bytes())
+            .collect();
+        let tree: MerkleTree<H::Domain, H::Function, U> =
+            g.merkle_tree(None, data.as_slice()).unwrap();
+        let leaf = |i: usize| H::Domain::try_from_bytes(&data[i * 32..(i + 1) * 32]).unwrap();
+
+        let indices = [0, 1, 5, 6, 7, 40, 63];
+        let proof = MultiLeafProof::<H, U>::generate(&tree, &indices).unwrap();
+        let leaves: Vec<_> = indices.iter().rev().map(|i| (*i, leaf(*i))).collect();
+        assert!(proof.verify(&tree.root(), &leaves));
+
+        let individual_size: usize = indices
+            .iter()
+            .map(|i| {
+                let p = MerkleProof::<H, U>::new_from_proof(&tree.gen_proof(*i).unwrap());
+                serde_json::to_vec(&p).unwrap().len()
+            })
+            .sum();
+        assert!(serde_json::to_vec(&proof).unwrap().len() < individual_size);
+
+        let mut wrong_leaf = leaves.clone();
+        wrong_leaf[0].1 = leaf(2);
+        assert!(!proof.verify(&tree.root(), &wrong_leaf));
+        assert!(!proof.verify(&tree.root(), &leaves[1..]));
+        assert!(!proof.verify(&leaf(0), &leaves));
+
+        assert!(MultiLeafProof::<H, U>::generate(&tree, &[leafs]).is_err());
+    }
+
+    #[test]
+    fn multi_leaf_proof_pedersen_binary() {
+        multi_leaf_proof::<PedersenHasher, typenum::U2>();
+    }
+
+    #[test]
+    fn multi_leaf_proof_poseidon_oct() {
+        multi_leaf_proof::<PoseidonHasher, typenum::U8>();
+    }
+
+    #[test]
+    fn verify_integrity_detects_corrupt_node() {
+        let leafs = 16;
+        let mut rng = rand::thread_rng();
+        let data: Vec<u8> = (0..leafs)
+            .flat_map(|_| <PoseidonHasher as Hasher>::Domain::random(&mut rng).into_bytes())
+            .collect();
+
+        let cache_dir = tempfile::tempdir().unwrap();
+        let config = StoreConfig::new(cache_dir.path(), "tree", 0);
+        let tree =
+            create_merkle_tree::<PoseidonHasher, typenum::U2>(Some(config.clone()), leafs, &data)
+                .unwrap();
+        verify_integrity::<PoseidonHasher, typenum::U2>(&tree).unwrap();
+
+        // The second node of the first level above the leaves.
+        let corrupt = leafs + 1;
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(StoreConfig::data_path(&config.path, &config.id))
+            .unwrap();
+        file.seek(SeekFrom::Start((corrupt * NODE_SIZE) as u64))
+            .unwrap();
+        file.write_all(&<PoseidonHasher as Hasher>::Domain::random(&mut rng).into_bytes())
+            .unwrap();
+        file.sync_all().unwrap();
+
+        let err = verify_integrity::<PoseidonHasher, typenum::U2>(&tree).unwrap_err();
+        match err.downcast_ref::<Error>() {
+            Some(Error::CorruptMerkleTree(index)) => assert_eq!(*index, corrupt),
+            _ => panic!("unexpected error: {}", err),
+        }
+    }
+
+    #[test]
+    fn verify_leaves_against_data_detects_corruption() {
+        let leafs = 16;
+        let mut rng = rand::thread_rng();
+        let mut data: Vec<u8> = (0..leafs)
+            .flat_map(|_| <PedersenHasher as Hasher>::Domain::random(&mut rng).into_bytes())
+            .collect();
+
+        let tree = create_merkle_tree::<PedersenHasher, typenum::U2>(None, leafs, &data).unwrap();
+        verify_leaves_against_data::<PedersenHasher, typenum::U2>(&tree, &data).unwrap();
+
+        // Flip the least significant bit of leaf 5, which keeps it a valid field element.
+        let corrupt = 5;
+        data[corrupt * NODE_SIZE] ^= 1;
+        let err =
+            verify_leaves_against_data::<PedersenHasher, typenum::U2>(&tree, &data).unwrap_err();
+        match err.downcast_ref::<Error>() {
+            Some(Error::LeafMismatch(index)) => assert_eq!(*index, corrupt),
+            _ => panic!("unexpected error: {}", err),
+        }
+
+        assert!(verify_leaves_against_data::<PedersenHasher, typenum::U2>(
+            &tree,
+            &data[NODE_SIZE..]
+        )
+        .is_err());
+    }
+
+    fn tree_shape<U: typenum::Unsigned>(leafs: usize, depth: usize) {
+        let g = BucketGraph::<PedersenHasher>::new(leafs, BASE_DEGREE, 0, new_seed()).unwrap();
+        let data = vec![0u8; leafs * NODE_SIZE];
+
+        let tree = create_merkle_tree::<PedersenHasher, U>(None, leafs, &data).unwrap();
+
+        assert_eq!(tree.leaf_count(), leafs);
+        assert_eq!(tree.depth(U::to_usize()), depth);
+        assert_eq!(
+            tree.depth(U::to_usize()),
+            g.merkle_tree_depth::<U>() as usize
+        );
+    }
+
+    #[test]
+    fn merkle_tree_shape() {
+        tree_shape::<typenum::U2>(2, 2);
+        tree_shape::<typenum::U2>(64, 7);
+        tree_shape::<typenum::U4>(16, 3);
+        tree_shape::<typenum::U4>(64, 4);
+        tree_shape::<typenum::U8>(64, 3);
+    }
 }
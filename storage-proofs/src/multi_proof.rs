@@ -1,10 +1,18 @@
 use bellperson::groth16;
 
 use crate::error::Result;
-use anyhow::Context;
-use paired::Engine;
+use anyhow::{ensure, Context};
+use byteorder::{ByteOrder, LittleEndian};
+use ff::PrimeField;
+use paired::{CurveAffine, CurveProjective, EncodedPoint, Engine};
 use std::io::{self, Read, Write};
 
+/// Version byte of the format written by `MultiProof::to_bytes_v1`.
+pub const MULTI_PROOF_FORMAT_V1: u8 = 1;
+
+/// Size of the header of the v1 format: the version byte and the partition count.
+const V1_HEADER_SIZE: usize = 1 + 4;
+
 pub struct MultiProof<'a, E: Engine> {
     pub circuit_proofs: Vec<groth16::Proof<E>>,
     pub verifying_key: &'a groth16::VerifyingKey<E>,
@@ -37,6 +45,48 @@ impl<'a, E: Engine> MultiProof<'a, E> {
         Ok(Self::new(proofs, verifying_key))
     }
 
+    /// Checks that this proof has `expected_partitions` groth proofs, made of points in the
+    /// prime order subgroups. This is much cheaper than verification, see
+    /// `check_proofs_wellformed`.
+    pub fn check_wellformed(&self, expected_partitions: usize) -> Result<()> {
+        Self::check_proofs_wellformed(&self.circuit_proofs, expected_partitions)
+    }
+
+    /// Checks that there are `expected_partitions` groth proofs, and that none of their points
+    /// is the identity or outside of the prime order subgroup. As the verifying key is not
+    /// needed, this can reject malformed proofs before the parameters are loaded.
+    pub fn check_proofs_wellformed(
+        proofs: &[groth16::Proof<E>],
+        expected_partitions: usize,
+    ) -> Result<()> {
+        ensure!(
+            proofs.len() == expected_partitions,
+            "expected {} partition proofs, got {}",
+            expected_partitions,
+            proofs.len()
+        );
+
+        for (k, proof) in proofs.iter().enumerate() {
+            ensure!(
+                is_in_prime_order_subgroup(&proof.a),
+                "partition {}: a is not a valid point",
+                k
+            );
+            ensure!(
+                is_in_prime_order_subgroup(&proof.b),
+                "partition {}: b is not a valid point",
+                k
+            );
+            ensure!(
+                is_in_prime_order_subgroup(&proof.c),
+                "partition {}: c is not a valid point",
+                k
+            );
+        }
+
+        Ok(())
+    }
+
     pub fn write<W: Write>(&self, mut writer: W) -> Result<()> {
         for proof in &self.circuit_proofs {
             proof.write(&mut writer)?
@@ -44,9 +94,306 @@ impl<'a, E: Engine> MultiProof<'a, E> {
         Ok(())
     }
 
+    /// Returns the number of bytes `write` produces for this proof: one compressed
+    /// groth proof per partition.
+    pub fn serialized_size(&self) -> usize {
+        self.circuit_proofs.len() * Self::groth_proof_size()
+    }
+
+    /// The size of a single compressed groth proof, `a` and `c` in G1 and `b` in G2.
+    pub(crate) fn groth_proof_size() -> usize {
+        2 * <<E::G1Affine as CurveAffine>::Compressed as EncodedPoint>::size()
+            + <<E::G2Affine as CurveAffine>::Compressed as EncodedPoint>::size()
+    }
+
+    /// Serializes the proof into the versioned v1 format, which does not depend on serde:
+    ///
+    /// * 1 byte: the version, `MULTI_PROOF_FORMAT_V1`.
+    /// * 4 bytes: the number of partitions, as a little endian `u32`.
+    /// * For each partition, the groth proof: `a` as a compressed G1 point, `b` as a compressed
+    ///   G2 point and `c` as a compressed G1 point.
+    pub fn to_bytes_v1(&self) -> Result<Vec<u8>> {
+        let mut out = vec![0u8; V1_HEADER_SIZE];
+        out[0] = MULTI_PROOF_FORMAT_V1;
+        LittleEndian::write_u32(
+            &mut out[1..V1_HEADER_SIZE],
+            self.circuit_proofs.len() as u32,
+        );
+        out.reserve(self.serialized_size());
+        self.write(&mut out).context("known allocation target")?;
+
+        Ok(out)
+    }
+
+    /// Reads a proof in the v1 format written by `to_bytes_v1`. Fails unless `bytes` holds
+    /// exactly `expected_partitions` valid groth proofs.
+    pub fn from_bytes_v1(
+        bytes: &[u8],
+        expected_partitions: usize,
+        verifying_key: &'a groth16::VerifyingKey<E>,
+    ) -> Result<Self> {
+        let partitions = read_v1_header(bytes)?;
+        ensure!(
+            partitions == expected_partitions,
+            "expected {} partition proofs, got {}",
+            expected_partitions,
+            partitions
+        );
+
+        Self::read_v1_body(&bytes[V1_HEADER_SIZE..], partitions, verifying_key)
+    }
+
+    /// Reads a proof in the v1 format written by `to_bytes_v1`, with the number of partitions
+    /// read from the header, e.g. when the verifier only knows an upper bound. The header is
+    /// untrusted, so the partition count is checked against `max_partitions` before anything
+    /// is allocated for the proofs. Fails unless `bytes` holds exactly that many valid groth
+    /// proofs.
+    pub fn from_bytes(
+        bytes: &[u8],
+        max_partitions: usize,
+        verifying_key: &'a groth16::VerifyingKey<E>,
+    ) -> Result<Self> {
+        let partitions = read_v1_header(bytes)?;
+        ensure!(
+            partitions <= max_partitions,
+            "proof claims {} partitions, more than the maximum of {}",
+            partitions,
+            max_partitions
+        );
+
+        Self::read_v1_body(&bytes[V1_HEADER_SIZE..], partitions, verifying_key)
+    }
+
+    fn read_v1_body(
+        body: &[u8],
+        partitions: usize,
+        verifying_key: &'a groth16::VerifyingKey<E>,
+    ) -> Result<Self> {
+        let expected_len = partitions
+            .checked_mul(Self::groth_proof_size())
+            .context("partition count overflows the proof size")?;
+        ensure!(
+            body.len() == expected_len,
+            "expected {} bytes of partition proofs, got {}",
+            expected_len,
+            body.len()
+        );
+
+        Self::new_from_reader(Some(partitions), body, verifying_key)
+    }
+
     pub fn to_vec(&self) -> Result<Vec<u8>> {
         let mut out = Vec::new();
         self.write(&mut out).context("known allocation target")?;
         Ok(out)
     }
+
+    /// Encodes the versioned serialization written by `to_bytes_v1` as standard base64, for
+    /// transports which carry proofs as strings.
+    pub fn to_base64(&self) -> Result<String> {
+        Ok(base64::encode(&self.to_bytes_v1()?))
+    }
+
+    /// Decodes a proof encoded by `to_base64`. Fails unless `s` is valid base64 holding a proof
+    /// in the v1 format with exactly `expected_partitions` groth proofs.
+    pub fn from_base64(
+        s: &str,
+        expected_partitions: usize,
+        verifying_key: &'a groth16::VerifyingKey<E>,
+    ) -> Result<Self> {
+        let bytes = base64::decode(s).context("invalid base64 proof")?;
+
+        Self::from_bytes_v1(&bytes, expected_partitions, verifying_key)
+    }
+}
+
+/// Checks the version byte of a proof in the v1 format and returns its partition count.
+fn read_v1_header(bytes: &[u8]) -> Result<usize> {
+    ensure!(
+        bytes.len() >= V1_HEADER_SIZE,
+        "proof of {} bytes is shorter than the {} byte header",
+        bytes.len(),
+        V1_HEADER_SIZE
+    );
+    ensure!(
+        bytes[0] == MULTI_PROOF_FORMAT_V1,
+        "unsupported proof format version {}, expected {}",
+        bytes[0],
+        MULTI_PROOF_FORMAT_V1
+    );
+
+    Ok(LittleEndian::read_u32(&bytes[1..V1_HEADER_SIZE]) as usize)
+}
+
+/// Returns true if `point` is not the identity and is of prime order. Points are always on the
+/// curve, so this is the case if multiplying by the group order gives the identity.
+fn is_in_prime_order_subgroup<G: CurveAffine>(point: &G) -> bool {
+    !point.is_zero() && point.mul(G::Scalar::char()).is_zero()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use paired::bls12_381::{Bls12, G1Affine, G1Uncompressed, G2Affine};
+
+    fn proof(a: G1Affine) -> groth16::Proof<Bls12> {
+        groth16::Proof {
+            a,
+            b: G2Affine::one(),
+            c: G1Affine::one(),
+        }
+    }
+
+    /// A point on the G1 curve, `(4, y)`, which is not in the prime order subgroup.
+    fn point_outside_subgroup() -> G1Affine {
+        let y = hex::decode(
+            "0a989badd40d6212b33cffc3f3763e9bc760f988c9926b26da9dd85e928483446346b8ed00e1de5d5ea93e354abe706c",
+        )
+        .unwrap();
+
+        let mut encoded = G1Uncompressed::empty();
+        encoded.as_mut()[47] = 4;
+        encoded.as_mut()[48..].copy_from_slice(&y);
+        assert!(encoded.into_affine().is_err());
+
+        encoded.into_affine_unchecked().unwrap()
+    }
+
+    fn verifying_key() -> groth16::VerifyingKey<Bls12> {
+        let g1 = G1Affine::one();
+        groth16::VerifyingKey::<Bls12> {
+            alpha_g1: g1,
+            beta_g1: g1,
+            beta_g2: G2Affine::one(),
+            gamma_g2: G2Affine::one(),
+            delta_g1: g1,
+            delta_g2: G2Affine::one(),
+            ic: vec![g1],
+        }
+    }
+
+    #[test]
+    fn check_wellformed() {
+        let g1 = G1Affine::one();
+        let vk = verifying_key();
+
+        let multi_proof = MultiProof::new(vec![proof(g1), proof(g1)], &vk);
+        multi_proof.check_wellformed(2).unwrap();
+        assert!(multi_proof.check_wellformed(1).is_err());
+
+        let bad = MultiProof::new(vec![proof(g1), proof(point_outside_subgroup())], &vk);
+        let err = bad.check_wellformed(2).unwrap_err();
+        assert!(err.to_string().contains("partition 1: a"), "{}", err);
+
+        let identity = MultiProof::new(vec![proof(G1Affine::zero())], &vk);
+        assert!(identity.check_wellformed(1).is_err());
+    }
+
+    #[test]
+    fn bytes_v1_roundtrip() {
+        let g1 = G1Affine::one();
+        let vk = verifying_key();
+
+        let multi_proof = MultiProof::new(vec![proof(g1), proof(g1.mul(3).into_affine())], &vk);
+        let bytes = multi_proof.to_bytes_v1().unwrap();
+        assert_eq!(bytes[0], MULTI_PROOF_FORMAT_V1);
+        assert_eq!(&bytes[1..5], &[2, 0, 0, 0]);
+        assert_eq!(bytes.len(), 5 + multi_proof.serialized_size());
+
+        let restored = MultiProof::from_bytes_v1(&bytes, 2, &vk).unwrap();
+        assert_eq!(restored.circuit_proofs, multi_proof.circuit_proofs);
+        assert_eq!(restored.to_bytes_v1().unwrap(), bytes);
+
+        assert!(MultiProof::from_bytes_v1(&bytes, 1, &vk).is_err());
+
+        let mut wrong_version = bytes.clone();
+        wrong_version[0] = 2;
+        let err = MultiProof::from_bytes_v1(&wrong_version, 2, &vk).unwrap_err();
+        assert!(err.to_string().contains("version"), "{}", err);
+    }
+
+    #[test]
+    fn bytes_v1_truncated() {
+        let g1 = G1Affine::one();
+        let vk = verifying_key();
+
+        let bytes = MultiProof::new(vec![proof(g1), proof(g1)], &vk)
+            .to_bytes_v1()
+            .unwrap();
+
+        for len in &[0, 3, 5, bytes.len() - 1] {
+            assert!(
+                MultiProof::from_bytes_v1(&bytes[..*len], 2, &vk).is_err(),
+                "truncated to {} bytes",
+                len
+            );
+        }
+    }
+
+    #[test]
+    fn from_bytes_max_partitions() {
+        let g1 = G1Affine::one();
+        let vk = verifying_key();
+
+        let multi_proof = MultiProof::new(vec![proof(g1), proof(g1.mul(7).into_affine())], &vk);
+        let bytes = multi_proof.to_bytes_v1().unwrap();
+
+        for max_partitions in &[2, 3, 16] {
+            let restored = MultiProof::from_bytes(&bytes, *max_partitions, &vk).unwrap();
+            assert_eq!(restored.circuit_proofs, multi_proof.circuit_proofs);
+        }
+
+        let err = MultiProof::from_bytes(&bytes, 1, &vk).unwrap_err();
+        assert!(err.to_string().contains("maximum of 1"), "{}", err);
+
+        // A header claiming u32::MAX partitions, about 800GB of proofs, is rejected from the
+        // header alone, without reading or allocating anything for the proofs.
+        let mut absurd = bytes[..V1_HEADER_SIZE].to_vec();
+        LittleEndian::write_u32(&mut absurd[1..], std::u32::MAX);
+        let err = MultiProof::from_bytes(&absurd, 16, &vk).unwrap_err();
+        assert!(err.to_string().contains("more than the maximum"), "{}", err);
+
+        let mut truncated = bytes.clone();
+        truncated.pop();
+        assert!(MultiProof::from_bytes(&truncated, 16, &vk).is_err());
+    }
+
+    #[test]
+    fn base64_roundtrip() {
+        let g1 = G1Affine::one();
+        let vk = verifying_key();
+
+        let multi_proof = MultiProof::new(vec![proof(g1), proof(g1.mul(5).into_affine())], &vk);
+        let encoded = multi_proof.to_base64().unwrap();
+        assert_eq!(
+            base64::decode(&encoded).unwrap(),
+            multi_proof.to_bytes_v1().unwrap()
+        );
+
+        let restored = MultiProof::from_base64(&encoded, 2, &vk).unwrap();
+        assert_eq!(restored.circuit_proofs, multi_proof.circuit_proofs);
+
+        let err = MultiProof::from_base64(&encoded, 3, &vk).unwrap_err();
+        assert!(err.to_string().contains("partition proofs"), "{}", err);
+
+        // The unversioned serialization is rejected.
+        let unversioned = base64::encode(&multi_proof.to_vec().unwrap());
+        assert!(MultiProof::from_base64(&unversioned, 2, &vk).is_err());
+    }
+
+    #[test]
+    fn base64_malformed() {
+        let g1 = G1Affine::one();
+        let vk = verifying_key();
+
+        for malformed in &["not base64!", "A", "####"] {
+            let err = MultiProof::from_base64(malformed, 1, &vk).unwrap_err();
+            assert!(err.to_string().contains("invalid base64"), "{}", err);
+        }
+
+        let encoded = MultiProof::new(vec![proof(g1)], &vk).to_base64().unwrap();
+        let truncated = base64::encode(&base64::decode(&encoded).unwrap()[..10]);
+        assert!(MultiProof::from_base64(&truncated, 1, &vk).is_err());
+    }
 }
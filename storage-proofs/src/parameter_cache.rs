@@ -1,5 +1,5 @@
 use crate::error::*;
-use anyhow::bail;
+use anyhow::{bail, ensure, Context};
 use bellperson::groth16::Parameters;
 use bellperson::{groth16, Circuit};
 use fil_sapling_crypto::jubjub::JubjubEngine;
@@ -10,13 +10,14 @@ use rand::RngCore;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
+use std::collections::BTreeMap;
 use std::env;
 use std::fs::{self, create_dir_all, File};
-use std::io::{self, SeekFrom};
-use std::path::{Path, PathBuf};
+use std::io::{self, Read, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
 
 /// Bump this when circuits change to invalidate the cache.
-pub const VERSION: usize = 24;
+pub const VERSION: usize = 25;
 
 pub const PARAMETER_CACHE_ENV_VAR: &str = "FIL_PROOFS_PARAMETER_CACHE";
 pub const PARAMETER_CACHE_DIR: &str = "/var/tmp/filecoin-proof-parameters/";
@@ -24,6 +25,13 @@ pub const GROTH_PARAMETER_EXT: &str = "params";
 pub const PARAMETER_METADATA_EXT: &str = "meta";
 pub const VERIFYING_KEY_EXT: &str = "vk";
 
+/// The extensions of the files making up the entries of the parameter cache.
+const CACHE_ENTRY_EXTS: [&str; 3] = [
+    GROTH_PARAMETER_EXT,
+    PARAMETER_METADATA_EXT,
+    VERIFYING_KEY_EXT,
+];
+
 #[derive(Debug)]
 struct LockedFile(File);
 
@@ -107,13 +115,47 @@ pub fn parameter_cache_metadata_path(parameter_set_identifier: &str) -> PathBuf
 }
 
 pub fn parameter_cache_verifying_key_path(parameter_set_identifier: &str) -> PathBuf {
+    parameter_cache_versioned_verifying_key_path(VERSION, parameter_set_identifier)
+}
+
+/// Returns the path of the verifying key cached for `parameter_set_identifier` by the parameter
+/// `version`, which may be older than the current `VERSION`.
+pub fn parameter_cache_versioned_verifying_key_path(
+    version: usize,
+    parameter_set_identifier: &str,
+) -> PathBuf {
     let dir = Path::new(&parameter_cache_dir_name()).to_path_buf();
     dir.join(format!(
         "v{}-{}.{}",
-        VERSION, parameter_set_identifier, VERIFYING_KEY_EXT
+        version, parameter_set_identifier, VERIFYING_KEY_EXT
     ))
 }
 
+/// Reads the verifying key cached for `parameter_set_identifier` by the parameter `version`.
+/// Unlike `CacheableParameters::get_verifying_key` it never generates a key, so that proofs made
+/// with older parameters can be verified with exactly those parameters.
+pub fn read_versioned_verifying_key<E: JubjubEngine>(
+    version: usize,
+    parameter_set_identifier: &str,
+) -> Result<groth16::VerifyingKey<E>> {
+    let cache_path =
+        parameter_cache_versioned_verifying_key_path(version, parameter_set_identifier);
+    ensure!(
+        cache_path.exists(),
+        "no cached verifying key for v{}-{} at {:?}",
+        version,
+        parameter_set_identifier,
+        cache_path
+    );
+
+    read_cached_verifying_key(&cache_path).with_context(|| {
+        format!(
+            "failed to read verifying key for v{}-{}",
+            version, parameter_set_identifier
+        )
+    })
+}
+
 fn ensure_ancestor_dirs_exist(cache_entry_path: PathBuf) -> Result<PathBuf> {
     info!(
         "ensuring that all ancestor directories for: {:?} exist",
@@ -248,6 +290,169 @@ where
     }
 }
 
+/// Writes groth parameters to `w`, prefixed with the `circuit_identifier` they were generated
+/// for, so that they can be distributed without going through the cache directory.
+pub fn write_parameters<E: JubjubEngine, W: Write>(
+    params: &groth16::Parameters<E>,
+    circuit_identifier: &str,
+    mut w: W,
+) -> Result<()> {
+    let identifier = circuit_identifier.as_bytes();
+    w.write_all(&(identifier.len() as u32).to_le_bytes())?;
+    w.write_all(identifier)?;
+    params.write(&mut w)?;
+    w.flush()?;
+
+    Ok(())
+}
+
+/// Reads groth parameters written by `write_parameters` from `r`, failing if they were not
+/// written for `circuit_identifier`.
+pub fn read_parameters<E: JubjubEngine, R: Read>(
+    mut r: R,
+    circuit_identifier: &str,
+) -> Result<groth16::Parameters<E>> {
+    let mut len_bytes = [0u8; 4];
+    r.read_exact(&mut len_bytes)?;
+    let len = u32::from_le_bytes(len_bytes) as usize;
+    ensure!(
+        len == circuit_identifier.len(),
+        "parameters are not for circuit {}",
+        circuit_identifier
+    );
+
+    let mut identifier = vec![0u8; len];
+    r.read_exact(&mut identifier)?;
+    ensure!(
+        identifier == circuit_identifier.as_bytes(),
+        "parameters are for circuit {}, expected {}",
+        String::from_utf8_lossy(&identifier),
+        circuit_identifier
+    );
+
+    let params = groth16::Parameters::read(&mut r, true)?;
+    info!(
+        "read groth parameters for {} from stream",
+        circuit_identifier
+    );
+
+    Ok(params)
+}
+
+/// Name of the file holding the digests of the entries of a parameter cache snapshot.
+pub const PARAMETER_DIGESTS_FILE: &str = "digests.json";
+
+/// Copies every entry of the parameter cache, the groth parameters, verifying keys and metadata,
+/// into `dest_dir`, together with a `PARAMETER_DIGESTS_FILE` holding the sha256 digest of each
+/// of them. Returns the names of the copied entries, sorted.
+pub fn snapshot_parameter_cache<P: AsRef<Path>>(dest_dir: P) -> Result<Vec<String>> {
+    snapshot_cache_dir(&parameter_cache_dir(), dest_dir.as_ref())
+}
+
+/// Copies the entries of a snapshot taken by `snapshot_parameter_cache` from `src_dir` into the
+/// parameter cache. The digest of every entry is checked before anything is copied, so a
+/// corrupted snapshot leaves the cache untouched.
+pub fn restore_parameter_cache<P: AsRef<Path>>(src_dir: P) -> Result<()> {
+    restore_cache_dir(src_dir.as_ref(), &parameter_cache_dir())
+}
+
+fn snapshot_cache_dir(cache_dir: &Path, dest_dir: &Path) -> Result<Vec<String>> {
+    create_dir_all(dest_dir)?;
+
+    let mut digests = BTreeMap::new();
+    for entry in fs::read_dir(cache_dir)
+        .with_context(|| format!("could not read parameter cache {:?}", cache_dir))?
+    {
+        let path = entry?.path();
+        if !path.is_file() || !has_cache_entry_ext(&path) {
+            continue;
+        }
+
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .with_context(|| format!("invalid cache entry name {:?}", path))?
+            .to_string();
+        fs::copy(&path, dest_dir.join(&name))?;
+        digests.insert(name, file_digest(&dest_dir.join(&name))?);
+        info!("added {:?} to parameter cache snapshot", path);
+    }
+
+    let digests_file = File::create(dest_dir.join(PARAMETER_DIGESTS_FILE))?;
+    serde_json::to_writer_pretty(digests_file, &digests)?;
+
+    Ok(digests.into_iter().map(|(name, _)| name).collect())
+}
+
+fn restore_cache_dir(src_dir: &Path, cache_dir: &Path) -> Result<()> {
+    let digests_path = src_dir.join(PARAMETER_DIGESTS_FILE);
+    let digests: BTreeMap<String, String> = serde_json::from_reader(
+        File::open(&digests_path)
+            .with_context(|| format!("could not open snapshot digests {:?}", digests_path))?,
+    )?;
+
+    for (name, digest) in &digests {
+        ensure!(
+            is_cache_entry_name(name),
+            "invalid cache entry name {:?} in snapshot digests",
+            name
+        );
+        let actual = file_digest(&src_dir.join(name))?;
+        ensure!(
+            &actual == digest,
+            "digest mismatch for {}: expected {}, got {}",
+            name,
+            digest,
+            actual
+        );
+    }
+
+    create_dir_all(cache_dir)?;
+    for name in digests.keys() {
+        fs::copy(src_dir.join(name), cache_dir.join(name))?;
+        info!("restored {} into parameter cache {:?}", name, cache_dir);
+    }
+
+    Ok(())
+}
+
+fn has_cache_entry_ext(path: &Path) -> bool {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => CACHE_ENTRY_EXTS.contains(&ext),
+        None => false,
+    }
+}
+
+/// Whether `name` is the plain file name of a cache entry, so that restoring it cannot write
+/// outside the cache directory.
+fn is_cache_entry_name(name: &str) -> bool {
+    let path = Path::new(name);
+    let mut components = path.components();
+    let is_file_name = match (components.next(), components.next()) {
+        (Some(Component::Normal(component)), None) => component == path.as_os_str(),
+        _ => false,
+    };
+
+    is_file_name && !name.contains('/') && !name.contains('\\') && has_cache_entry_ext(path)
+}
+
+/// Returns the hex encoded sha256 digest of the file at `path`.
+fn file_digest(path: &Path) -> Result<String> {
+    let mut file =
+        File::open(path).with_context(|| format!("could not open cache entry {:?}", path))?;
+    let mut hasher = Sha256::default();
+    let mut buf = vec![0u8; 1 << 16];
+    loop {
+        let read = file.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        hasher.input(&buf[..read]);
+    }
+
+    Ok(hex::encode(hasher.result()))
+}
+
 fn ensure_parent(path: &PathBuf) -> Result<()> {
     match path.parent() {
         Some(dir) => {
@@ -355,3 +560,136 @@ fn with_open_file<'a, T>(
     ensure_parent(&file_path)?;
     f(&mut open_file(&file_path)?)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bellperson::gadgets::num;
+    use bellperson::{ConstraintSystem, SynthesisError};
+    use ff::Field;
+    use paired::bls12_381::{Bls12, Fr};
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    /// Proves knowledge of `x` and `y` such that `x * y` is the public input.
+    struct MulCircuit {
+        x: Option<Fr>,
+        y: Option<Fr>,
+    }
+
+    impl Circuit<Bls12> for MulCircuit {
+        fn synthesize<CS: ConstraintSystem<Bls12>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let x = num::AllocatedNum::alloc(cs.namespace(|| "x"), || {
+                self.x.ok_or(SynthesisError::AssignmentMissing)
+            })?;
+            let y = num::AllocatedNum::alloc(cs.namespace(|| "y"), || {
+                self.y.ok_or(SynthesisError::AssignmentMissing)
+            })?;
+            let product = x.mul(cs.namespace(|| "x * y"), &y)?;
+            product.inputize(cs.namespace(|| "product"))
+        }
+    }
+
+    #[test]
+    fn parameters_stream_roundtrip() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let identifier = "mul-circuit";
+
+        let params = groth16::generate_random_parameters::<Bls12, _, _>(
+            MulCircuit { x: None, y: None },
+            rng,
+        )
+        .unwrap();
+
+        let mut buf = Vec::new();
+        write_parameters(&params, identifier, &mut buf).unwrap();
+
+        let err = read_parameters::<Bls12, _>(buf.as_slice(), "other-circuit").unwrap_err();
+        assert!(err.to_string().contains("other-circuit"), "{}", err);
+
+        let read_params = read_parameters::<Bls12, _>(buf.as_slice(), identifier).unwrap();
+        assert!(read_params == params);
+
+        let x = Fr::random(rng);
+        let y = Fr::random(rng);
+        let mut product = x;
+        product.mul_assign(&y);
+
+        let proof = groth16::create_random_proof(
+            MulCircuit {
+                x: Some(x),
+                y: Some(y),
+            },
+            &read_params,
+            rng,
+        )
+        .unwrap();
+
+        let pvk = groth16::prepare_verifying_key(&read_params.vk);
+        assert!(groth16::verify_proof(&pvk, &proof, &[product]).unwrap());
+        assert!(!groth16::verify_proof(&pvk, &proof, &[x]).unwrap());
+    }
+
+    #[test]
+    fn snapshot_restore_parameter_cache() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let entries = [
+            ("v24-dummy.params", b"groth parameters".to_vec()),
+            ("v24-dummy.meta", b"{\"sector_size\":2048}".to_vec()),
+            ("v24-dummy.vk", b"verifying key".to_vec()),
+        ];
+        for (name, contents) in &entries {
+            fs::write(cache_dir.path().join(name), contents).unwrap();
+        }
+        fs::write(cache_dir.path().join("notes.txt"), b"not an entry").unwrap();
+
+        let snapshot_dir = tempfile::tempdir().unwrap();
+        let names = snapshot_cache_dir(cache_dir.path(), snapshot_dir.path()).unwrap();
+        assert_eq!(
+            names,
+            vec!["v24-dummy.meta", "v24-dummy.params", "v24-dummy.vk"]
+        );
+        assert!(!snapshot_dir.path().join("notes.txt").exists());
+
+        let restored_dir = tempfile::tempdir().unwrap();
+        let restored_cache = restored_dir.path().join("cache");
+        restore_cache_dir(snapshot_dir.path(), &restored_cache).unwrap();
+        for (name, contents) in &entries {
+            assert_eq!(&fs::read(restored_cache.join(name)).unwrap(), contents);
+        }
+
+        // A corrupted entry fails the digest check, and nothing is restored.
+        fs::write(snapshot_dir.path().join("v24-dummy.vk"), b"tampered key").unwrap();
+        let untouched_cache = restored_dir.path().join("untouched");
+        let err = restore_cache_dir(snapshot_dir.path(), &untouched_cache).unwrap_err();
+        assert!(
+            err.to_string().contains("digest mismatch for v24-dummy.vk"),
+            "{}",
+            err
+        );
+        assert!(!untouched_cache.exists());
+
+        // Entry names must stay inside the cache directory and carry a cache entry extension.
+        for name in &["../v24-dummy.vk", "sub/v24-dummy.vk", "..", "notes.txt"] {
+            let mut digests = BTreeMap::new();
+            digests.insert(name.to_string(), String::new());
+            fs::write(
+                snapshot_dir.path().join(PARAMETER_DIGESTS_FILE),
+                serde_json::to_vec(&digests).unwrap(),
+            )
+            .unwrap();
+
+            let err = restore_cache_dir(snapshot_dir.path(), &untouched_cache).unwrap_err();
+            assert!(
+                err.to_string().contains("invalid cache entry name"),
+                "{}",
+                err
+            );
+            assert!(!untouched_cache.exists());
+        }
+    }
+}
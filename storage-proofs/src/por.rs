@@ -1,5 +1,6 @@
 use std::marker::PhantomData;
 
+#[cfg(not(feature = "verify-only"))]
 use anyhow::ensure;
 use generic_array::typenum;
 use serde::{Deserialize, Serialize};
@@ -113,6 +114,7 @@ impl<'a, H: 'a + Hasher, U: 'a + typenum::Unsigned + Sync + Send + Clone> ProofS
         })
     }
 
+    #[cfg(not(feature = "verify-only"))]
     fn prove<'b>(
         pub_params: &'b Self::PublicParams,
         pub_inputs: &'b Self::PublicInputs,
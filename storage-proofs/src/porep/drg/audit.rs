@@ -0,0 +1,297 @@
+use std::collections::BTreeSet;
+
+use anyhow::ensure;
+use generic_array::typenum;
+use rand::Rng;
+
+use crate::drgraph::Graph;
+use crate::error::Result;
+use crate::hasher::{Domain, Hasher};
+use crate::parameter_cache::ParameterSetMetadata;
+
+use super::layout::{LaidOutReplica, ReplicaSource};
+use super::vanilla::{create_key_from_laid_out_replica, decode, PublicParams, Tau};
+
+/// The outcome of sampling a replica with `audit_encoding`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuditReport {
+    /// Number of nodes that were sampled, including repeated samples of the same node.
+    pub sampled: usize,
+    /// Sampled nodes whose replica data does not match the expected encoding, in ascending order.
+    pub mismatches: Vec<usize>,
+}
+
+impl AuditReport {
+    /// Returns true if every sampled node was encoded correctly.
+    pub fn is_consistent(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+/// Checks, without producing a proof, that randomly sampled nodes of `replica` are the encoding
+/// of the corresponding nodes in `data`.
+///
+/// For each sampled node the encoding key is recomputed from its parents in `replica`, the data
+/// node is encoded with it and the result is compared to the replica node. A replica with a
+/// fraction `f` of mis-encoded nodes passes with probability `(1 - f)^sample_nodes`.
+pub fn audit_encoding<H, G, R>(
+    pub_params: &PublicParams<H, G>,
+    replica_id: &H::Domain,
+    replica: &LaidOutReplica<'_>,
+    data: &LaidOutReplica<'_>,
+    sample_nodes: usize,
+    rng: &mut R,
+) -> Result<AuditReport>
+where
+    H: Hasher,
+    G: Graph<H> + ParameterSetMetadata,
+    R: Rng,
+{
+    ensure!(sample_nodes > 0, "must sample at least one node");
+
+    let graph = &pub_params.graph;
+    let mut parents = vec![0; graph.degree()];
+    let mut mismatches = BTreeSet::new();
+
+    for _ in 0..sample_nodes {
+        let node = rng.gen_range(0, graph.size());
+        graph.parents(node, &mut parents)?;
+
+        let key = create_key_from_laid_out_replica::<H>(replica_id, node, &parents, replica)?;
+        let data_node = H::Domain::try_from_bytes(data.read_node(node)?)?;
+        let replica_node = H::Domain::try_from_bytes(replica.read_node(node)?)?;
+
+        if pub_params.encoding_op.encode::<H>(&key, &data_node)? != replica_node {
+            mismatches.insert(node);
+        }
+    }
+
+    Ok(AuditReport {
+        sampled: sample_nodes,
+        mismatches: mismatches.into_iter().collect(),
+    })
+}
+
+/// Checks that `replica` decodes to the data committed to by `expected.comm_d`, and that the
+/// replica itself is committed to by `expected.comm_r`.
+///
+/// The whole replica is decoded and tree_d is rebuilt from the recovered data. Encoding is
+/// deterministic, so replicating the recovered data again yields `replica` itself, and its
+/// tree_r is built directly. Unlike `audit_encoding` this covers every node, at the cost of a
+/// full decoding, with two copies of the sector held in memory. A remote replica is fetched in
+/// a single range read.
+pub fn verify_roundtrip<H, G>(
+    pub_params: &PublicParams<H, G>,
+    replica_id: &H::Domain,
+    replica: &ReplicaSource<'_>,
+    expected: &Tau<H::Domain>,
+) -> Result<bool>
+where
+    H: Hasher,
+    G::Key: AsRef<H::Domain>,
+    G: Graph<H> + ParameterSetMetadata + Sync,
+{
+    let graph = &pub_params.graph;
+    let sector_size = graph.expected_size();
+
+    // Decoding reads the parents of each node by logical index, so the replica is gathered in
+    // logical order first.
+    let sealed = match replica {
+        ReplicaSource::Local(replica) => {
+            let mut sealed = Vec::with_capacity(sector_size);
+            for node in 0..graph.size() {
+                sealed.extend_from_slice(replica.read_node(node)?);
+            }
+            sealed
+        }
+        ReplicaSource::Remote(reader) => {
+            let sealed = reader.read_range(0, sector_size)?;
+            ensure!(
+                sealed.len() == sector_size,
+                "expected {} bytes of replica, got {}",
+                sector_size,
+                sealed.len()
+            );
+            sealed
+        }
+    };
+
+    let tree_r = graph.merkle_tree::<typenum::U2>(None, &sealed)?;
+    if tree_r.root() != expected.comm_r {
+        return Ok(false);
+    }
+
+    let data = decode(graph, replica_id, &sealed, None, pub_params.encoding_op)?;
+    let tree_d = graph.merkle_tree::<typenum::U2>(None, &data)?;
+
+    Ok(tree_d.root() == expected.comm_d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::drgraph::{new_seed, BucketGraph, BASE_DEGREE};
+    use crate::hasher::{PedersenHasher, Sha256Hasher};
+    use crate::porep::drg::{
+        DrgParams, DrgPoRep, Identity, InMemoryRangeReader, SetupParams, TestReplica,
+    };
+    use crate::proof::ProofScheme;
+    use crate::util::{data_at_node_offset, NODE_SIZE};
+
+    fn test_audit_catches_misencoded_node<H: Hasher>() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let nodes = 16;
+        let sp = SetupParams {
+            drg: DrgParams {
+                nodes,
+                degree: BASE_DEGREE,
+                expansion_degree: 0,
+                seed: new_seed(),
+            },
+            private: false,
+            challenges_count: 1,
+        };
+        let pp = DrgPoRep::<H, BucketGraph<H>>::setup(&sp).expect("setup failed");
+
+        let mut replica = TestReplica::new(&pp, rng);
+
+        let report = audit_encoding(
+            &pp,
+            &replica.replica_id,
+            &LaidOutReplica::new(&replica.replica, &Identity),
+            &LaidOutReplica::new(&replica.data, &Identity),
+            64,
+            rng,
+        )
+        .unwrap();
+        assert_eq!(report.sampled, 64);
+        assert!(report.is_consistent(), "{:?}", report.mismatches);
+
+        // Replace the encoding of a single node. With 256 samples over 16 nodes the chance of
+        // never sampling it is (15/16)^256, well below one in a million.
+        let bad_node = nodes - 3;
+        let offset = data_at_node_offset(bad_node);
+        let bogus: H::Domain = H::Domain::random(rng);
+        bogus
+            .write_bytes(&mut replica.replica[offset..offset + NODE_SIZE])
+            .unwrap();
+
+        let report = audit_encoding(
+            &pp,
+            &replica.replica_id,
+            &LaidOutReplica::new(&replica.replica, &Identity),
+            &LaidOutReplica::new(&replica.data, &Identity),
+            256,
+            rng,
+        )
+        .unwrap();
+        assert!(!report.is_consistent());
+        assert!(report.mismatches.contains(&bad_node));
+    }
+
+    #[test]
+    fn audit_catches_misencoded_node_pedersen() {
+        test_audit_catches_misencoded_node::<PedersenHasher>();
+    }
+
+    #[test]
+    fn audit_catches_misencoded_node_sha256() {
+        test_audit_catches_misencoded_node::<Sha256Hasher>();
+    }
+
+    #[test]
+    fn audit_requires_samples() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let data = vec![0u8; 4 * NODE_SIZE];
+
+        let sp = SetupParams {
+            drg: DrgParams {
+                nodes: 4,
+                degree: BASE_DEGREE,
+                expansion_degree: 0,
+                seed: new_seed(),
+            },
+            private: false,
+            challenges_count: 1,
+        };
+        let pp =
+            DrgPoRep::<PedersenHasher, BucketGraph<PedersenHasher>>::setup(&sp).expect("setup");
+        let source = LaidOutReplica::new(&data, &Identity);
+
+        assert!(audit_encoding(&pp, &Default::default(), &source, &source, 0, rng).is_err());
+    }
+
+    fn test_verify_roundtrip<H: Hasher>() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let nodes = 16;
+        let sp = SetupParams {
+            drg: DrgParams {
+                nodes,
+                degree: BASE_DEGREE,
+                expansion_degree: 0,
+                seed: new_seed(),
+            },
+            private: false,
+            challenges_count: 1,
+        };
+        let pp = DrgPoRep::<H, BucketGraph<H>>::setup(&sp).expect("setup failed");
+
+        let mut replica = TestReplica::new(&pp, rng);
+
+        fn local(replica: &[u8]) -> ReplicaSource<'_> {
+            ReplicaSource::Local(LaidOutReplica::new(replica, &Identity))
+        }
+
+        assert!(verify_roundtrip(
+            &pp,
+            &replica.replica_id,
+            &local(&replica.replica),
+            &replica.tau
+        )
+        .unwrap());
+
+        let remote =
+            ReplicaSource::Remote(Box::new(InMemoryRangeReader::new(replica.replica.clone())));
+        assert!(verify_roundtrip(&pp, &replica.replica_id, &remote, &replica.tau).unwrap());
+
+        // The replica matches comm_r, but does not decode to the data committed to by comm_d.
+        let wrong_comm_d = Tau::new(H::Domain::random(rng), replica.tau.comm_r);
+        assert!(!verify_roundtrip(
+            &pp,
+            &replica.replica_id,
+            &local(&replica.replica),
+            &wrong_comm_d
+        )
+        .unwrap());
+
+        let offset = data_at_node_offset(nodes / 2);
+        let bogus: H::Domain = H::Domain::random(rng);
+        bogus
+            .write_bytes(&mut replica.replica[offset..offset + NODE_SIZE])
+            .unwrap();
+
+        assert!(!verify_roundtrip(
+            &pp,
+            &replica.replica_id,
+            &local(&replica.replica),
+            &replica.tau
+        )
+        .unwrap());
+    }
+
+    #[test]
+    fn verify_roundtrip_pedersen() {
+        test_verify_roundtrip::<PedersenHasher>();
+    }
+
+    #[test]
+    fn verify_roundtrip_sha256() {
+        test_verify_roundtrip::<Sha256Hasher>();
+    }
+}
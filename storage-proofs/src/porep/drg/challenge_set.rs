@@ -0,0 +1,88 @@
+use anyhow::ensure;
+use bitvec::{bitvec, order::Lsb0, vec::BitVec};
+
+use crate::error::Result;
+
+/// The nodes challenged by a `DrgPoRep` proof.
+///
+/// Both forms of the same set of nodes produce identical proofs and public inputs, as long as the
+/// indices are ascending and distinct.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChallengeSet {
+    /// The challenged nodes, in proving order. Nodes may repeat.
+    Indices(Vec<usize>),
+    /// Bit `i` is set if node `i` is challenged. The nodes are proven in ascending order. This
+    /// takes one bit per node of the graph, much less than `Indices` if most nodes are
+    /// challenged.
+    Bitmap(BitVec<Lsb0, u64>),
+}
+
+impl ChallengeSet {
+    /// Builds the bitmap of a graph with `nodes` nodes, challenging `challenges`. Fails if a
+    /// challenge is not a node of the graph.
+    pub fn bitmap(nodes: usize, challenges: &[usize]) -> Result<Self> {
+        let mut bitmap = bitvec![Lsb0, u64; 0; nodes];
+        for &challenge in challenges {
+            ensure!(
+                challenge < nodes,
+                "challenge {} is out of range for {} nodes",
+                challenge,
+                nodes
+            );
+            bitmap.set(challenge, true);
+        }
+
+        Ok(ChallengeSet::Bitmap(bitmap))
+    }
+
+    /// Returns the number of challenges.
+    pub fn len(&self) -> usize {
+        match self {
+            ChallengeSet::Indices(indices) => indices.len(),
+            ChallengeSet::Bitmap(bitmap) => bitmap.iter().filter(|bit| **bit).count(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the challenged nodes in proving order.
+    pub fn iter(&self) -> Box<dyn Iterator<Item = usize> + '_> {
+        match self {
+            ChallengeSet::Indices(indices) => Box::new(indices.iter().copied()),
+            ChallengeSet::Bitmap(bitmap) => Box::new(
+                bitmap
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, bit)| **bit)
+                    .map(|(node, _)| node),
+            ),
+        }
+    }
+}
+
+impl From<Vec<usize>> for ChallengeSet {
+    fn from(indices: Vec<usize>) -> Self {
+        ChallengeSet::Indices(indices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bitmap_iterates_ascending() {
+        let challenges = ChallengeSet::bitmap(64, &[17, 3, 63, 3, 0]).unwrap();
+
+        assert_eq!(challenges.len(), 4);
+        assert_eq!(challenges.iter().collect::<Vec<_>>(), vec![0, 3, 17, 63]);
+        assert!(ChallengeSet::bitmap(64, &[]).unwrap().is_empty());
+        assert!(ChallengeSet::bitmap(64, &[3, 64]).is_err());
+
+        let indices = ChallengeSet::from(vec![17, 3, 3]);
+        assert_eq!(indices.len(), 3);
+        assert_eq!(indices.iter().collect::<Vec<_>>(), vec![17, 3, 3]);
+    }
+}
@@ -11,18 +11,20 @@ use fil_sapling_crypto::jubjub::JubjubEngine;
 use generic_array::typenum;
 use paired::bls12_381::{Bls12, Fr};
 
+use crate::circuit_timing::{time_phase, SynthesisPhase};
 use crate::compound_proof::CircuitComponent;
 use crate::crypto::pedersen::JJ_PARAMS;
 use crate::error::Result;
 use crate::fr32::fr_into_bytes;
 use crate::gadgets::constraint;
-use crate::gadgets::encode;
 use crate::gadgets::por::PoRCircuit;
 use crate::gadgets::uint64;
 use crate::gadgets::variables::Root;
 use crate::hasher::Hasher;
 use crate::util::bytes_into_boolean_vec_be;
 
+use super::encoding_op::EncodingOp;
+
 /// DRG based Proof of Replication.
 ///
 /// # Fields
@@ -43,6 +45,7 @@ use crate::util::bytes_into_boolean_vec_be;
 /// * `data_node_path` - The path of the data node being proven.
 /// * `data_root` - The merkle root of the data.
 /// * `replica_id` - The id of the replica.
+/// * `encoding_op` - How the replica nodes were encoded.
 ///
 
 pub struct DrgPoRepCircuit<'a, H: Hasher> {
@@ -60,6 +63,7 @@ pub struct DrgPoRepCircuit<'a, H: Hasher> {
     pub data_root: Root<Bls12>,
     pub replica_id: Option<Fr>,
     pub private: bool,
+    pub encoding_op: EncodingOp,
     pub _h: PhantomData<H>,
 }
 
@@ -77,6 +81,7 @@ impl<'a, H: Hasher> DrgPoRepCircuit<'a, H> {
         data_root: Root<Bls12>,
         replica_id: Option<Fr>,
         private: bool,
+        encoding_op: EncodingOp,
     ) -> Result<(), SynthesisError>
     where
         CS: ConstraintSystem<Bls12>,
@@ -93,6 +98,7 @@ impl<'a, H: Hasher> DrgPoRepCircuit<'a, H> {
             data_root,
             replica_id,
             private,
+            encoding_op,
             _h: Default::default(),
         }
         .synthesize(&mut cs)
@@ -148,21 +154,30 @@ impl<'a, H: Hasher> Circuit<Bls12> for DrgPoRepCircuit<'a, H> {
         assert_eq!(self.replica_parents_paths.len(), nodes);
         assert_eq!(self.data_nodes_paths.len(), nodes);
 
-        // get the replica_id in bits
-        let replica_id_bits = match replica_id {
-            Some(id) => {
-                let raw_bytes = fr_into_bytes::<Bls12>(&id);
-                bytes_into_boolean_vec_be(cs.namespace(|| "replica_id_bits"), Some(&raw_bytes), 256)
-            }
-            None => bytes_into_boolean_vec_be(cs.namespace(|| "replica_id_bits"), None, 256),
-        }?;
-
         let replica_node_num = num::AllocatedNum::alloc(cs.namespace(|| "replica_id_num"), || {
             replica_id.ok_or_else(|| SynthesisError::AssignmentMissing)
         })?;
 
         replica_node_num.inputize(cs.namespace(|| "replica_id"))?;
 
+        // get the replica_id in bits, in the byte order of `fr_into_bytes`, with the bits of each
+        // byte in big endian. The bits are unpacked from the public replica_id, so that the key
+        // derivation is bound to it. The decomposition is strict, as the bits of `replica_id + r`
+        // would otherwise derive another key from the same public input.
+        let replica_id_bits = {
+            let mut bits_le =
+                replica_node_num.to_bits_le_strict(cs.namespace(|| "replica_id_bits"))?;
+            while bits_le.len() % 8 != 0 {
+                bits_le.push(Boolean::Constant(false));
+            }
+
+            bits_le
+                .chunks_exact(8)
+                .flat_map(|chunk| chunk.iter().rev())
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+
         let replica_root_var = Root::Var(replica_root.allocated(cs.namespace(|| "replica_root"))?);
         let data_root_var = Root::Var(data_root.allocated(cs.namespace(|| "data_root"))?);
 
@@ -184,40 +199,46 @@ impl<'a, H: Hasher> Circuit<Bls12> for DrgPoRepCircuit<'a, H> {
             // Inclusion checks
             {
                 let mut cs = cs.namespace(|| "inclusion_checks");
-                PoRCircuit::<typenum::U2, Bls12, H>::synthesize(
-                    cs.namespace(|| "replica_inclusion"),
-                    &params,
-                    Root::Val(*replica_node),
-                    replica_node_path.clone(),
-                    replica_root_var.clone(),
-                    self.private,
-                )?;
-
-                // validate each replica_parents merkle proof
-                for j in 0..replica_parents.len() {
+                time_phase(SynthesisPhase::PorReplica, || {
                     PoRCircuit::<typenum::U2, Bls12, H>::synthesize(
-                        cs.namespace(|| format!("parents_inclusion_{}", j)),
+                        cs.namespace(|| "replica_inclusion"),
                         &params,
-                        Root::Val(replica_parents[j]),
-                        replica_parents_paths[j].clone(),
+                        Root::Val(*replica_node),
+                        replica_node_path.clone(),
                         replica_root_var.clone(),
                         self.private,
                     )?;
-                }
+
+                    // validate each replica_parents merkle proof
+                    for j in 0..replica_parents.len() {
+                        PoRCircuit::<typenum::U2, Bls12, H>::synthesize(
+                            cs.namespace(|| format!("parents_inclusion_{}", j)),
+                            &params,
+                            Root::Val(replica_parents[j]),
+                            replica_parents_paths[j].clone(),
+                            replica_root_var.clone(),
+                            self.private,
+                        )?;
+                    }
+
+                    Ok::<_, SynthesisError>(())
+                })?;
 
                 // validate data node commitment
-                PoRCircuit::<typenum::U2, Bls12, H>::synthesize(
-                    cs.namespace(|| "data_inclusion"),
-                    &params,
-                    Root::Val(*data_node),
-                    data_node_path.clone(),
-                    data_root_var.clone(),
-                    self.private,
-                )?;
+                time_phase(SynthesisPhase::PorData, || {
+                    PoRCircuit::<typenum::U2, Bls12, H>::synthesize(
+                        cs.namespace(|| "data_inclusion"),
+                        &params,
+                        Root::Val(*data_node),
+                        data_node_path.clone(),
+                        data_root_var.clone(),
+                        self.private,
+                    )
+                })?;
             }
 
             // Encoding checks
-            {
+            time_phase(SynthesisPhase::Encoding, || {
                 let mut cs = cs.namespace(|| "encoding_checks");
                 // get the parents into bits
                 let parents_bits: Vec<Vec<Boolean>> = replica_parents
@@ -254,7 +275,11 @@ impl<'a, H: Hasher> Circuit<Bls12> for DrgPoRepCircuit<'a, H> {
                         (*replica_node).ok_or_else(|| SynthesisError::AssignmentMissing)
                     })?;
 
-                let decoded = encode::decode(cs.namespace(|| "decode"), &key, &replica_node_num)?;
+                let decoded = self.encoding_op.decode_circuit(
+                    cs.namespace(|| "decode"),
+                    &key,
+                    &replica_node_num,
+                )?;
 
                 // TODO this should not be here, instead, this should be the leaf Fr in the data_auth_path
                 // TODO also note that we need to change/makesurethat the leaves are the data, instead of hashes of the data
@@ -264,7 +289,9 @@ impl<'a, H: Hasher> Circuit<Bls12> for DrgPoRepCircuit<'a, H> {
 
                 // ensure the encrypted data and data_node match
                 constraint::equal(&mut cs, || "equality", &expected, &decoded);
-            }
+
+                Ok::<_, SynthesisError>(())
+            })?;
         }
         // profit!
         Ok(())
@@ -408,7 +435,7 @@ mod tests {
 
         let pub_inputs = drg::PublicInputs {
             replica_id: Some(replica_id.into()),
-            challenges: vec![challenge],
+            challenges: vec![challenge].into(),
             tau: Some(tau.into()),
         };
 
@@ -416,6 +443,7 @@ mod tests {
             tree_d: &aux.tree_d,
             tree_r: &aux.tree_r,
             tree_r_config_levels: StoreConfig::default_cached_above_base_layer(nodes, BINARY_ARITY),
+            replica: None,
         };
 
         let proof_nc = drg::DrgPoRep::<PedersenHasher, _>::prove(&pp, &pub_inputs, &priv_inputs)
@@ -478,6 +506,7 @@ mod tests {
             data_root,
             replica_id,
             false,
+            EncodingOp::default(),
         )
         .expect("failed to synthesize circuit");
 
@@ -490,7 +519,7 @@ mod tests {
 
         assert!(cs.is_satisfied(), "constraints not satisfied");
         assert_eq!(cs.num_inputs(), 18, "wrong number of inputs");
-        assert_eq!(cs.num_constraints(), 149607, "wrong number of constraints");
+        assert_eq!(cs.num_constraints(), 149739, "wrong number of constraints");
 
         assert_eq!(cs.get_input(0, "ONE"), Fr::one());
 
@@ -519,6 +548,17 @@ mod tests {
             expected_inputs.len() - 1,
             "inputs are not the same length"
         );
+
+        // The key derivation is bound to the public replica_id: substituting a different
+        // replica_id for the public input, while keeping the encoding, must not satisfy.
+        let mut other_replica_id = replica_id.unwrap();
+        other_replica_id.add_assign(&Fr::one());
+        cs.set("drgporep/replica_id/input variable", other_replica_id);
+        cs.set("drgporep/replica_id_num/num", other_replica_id);
+        assert!(
+            !cs.is_satisfied(),
+            "constraints satisfied with a different replica_id"
+        );
     }
 
     #[test]
@@ -543,10 +583,57 @@ mod tests {
             Root::Val(Some(Fr::random(rng))),
             Some(Fr::random(rng)),
             false,
+            EncodingOp::default(),
         )
         .expect("failed to synthesize circuit");
 
         assert_eq!(cs.num_inputs(), 18, "wrong number of inputs");
-        assert_eq!(cs.num_constraints(), 391_431, "wrong number of constraints");
+        assert_eq!(cs.num_constraints(), 391_563, "wrong number of constraints");
+    }
+
+    #[cfg(feature = "circuit-timing")]
+    #[test]
+    fn drgporep_synthesis_timing() {
+        use crate::circuit_timing::take_synthesis_timing;
+
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let n = 64;
+        let m = BASE_DEGREE;
+        let tree_depth = graph_height::<typenum::U2>(n);
+
+        take_synthesis_timing();
+        let mut cs = TestConstraintSystem::<Bls12>::new();
+        DrgPoRepCircuit::<PedersenHasher>::synthesize(
+            cs.namespace(|| "drgporep"),
+            vec![Some(Fr::random(rng)); 2],
+            vec![vec![(vec![Some(Fr::random(rng))], Some(0)); tree_depth]; 2],
+            Root::Val(Some(Fr::random(rng))),
+            vec![vec![Some(Fr::random(rng)); m]; 2],
+            vec![vec![vec![(vec![Some(Fr::random(rng))], Some(0)); tree_depth]; m]; 2],
+            vec![Some(Fr::random(rng)); 2],
+            vec![vec![(vec![Some(Fr::random(rng))], Some(0)); tree_depth]; 2],
+            Root::Val(Some(Fr::random(rng))),
+            Some(Fr::random(rng)),
+            false,
+            EncodingOp::default(),
+        )
+        .expect("failed to synthesize circuit");
+
+        let timing = take_synthesis_timing();
+        for phase in &[
+            SynthesisPhase::PorData,
+            SynthesisPhase::PorReplica,
+            SynthesisPhase::Encoding,
+        ] {
+            assert!(
+                timing.get(*phase) > std::time::Duration::from_secs(0),
+                "no time recorded for {:?}",
+                phase
+            );
+        }
+        assert_eq!(timing.phases.len(), 3);
+
+        assert!(take_synthesis_timing().phases.is_empty());
     }
 }
@@ -1,21 +1,24 @@
+use std::collections::BTreeMap;
 use std::marker::PhantomData;
 
 use anyhow::{ensure, Context};
-use bellperson::Circuit;
+use bellperson::{groth16, Circuit};
 use fil_sapling_crypto::jubjub::JubjubEngine;
 use generic_array::typenum;
 use paired::bls12_381::{Bls12, Fr};
 
-use crate::compound_proof::{CircuitComponent, CompoundProof};
+use crate::compound_proof::{self, CircuitComponent, CompoundProof};
 use crate::crypto::pedersen::JJ_PARAMS;
 use crate::drgraph::Graph;
 use crate::error::Result;
 use crate::gadgets::por::PoRCompound;
 use crate::gadgets::variables::Root;
-use crate::hasher::Hasher;
+use crate::gadgets::MetricCS;
+use crate::hasher::{Domain, Hasher};
+use crate::multi_proof::MultiProof;
 use crate::parameter_cache::{CacheableParameters, ParameterSetMetadata};
 use crate::por;
-use crate::porep::drg::DrgPoRep;
+use crate::porep::drg::{DrgPoRep, PrivateInputs, PublicInputs, PublicParams};
 use crate::proof::ProofScheme;
 
 use super::circuit::DrgPoRepCircuit;
@@ -76,237 +79,1584 @@ where
         // We can ignore k because challenges are generated by caller and included
         // in PublicInputs.
         _k: Option<usize>,
+    ) -> Result<Vec<Fr>> {
+        Self::generate_inputs(pub_in, pub_params, true)
+    }
+
+    fn circuit(
+        public_inputs: &<DrgPoRep<'a, H, G> as ProofScheme<'a>>::PublicInputs,
+        component_private_inputs: <DrgPoRepCircuit<'a, H> as CircuitComponent>::ComponentPrivateInputs,
+        proof: &<DrgPoRep<'a, H, G> as ProofScheme<'a>>::Proof,
+        public_params: &<DrgPoRep<'a, H, G> as ProofScheme<'a>>::PublicParams,
+    ) -> Result<DrgPoRepCircuit<'a, H>> {
+        Self::check_circuit_inputs(
+            public_inputs,
+            &component_private_inputs,
+            proof,
+            public_params,
+        )?;
+
+        let replica_nodes: Vec<_> = proof
+            .replica_nodes
+            .iter()
+            .map(|node| Some(node.data.into()))
+            .collect();
+
+        let replica_nodes_paths: Vec<_> = proof
+            .replica_nodes
+            .iter()
+            .map(|node| node.proof.as_options())
+            .collect();
+
+        let is_private = public_params.private;
+
+        let (data_root, replica_root) = if is_private {
+            (
+                component_private_inputs.comm_d.context("is_private")?,
+                component_private_inputs.comm_r.context("is_private")?,
+            )
+        } else {
+            (
+                Root::Val(Some(proof.data_root.into())),
+                Root::Val(Some(proof.replica_root.into())),
+            )
+        };
+
+        let replica_id = public_inputs.replica_id;
+
+        let replica_parents: Vec<_> = proof
+            .replica_parents
+            .iter()
+            .map(|parents| {
+                parents
+                    .iter()
+                    .map(|(_, parent)| Some(parent.data.into()))
+                    .collect()
+            })
+            .collect();
+
+        let replica_parents_paths: Vec<Vec<_>> = proof
+            .replica_parents
+            .iter()
+            .map(|parents| {
+                let p: Vec<_> = parents
+                    .iter()
+                    .map(|(_, parent)| parent.proof.as_options())
+                    .collect();
+                p
+            })
+            .collect();
+
+        let data_nodes: Vec<_> = proof
+            .nodes
+            .iter()
+            .map(|node| Some(node.data.into()))
+            .collect();
+
+        let data_nodes_paths: Vec<_> = proof
+            .nodes
+            .iter()
+            .map(|node| node.proof.as_options())
+            .collect();
+
+        Ok(DrgPoRepCircuit {
+            params: &*JJ_PARAMS,
+            replica_nodes,
+            replica_nodes_paths,
+            replica_root,
+            replica_parents,
+            replica_parents_paths,
+            data_nodes,
+            data_nodes_paths,
+            data_root,
+            replica_id: replica_id.map(Into::into),
+            private: public_params.private,
+            encoding_op: public_params.encoding_op,
+            _h: Default::default(),
+        })
+    }
+
+    fn check_circuit_inputs(
+        public_inputs: &<DrgPoRep<'a, H, G> as ProofScheme<'a>>::PublicInputs,
+        component_private_inputs: &<DrgPoRepCircuit<'a, H> as CircuitComponent>::ComponentPrivateInputs,
+        proof: &<DrgPoRep<'a, H, G> as ProofScheme<'a>>::Proof,
+        public_params: &<DrgPoRep<'a, H, G> as ProofScheme<'a>>::PublicParams,
+    ) -> Result<()> {
+        let challenges = public_params.challenges_count;
+        let len = proof.nodes.len();
+
+        ensure!(len <= challenges, "too many challenges");
+        ensure!(
+            proof.replica_parents.len() == len,
+            "Number of replica parents must match"
+        );
+        ensure!(
+            proof.replica_nodes.len() == len,
+            "Number of replica nodes must match"
+        );
+
+        let degree = public_params.graph.degree();
+        for (i, parents) in proof.replica_parents.iter().enumerate() {
+            ensure!(
+                parents.len() == degree,
+                "challenge {} has {} parents, expected {}",
+                i,
+                parents.len(),
+                degree
+            );
+        }
+
+        ensure!(
+            public_inputs.tau.is_none() == public_params.private,
+            "inconsistent private state"
+        );
+        if public_params.private {
+            ensure!(
+                component_private_inputs.comm_d.is_some()
+                    && component_private_inputs.comm_r.is_some(),
+                "private proofs require comm_d and comm_r"
+            );
+        }
+
+        Ok(())
+    }
+
+    fn blank_circuit(
+        public_params: &<DrgPoRep<'a, H, G> as ProofScheme<'a>>::PublicParams,
+    ) -> DrgPoRepCircuit<'a, H> {
+        let depth = public_params.graph.merkle_tree_depth::<typenum::U2>() as usize;
+        let degree = public_params.graph.degree();
+        let arity = 2;
+
+        let challenges_count = public_params.challenges_count;
+
+        let replica_nodes = vec![None; challenges_count];
+        let replica_nodes_paths =
+            vec![vec![(vec![None; arity - 1], None); depth - 1]; challenges_count];
+
+        let replica_root = Root::Val(None);
+        let replica_parents = vec![vec![None; degree]; challenges_count];
+        let replica_parents_paths =
+            vec![vec![vec![(vec![None; arity - 1], None); depth - 1]; degree]; challenges_count];
+        let data_nodes = vec![None; challenges_count];
+        let data_nodes_paths =
+            vec![vec![(vec![None; arity - 1], None); depth - 1]; challenges_count];
+        let data_root = Root::Val(None);
+
+        DrgPoRepCircuit {
+            params: &*JJ_PARAMS,
+            replica_nodes,
+            replica_nodes_paths,
+            replica_root,
+            replica_parents,
+            replica_parents_paths,
+            data_nodes,
+            data_nodes_paths,
+            data_root,
+            replica_id: None,
+            private: public_params.private,
+            encoding_op: public_params.encoding_op,
+            _h: Default::default(),
+        }
+    }
+}
+
+impl<'a, H, G> DrgPoRepCompound<H, G>
+where
+    H: 'a + Hasher,
+    G::Key: AsRef<H::Domain>,
+    G: 'a + Graph<H> + ParameterSetMetadata + Sync + Send,
+{
+    /// prove_with_roots is like `prove`, but proves against the externally computed
+    /// `data_root` and `replica_root`, e.g. commitments published before proving, instead of
+    /// the roots read from the vanilla proofs. Only non-private proofs expose the roots, and
+    /// the roots must match those of the trees in `priv_inputs`.
+    pub fn prove_with_roots<'b>(
+        pub_params: &compound_proof::PublicParams<'a, DrgPoRep<'a, H, G>>,
+        pub_inputs: &PublicInputs<H::Domain>,
+        priv_inputs: &PrivateInputs<'a, H>,
+        data_root: H::Domain,
+        replica_root: H::Domain,
+        groth_params: &'b groth16::MappedParameters<Bls12>,
+    ) -> Result<MultiProof<'b, Bls12>> {
+        ensure!(
+            !pub_params.vanilla_params.private,
+            "proving with external roots requires non-private mode"
+        );
+
+        let tree_d_root = priv_inputs.tree_d.root();
+        ensure!(
+            data_root == tree_d_root,
+            "data_root {:?} does not match the root of tree_d {:?}",
+            data_root,
+            tree_d_root
+        );
+        let tree_r_root = priv_inputs.tree_r.root();
+        ensure!(
+            replica_root == tree_r_root,
+            "replica_root {:?} does not match the root of tree_r {:?}",
+            replica_root,
+            tree_r_root
+        );
+
+        let partitions = Self::partition_count(pub_params);
+        let mut vanilla_proofs = DrgPoRep::prove_all_partitions(
+            &pub_params.vanilla_params,
+            pub_inputs,
+            priv_inputs,
+            partitions,
+        )?;
+        for proof in &mut vanilla_proofs {
+            proof.data_root = data_root;
+            proof.replica_root = replica_root;
+        }
+
+        Self::prove_from_vanilla(pub_params, pub_inputs, vanilla_proofs, groth_params)
+    }
+
+    /// Synthesizes the blank circuit of `pub_params` with each of `challenge_counts` challenges
+    /// and returns the pairs of challenge count and number of constraints, e.g. to fit the cost
+    /// of a single challenge for capacity planning.
+    pub fn constraints_by_challenge_count(
+        pub_params: &PublicParams<H, G>,
+        challenge_counts: &[usize],
+    ) -> Result<Vec<(usize, usize)>> {
+        challenge_counts
+            .iter()
+            .map(|&challenges_count| {
+                let mut params = pub_params.clone();
+                params.challenges_count = challenges_count;
+
+                let mut cs = MetricCS::<Bls12>::new();
+                Self::blank_circuit(&params)
+                    .synthesize(&mut cs)
+                    .with_context(|| {
+                        format!("failed to synthesize {} challenges", challenges_count)
+                    })?;
+
+                Ok((challenges_count, cs.num_constraints()))
+            })
+            .collect()
+    }
+}
+
+impl<H, G> DrgPoRepCompound<H, G>
+where
+    H: Hasher,
+    G::Key: AsRef<H::Domain>,
+    G: Graph<H> + ParameterSetMetadata,
+{
+    /// Generates the public inputs for `pub_in`. With `memoize` set, the PoR inputs of a
+    /// `(commitment, node)` pair are computed once and reused when the node is challenged
+    /// or appears as a parent again. The result is identical either way.
+    fn generate_inputs(
+        pub_in: &PublicInputs<H::Domain>,
+        pub_params: &PublicParams<H, G>,
+        memoize: bool,
     ) -> Result<Vec<Fr>> {
         let replica_id = pub_in.replica_id.context("missing replica id")?;
-        let challenges = &pub_in.challenges;
 
         ensure!(
             pub_in.tau.is_none() == pub_params.private,
             "Public input parameter tau must be unset"
         );
 
-        let (comm_r, comm_d) = match pub_in.tau {
-            None => (None, None),
-            Some(tau) => (Some(tau.comm_r), Some(tau.comm_d)),
-        };
+        let (comm_r, comm_d) = match pub_in.tau {
+            None => (None, None),
+            Some(tau) => (Some(tau.comm_r), Some(tau.comm_d)),
+        };
+
+        let leaves = pub_params.graph.size();
+
+        let por_pub_params = por::PublicParams {
+            leaves,
+            private: pub_params.private,
+        };
+
+        let mut por_inputs_cache: BTreeMap<(Option<H::Domain>, usize), Vec<Fr>> = BTreeMap::new();
+        let mut por_inputs = |commitment: Option<H::Domain>, challenge: usize| -> Result<Vec<Fr>> {
+            if let Some(inputs) = por_inputs_cache.get(&(commitment, challenge)) {
+                return Ok(inputs.clone());
+            }
+
+            let por_pub_inputs = por::PublicInputs {
+                commitment,
+                challenge,
+            };
+            let inputs = PoRCompound::<H, typenum::U2>::generate_public_inputs(
+                &por_pub_inputs,
+                &por_pub_params,
+                None,
+            )?;
+
+            if memoize {
+                por_inputs_cache.insert((commitment, challenge), inputs.clone());
+            }
+            Ok(inputs)
+        };
+
+        let mut input: Vec<Fr> = Vec::new();
+        input.push(replica_id.try_into_fr().context("invalid replica id")?);
+
+        let mut parents = vec![0; pub_params.graph.degree()];
+        for challenge in pub_in.challenges.iter() {
+            let mut por_nodes = vec![challenge as u32];
+            pub_params.graph.parents(challenge, &mut parents)?;
+            por_nodes.extend_from_slice(&parents);
+
+            for node in por_nodes {
+                input.extend(por_inputs(comm_r, node as usize)?);
+            }
+
+            input.extend(por_inputs(comm_d, challenge)?);
+        }
+        Ok(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use crate::cache_key::CacheKey;
+    use crate::compound_proof;
+    use crate::drgraph::{new_seed, BucketGraph, Seed, BASE_DEGREE};
+    use crate::fr32::fr_into_bytes;
+    use crate::gadgets::{MetricCS, TestConstraintSystem};
+    use crate::hasher::{Hasher, PedersenHasher, PoseidonHasher};
+    use crate::multi_proof::MultiProof;
+    use crate::parameter_cache::{
+        parameter_cache_versioned_verifying_key_path, PARAMETER_CACHE_ENV_VAR, VERSION,
+    };
+    use crate::porep::stacked::BINARY_ARITY;
+    use crate::porep::{drg, PoRep};
+
+    use ff::{Field, PrimeField};
+    use merkletree::store::StoreConfig;
+    use pretty_assertions::assert_eq;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    #[test]
+    #[ignore] // Slow test – run only when compiled for release.
+    fn test_drgporep_compound_pedersen() {
+        drgporep_test_compound::<PedersenHasher>();
+    }
+
+    #[test]
+    #[ignore] // Slow test – run only when compiled for release.
+    fn test_drgporep_compound_poseidon() {
+        drgporep_test_compound::<PoseidonHasher>();
+    }
+
+    #[test]
+    fn drgporep_compound_test_mode() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let test_mode = drg::TestMode::default();
+        let nodes = test_mode.nodes;
+        let setup_params = compound_proof::SetupParams {
+            vanilla_params: test_mode.setup_params(new_seed()),
+            partitions: None,
+            priority: false,
+        };
+        let public_params =
+            DrgPoRepCompound::<PoseidonHasher, BucketGraph<_>>::setup(&setup_params)
+                .expect("setup failed");
+
+        let replica = drg::TestReplica::new(&public_params.vanilla_params, rng);
+        let public_inputs = replica.public_inputs(vec![nodes - 1; test_mode.challenges_count]);
+        let private_inputs = replica.private_inputs();
+
+        // The circuit must be satisfied and have the same shape as the blank circuit the
+        // parameters are generated from.
+        let (circuit, inputs) = DrgPoRepCompound::<PoseidonHasher, _>::circuit_for_test(
+            &public_params,
+            &public_inputs,
+            &private_inputs,
+        )
+        .unwrap();
+        let mut cs = TestConstraintSystem::new();
+        circuit
+            .synthesize(&mut cs)
+            .expect("failed to synthesize test circuit");
+        assert!(cs.is_satisfied());
+        assert!(cs.verify(&inputs));
+
+        let blank_circuit = <DrgPoRepCompound<_, _> as CompoundProof<_, _, _>>::blank_circuit(
+            &public_params.vanilla_params,
+        );
+        let mut cs_blank = MetricCS::new();
+        blank_circuit
+            .synthesize(&mut cs_blank)
+            .expect("failed to synthesize blank circuit");
+        assert_eq!(cs_blank.pretty_print_list(), cs.pretty_print_list());
+
+        let gparams = DrgPoRepCompound::<PoseidonHasher, _>::groth_params(
+            Some(rng),
+            &public_params.vanilla_params,
+        )
+        .expect("failed to get groth params");
+        let proof = DrgPoRepCompound::<PoseidonHasher, _>::prove(
+            &public_params,
+            &public_inputs,
+            &private_inputs,
+            &gparams,
+        )
+        .expect("failed while proving");
+
+        let verified = DrgPoRepCompound::<PoseidonHasher, _>::verify(
+            &public_params,
+            &public_inputs,
+            &proof,
+            &Default::default(),
+        )
+        .expect("failed while verifying");
+        assert!(verified);
+    }
+
+    #[test]
+    fn drgporep_public_inputs_match_circuit_order() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let nodes = 8;
+        let test_mode = drg::TestMode {
+            nodes,
+            challenges_count: 2,
+        };
+        let setup_params = compound_proof::SetupParams {
+            vanilla_params: test_mode.setup_params(new_seed()),
+            partitions: None,
+            priority: false,
+        };
+        let public_params =
+            DrgPoRepCompound::<PoseidonHasher, BucketGraph<_>>::setup(&setup_params)
+                .expect("setup failed");
+
+        let replica = drg::TestReplica::new(&public_params.vanilla_params, rng);
+
+        // Distinct challenges, so that inputs swapped between challenges are detected.
+        let public_inputs = replica.public_inputs(vec![3, 6]);
+        let private_inputs = replica.private_inputs();
+
+        let (circuit, _) = DrgPoRepCompound::<PoseidonHasher, _>::circuit_for_test(
+            &public_params,
+            &public_inputs,
+            &private_inputs,
+        )
+        .unwrap();
+        let mut cs = TestConstraintSystem::new();
+        circuit
+            .synthesize(&mut cs)
+            .expect("failed to synthesize test circuit");
+        assert!(cs.is_satisfied());
+
+        let generated = DrgPoRepCompound::<PoseidonHasher, _>::generate_public_inputs(
+            &public_inputs,
+            &public_params.vanilla_params,
+            None,
+        )
+        .unwrap();
+
+        // The first input of the constraint system is the constant ONE.
+        let allocated = cs.get_inputs();
+        assert_eq!(allocated[0].1, "ONE");
+        assert!(allocated[1].1.contains("replica_id"), "{}", allocated[1].1);
+        assert_eq!(
+            allocated.len() - 1,
+            generated.len(),
+            "circuit and generate_public_inputs disagree on the number of inputs"
+        );
+
+        for (i, ((input, name), generated)) in
+            allocated.iter().skip(1).zip(generated.iter()).enumerate()
+        {
+            assert_eq!(input, generated, "input {} ({}) out of order", i, name);
+        }
+    }
+
+    #[test]
+    fn drgporep_validate_inputs() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let nodes = 8;
+        let test_mode = drg::TestMode {
+            nodes,
+            challenges_count: 2,
+        };
+        let setup_params = compound_proof::SetupParams {
+            vanilla_params: test_mode.setup_params(new_seed()),
+            partitions: None,
+            priority: false,
+        };
+        let public_params =
+            DrgPoRepCompound::<PedersenHasher, BucketGraph<_>>::setup(&setup_params)
+                .expect("setup failed");
+        let vanilla_params = &public_params.vanilla_params;
+
+        let replica = drg::TestReplica::new(vanilla_params, rng);
+        let public_inputs = replica.public_inputs(vec![3, 6]);
+        let private_inputs = replica.private_inputs();
+
+        DrgPoRepCompound::<PedersenHasher, _>::validate_inputs(
+            &public_params,
+            &public_inputs,
+            &private_inputs,
+        )
+        .expect("valid inputs must pass");
+
+        let too_many = drg::PublicInputs {
+            challenges: vec![3, 5, 6].into(),
+            ..public_inputs.clone()
+        };
+        assert!(DrgPoRepCompound::<PedersenHasher, _>::validate_inputs(
+            &public_params,
+            &too_many,
+            &private_inputs,
+        )
+        .is_err());
+
+        let proof = drg::DrgPoRep::<PedersenHasher, _>::prove(
+            vanilla_params,
+            &public_inputs,
+            &private_inputs,
+        )
+        .expect("proving failed");
+        let check = |public_inputs: &drg::PublicInputs<_>, proof: &drg::Proof<_>| {
+            DrgPoRepCompound::<PedersenHasher, _>::check_circuit_inputs(
+                public_inputs,
+                &Default::default(),
+                proof,
+                vanilla_params,
+            )
+            .map_err(|err| err.to_string())
+        };
+
+        assert_eq!(check(&public_inputs, &proof), Ok(()));
+
+        let mut extra_challenge = proof.clone();
+        extra_challenge.nodes.push(proof.nodes[0].clone());
+        extra_challenge
+            .replica_nodes
+            .push(proof.replica_nodes[0].clone());
+        extra_challenge
+            .replica_parents
+            .push(proof.replica_parents[0].clone());
+        assert_eq!(
+            check(&public_inputs, &extra_challenge),
+            Err("too many challenges".to_string())
+        );
+
+        let mut missing_parent = proof.clone();
+        missing_parent.replica_parents[1].pop();
+        let err = check(&public_inputs, &missing_parent).unwrap_err();
+        assert!(err.contains("challenge 1 has"), "{}", err);
+
+        let mut missing_parents = proof.clone();
+        missing_parents.replica_parents.pop();
+        assert_eq!(
+            check(&public_inputs, &missing_parents),
+            Err("Number of replica parents must match".to_string())
+        );
+
+        let without_tau = drg::PublicInputs {
+            tau: None,
+            ..public_inputs.clone()
+        };
+        assert_eq!(
+            check(&without_tau, &proof),
+            Err("inconsistent private state".to_string())
+        );
+    }
+
+    #[test]
+    fn drgporep_setup_params_validate() {
+        let valid = compound_proof::SetupParams {
+            vanilla_params: drg::TestMode {
+                nodes: 8,
+                challenges_count: 2,
+            }
+            .setup_params(new_seed()),
+            partitions: Some(1),
+            priority: false,
+        };
+        let check =
+            |sp: &compound_proof::SetupParams<drg::DrgPoRep<PedersenHasher, BucketGraph<_>>>| {
+                let validated = sp.validate().map_err(|err| err.to_string());
+                let setup = DrgPoRepCompound::<PedersenHasher, BucketGraph<_>>::setup(sp)
+                    .map(|_| ())
+                    .map_err(|err| err.to_string());
+                assert_eq!(validated, setup, "setup must validate up front");
+
+                validated
+            };
+
+        assert_eq!(check(&valid), Ok(()));
+
+        let mut no_partitions = valid.clone();
+        no_partitions.partitions = Some(0);
+        assert_eq!(
+            check(&no_partitions),
+            Err(
+                "invalid setup params: partitions must be greater than zero when specified"
+                    .to_string()
+            )
+        );
+
+        let mut no_nodes = valid.clone();
+        no_nodes.vanilla_params.drg.nodes = 0;
+        assert_eq!(
+            check(&no_nodes),
+            Err("invalid setup params: drg.nodes must be greater than zero".to_string())
+        );
+
+        let mut no_degree = valid.clone();
+        no_degree.vanilla_params.drg.degree = 0;
+        assert_eq!(
+            check(&no_degree),
+            Err("invalid setup params: drg.degree must be greater than zero".to_string())
+        );
+
+        let mut no_challenges = valid.clone();
+        no_challenges.vanilla_params.challenges_count = 0;
+        assert_eq!(
+            check(&no_challenges),
+            Err("invalid setup params: challenges_count must be greater than zero".to_string())
+        );
+
+        let mut too_many_challenges = valid.clone();
+        too_many_challenges.vanilla_params.challenges_count = 9;
+        assert_eq!(
+            check(&too_many_challenges),
+            Err("invalid setup params: challenges_count 9 exceeds drg.nodes 8".to_string())
+        );
+    }
+
+    #[test]
+    fn drgporep_public_inputs_memoized_match_uncached() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let nodes = 32;
+        let test_mode = drg::TestMode {
+            nodes,
+            challenges_count: 8,
+        };
+        let public_params =
+            DrgPoRep::<PoseidonHasher, BucketGraph<_>>::setup(&test_mode.setup_params(new_seed()))
+                .expect("setup failed");
+
+        // Repeated challenges and the parents they share exercise the cache.
+        let public_inputs = drg::PublicInputs {
+            replica_id: Some(Fr::random(rng).into()),
+            challenges: vec![nodes - 1, 17, nodes - 1, 9, 17, 30, 2, nodes - 1].into(),
+            tau: Some(drg::Tau::new(
+                Fr::random(rng).into(),
+                Fr::random(rng).into(),
+            )),
+        };
+
+        let cached = DrgPoRepCompound::<PoseidonHasher, _>::generate_inputs(
+            &public_inputs,
+            &public_params,
+            true,
+        )
+        .unwrap();
+        let uncached = DrgPoRepCompound::<PoseidonHasher, _>::generate_inputs(
+            &public_inputs,
+            &public_params,
+            false,
+        )
+        .unwrap();
+
+        let to_bytes =
+            |inputs: &[Fr]| -> Vec<u8> { inputs.iter().flat_map(fr_into_bytes::<Bls12>).collect() };
+        assert_eq!(to_bytes(&cached), to_bytes(&uncached));
+    }
+
+    #[test]
+    fn drgporep_public_inputs_reject_non_canonical_commitment() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let test_mode = drg::TestMode::default();
+        let public_params =
+            DrgPoRep::<PoseidonHasher, BucketGraph<_>>::setup(&test_mode.setup_params(new_seed()))
+                .expect("setup failed");
+
+        // The modulus itself is the smallest value which is not a canonical field element.
+        let non_canonical: <PoseidonHasher as Hasher>::Domain = Fr::char().into();
+        let public_inputs = drg::PublicInputs {
+            replica_id: Some(Fr::random(rng).into()),
+            challenges: vec![test_mode.nodes - 1].into(),
+            tau: Some(drg::Tau::new(Fr::random(rng).into(), non_canonical)),
+        };
+
+        let err = DrgPoRepCompound::<PoseidonHasher, _>::generate_public_inputs(
+            &public_inputs,
+            &public_params,
+            None,
+        )
+        .unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("invalid commitment"),
+            "{:#}",
+            err
+        );
+    }
+
+    #[test]
+    fn drgporep_blank_circuit_constraints_by_prefix() {
+        let setup_params = compound_proof::SetupParams {
+            vanilla_params: drg::SetupParams {
+                drg: drg::DrgParams {
+                    nodes: 8,
+                    degree: BASE_DEGREE,
+                    expansion_degree: 0,
+                    seed: new_seed(),
+                },
+                private: false,
+                challenges_count: 2,
+            },
+            partitions: None,
+            priority: false,
+        };
+
+        let public_params =
+            DrgPoRepCompound::<PedersenHasher, BucketGraph<_>>::setup(&setup_params)
+                .expect("setup failed");
+
+        let blank_circuit = <DrgPoRepCompound<_, _> as CompoundProof<_, _, _>>::blank_circuit(
+            &public_params.vanilla_params,
+        );
+
+        let mut cs = MetricCS::<Bls12>::new();
+        blank_circuit
+            .synthesize(&mut cs)
+            .expect("failed to synthesize blank circuit");
+
+        let by_prefix = cs.constraints_by_prefix();
+        for name in &["challenge_0", "challenge_1"] {
+            assert!(
+                by_prefix.get(*name).copied().unwrap_or(0) > 0,
+                "missing constraints for {}",
+                name
+            );
+        }
+        assert_eq!(by_prefix.values().sum::<usize>(), cs.num_constraints());
+    }
+
+    #[test]
+    fn drgporep_constraints_by_challenge_count() {
+        let test_mode = drg::TestMode {
+            nodes: 8,
+            challenges_count: 1,
+        };
+        let public_params =
+            DrgPoRep::<PedersenHasher, BucketGraph<_>>::setup(&test_mode.setup_params(new_seed()))
+                .expect("setup failed");
+
+        let counts = DrgPoRepCompound::<PedersenHasher, _>::constraints_by_challenge_count(
+            &public_params,
+            &[1, 2, 4, 8],
+        )
+        .unwrap();
+        assert_eq!(
+            counts.iter().map(|(c, _)| *c).collect::<Vec<_>>(),
+            vec![1, 2, 4, 8]
+        );
+
+        // The challenges are synthesized independently, so every challenge adds the same
+        // number of constraints on top of those for the replica id.
+        let per_challenge = counts[1].1 - counts[0].1;
+        assert!(per_challenge > 0);
+        for window in counts.windows(2) {
+            let ((c0, n0), (c1, n1)) = (window[0], window[1]);
+            assert!(n1 > n0, "{:?}", counts);
+            assert_eq!(n1 - n0, (c1 - c0) * per_challenge, "{:?}", counts);
+        }
+    }
+
+    type KdfTau = drg::Tau<<PedersenHasher as Hasher>::Domain>;
+
+    fn drgporep_replicate_with_degree(degree: usize, seed: Seed) -> (KdfTau, usize) {
+        type H = PedersenHasher;
+
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let nodes = 8;
+        let challenges = vec![1, 3];
+
+        let setup_params = compound_proof::SetupParams {
+            vanilla_params: drg::SetupParams {
+                drg: drg::DrgParams {
+                    nodes,
+                    degree,
+                    expansion_degree: 0,
+                    seed,
+                },
+                private: false,
+                challenges_count: 2,
+            },
+            partitions: None,
+            priority: false,
+        };
+
+        let public_params =
+            DrgPoRepCompound::<H, BucketGraph<_>>::setup(&setup_params).expect("setup failed");
+
+        let replica = drg::TestReplica::new(&public_params.vanilla_params, rng);
+        let public_inputs = replica.public_inputs(challenges);
+        let private_inputs = replica.private_inputs();
+
+        let proof = drg::DrgPoRep::<H, BucketGraph<_>>::prove(
+            &public_params.vanilla_params,
+            &public_inputs,
+            &private_inputs,
+        )
+        .expect("failed to prove");
+        assert_eq!(proof.replica_parents[0].len(), degree);
+        assert!(drg::DrgPoRep::<H, BucketGraph<_>>::verify(
+            &public_params.vanilla_params,
+            &public_inputs,
+            &proof,
+        )
+        .expect("failed to verify"));
+
+        let (circuit, inputs) = DrgPoRepCompound::<H, _>::circuit_for_test(
+            &public_params,
+            &public_inputs,
+            &private_inputs,
+        )
+        .unwrap();
+        let mut cs = TestConstraintSystem::new();
+        circuit
+            .synthesize(&mut cs)
+            .expect("failed to synthesize test circuit");
+        assert!(cs.is_satisfied(), "unsatisfied at degree {}", degree);
+        assert!(cs.verify(&inputs));
+
+        (replica.tau, cs.num_constraints())
+    }
+
+    #[test]
+    fn drgporep_kdf_follows_degree() {
+        let seed = new_seed();
+
+        let (tau_3, constraints_3) = drgporep_replicate_with_degree(3, seed);
+        let (tau_9, constraints_9) = drgporep_replicate_with_degree(9, seed);
+
+        // Replication is deterministic for each degree.
+        let (tau_3_again, _) = drgporep_replicate_with_degree(3, seed);
+        let (tau_9_again, _) = drgporep_replicate_with_degree(9, seed);
+        assert_eq!(tau_3.comm_r, tau_3_again.comm_r);
+        assert_eq!(tau_3.comm_d, tau_3_again.comm_d);
+        assert_eq!(tau_9.comm_r, tau_9_again.comm_r);
+
+        // The data is the same, but the keys hash a different number of parents.
+        assert_eq!(tau_3.comm_d, tau_9.comm_d);
+        assert_ne!(tau_3.comm_r, tau_9.comm_r);
+        assert!(constraints_9 > constraints_3);
+    }
+
+    #[test]
+    fn drgporep_verify_streaming_matches_verify() {
+        type H = PedersenHasher;
+
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let partitions = 4;
+        let test_mode_proof = TestModeProof::<H>::new(rng, partitions);
+
+        let requirements = drg::Requirements::default();
+        let verify = |proof: &MultiProof<'_, Bls12>| {
+            let batch = DrgPoRepCompound::<H, _>::verify(
+                &test_mode_proof.public_params,
+                &test_mode_proof.public_inputs,
+                proof,
+                &requirements,
+            )
+            .expect("failed while verifying");
+            let streaming = DrgPoRepCompound::<H, _>::verify_streaming(
+                &test_mode_proof.public_params,
+                &test_mode_proof.public_inputs,
+                proof,
+                &requirements,
+            )
+            .expect("failed while verifying");
+            assert_eq!(batch, streaming);
+            streaming
+        };
+
+        assert_eq!(test_mode_proof.circuit_proofs.len(), partitions);
+        assert!(verify(&test_mode_proof.multi_proof()));
+        assert!(!verify(&test_mode_proof.tampered()));
+    }
+
+    #[test]
+    fn drgporep_resume_prove() {
+        type H = PedersenHasher;
+
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let nodes = 8;
+        let partitions = 3;
+
+        let setup_params = compound_proof::SetupParams {
+            vanilla_params: drg::SetupParams {
+                drg: drg::DrgParams {
+                    nodes,
+                    degree: BASE_DEGREE,
+                    expansion_degree: 0,
+                    seed: new_seed(),
+                },
+                private: false,
+                challenges_count: 2,
+            },
+            partitions: Some(partitions),
+            priority: false,
+        };
+        let public_params =
+            DrgPoRepCompound::<H, BucketGraph<_>>::setup(&setup_params).expect("setup failed");
+
+        let replica = drg::TestReplica::new(&public_params.vanilla_params, rng);
+        let public_inputs = replica.public_inputs(vec![1, 3]);
+        let private_inputs = replica.private_inputs();
+
+        let gparams =
+            DrgPoRepCompound::<H, _>::groth_params(Some(rng), &public_params.vanilla_params)
+                .expect("failed to get groth params");
+        let requirements = drg::Requirements::default();
+        let verify = |proof: &MultiProof<'_, Bls12>| {
+            DrgPoRepCompound::<H, _>::verify(&public_params, &public_inputs, proof, &requirements)
+                .expect("failed while verifying")
+        };
+
+        let checkpoint = replica.cache_dir.path().join("proof-checkpoint");
+        let proof = DrgPoRepCompound::<H, _>::prove_resumable(
+            &public_params,
+            &public_inputs,
+            &private_inputs,
+            &gparams,
+            &checkpoint,
+        )
+        .expect("failed while proving");
+        assert_eq!(proof.circuit_proofs.len(), partitions);
+        assert!(verify(&proof));
+
+        // Simulate a crash after the first partition, in the middle of writing the second.
+        let proof_size = MultiProof::<Bls12>::groth_proof_size();
+        let written = std::fs::read(&checkpoint).unwrap();
+        let header_len = written.len() - partitions * proof_size;
+        assert_eq!(header_len, 32);
+        std::fs::write(
+            &checkpoint,
+            &written[..header_len + proof_size + proof_size / 2],
+        )
+        .unwrap();
+
+        let resumed = DrgPoRepCompound::<H, _>::resume_prove(
+            &public_params,
+            &public_inputs,
+            &private_inputs,
+            &gparams,
+            &checkpoint,
+        )
+        .expect("failed while resuming");
+        assert_eq!(resumed.circuit_proofs.len(), partitions);
+        assert_eq!(resumed.circuit_proofs[0], proof.circuit_proofs[0]);
+        assert!(verify(&resumed));
+        let rewritten = std::fs::read(&checkpoint).unwrap();
+        assert_eq!(rewritten.len(), written.len());
+        assert_eq!(rewritten[..header_len], written[..header_len]);
+
+        // Resuming from a complete checkpoint generates nothing new.
+        let complete = DrgPoRepCompound::<H, _>::resume_prove(
+            &public_params,
+            &public_inputs,
+            &private_inputs,
+            &gparams,
+            &checkpoint,
+        )
+        .expect("failed while resuming");
+        assert_eq!(complete.circuit_proofs, resumed.circuit_proofs);
+
+        // A checkpointed proof which does not verify is proven again.
+        let mut corrupted = resumed.circuit_proofs[1].clone();
+        std::mem::swap(&mut corrupted.a, &mut corrupted.c);
+        let mut corrupted_bytes = Vec::new();
+        corrupted.write(&mut corrupted_bytes).unwrap();
+        let mut tampered = rewritten.clone();
+        tampered[header_len + proof_size..header_len + 2 * proof_size]
+            .copy_from_slice(&corrupted_bytes);
+        std::fs::write(&checkpoint, &tampered).unwrap();
+
+        let repaired = DrgPoRepCompound::<H, _>::resume_prove(
+            &public_params,
+            &public_inputs,
+            &private_inputs,
+            &gparams,
+            &checkpoint,
+        )
+        .expect("failed while resuming");
+        assert_eq!(repaired.circuit_proofs[0], resumed.circuit_proofs[0]);
+        assert_ne!(repaired.circuit_proofs[1], corrupted);
+        assert!(verify(&repaired));
+        let rewritten = std::fs::read(&checkpoint).unwrap();
+
+        // A checkpoint cannot be resumed for other public inputs.
+        let other_inputs = drg::PublicInputs::<<H as Hasher>::Domain> {
+            challenges: vec![2, 3].into(),
+            ..public_inputs.clone()
+        };
+        let err = DrgPoRepCompound::<H, _>::resume_prove(
+            &public_params,
+            &other_inputs,
+            &private_inputs,
+            &gparams,
+            &checkpoint,
+        )
+        .err()
+        .expect("resumed a checkpoint for other public inputs");
+        assert!(err
+            .to_string()
+            .contains("other parameters or public inputs"));
+        assert_eq!(std::fs::read(&checkpoint).unwrap(), rewritten);
+    }
+
+    #[test]
+    fn drgporep_prove_with_pool() {
+        type H = PedersenHasher;
+
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let nodes = 8;
+        let partitions = 2;
+
+        let setup_params = compound_proof::SetupParams {
+            vanilla_params: drg::SetupParams {
+                drg: drg::DrgParams {
+                    nodes,
+                    degree: BASE_DEGREE,
+                    expansion_degree: 0,
+                    seed: new_seed(),
+                },
+                private: false,
+                challenges_count: 2,
+            },
+            partitions: Some(partitions),
+            priority: false,
+        };
+        let public_params =
+            DrgPoRepCompound::<H, BucketGraph<_>>::setup(&setup_params).expect("setup failed");
+
+        let replica = drg::TestReplica::new(&public_params.vanilla_params, rng);
+        let public_inputs = replica.public_inputs(vec![1, 3]);
+        let private_inputs = replica.private_inputs();
+
+        let gparams =
+            DrgPoRepCompound::<H, _>::groth_params(Some(rng), &public_params.vanilla_params)
+                .expect("failed to get groth params");
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(2)
+            .build()
+            .expect("failed to build thread pool");
+        let proof = DrgPoRepCompound::<H, _>::prove_with_pool(
+            &public_params,
+            &public_inputs,
+            &private_inputs,
+            &gparams,
+            &pool,
+        )
+        .expect("failed while proving");
+        assert_eq!(proof.circuit_proofs.len(), partitions);
+
+        let verified = DrgPoRepCompound::<H, _>::verify(
+            &public_params,
+            &public_inputs,
+            &proof,
+            &drg::Requirements::default(),
+        )
+        .expect("failed while verifying");
+        assert!(verified);
+    }
+
+    #[test]
+    fn drgporep_verify_versioned() {
+        type H = PoseidonHasher;
+
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let test_mode = drg::TestMode::default();
+        let setup_params = compound_proof::SetupParams {
+            vanilla_params: test_mode.setup_params(new_seed()),
+            partitions: None,
+            priority: false,
+        };
+        let public_params =
+            DrgPoRepCompound::<H, BucketGraph<_>>::setup(&setup_params).expect("setup failed");
+
+        let replica = drg::TestReplica::new(&public_params.vanilla_params, rng);
+        let public_inputs = replica.public_inputs(vec![1, 3]);
+        let private_inputs = replica.private_inputs();
+
+        let gparams =
+            DrgPoRepCompound::<H, _>::groth_params(Some(rng), &public_params.vanilla_params)
+                .expect("failed to get groth params");
+        let proof = DrgPoRepCompound::<H, _>::prove(
+            &public_params,
+            &public_inputs,
+            &private_inputs,
+            &gparams,
+        )
+        .expect("failed while proving");
+
+        // Stores the verifying key the proof was made with as the key of an older version, in a
+        // parameter cache of its own rather than the shared one. The override is only in place
+        // while the old key is written and read.
+        let cache_dir = tempfile::tempdir().unwrap();
+        let shared_cache_dir = std::env::var_os(PARAMETER_CACHE_ENV_VAR);
+        std::env::set_var(PARAMETER_CACHE_ENV_VAR, cache_dir.path());
+
+        let identifier = <DrgPoRepCompound<H, BucketGraph<H>> as CacheableParameters<
+            Bls12,
+            DrgPoRepCircuit<H>,
+            _,
+        >>::cache_identifier(&public_params.vanilla_params);
+        let old_version = VERSION - 1;
+        let old_key_path = parameter_cache_versioned_verifying_key_path(old_version, &identifier);
+        assert!(old_key_path.starts_with(cache_dir.path()));
+        gparams
+            .vk
+            .write(std::fs::File::create(&old_key_path).unwrap())
+            .expect("failed to write verifying key");
+
+        let verify_versioned = |identifier: &str| {
+            DrgPoRepCompound::<H, _>::verify_versioned(
+                &public_params,
+                &public_inputs,
+                proof.circuit_proofs.clone(),
+                &Default::default(),
+                old_version,
+                identifier,
+            )
+        };
+        let verified = verify_versioned(&identifier);
+        let missing = verify_versioned("missing-identifier");
+
+        match shared_cache_dir {
+            Some(dir) => std::env::set_var(PARAMETER_CACHE_ENV_VAR, dir),
+            None => std::env::remove_var(PARAMETER_CACHE_ENV_VAR),
+        }
+
+        assert!(verified.expect("failed while verifying"));
+        let err = missing.unwrap_err();
+        assert!(err.to_string().contains("missing-identifier"), "{}", err);
+    }
+
+    #[test]
+    fn drgporep_prove_with_roots() {
+        type H = PedersenHasher;
+
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let nodes = 8;
+        let test_mode = drg::TestMode {
+            nodes,
+            challenges_count: 2,
+        };
+        let setup_params = compound_proof::SetupParams {
+            vanilla_params: test_mode.setup_params(new_seed()),
+            partitions: None,
+            priority: false,
+        };
+        let public_params =
+            DrgPoRepCompound::<H, BucketGraph<_>>::setup(&setup_params).expect("setup failed");
+
+        let replica = drg::TestReplica::new(&public_params.vanilla_params, rng);
+        let public_inputs = replica.public_inputs(vec![2, 5]);
+        let private_inputs = replica.private_inputs();
+
+        let gparams =
+            DrgPoRepCompound::<H, _>::groth_params(Some(rng), &public_params.vanilla_params)
+                .expect("failed to get groth params");
+
+        // The roots as computed by another party, which only published the commitments.
+        let data_root = replica.tau.comm_d;
+        let replica_root = replica.tau.comm_r;
+
+        let proof = DrgPoRepCompound::<H, _>::prove_with_roots(
+            &public_params,
+            &public_inputs,
+            &private_inputs,
+            data_root,
+            replica_root,
+            &gparams,
+        )
+        .expect("failed while proving");
+
+        let verified = DrgPoRepCompound::<H, _>::verify(
+            &public_params,
+            &public_inputs,
+            &proof,
+            &drg::Requirements::default(),
+        )
+        .expect("failed while verifying");
+        assert!(verified);
+
+        let err = DrgPoRepCompound::<H, _>::prove_with_roots(
+            &public_params,
+            &public_inputs,
+            &private_inputs,
+            replica_root,
+            replica_root,
+            &gparams,
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("does not match the root of tree_d"),
+            "{}",
+            err
+        );
+
+        let err = DrgPoRepCompound::<H, _>::prove_with_roots(
+            &public_params,
+            &public_inputs,
+            &private_inputs,
+            data_root,
+            data_root,
+            &gparams,
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("does not match the root of tree_r"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn drgporep_verify_without_prover_data() {
+        type H = PedersenHasher;
 
-        let leaves = pub_params.graph.size();
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
 
-        let por_pub_params = por::PublicParams {
-            leaves,
-            private: pub_params.private,
+        let nodes = 8;
+        let test_mode = drg::TestMode {
+            nodes,
+            challenges_count: 2,
+        };
+        let setup_params = compound_proof::SetupParams {
+            vanilla_params: test_mode.setup_params(new_seed()),
+            partitions: Some(2),
+            priority: false,
         };
+        let challenges = vec![1, 6];
 
-        let mut input: Vec<Fr> = Vec::new();
-        input.push(replica_id.into());
+        // Everything the prover holds lives in this scope, and is dropped, or removed from
+        // disk, before verifying. Only the commitments, the proof and the verifying key leave it.
+        let (replica_id, tau, proof_bytes, verifying_key) = {
+            let public_params =
+                DrgPoRepCompound::<H, BucketGraph<_>>::setup(&setup_params).expect("setup failed");
 
-        let mut parents = vec![0; pub_params.graph.degree()];
-        for challenge in challenges {
-            let mut por_nodes = vec![*challenge as u32];
-            pub_params.graph.parents(*challenge, &mut parents)?;
-            por_nodes.extend_from_slice(&parents);
+            let replica = drg::TestReplica::new(&public_params.vanilla_params, rng);
+            let public_inputs = replica.public_inputs(challenges.clone());
+            let private_inputs = replica.private_inputs();
 
-            for node in por_nodes {
-                let por_pub_inputs = por::PublicInputs {
-                    commitment: comm_r,
-                    challenge: node as usize,
-                };
-                let por_inputs = PoRCompound::<H, typenum::U2>::generate_public_inputs(
-                    &por_pub_inputs,
-                    &por_pub_params,
-                    None,
-                )?;
-
-                input.extend(por_inputs);
-            }
+            let gparams =
+                DrgPoRepCompound::<H, _>::groth_params(Some(rng), &public_params.vanilla_params)
+                    .expect("failed to get groth params");
+            let proof = DrgPoRepCompound::<H, _>::prove(
+                &public_params,
+                &public_inputs,
+                &private_inputs,
+                &gparams,
+            )
+            .expect("failed while proving");
 
-            let por_pub_inputs = por::PublicInputs {
-                commitment: comm_d,
-                challenge: *challenge,
-            };
+            (
+                replica.replica_id,
+                replica.tau,
+                proof.to_vec().unwrap(),
+                gparams.vk.clone(),
+            )
+        };
 
-            let por_inputs = PoRCompound::<H, typenum::U2>::generate_public_inputs(
-                &por_pub_inputs,
-                &por_pub_params,
-                None,
-            )?;
-            input.extend(por_inputs);
-        }
-        Ok(input)
+        let public_params =
+            DrgPoRepCompound::<H, BucketGraph<_>>::setup(&setup_params).expect("setup failed");
+        let public_inputs = drg::PublicInputs {
+            replica_id: Some(replica_id),
+            challenges: challenges.into(),
+            tau: Some(tau),
+        };
+        let proof =
+            MultiProof::new_from_reader(setup_params.partitions, &proof_bytes[..], &verifying_key)
+                .unwrap();
+
+        let verified = DrgPoRepCompound::<H, _>::verify(
+            &public_params,
+            &public_inputs,
+            &proof,
+            &drg::Requirements::default(),
+        )
+        .expect("failed while verifying");
+        assert!(verified);
     }
 
-    fn circuit(
-        public_inputs: &<DrgPoRep<'a, H, G> as ProofScheme<'a>>::PublicInputs,
-        component_private_inputs: <DrgPoRepCircuit<'a, H> as CircuitComponent>::ComponentPrivateInputs,
-        proof: &<DrgPoRep<'a, H, G> as ProofScheme<'a>>::Proof,
-        public_params: &<DrgPoRep<'a, H, G> as ProofScheme<'a>>::PublicParams,
-    ) -> Result<DrgPoRepCircuit<'a, H>> {
-        let challenges = public_params.challenges_count;
-        let len = proof.nodes.len();
+    /// A proof of a `TestMode` replica, with everything needed to verify it.
+    struct TestModeProof<H: 'static + Hasher> {
+        public_params:
+            compound_proof::PublicParams<'static, drg::DrgPoRep<'static, H, BucketGraph<H>>>,
+        public_inputs: drg::PublicInputs<H::Domain>,
+        verifying_key: groth16::VerifyingKey<Bls12>,
+        circuit_proofs: Vec<groth16::Proof<Bls12>>,
+    }
 
-        ensure!(len <= challenges, "too many challenges");
-        ensure!(
-            proof.replica_parents.len() == len,
-            "Number of replica parents must match"
-        );
-        ensure!(
-            proof.replica_nodes.len() == len,
-            "Number of replica nodes must match"
-        );
+    impl<H: 'static + Hasher> TestModeProof<H> {
+        /// Replicates a `TestMode` sector and proves it in `partitions` partitions.
+        fn new(rng: &mut XorShiftRng, partitions: usize) -> Self {
+            let test_mode = drg::TestMode::default();
+            let seed = new_seed();
+            let setup_params = || compound_proof::SetupParams {
+                vanilla_params: test_mode.setup_params(seed),
+                partitions: Some(partitions),
+                priority: false,
+            };
 
-        let replica_nodes: Vec<_> = proof
-            .replica_nodes
-            .iter()
-            .map(|node| Some(node.data.into()))
-            .collect();
+            // The prover's public parameters only live as long as the private inputs.
+            let prover_params = DrgPoRepCompound::<H, BucketGraph<_>>::setup(&setup_params())
+                .expect("setup failed");
+            let replica = drg::TestReplica::new(&prover_params.vanilla_params, rng);
+            let public_inputs =
+                replica.public_inputs(vec![test_mode.nodes - 1; test_mode.challenges_count]);
 
-        let replica_nodes_paths: Vec<_> = proof
-            .replica_nodes
-            .iter()
-            .map(|node| node.proof.as_options())
-            .collect();
+            let gparams =
+                DrgPoRepCompound::<H, _>::groth_params(Some(rng), &prover_params.vanilla_params)
+                    .expect("failed to get groth params");
+            let proof = DrgPoRepCompound::<H, _>::prove(
+                &prover_params,
+                &public_inputs,
+                &replica.private_inputs(),
+                &gparams,
+            )
+            .expect("failed while proving");
 
-        let is_private = public_params.private;
+            TestModeProof {
+                public_params: DrgPoRepCompound::<H, BucketGraph<_>>::setup(&setup_params())
+                    .expect("setup failed"),
+                public_inputs,
+                verifying_key: gparams.vk.clone(),
+                circuit_proofs: proof.circuit_proofs,
+            }
+        }
 
-        let (data_root, replica_root) = if is_private {
-            (
-                component_private_inputs.comm_d.context("is_private")?,
-                component_private_inputs.comm_r.context("is_private")?,
-            )
-        } else {
-            (
-                Root::Val(Some(proof.data_root.into())),
-                Root::Val(Some(proof.replica_root.into())),
+        fn multi_proof(&self) -> MultiProof<'_, Bls12> {
+            MultiProof::new(self.circuit_proofs.clone(), &self.verifying_key)
+        }
+
+        /// The proof with the pairing of its last partition broken.
+        fn tampered(&self) -> MultiProof<'_, Bls12> {
+            let mut tampered = self.multi_proof();
+            let groth_proof = tampered.circuit_proofs.last_mut().unwrap();
+            std::mem::swap(&mut groth_proof.a, &mut groth_proof.c);
+            tampered
+        }
+    }
+
+    #[test]
+    fn drgporep_verify_detailed() {
+        type H = PedersenHasher;
+
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let test_mode_proof = TestModeProof::<H>::new(rng, 1);
+        let verify_detailed = |proof: &MultiProof<'_, Bls12>| {
+            DrgPoRepCompound::<H, _>::verify_detailed(
+                &test_mode_proof.public_params,
+                &test_mode_proof.public_inputs,
+                proof,
+                &Default::default(),
             )
+            .expect("failed while verifying")
         };
 
-        let replica_id = public_inputs.replica_id;
+        let report = verify_detailed(&test_mode_proof.multi_proof());
+        assert!(report.is_valid());
+        assert!(report.reason.is_none());
 
-        let replica_parents: Vec<_> = proof
-            .replica_parents
-            .iter()
-            .map(|parents| {
-                parents
-                    .iter()
-                    .map(|(_, parent)| Some(parent.data.into()))
-                    .collect()
-            })
-            .collect();
+        let report = verify_detailed(&test_mode_proof.tampered());
+        assert!(!report.is_valid());
+        assert!(report.requirements_satisfied);
+        assert!(report.public_inputs_generated);
+        assert!(!report.pairing_check_passed);
+        assert_eq!(report.reason.as_deref(), Some("pairing check failed"));
+    }
 
-        let replica_parents_paths: Vec<Vec<_>> = proof
-            .replica_parents
-            .iter()
-            .map(|parents| {
-                let p: Vec<_> = parents
-                    .iter()
-                    .map(|(_, parent)| parent.proof.as_options())
-                    .collect();
-                p
-            })
-            .collect();
+    #[test]
+    fn drgporep_verify_cancellable() {
+        type H = PedersenHasher;
 
-        let data_nodes: Vec<_> = proof
-            .nodes
-            .iter()
-            .map(|node| Some(node.data.into()))
-            .collect();
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let partitions = 2;
+        let test_mode_proof = TestModeProof::<H>::new(rng, partitions);
+        let verify_cancellable =
+            |proof: &MultiProof<'_, Bls12>, cancel: &compound_proof::CancellationToken| {
+                DrgPoRepCompound::<H, _>::verify_cancellable(
+                    &test_mode_proof.public_params,
+                    &test_mode_proof.public_inputs,
+                    proof,
+                    &Default::default(),
+                    cancel,
+                )
+                .expect("failed while verifying")
+            };
 
-        let data_nodes_paths: Vec<_> = proof
-            .nodes
-            .iter()
-            .map(|node| node.proof.as_options())
-            .collect();
+        let cancel = compound_proof::CancellationToken::new();
+        assert_eq!(
+            verify_cancellable(&test_mode_proof.multi_proof(), &cancel),
+            Some(true)
+        );
+        assert_eq!(
+            verify_cancellable(&test_mode_proof.tampered(), &cancel),
+            Some(false)
+        );
 
-        ensure!(
-            public_inputs.tau.is_none() == public_params.private,
-            "inconsistent private state"
+        // The token is checked before each partition. Cancelling once the first partition has
+        // been verified stops before the broken last partition is reached.
+        let checks = Arc::new(AtomicUsize::new(0));
+        let cancel_mid_way = compound_proof::CancellationToken::from_fn({
+            let checks = checks.clone();
+            move || checks.fetch_add(1, Ordering::SeqCst) + 1 >= partitions
+        });
+        assert_eq!(
+            verify_cancellable(&test_mode_proof.tampered(), &cancel_mid_way),
+            None,
+            "cancelled verification must not complete"
         );
+        assert_eq!(checks.load(Ordering::SeqCst), partitions);
 
-        Ok(DrgPoRepCircuit {
-            params: &*JJ_PARAMS,
-            replica_nodes,
-            replica_nodes_paths,
-            replica_root,
-            replica_parents,
-            replica_parents_paths,
-            data_nodes,
-            data_nodes_paths,
-            data_root,
-            replica_id: replica_id.map(Into::into),
-            private: public_params.private,
-            _h: Default::default(),
-        })
+        cancel.clone().cancel();
+        assert_eq!(
+            verify_cancellable(&test_mode_proof.multi_proof(), &cancel),
+            None
+        );
     }
 
-    fn blank_circuit(
-        public_params: &<DrgPoRep<'a, H, G> as ProofScheme<'a>>::PublicParams,
-    ) -> DrgPoRepCircuit<'a, H> {
-        let depth = public_params.graph.merkle_tree_depth::<typenum::U2>() as usize;
-        let degree = public_params.graph.degree();
-        let arity = 2;
+    #[test]
+    fn drgporep_prove_from_vanilla() {
+        type H = PedersenHasher;
 
-        let challenges_count = public_params.challenges_count;
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
 
-        let replica_nodes = vec![None; challenges_count];
-        let replica_nodes_paths =
-            vec![vec![(vec![None; arity - 1], None); depth - 1]; challenges_count];
+        let test_mode = drg::TestMode::default();
+        let setup_params = compound_proof::SetupParams {
+            vanilla_params: test_mode.setup_params(new_seed()),
+            partitions: None,
+            priority: false,
+        };
+        let public_params =
+            DrgPoRepCompound::<H, BucketGraph<_>>::setup(&setup_params).expect("setup failed");
 
-        let replica_root = Root::Val(None);
-        let replica_parents = vec![vec![None; degree]; challenges_count];
-        let replica_parents_paths =
-            vec![vec![vec![(vec![None; arity - 1], None); depth - 1]; degree]; challenges_count];
-        let data_nodes = vec![None; challenges_count];
-        let data_nodes_paths =
-            vec![vec![(vec![None; arity - 1], None); depth - 1]; challenges_count];
-        let data_root = Root::Val(None);
+        let replica = drg::TestReplica::new(&public_params.vanilla_params, rng);
+        let public_inputs = replica.public_inputs(vec![test_mode.nodes - 1]);
+        let gparams =
+            DrgPoRepCompound::<H, _>::groth_params(Some(rng), &public_params.vanilla_params)
+                .expect("failed to get groth params");
 
-        DrgPoRepCircuit {
-            params: &*JJ_PARAMS,
+        // A service holding the trees ships the inclusion proofs, the SNARK is built from
+        // them without access to the trees.
+        let vanilla_proof = drg::DrgPoRep::<H, _>::prove(
+            &public_params.vanilla_params,
+            &public_inputs,
+            &replica.private_inputs(),
+        )
+        .expect("failed while proving vanilla");
+        let shipped = serde_json::to_vec(&(
+            &vanilla_proof.replica_nodes,
+            &vanilla_proof.replica_parents,
+            &vanilla_proof.nodes,
+        ))
+        .unwrap();
+
+        let (replica_nodes, replica_parents, nodes) = serde_json::from_slice(&shipped).unwrap();
+        let rebuilt = drg::Proof::<H>::from_components(
             replica_nodes,
-            replica_nodes_paths,
-            replica_root,
             replica_parents,
-            replica_parents_paths,
-            data_nodes,
-            data_nodes_paths,
-            data_root,
-            replica_id: None,
-            private: public_params.private,
-            _h: Default::default(),
-        }
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    use crate::cache_key::CacheKey;
-    use crate::compound_proof;
-    use crate::drgraph::{new_seed, BucketGraph, BASE_DEGREE};
-    use crate::fr32::fr_into_bytes;
-    use crate::gadgets::{MetricCS, TestConstraintSystem};
-    use crate::hasher::{Hasher, PedersenHasher, PoseidonHasher};
-    use crate::porep::stacked::BINARY_ARITY;
-    use crate::porep::{drg, PoRep};
-    use crate::proof::NoRequirements;
+            nodes,
+            replica.tau.comm_d,
+            replica.tau.comm_r,
+        )
+        .expect("failed to assemble vanilla proof");
 
-    use ff::Field;
-    use merkletree::store::StoreConfig;
-    use pretty_assertions::assert_eq;
-    use rand::SeedableRng;
-    use rand_xorshift::XorShiftRng;
+        let proof = DrgPoRepCompound::<H, _>::prove_from_vanilla(
+            &public_params,
+            &public_inputs,
+            vec![rebuilt],
+            &gparams,
+        )
+        .expect("failed while proving from vanilla");
 
-    #[test]
-    #[ignore] // Slow test – run only when compiled for release.
-    fn test_drgporep_compound_pedersen() {
-        drgporep_test_compound::<PedersenHasher>();
+        let verified = DrgPoRepCompound::<H, _>::verify(
+            &public_params,
+            &public_inputs,
+            &proof,
+            &Default::default(),
+        )
+        .expect("failed while verifying");
+        assert!(verified);
+
+        assert!(drg::Proof::<H>::from_components(
+            vanilla_proof.replica_nodes.clone(),
+            vanilla_proof.replica_parents.clone(),
+            vanilla_proof.nodes.clone(),
+            replica.tau.comm_r,
+            replica.tau.comm_r,
+        )
+        .is_err());
     }
 
     #[test]
-    #[ignore] // Slow test – run only when compiled for release.
-    fn test_drgporep_compound_poseidon() {
-        drgporep_test_compound::<PoseidonHasher>();
+    fn drgporep_verify_with_inputs() {
+        type H = PedersenHasher;
+
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let partitions = 2;
+        let test_mode_proof = TestModeProof::<H>::new(rng, partitions);
+        let public_params = &test_mode_proof.public_params;
+        let public_inputs = &test_mode_proof.public_inputs;
+
+        let (verified, inputs) = DrgPoRepCompound::<H, _>::verify_with_inputs(
+            public_params,
+            public_inputs,
+            &test_mode_proof.multi_proof(),
+            &Default::default(),
+        )
+        .expect("failed while verifying");
+        assert!(verified);
+
+        let expected: Vec<Fr> = (0..partitions)
+            .flat_map(|k| {
+                DrgPoRepCompound::<H, _>::generate_public_inputs(
+                    public_inputs,
+                    &public_params.vanilla_params,
+                    Some(k),
+                )
+                .expect("failed to generate public inputs")
+            })
+            .collect();
+        assert_eq!(inputs, expected);
+
+        let (verified, tampered_inputs) = DrgPoRepCompound::<H, _>::verify_with_inputs(
+            public_params,
+            public_inputs,
+            &test_mode_proof.tampered(),
+            &Default::default(),
+        )
+        .expect("failed while verifying");
+        assert!(!verified);
+        assert_eq!(tampered_inputs, expected);
     }
 
     fn drgporep_test_compound<H: Hasher>() {
@@ -372,13 +1722,14 @@ mod tests {
 
         let public_inputs = drg::PublicInputs::<H::Domain> {
             replica_id: Some(replica_id.into()),
-            challenges,
+            challenges: challenges.into(),
             tau: Some(tau),
         };
         let private_inputs = drg::PrivateInputs {
             tree_d: &aux.tree_d,
             tree_r: &aux.tree_r,
             tree_r_config_levels: StoreConfig::default_cached_above_base_layer(nodes, BINARY_ARITY),
+            replica: None,
         };
 
         // This duplication is necessary so public_params don't outlive public_inputs and private_inputs.
@@ -446,15 +1797,53 @@ mod tests {
             )
             .expect("failed while proving");
 
+            assert_eq!(
+                proof.serialized_size(),
+                proof.to_vec().expect("failed to serialize proof").len()
+            );
+
             let verified = DrgPoRepCompound::<H, _>::verify(
                 &public_params,
                 &public_inputs,
                 &proof,
-                &NoRequirements,
+                &drg::Requirements {
+                    expected_replica_id: Some(replica_id.into()),
+                    minimum_challenges: 2,
+                },
             )
             .expect("failed while verifying");
 
             assert!(verified);
+
+            let too_few_challenges = DrgPoRepCompound::<H, _>::verify(
+                &public_params,
+                &public_inputs,
+                &proof,
+                &drg::Requirements {
+                    minimum_challenges: 3,
+                    ..Default::default()
+                },
+            );
+
+            assert!(
+                too_few_challenges.is_err(),
+                "proof with too few challenges must be rejected"
+            );
+
+            let mismatched = DrgPoRepCompound::<H, _>::verify(
+                &public_params,
+                &public_inputs,
+                &proof,
+                &drg::Requirements {
+                    expected_replica_id: Some(Fr::random(rng).into()),
+                    ..Default::default()
+                },
+            );
+
+            assert!(
+                mismatched.is_err(),
+                "proof for the wrong replica_id must be rejected"
+            );
         }
     }
 }
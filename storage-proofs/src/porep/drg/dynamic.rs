@@ -0,0 +1,179 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Context};
+use merkletree::store::StoreConfig;
+
+use crate::drgraph::BucketGraph;
+use crate::error::Result;
+use crate::hasher::{Blake2sHasher, Domain, Hasher, PedersenHasher, PoseidonHasher};
+use crate::porep::PoRep;
+use crate::proof::ProofScheme;
+
+use super::vanilla::{DrgPoRep, SetupParams, Tau};
+
+/// The hasher names accepted by `replicate_dyn`.
+pub const DYN_HASHERS: [&str; 3] = ["pedersen", "poseidon", "blake2s"];
+
+/// Replicates `data` in place with the `DrgPoRep` over a `BucketGraph`, using the hasher named
+/// `hasher_name`, one of `DYN_HASHERS`.
+///
+/// `replica_id` is the byte encoding of the replica id in the domain of the chosen hasher. The
+/// commitments are returned in the same byte encoding.
+pub fn replicate_dyn(
+    hasher_name: &str,
+    setup_params: &SetupParams,
+    replica_id: &[u8],
+    data: &mut [u8],
+    config: StoreConfig,
+    replica_path: PathBuf,
+) -> Result<Tau<Vec<u8>>> {
+    match hasher_name {
+        "pedersen" => {
+            replicate_typed::<PedersenHasher>(setup_params, replica_id, data, config, replica_path)
+        }
+        "poseidon" => {
+            replicate_typed::<PoseidonHasher>(setup_params, replica_id, data, config, replica_path)
+        }
+        "blake2s" => {
+            replicate_typed::<Blake2sHasher>(setup_params, replica_id, data, config, replica_path)
+        }
+        _ => bail!(
+            "unknown hasher {:?}, expected one of {:?}",
+            hasher_name,
+            DYN_HASHERS
+        ),
+    }
+}
+
+fn replicate_typed<H: Hasher>(
+    setup_params: &SetupParams,
+    replica_id: &[u8],
+    data: &mut [u8],
+    config: StoreConfig,
+    replica_path: PathBuf,
+) -> Result<Tau<Vec<u8>>> {
+    let replica_id = H::Domain::try_from_bytes(replica_id).context("invalid replica id")?;
+    let pub_params = DrgPoRep::<H, BucketGraph<H>>::setup(setup_params)?;
+    let (tau, _) = DrgPoRep::<H, BucketGraph<H>>::replicate(
+        &pub_params,
+        &replica_id,
+        data.into(),
+        None,
+        config,
+        replica_path,
+    )?;
+
+    Ok(Tau {
+        comm_r: tau.comm_r.into_bytes(),
+        comm_d: tau.comm_d.into_bytes(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ff::Field;
+    use paired::bls12_381::{Bls12, Fr};
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::cache_key::CacheKey;
+    use crate::drgraph::{new_seed, BASE_DEGREE};
+    use crate::fr32::fr_into_bytes;
+    use crate::porep::drg::{DrgParams, TestReplica};
+    use crate::porep::stacked::BINARY_ARITY;
+
+    fn config(dir: &tempfile::TempDir, nodes: usize) -> StoreConfig {
+        StoreConfig::new(
+            dir.path(),
+            CacheKey::CommDTree.to_string(),
+            StoreConfig::default_cached_above_base_layer(nodes, BINARY_ARITY),
+        )
+    }
+
+    fn replicate_dyn_matches_typed<H: Hasher>(name: &str) {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let nodes = 8;
+
+        let replica_id = H::Domain::random(rng);
+        let data: Vec<u8> = (0..nodes)
+            .flat_map(|_| fr_into_bytes::<Bls12>(&Fr::random(rng)))
+            .collect();
+        let sp = SetupParams {
+            drg: DrgParams {
+                nodes,
+                degree: BASE_DEGREE,
+                expansion_degree: 0,
+                seed: new_seed(),
+            },
+            private: false,
+            challenges_count: 1,
+        };
+
+        let dyn_dir = tempfile::tempdir().unwrap();
+        let mut dyn_data = data.clone();
+        let dyn_tau = replicate_dyn(
+            name,
+            &sp,
+            &replica_id.into_bytes(),
+            &mut dyn_data,
+            config(&dyn_dir, nodes),
+            dyn_dir.path().join("replica-path"),
+        )
+        .expect("dynamic replication failed");
+
+        let pp = DrgPoRep::<H, BucketGraph<H>>::setup(&sp).unwrap();
+        let typed = TestReplica::with_data(&pp, replica_id, data);
+
+        assert_eq!(dyn_tau.comm_r, typed.tau.comm_r.into_bytes());
+        assert_eq!(dyn_tau.comm_d, typed.tau.comm_d.into_bytes());
+        assert_eq!(dyn_data, typed.replica);
+    }
+
+    #[test]
+    fn replicate_dyn_pedersen() {
+        replicate_dyn_matches_typed::<PedersenHasher>("pedersen");
+    }
+
+    #[test]
+    fn replicate_dyn_poseidon() {
+        replicate_dyn_matches_typed::<PoseidonHasher>("poseidon");
+    }
+
+    #[test]
+    fn replicate_dyn_blake2s() {
+        replicate_dyn_matches_typed::<Blake2sHasher>("blake2s");
+    }
+
+    #[test]
+    fn replicate_dyn_unknown_hasher() {
+        let nodes = 8;
+        let sp = SetupParams {
+            drg: DrgParams {
+                nodes,
+                degree: BASE_DEGREE,
+                expansion_degree: 0,
+                seed: new_seed(),
+            },
+            private: false,
+            challenges_count: 1,
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let err = replicate_dyn(
+            "sha3",
+            &sp,
+            &[0; 32],
+            &mut vec![0; nodes * 32],
+            config(&dir, nodes),
+            dir.path().join("replica-path"),
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string().contains("unknown hasher \"sha3\""),
+            "{}",
+            err
+        );
+    }
+}
@@ -0,0 +1,278 @@
+use anyhow::ensure;
+use bellperson::gadgets::{boolean::Boolean, num};
+use bellperson::{ConstraintSystem, SynthesisError};
+use paired::Engine;
+
+use crate::encode;
+use crate::error::Result;
+use crate::gadgets::{self, multipack};
+use crate::hasher::{Domain, Hasher};
+
+/// Combines the encoding key of a node with its data, see `PublicParams::with_encoding_op`.
+///
+/// The operation changes the replica and hence `comm_r`, so it is part of the identifier of the
+/// parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodingOp {
+    /// `replica = data + key` in the scalar field.
+    FieldAdd,
+    /// `replica = data ^ key` on the little endian bits. Only defined for fr32 padded nodes,
+    /// which have the top two bits cleared, so that the result is a field element as well.
+    Xor,
+}
+
+impl Default for EncodingOp {
+    fn default() -> Self {
+        EncodingOp::FieldAdd
+    }
+}
+
+impl EncodingOp {
+    /// Encodes the data node `value` with `key`.
+    pub fn encode<H: Hasher>(self, key: &H::Domain, value: &H::Domain) -> Result<H::Domain> {
+        match self {
+            EncodingOp::FieldAdd => H::sloth_encode(key, value),
+            EncodingOp::Xor => xor(key, value),
+        }
+    }
+
+    /// Decodes the replica node `value` with `key`, the inverse of `encode`.
+    pub fn decode<H: Hasher>(self, key: &H::Domain, value: &H::Domain) -> Result<H::Domain> {
+        match self {
+            EncodingOp::FieldAdd => Ok(encode::decode(*key, *value)),
+            EncodingOp::Xor => xor(key, value),
+        }
+    }
+
+    /// The circuit analog of `encode`.
+    pub fn encode_circuit<E, CS>(
+        self,
+        cs: CS,
+        key: &num::AllocatedNum<E>,
+        value: &num::AllocatedNum<E>,
+    ) -> std::result::Result<num::AllocatedNum<E>, SynthesisError>
+    where
+        E: Engine,
+        CS: ConstraintSystem<E>,
+    {
+        match self {
+            EncodingOp::FieldAdd => gadgets::encode::encode(cs, key, value),
+            EncodingOp::Xor => xor_circuit(cs, key, value),
+        }
+    }
+
+    /// The circuit analog of `decode`.
+    pub fn decode_circuit<E, CS>(
+        self,
+        cs: CS,
+        key: &num::AllocatedNum<E>,
+        value: &num::AllocatedNum<E>,
+    ) -> std::result::Result<num::AllocatedNum<E>, SynthesisError>
+    where
+        E: Engine,
+        CS: ConstraintSystem<E>,
+    {
+        match self {
+            EncodingOp::FieldAdd => gadgets::encode::decode(cs, key, value),
+            EncodingOp::Xor => xor_circuit(cs, key, value),
+        }
+    }
+
+    /// The suffix of the parameter identifier, empty for the default so that existing
+    /// identifiers are unchanged.
+    pub fn identifier(self) -> &'static str {
+        match self {
+            EncodingOp::FieldAdd => "",
+            EncodingOp::Xor => ", encoding_op: xor",
+        }
+    }
+}
+
+fn xor<T: Domain>(key: &T, value: &T) -> Result<T> {
+    let key = key.into_bytes();
+    let mut bytes = value.into_bytes();
+    ensure!(
+        (key[31] | bytes[31]) & 0b1100_0000 == 0,
+        "xor encoding requires fr32 padded nodes"
+    );
+
+    for (byte, key) in bytes.iter_mut().zip(&key) {
+        *byte ^= key;
+    }
+
+    T::try_from_bytes(&bytes)
+}
+
+fn xor_circuit<E, CS>(
+    mut cs: CS,
+    key: &num::AllocatedNum<E>,
+    value: &num::AllocatedNum<E>,
+) -> std::result::Result<num::AllocatedNum<E>, SynthesisError>
+where
+    E: Engine,
+    CS: ConstraintSystem<E>,
+{
+    // The decomposition must be canonical, otherwise the bits of `key + r` would be accepted
+    // in place of those of `key`.
+    let key_bits = key.to_bits_le_strict(cs.namespace(|| "key_bits"))?;
+    let value_bits = value.to_bits_le_strict(cs.namespace(|| "value_bits"))?;
+
+    // As in `xor`, the top two bits of both inputs must be cleared. The strict decomposition
+    // covers the 255 bits below 2^255, so only the top one of them is left to check.
+    for (name, bits) in &[("key", &key_bits), ("value", &value_bits)] {
+        Boolean::enforce_equal(
+            cs.namespace(|| format!("{}_top_bit_cleared", name)),
+            bits.last().expect("field elements have bits"),
+            &Boolean::constant(false),
+        )?;
+    }
+
+    let bits = key_bits
+        .iter()
+        .zip(&value_bits)
+        .enumerate()
+        .map(|(i, (a, b))| Boolean::xor(cs.namespace(|| format!("xor_{}", i)), a, b))
+        .collect::<std::result::Result<Vec<_>, SynthesisError>>()?;
+
+    // The top bit of the result is cleared, so packing the bits below it is exact.
+    multipack::pack_bits(cs.namespace(|| "pack"), &bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ff::{BitIterator, Field, PrimeField, PrimeFieldRepr};
+    use paired::bls12_381::{Bls12, Fr, FrRepr};
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::fr32::bytes_into_fr_repr_safe;
+    use crate::gadgets::TestConstraintSystem;
+    use crate::hasher::{PedersenHasher, Sha256Hasher};
+
+    fn padded<H: Hasher>(rng: &mut XorShiftRng) -> H::Domain {
+        let fr: Fr = Fr::random(rng);
+        let domain: H::Domain = fr.into();
+        bytes_into_fr_repr_safe(&domain.into_bytes()).into()
+    }
+
+    fn encoding_op_roundtrip<H: Hasher>() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        for op in &[EncodingOp::FieldAdd, EncodingOp::Xor] {
+            for _ in 0..10 {
+                let key = padded::<H>(rng);
+                let data = padded::<H>(rng);
+
+                let encoded = op.encode::<H>(&key, &data).unwrap();
+                assert_ne!(encoded, data);
+                assert_eq!(op.decode::<H>(&key, &encoded).unwrap(), data);
+            }
+        }
+
+        let key = padded::<H>(rng);
+        let data = padded::<H>(rng);
+        assert_ne!(
+            EncodingOp::FieldAdd.encode::<H>(&key, &data).unwrap(),
+            EncodingOp::Xor.encode::<H>(&key, &data).unwrap()
+        );
+    }
+
+    #[test]
+    fn encoding_op_roundtrip_pedersen() {
+        encoding_op_roundtrip::<PedersenHasher>();
+    }
+
+    #[test]
+    fn encoding_op_roundtrip_sha256() {
+        encoding_op_roundtrip::<Sha256Hasher>();
+    }
+
+    #[test]
+    fn xor_rejects_unpadded_nodes() {
+        let key = <PedersenHasher as Hasher>::Domain::default();
+        let mut bytes = [0u8; 32];
+        bytes[31] = 0b0100_0000;
+        let value = <PedersenHasher as Hasher>::Domain::try_from_bytes(&bytes).unwrap();
+
+        assert!(EncodingOp::Xor
+            .encode::<PedersenHasher>(&key, &value)
+            .is_err());
+        assert!(EncodingOp::FieldAdd
+            .encode::<PedersenHasher>(&key, &value)
+            .is_ok());
+    }
+
+    #[test]
+    fn encoding_op_circuit_matches_vanilla() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        for op in &[EncodingOp::FieldAdd, EncodingOp::Xor] {
+            let key = padded::<PedersenHasher>(rng);
+            let data = padded::<PedersenHasher>(rng);
+            let encoded = op.encode::<PedersenHasher>(&key, &data).unwrap();
+
+            let mut cs = TestConstraintSystem::<Bls12>::new();
+            let key_num =
+                num::AllocatedNum::alloc(cs.namespace(|| "key"), || Ok(key.into())).unwrap();
+            let data_num =
+                num::AllocatedNum::alloc(cs.namespace(|| "data"), || Ok(data.into())).unwrap();
+            let encoded_num =
+                num::AllocatedNum::alloc(cs.namespace(|| "encoded"), || Ok(encoded.into()))
+                    .unwrap();
+
+            let out = op
+                .encode_circuit(cs.namespace(|| "encode"), &key_num, &data_num)
+                .unwrap();
+            let back = op
+                .decode_circuit(cs.namespace(|| "decode"), &key_num, &encoded_num)
+                .unwrap();
+
+            assert!(cs.is_satisfied(), "{:?}", op);
+            assert_eq!(out.get_value(), Some(encoded.into()), "{:?}", op);
+            assert_eq!(back.get_value(), Some(data.into()), "{:?}", op);
+        }
+    }
+
+    fn xor_circuit_with_value(value: Fr) -> TestConstraintSystem<Bls12> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let key = padded::<PedersenHasher>(rng);
+
+        let mut cs = TestConstraintSystem::<Bls12>::new();
+        let key_num = num::AllocatedNum::alloc(cs.namespace(|| "key"), || Ok(key.into())).unwrap();
+        let value_num = num::AllocatedNum::alloc(cs.namespace(|| "value"), || Ok(value)).unwrap();
+        EncodingOp::Xor
+            .encode_circuit(cs.namespace(|| "encode"), &key_num, &value_num)
+            .unwrap();
+
+        cs
+    }
+
+    #[test]
+    fn xor_circuit_rejects_unpadded_nodes() {
+        let mut top_bit = FrRepr::from(1);
+        top_bit.shl(254);
+
+        assert!(xor_circuit_with_value(Fr::from_repr(FrRepr::from(7)).unwrap()).is_satisfied());
+        assert!(!xor_circuit_with_value(Fr::from_repr(top_bit).unwrap()).is_satisfied());
+    }
+
+    #[test]
+    fn xor_circuit_rejects_non_canonical_bits() {
+        let value = Fr::from_repr(FrRepr::from(7)).unwrap();
+        let mut cs = xor_circuit_with_value(value);
+        assert!(cs.is_satisfied());
+
+        // The bits of `value + r` pack to `value` as well, but must not be accepted in its
+        // place. The strict decomposition allocates the bits from the most significant one.
+        let mut non_canonical = value.into_repr();
+        non_canonical.add_nocarry(&Fr::char());
+        for (i, bit) in BitIterator::new(non_canonical).skip(1).enumerate() {
+            let bit = if bit { Fr::one() } else { Fr::zero() };
+            cs.set(&format!("encode/value_bits/bit {}/boolean", i), bit);
+        }
+
+        assert!(!cs.is_satisfied());
+    }
+}
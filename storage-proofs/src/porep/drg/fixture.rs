@@ -0,0 +1,151 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use anyhow::ensure;
+use ff::Field;
+use merkletree::store::StoreConfig;
+use paired::bls12_381::{Bls12, Fr};
+use rand::Rng;
+use tempfile::TempDir;
+
+use crate::cache_key::CacheKey;
+use crate::drgraph::Graph;
+use crate::error::{Error, Result};
+use crate::fr32::fr_into_bytes;
+use crate::hasher::{Domain, Hasher};
+use crate::parameter_cache::ParameterSetMetadata;
+use crate::porep::stacked::BINARY_ARITY;
+use crate::porep::PoRep;
+
+use super::challenge_set::ChallengeSet;
+use super::remote::RangeReader;
+use super::vanilla::{DrgPoRep, PrivateInputs, ProverAux, PublicInputs, PublicParams, Tau};
+
+/// A sector of random data replicated for tests, with everything needed to prove it.
+#[derive(Debug)]
+pub struct TestReplica<H: Hasher> {
+    pub replica_id: H::Domain,
+    /// The data before replication.
+    pub data: Vec<u8>,
+    /// The replica, in logical order.
+    pub replica: Vec<u8>,
+    pub tau: Tau<H::Domain>,
+    pub aux: ProverAux<H>,
+    pub tree_r_config_levels: usize,
+    /// Holds the trees and the replica file, which are removed when the fixture is dropped.
+    pub cache_dir: TempDir,
+}
+
+impl<H: Hasher> TestReplica<H> {
+    /// Replicates one random field element per node of `pub_params` under a random replica id.
+    pub fn new<G, R>(pub_params: &PublicParams<H, G>, rng: &mut R) -> Self
+    where
+        G::Key: AsRef<H::Domain>,
+        G: Graph<H> + ParameterSetMetadata + Sync + Send,
+        R: Rng,
+    {
+        let replica_id = H::Domain::random(rng);
+        let data = (0..pub_params.graph.size())
+            .flat_map(|_| fr_into_bytes::<Bls12>(&Fr::random(rng)))
+            .collect();
+
+        Self::with_data(pub_params, replica_id, data)
+    }
+
+    /// Replicates `data` under `replica_id`.
+    pub fn with_data<G>(
+        pub_params: &PublicParams<H, G>,
+        replica_id: H::Domain,
+        data: Vec<u8>,
+    ) -> Self
+    where
+        G::Key: AsRef<H::Domain>,
+        G: Graph<H> + ParameterSetMetadata + Sync + Send,
+    {
+        let nodes = pub_params.graph.size();
+        let cache_dir = tempfile::tempdir().expect("failed to create cache dir");
+        let tree_r_config_levels =
+            StoreConfig::default_cached_above_base_layer(nodes, BINARY_ARITY);
+        let config = StoreConfig::new(
+            cache_dir.path(),
+            CacheKey::CommDTree.to_string(),
+            tree_r_config_levels,
+        );
+        let replica_path = cache_dir.path().join("replica-path");
+
+        let mut replica = data.clone();
+        let (tau, aux) = DrgPoRep::<H, G>::replicate(
+            pub_params,
+            &replica_id,
+            (&mut replica[..]).into(),
+            None,
+            config,
+            replica_path,
+        )
+        .expect("failed to replicate");
+
+        TestReplica {
+            replica_id,
+            data,
+            replica,
+            tau,
+            aux,
+            tree_r_config_levels,
+            cache_dir,
+        }
+    }
+
+    /// The path `replica` was written to during replication.
+    pub fn replica_path(&self) -> PathBuf {
+        self.cache_dir.path().join("replica-path")
+    }
+
+    pub fn public_inputs(&self, challenges: impl Into<ChallengeSet>) -> PublicInputs<H::Domain> {
+        PublicInputs {
+            replica_id: Some(self.replica_id),
+            challenges: challenges.into(),
+            tau: Some(self.tau),
+        }
+    }
+
+    /// Private inputs reading the replica from `tree_r`.
+    pub fn private_inputs(&self) -> PrivateInputs<'_, H> {
+        PrivateInputs {
+            tree_d: &self.aux.tree_d,
+            tree_r: &self.aux.tree_r,
+            tree_r_config_levels: self.tree_r_config_levels,
+            replica: None,
+        }
+    }
+}
+
+/// A remote replica held in memory, recording the ranges read.
+#[derive(Debug)]
+pub struct InMemoryRangeReader {
+    pub data: Vec<u8>,
+    /// Shared, so that the reads can be inspected after the reader is moved into a
+    /// `ReplicaSource`.
+    pub reads: Arc<Mutex<Vec<(u64, usize)>>>,
+}
+
+impl InMemoryRangeReader {
+    pub fn new(data: Vec<u8>) -> Self {
+        InMemoryRangeReader {
+            data,
+            reads: Default::default(),
+        }
+    }
+}
+
+impl RangeReader for InMemoryRangeReader {
+    fn read_range(&self, offset: u64, len: usize) -> Result<Vec<u8>> {
+        self.reads.lock().unwrap().push((offset, len));
+
+        let start = offset as usize;
+        ensure!(
+            start + len <= self.data.len(),
+            Error::OutOfBounds(start + len, self.data.len())
+        );
+        Ok(self.data[start..start + len].to_vec())
+    }
+}
@@ -0,0 +1,138 @@
+use anyhow::{ensure, Context};
+
+use crate::drgraph::Graph;
+use crate::error::Result;
+use crate::hasher::{Domain, Hasher};
+use crate::parameter_cache::ParameterSetMetadata;
+use crate::proof::ProofScheme;
+use crate::util::NODE_SIZE;
+
+use super::vanilla::{DrgPoRep, Proof, PublicInputs, SetupParams, Tau};
+
+/// Parses a hex encoded commitment, with or without a `0x` prefix. `name` is used in error
+/// messages.
+pub fn domain_from_hex<H: Hasher>(name: &str, value: &str) -> Result<H::Domain> {
+    let value = value.trim_start_matches("0x");
+    let bytes = hex::decode(value).with_context(|| format!("{} is not valid hex", name))?;
+    ensure!(
+        bytes.len() == NODE_SIZE,
+        "{} must be {} bytes, got {}",
+        name,
+        NODE_SIZE,
+        bytes.len()
+    );
+
+    H::Domain::try_from_bytes(&bytes).with_context(|| format!("{} is not a valid element", name))
+}
+
+/// Verifies a vanilla `DrgPoRep` proof from untyped inputs, as handed to a command line tool.
+///
+/// `setup_params_json` is the JSON encoded `SetupParams` the public parameters are derived from,
+/// the commitments are hex encoded and `proof_bytes` is the JSON encoded `Proof`.
+pub fn verify_from_hex<H, G>(
+    setup_params_json: &str,
+    replica_id_hex: &str,
+    comm_r_hex: &str,
+    comm_d_hex: &str,
+    challenges: &[usize],
+    proof_bytes: &[u8],
+) -> Result<bool>
+where
+    H: Hasher,
+    G: Graph<H> + ParameterSetMetadata + Sync,
+{
+    let setup_params: SetupParams =
+        serde_json::from_str(setup_params_json).context("invalid setup params")?;
+    let replica_id = domain_from_hex::<H>("replica_id", replica_id_hex)?;
+    let comm_r = domain_from_hex::<H>("comm_r", comm_r_hex)?;
+    let comm_d = domain_from_hex::<H>("comm_d", comm_d_hex)?;
+    let proof: Proof<H> = serde_json::from_slice(proof_bytes).context("invalid proof")?;
+
+    let pub_params = DrgPoRep::<H, G>::setup(&setup_params)?;
+    let pub_inputs = PublicInputs {
+        replica_id: Some(replica_id),
+        challenges: challenges.to_vec().into(),
+        tau: Some(Tau::new(comm_d, comm_r)),
+    };
+
+    DrgPoRep::<H, G>::verify(&pub_params, &pub_inputs, &proof)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::drgraph::{new_seed, BucketGraph, BASE_DEGREE};
+    use crate::hasher::PedersenHasher;
+    use crate::porep::drg::{DrgParams, TestReplica};
+
+    type H = PedersenHasher;
+    type G = BucketGraph<PedersenHasher>;
+
+    #[test]
+    fn verify_from_hex_valid_and_malformed() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let nodes = 8;
+
+        let sp = SetupParams {
+            drg: DrgParams {
+                nodes,
+                degree: BASE_DEGREE,
+                expansion_degree: 0,
+                seed: new_seed(),
+            },
+            private: false,
+            challenges_count: 1,
+        };
+        let pp = DrgPoRep::<H, G>::setup(&sp).expect("setup failed");
+
+        let replica = TestReplica::new(&pp, rng);
+
+        let challenges = vec![5];
+        let pub_inputs = replica.public_inputs(challenges.clone());
+        let priv_inputs = replica.private_inputs();
+        let proof =
+            DrgPoRep::<H, G>::prove(&pp, &pub_inputs, &priv_inputs).expect("proving failed");
+
+        let setup_params_json = serde_json::to_string(&sp).unwrap();
+        let proof_bytes = serde_json::to_vec(&proof).unwrap();
+        let replica_id_hex = hex::encode(replica.replica_id);
+        let comm_r_hex = format!("0x{}", hex::encode(replica.tau.comm_r));
+        let comm_d_hex = hex::encode(replica.tau.comm_d);
+
+        assert!(verify_from_hex::<H, G>(
+            &setup_params_json,
+            &replica_id_hex,
+            &comm_r_hex,
+            &comm_d_hex,
+            &challenges,
+            &proof_bytes,
+        )
+        .unwrap());
+
+        let err = verify_from_hex::<H, G>(
+            &setup_params_json,
+            &replica_id_hex,
+            "0xnot-hex",
+            &comm_d_hex,
+            &challenges,
+            &proof_bytes,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("comm_r"), "{}", err);
+
+        let err = verify_from_hex::<H, G>(
+            &setup_params_json,
+            &replica_id_hex[2..],
+            &comm_r_hex,
+            &comm_d_hex,
+            &challenges,
+            &proof_bytes,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("replica_id"), "{}", err);
+    }
+}
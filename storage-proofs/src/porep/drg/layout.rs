@@ -0,0 +1,82 @@
+use anyhow::ensure;
+
+use crate::error::{Error, Result};
+use crate::util::{data_at_node_offset, NODE_SIZE};
+
+use super::remote::RangeReader;
+
+/// Maps logical node indices to the position of their data in a replica on disk.
+///
+/// The layout only affects where node data is read from. Merkle trees are always built over
+/// nodes in logical order, so proofs are independent of the layout a replica is stored in.
+pub trait NodeLayout: std::fmt::Debug + Sync {
+    /// Returns the byte offset at which the data of node `node_index` is stored.
+    fn disk_offset(&self, node_index: usize) -> u64;
+}
+
+/// The default layout: nodes are stored back to back, in logical order.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Identity;
+
+impl NodeLayout for Identity {
+    fn disk_offset(&self, node_index: usize) -> u64 {
+        data_at_node_offset(node_index) as u64
+    }
+}
+
+/// Replica data, together with the layout its nodes are stored in.
+#[derive(Debug, Clone, Copy)]
+pub struct LaidOutReplica<'a> {
+    pub data: &'a [u8],
+    pub layout: &'a dyn NodeLayout,
+}
+
+impl<'a> LaidOutReplica<'a> {
+    pub fn new(data: &'a [u8], layout: &'a dyn NodeLayout) -> Self {
+        LaidOutReplica { data, layout }
+    }
+
+    /// Returns the bytes of the logical node `node`.
+    pub fn read_node(&self, node: usize) -> Result<&'a [u8]> {
+        let offset = self.layout.disk_offset(node) as usize;
+
+        ensure!(
+            offset + NODE_SIZE <= self.data.len(),
+            Error::OutOfBounds(offset + NODE_SIZE, self.data.len())
+        );
+
+        Ok(&self.data[offset..offset + NODE_SIZE])
+    }
+}
+
+/// Where the prover reads replica nodes from, see `PrivateInputs::replica`.
+#[derive(Debug)]
+pub enum ReplicaSource<'a> {
+    /// Replica data held locally, in the given layout.
+    Local(LaidOutReplica<'a>),
+    /// Replica data in remote storage, stored in logical order. Proving fetches only the
+    /// challenged nodes and their parents, see `read_remote_nodes`.
+    Remote(Box<dyn RangeReader>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::util::data_at_node;
+
+    #[test]
+    fn identity_matches_data_at_node() {
+        let data: Vec<u8> = (0..8 * NODE_SIZE).map(|i| i as u8).collect();
+        let replica = LaidOutReplica::new(&data, &Identity);
+
+        for node in 0..8 {
+            assert_eq!(
+                replica.read_node(node).unwrap(),
+                data_at_node(&data, node).unwrap()
+            );
+        }
+
+        assert!(replica.read_node(8).is_err());
+    }
+}
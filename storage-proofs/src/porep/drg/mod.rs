@@ -1,7 +1,33 @@
+#[cfg(not(feature = "verify-only"))]
+mod audit;
+mod challenge_set;
 mod circuit;
 mod compound;
+mod dynamic;
+mod encoding_op;
+#[cfg(test)]
+mod fixture;
+mod from_hex;
+mod layout;
+mod remote;
+#[cfg(not(feature = "verify-only"))]
+mod source;
 mod vanilla;
+mod windowed;
 
+#[cfg(not(feature = "verify-only"))]
+pub use self::audit::*;
+pub use self::challenge_set::*;
 pub use self::circuit::*;
 pub use self::compound::*;
+pub use self::dynamic::*;
+pub use self::encoding_op::*;
+#[cfg(test)]
+pub(crate) use self::fixture::*;
+pub use self::from_hex::*;
+pub use self::layout::*;
+pub use self::remote::*;
+#[cfg(not(feature = "verify-only"))]
+pub use self::source::*;
 pub use self::vanilla::*;
+pub use self::windowed::*;
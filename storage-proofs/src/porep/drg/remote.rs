@@ -0,0 +1,87 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::ensure;
+
+use crate::error::Result;
+use crate::hasher::{Domain, Hasher};
+use crate::util::{data_at_node_offset, NODE_SIZE};
+
+/// Reads byte ranges of a replica held in remote storage, e.g. an object store, so that the
+/// prover only fetches the nodes it needs. Implementations for specific stores live downstream.
+///
+/// The replica is expected to be stored with its nodes back to back, in logical order.
+pub trait RangeReader: std::fmt::Debug + Send + Sync {
+    /// Reads the `len` bytes starting at byte `offset` of the replica.
+    fn read_range(&self, offset: u64, len: usize) -> Result<Vec<u8>>;
+}
+
+/// Reads the logical nodes `nodes` of a remote replica. Repeated nodes are read once, and runs
+/// of adjacent nodes are coalesced into a single range read. Returns the node data by index.
+pub fn read_remote_nodes<H: Hasher>(
+    reader: &dyn RangeReader,
+    nodes: &[usize],
+) -> Result<BTreeMap<usize, H::Domain>> {
+    let nodes: BTreeSet<usize> = nodes.iter().copied().collect();
+    let mut result = BTreeMap::new();
+
+    let mut nodes = nodes.into_iter().peekable();
+    while let Some(start) = nodes.next() {
+        let mut end = start + 1;
+        while nodes.peek() == Some(&end) {
+            nodes.next();
+            end += 1;
+        }
+
+        let len = (end - start) * NODE_SIZE;
+        let bytes = reader.read_range(data_at_node_offset(start) as u64, len)?;
+        ensure!(
+            bytes.len() == len,
+            "expected {} bytes for nodes {}..{}, got {}",
+            len,
+            start,
+            end,
+            bytes.len()
+        );
+
+        for (node, node_bytes) in (start..end).zip(bytes.chunks(NODE_SIZE)) {
+            result.insert(node, H::Domain::try_from_bytes(node_bytes)?);
+        }
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::hasher::PedersenHasher;
+    use crate::porep::drg::InMemoryRangeReader;
+    use crate::util::data_at_node;
+
+    #[test]
+    fn read_remote_nodes_coalesces_adjacent_nodes() {
+        let data: Vec<u8> = (0..16 * NODE_SIZE).map(|i| (i % 64) as u8).collect();
+        let reader = InMemoryRangeReader::new(data.clone());
+
+        let nodes = read_remote_nodes::<PedersenHasher>(&reader, &[9, 3, 4, 5, 9, 12, 11]).unwrap();
+
+        assert_eq!(
+            nodes.keys().copied().collect::<Vec<_>>(),
+            vec![3, 4, 5, 9, 11, 12]
+        );
+        for (node, value) in &nodes {
+            assert_eq!(value.into_bytes(), data_at_node(&data, *node).unwrap());
+        }
+        assert_eq!(
+            *reader.reads.lock().unwrap(),
+            vec![
+                (3 * NODE_SIZE as u64, 3 * NODE_SIZE),
+                (9 * NODE_SIZE as u64, NODE_SIZE),
+                (11 * NODE_SIZE as u64, 2 * NODE_SIZE),
+            ]
+        );
+
+        assert!(read_remote_nodes::<PedersenHasher>(&reader, &[15, 16]).is_err());
+    }
+}
@@ -0,0 +1,24 @@
+use crate::error::Result;
+use crate::hasher::{Domain, Hasher};
+use crate::util::{data_at_node, NODE_SIZE};
+
+/// Produces the nodes of the original data, e.g. by decompressing them on demand, so that
+/// replication does not need the whole data as a flat slice.
+pub trait DataSource<H: Hasher> {
+    /// Returns the number of nodes of the data.
+    fn nodes(&self) -> usize;
+
+    /// Returns the node at `index`.
+    fn read_node(&self, index: usize) -> Result<H::Domain>;
+}
+
+/// Flat data, with the nodes stored back to back.
+impl<H: Hasher> DataSource<H> for [u8] {
+    fn nodes(&self) -> usize {
+        self.len() / NODE_SIZE
+    }
+
+    fn read_node(&self, index: usize) -> Result<H::Domain> {
+        H::Domain::try_from_bytes(data_at_node(self, index)?)
+    }
+}
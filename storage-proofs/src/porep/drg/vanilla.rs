@@ -1,27 +1,45 @@
+#[cfg(not(feature = "verify-only"))]
+use std::collections::BTreeMap;
+use std::fmt;
 use std::fs::OpenOptions;
 use std::marker::PhantomData;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::{ensure, Context};
 use byteorder::{LittleEndian, WriteBytesExt};
 use generic_array::typenum;
+use merkletree::merkle::get_merkle_tree_len;
 use merkletree::store::StoreConfig;
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
-use crate::drgraph::Graph;
-use crate::encode;
-use crate::error::Result;
+use crate::drgraph::{Graph, Seed, BASE_DEGREE};
+use crate::error::{Error, Result};
 use crate::fr32::bytes_into_fr_repr_safe;
 use crate::hasher::{Domain, HashFunction, Hasher};
-use crate::merkle::{BinaryLCMerkleTree, BinaryMerkleTree, LCMerkleTree, MerkleProof};
+use crate::merkle::{
+    check_available_space, BinaryLCMerkleTree, BinaryMerkleTree, DiskStore, LCMerkleTree,
+    MerkleProof, Store, StoreConfigExt,
+};
 use crate::parameter_cache::ParameterSetMetadata;
+use crate::porep::stacked::BINARY_ARITY;
 use crate::porep::PoRep;
-use crate::proof::{NoRequirements, ProofScheme};
+use crate::proof::ProofScheme;
 use crate::util::{data_at_node, data_at_node_offset, NODE_SIZE};
 use crate::Data;
 
+use super::challenge_set::ChallengeSet;
+use super::encoding_op::EncodingOp;
+use super::layout::{LaidOutReplica, ReplicaSource};
+use super::remote::read_remote_nodes;
+#[cfg(not(feature = "verify-only"))]
+use super::source::DataSource;
+
+/// The number of challenges proven at once by `DrgPoRep::prove`.
+#[cfg(not(feature = "verify-only"))]
+const PROVE_CHUNK_SIZE: usize = 1 << 12;
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Tau<T> {
     pub comm_r: T,
@@ -38,6 +56,17 @@ impl<T: Domain> Tau<T> {
 pub struct ProverAux<H: Hasher> {
     pub tree_d: BinaryMerkleTree<H::Domain, H::Function>,
     pub tree_r: BinaryLCMerkleTree<H::Domain, H::Function>,
+    /// Where the trees are stored on disk, if known. Required by `persist`.
+    pub stores: Option<AuxStores>,
+}
+
+/// The on disk locations of the trees of a `ProverAux`, from which they can be reopened in
+/// another process.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuxStores {
+    pub tree_d_config: StoreConfig,
+    pub tree_r_config: StoreConfig,
+    pub replica_path: PathBuf,
 }
 
 impl<H: Hasher> ProverAux<H> {
@@ -45,32 +74,157 @@ impl<H: Hasher> ProverAux<H> {
         tree_d: BinaryMerkleTree<H::Domain, H::Function>,
         tree_r: BinaryLCMerkleTree<H::Domain, H::Function>,
     ) -> Self {
-        ProverAux { tree_d, tree_r }
+        ProverAux {
+            tree_d,
+            tree_r,
+            stores: None,
+        }
+    }
+
+    /// Writes the locations of the trees to `path`, so that proving can resume in a separate
+    /// process through `load`, once the commitments have been published. The tree stores and
+    /// the replica must be kept in place until then.
+    pub fn persist<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let stores = self
+            .stores
+            .as_ref()
+            .context("tree locations are unknown, tree_d was not built during replication")?;
+        let bytes = serde_json::to_vec(stores)?;
+        std::fs::write(path.as_ref(), bytes)
+            .with_context(|| format!("could not write {}", path.as_ref().display()))?;
+
+        Ok(())
+    }
+
+    /// Reopens the trees written by `persist`. The trees have the size of the graph in
+    /// `pub_params`.
+    pub fn load<P: AsRef<Path>, G>(path: P, pub_params: &PublicParams<H, G>) -> Result<Self>
+    where
+        G: Graph<H> + ParameterSetMetadata,
+    {
+        let bytes = std::fs::read(path.as_ref())
+            .with_context(|| format!("could not read {}", path.as_ref().display()))?;
+        let stores: AuxStores = serde_json::from_slice(&bytes).context("invalid prover aux")?;
+
+        let nodes = pub_params.graph.size();
+        let tree_d_store = DiskStore::new_from_disk(
+            get_merkle_tree_len(nodes, BINARY_ARITY)?,
+            BINARY_ARITY,
+            &stores.tree_d_config,
+        )
+        .context("tree_d_store")?;
+        let tree_d = BinaryMerkleTree::from_data_store(tree_d_store, nodes).context("tree_d")?;
+        let tree_r = pub_params
+            .graph
+            .lcmerkle_open(stores.tree_r_config.clone(), &stores.replica_path)
+            .context("tree_r")?;
+
+        Ok(ProverAux {
+            tree_d,
+            tree_r,
+            stores: Some(stores),
+        })
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct PublicInputs<T: Domain> {
     pub replica_id: Option<T>,
-    pub challenges: Vec<usize>,
+    pub challenges: ChallengeSet,
     pub tau: Option<Tau<T>>,
 }
 
+impl<T: Domain> PublicInputs<T> {
+    /// Creates public inputs for a scheme with the given `private` setting. `tau` must be unset
+    /// exactly when the scheme is private.
+    pub fn new(
+        replica_id: Option<T>,
+        challenges: ChallengeSet,
+        tau: Option<Tau<T>>,
+        private: bool,
+    ) -> Result<Self> {
+        ensure!(
+            tau.is_none() == private,
+            "inconsistent private state: tau must be unset exactly when private is set"
+        );
+
+        Ok(PublicInputs {
+            replica_id,
+            challenges,
+            tau,
+        })
+    }
+}
+
+/// Requirements checked against the public inputs before a proof is verified.
+#[derive(Debug, Clone, Default)]
+pub struct Requirements<T: Domain> {
+    /// When set, verification fails early unless `PublicInputs::replica_id` matches it.
+    pub expected_replica_id: Option<T>,
+    /// Verification fails early if the public inputs contain fewer challenges than this.
+    pub minimum_challenges: usize,
+}
+
 #[derive(Debug)]
 pub struct PrivateInputs<'a, H: 'a + Hasher> {
     pub tree_d: &'a BinaryMerkleTree<H::Domain, H::Function>,
     pub tree_r: &'a BinaryLCMerkleTree<H::Domain, H::Function>,
     pub tree_r_config_levels: usize,
+    /// When set, replica node data is read from here instead of from `tree_r`, which allows
+    /// proving against a replica stored in a custom node layout, or in remote storage.
+    pub replica: Option<ReplicaSource<'a>>,
+}
+
+impl<'a, H: 'a + Hasher> PrivateInputs<'a, H> {
+    /// Reads the data of the logical replica node `node`.
+    pub fn read_replica_node(&self, node: usize) -> Result<H::Domain> {
+        match self.replica {
+            Some(ReplicaSource::Local(ref replica)) => {
+                H::Domain::try_from_bytes(replica.read_node(node)?)
+            }
+            Some(ReplicaSource::Remote(ref reader)) => {
+                read_remote_nodes::<H>(reader.as_ref(), &[node])?
+                    .remove(&node)
+                    .context("remote node missing")
+            }
+            None => self.tree_r.read_at(node),
+        }
+    }
+
+    /// Fetches the replica nodes of `challenges` and their parents up front if the replica is
+    /// remote, so that they are read in coalesced ranges instead of one by one.
+    #[cfg(not(feature = "verify-only"))]
+    fn fetch_remote_nodes<G: Graph<H>>(
+        &self,
+        graph: &G,
+        challenges: &[usize],
+    ) -> Result<Option<BTreeMap<usize, H::Domain>>> {
+        let reader = match self.replica {
+            Some(ReplicaSource::Remote(ref reader)) => reader,
+            _ => return Ok(None),
+        };
+
+        let mut nodes = Vec::with_capacity(challenges.len() * (graph.degree() + 1));
+        let mut parents = vec![0; graph.degree()];
+        for challenge in challenges {
+            let challenge = challenge % graph.size();
+            graph.parents(challenge, &mut parents)?;
+            nodes.push(challenge);
+            nodes.extend(parents.iter().map(|parent| *parent as usize));
+        }
+
+        Ok(Some(read_remote_nodes::<H>(reader.as_ref(), &nodes)?))
+    }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SetupParams {
     pub drg: DrgParams,
     pub private: bool,
     pub challenges_count: usize,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DrgParams {
     // Number of nodes
     pub nodes: usize,
@@ -81,7 +235,39 @@ pub struct DrgParams {
     pub expansion_degree: usize,
 
     // Random seed
-    pub seed: [u8; 28],
+    pub seed: Seed,
+}
+
+/// A configuration for fast end-to-end tests: a tiny graph with few challenges. It exercises the
+/// full proving path, but provides no meaningful security and must not be used in production.
+#[derive(Debug, Clone)]
+pub struct TestMode {
+    pub nodes: usize,
+    pub challenges_count: usize,
+}
+
+impl Default for TestMode {
+    fn default() -> Self {
+        TestMode {
+            nodes: 8,
+            challenges_count: 1,
+        }
+    }
+}
+
+impl TestMode {
+    pub fn setup_params(&self, seed: Seed) -> SetupParams {
+        SetupParams {
+            drg: DrgParams {
+                nodes: self.nodes,
+                degree: BASE_DEGREE,
+                expansion_degree: 0,
+                seed,
+            },
+            private: false,
+            challenges_count: self.challenges_count,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -93,6 +279,8 @@ where
     pub graph: G,
     pub private: bool,
     pub challenges_count: usize,
+    /// How nodes are encoded, see `with_encoding_op`.
+    pub encoding_op: EncodingOp,
 
     _h: PhantomData<H>,
 }
@@ -107,9 +295,81 @@ where
             graph,
             private,
             challenges_count,
+            encoding_op: EncodingOp::default(),
             _h: PhantomData,
         }
     }
+
+    /// Selects how the key of each node is combined with its data. This changes the replica and
+    /// `comm_r`, so replicas and proofs are only compatible with parameters using the same
+    /// operation.
+    pub fn with_encoding_op(mut self, encoding_op: EncodingOp) -> Self {
+        self.encoding_op = encoding_op;
+        self
+    }
+
+    /// Lists the fields in which `other` differs from `self`, e.g. to find out why a proof
+    /// generated with one set of parameters does not verify with another.
+    pub fn diff(&self, other: &Self) -> Vec<ParamDiff> {
+        let mut diffs = Vec::new();
+        let mut compare = |field, left: String, right: String| {
+            if left != right {
+                diffs.push(ParamDiff { field, left, right });
+            }
+        };
+
+        compare(
+            "nodes",
+            self.graph.size().to_string(),
+            other.graph.size().to_string(),
+        );
+        compare(
+            "degree",
+            (self.graph.degree() - self.graph.expansion_degree()).to_string(),
+            (other.graph.degree() - other.graph.expansion_degree()).to_string(),
+        );
+        compare(
+            "expansion_degree",
+            self.graph.expansion_degree().to_string(),
+            other.graph.expansion_degree().to_string(),
+        );
+        compare(
+            "challenges_count",
+            self.challenges_count.to_string(),
+            other.challenges_count.to_string(),
+        );
+        compare(
+            "private",
+            self.private.to_string(),
+            other.private.to_string(),
+        );
+        compare(
+            "encoding_op",
+            format!("{:?}", self.encoding_op),
+            format!("{:?}", other.encoding_op),
+        );
+        compare(
+            "seed",
+            hex::encode(self.graph.seed().as_bytes()),
+            hex::encode(other.graph.seed().as_bytes()),
+        );
+
+        diffs
+    }
+}
+
+/// A field which differs between two `PublicParams`, see `PublicParams::diff`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParamDiff {
+    pub field: &'static str,
+    pub left: String,
+    pub right: String,
+}
+
+impl fmt::Display for ParamDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {} != {}", self.field, self.left, self.right)
+    }
 }
 
 impl<H, G> ParameterSetMetadata for PublicParams<H, G>
@@ -119,8 +379,9 @@ where
 {
     fn identifier(&self) -> String {
         format!(
-            "drgporep::PublicParams{{graph: {}}}",
+            "drgporep::PublicParams{{graph: {}{}}}",
             self.graph.identifier(),
+            self.encoding_op.identifier(),
         )
     }
 
@@ -157,6 +418,11 @@ impl<H: Hasher, U: typenum::Unsigned> DataProof<H, U> {
         out
     }
 
+    /// Returns the number of bytes `serialize` produces for this proof, without serializing it.
+    pub fn serialized_size(&self) -> usize {
+        self.proof.serialized_size() + NODE_SIZE
+    }
+
     /// proves_challenge returns true if this self.proof corresponds to challenge.
     /// This is useful for verifying that a supplied proof is actually relevant to a given challenge.
     pub fn proves_challenge(&self, challenge: usize) -> bool {
@@ -230,6 +496,23 @@ impl<H: Hasher> Proof<H> {
         res
     }
 
+    /// Returns the number of bytes `serialize` produces for this proof, computed from the
+    /// challenged nodes and their parents without serializing anything.
+    pub fn serialized_size(&self) -> usize {
+        (0..self.nodes.len())
+            .map(|i| {
+                let parents_size: usize = self.replica_parents[i]
+                    .iter()
+                    .map(|(_, p)| std::mem::size_of::<u32>() + p.serialized_size())
+                    .sum();
+
+                self.replica_nodes[i].serialized_size()
+                    + parents_size
+                    + self.nodes[i].serialized_size()
+            })
+            .sum()
+    }
+
     pub fn new(
         replica_nodes: Vec<DataProof<H, typenum::U2>>,
         replica_parents: Vec<ReplicaParents<H>>,
@@ -243,6 +526,44 @@ impl<H: Hasher> Proof<H> {
             nodes,
         }
     }
+
+    /// Assembles a proof from inclusion proofs generated elsewhere, for example by a service
+    /// holding `tree_d` and `tree_r`, checking that they are consistent with the given roots.
+    pub fn from_components(
+        replica_nodes: Vec<DataProof<H, typenum::U2>>,
+        replica_parents: Vec<ReplicaParents<H>>,
+        nodes: Vec<DataProof<H, typenum::U2>>,
+        data_root: H::Domain,
+        replica_root: H::Domain,
+    ) -> Result<Proof<H>> {
+        ensure!(!nodes.is_empty(), "no challenged nodes");
+        ensure!(
+            replica_nodes.len() == nodes.len() && replica_parents.len() == nodes.len(),
+            "inconsistent number of challenges: {} replica nodes, {} replica parents, {} nodes",
+            replica_nodes.len(),
+            replica_parents.len(),
+            nodes.len()
+        );
+        ensure!(
+            nodes.iter().all(|node| *node.proof.root() == data_root),
+            "data node proofs do not match data_root"
+        );
+        ensure!(
+            replica_nodes
+                .iter()
+                .chain(replica_parents.iter().flatten().map(|(_, parent)| parent))
+                .all(|node| *node.proof.root() == replica_root),
+            "replica node proofs do not match replica_root"
+        );
+
+        Ok(Proof {
+            data_root,
+            replica_root,
+            replica_nodes,
+            replica_parents,
+            nodes,
+        })
+    }
 }
 
 impl<'a, H: Hasher> From<&'a Proof<H>> for Proof<H> {
@@ -270,14 +591,14 @@ where
 impl<'a, H, G> ProofScheme<'a> for DrgPoRep<'a, H, G>
 where
     H: 'a + Hasher,
-    G: 'a + Graph<H> + ParameterSetMetadata,
+    G: 'a + Graph<H> + ParameterSetMetadata + Sync,
 {
     type PublicParams = PublicParams<H, G>;
     type SetupParams = SetupParams;
     type PublicInputs = PublicInputs<H::Domain>;
     type PrivateInputs = PrivateInputs<'a, H>;
     type Proof = Proof<H>;
-    type Requirements = NoRequirements;
+    type Requirements = Requirements<H::Domain>;
 
     fn setup(sp: &Self::SetupParams) -> Result<Self::PublicParams> {
         let graph = G::new(
@@ -290,6 +611,30 @@ where
         Ok(PublicParams::new(graph, sp.private, sp.challenges_count))
     }
 
+    fn validate_setup_params(sp: &Self::SetupParams) -> Result<()> {
+        ensure!(
+            sp.drg.nodes > 0,
+            "invalid setup params: drg.nodes must be greater than zero"
+        );
+        ensure!(
+            sp.drg.degree > 0,
+            "invalid setup params: drg.degree must be greater than zero"
+        );
+        ensure!(
+            sp.challenges_count > 0,
+            "invalid setup params: challenges_count must be greater than zero"
+        );
+        ensure!(
+            sp.challenges_count <= sp.drg.nodes,
+            "invalid setup params: challenges_count {} exceeds drg.nodes {}",
+            sp.challenges_count,
+            sp.drg.nodes
+        );
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "verify-only"))]
     fn prove<'b>(
         pub_params: &'b Self::PublicParams,
         pub_inputs: &'b Self::PublicInputs,
@@ -305,74 +650,35 @@ where
 
         let mut replica_nodes = Vec::with_capacity(len);
         let mut replica_parents = Vec::with_capacity(len);
-        let mut data_nodes: Vec<DataProof<H, typenum::U2>> = Vec::with_capacity(len);
-
-        for i in 0..len {
-            let challenge = pub_inputs.challenges[i] % pub_params.graph.size();
-            ensure!(challenge != 0, "cannot prove the first node");
-
-            let tree_d = &priv_inputs.tree_d;
-            let tree_r = &priv_inputs.tree_r;
-            let tree_r_config_levels = priv_inputs.tree_r_config_levels;
+        let mut data_nodes = Vec::with_capacity(len);
 
-            let data = tree_r.read_at(challenge)?;
-
-            let tree_proof = {
-                if tree_r_config_levels == 0 {
-                    tree_r.gen_proof(challenge)
-                } else {
-                    tree_r.gen_cached_proof(challenge, tree_r_config_levels)
-                }
-            }?;
-            replica_nodes.push(DataProof {
-                proof: MerkleProof::new_from_proof(&tree_proof),
-                data,
-            });
-
-            let mut parents = vec![0; pub_params.graph.degree()];
-            pub_params.graph.parents(challenge, &mut parents)?;
-            let mut replica_parentsi = Vec::with_capacity(parents.len());
-
-            for p in &parents {
-                replica_parentsi.push((*p, {
-                    let proof = {
-                        if tree_r_config_levels == 0 {
-                            tree_r.gen_proof(*p as usize)
-                        } else {
-                            tree_r.gen_cached_proof(*p as usize, tree_r_config_levels)
-                        }
-                    }?;
-                    DataProof {
-                        proof: MerkleProof::new_from_proof(&proof),
-                        data: tree_r.read_at(*p as usize)?,
-                    }
-                }));
+        // The challenges are independent, and the trees are only read. They are taken in chunks,
+        // so that the indices of a bitmap are never all collected at once.
+        let mut challenges = pub_inputs.challenges.iter();
+        loop {
+            let chunk: Vec<usize> = challenges.by_ref().take(PROVE_CHUNK_SIZE).collect();
+            if chunk.is_empty() {
+                break;
             }
 
-            replica_parents.push(replica_parentsi);
-
-            let node_proof = tree_d.gen_proof(challenge)?;
-
-            {
-                // TODO: use this again, I can't make lifetimes work though atm and I do not know why
-                // let extracted = Self::extract(
-                //     pub_params,
-                //     &pub_inputs.replica_id.into_bytes(),
-                //     &replica,
-                //     challenge,
-                // )?;
-
-                let extracted = decode_domain_block::<H>(
-                    &pub_inputs.replica_id.context("missing replica_id")?,
-                    tree_r,
-                    challenge,
-                    tree_r.read_at(challenge)?,
-                    &parents,
-                )?;
-                data_nodes.push(DataProof {
-                    data: extracted,
-                    proof: MerkleProof::new_from_proof(&node_proof),
-                });
+            let remote_nodes = priv_inputs.fetch_remote_nodes(&pub_params.graph, &chunk)?;
+            let challenge_proofs = chunk
+                .par_iter()
+                .map(|challenge| {
+                    prove_challenge(
+                        pub_params,
+                        pub_inputs,
+                        priv_inputs,
+                        remote_nodes.as_ref(),
+                        *challenge,
+                    )
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            for (replica_node, replica_parentsi, data_node) in challenge_proofs {
+                replica_nodes.push(replica_node);
+                replica_parents.push(replica_parentsi);
+                data_nodes.push(data_node);
             }
         }
 
@@ -388,25 +694,23 @@ where
     ) -> Result<bool> {
         let mut hasher = Sha256::new();
 
-        for i in 0..pub_inputs.challenges.len() {
+        for (i, challenge) in pub_inputs.challenges.iter().enumerate() {
             {
                 // This was verify_proof_meta.
-                if pub_inputs.challenges[i] >= pub_params.graph.size() {
+                if challenge >= pub_params.graph.size() {
                     return Ok(false);
                 }
 
-                if !(proof.nodes[i].proves_challenge(pub_inputs.challenges[i])) {
+                if !(proof.nodes[i].proves_challenge(challenge)) {
                     return Ok(false);
                 }
 
-                if !(proof.replica_nodes[i].proves_challenge(pub_inputs.challenges[i])) {
+                if !(proof.replica_nodes[i].proves_challenge(challenge)) {
                     return Ok(false);
                 }
 
                 let mut expected_parents = vec![0; pub_params.graph.degree()];
-                pub_params
-                    .graph
-                    .parents(pub_inputs.challenges[i], &mut expected_parents)?;
+                pub_params.graph.parents(challenge, &mut expected_parents)?;
                 if proof.replica_parents[i].len() != expected_parents.len() {
                     println!(
                         "proof parents were not the same length as in public parameters: {} != {}",
@@ -427,7 +731,7 @@ where
                 }
             }
 
-            let challenge = pub_inputs.challenges[i] % pub_params.graph.size();
+            let challenge = challenge % pub_params.graph.size();
             ensure!(challenge != 0, "cannot prove the first node");
 
             if !proof.replica_nodes[i].proof.validate(challenge) {
@@ -452,7 +756,13 @@ where
                 bytes_into_fr_repr_safe(hash.as_ref()).into()
             };
 
-            let unsealed = encode::decode(key, proof.replica_nodes[i].data);
+            let unsealed = match pub_params
+                .encoding_op
+                .decode::<H>(&key, &proof.replica_nodes[i].data)
+            {
+                Ok(unsealed) => unsealed,
+                Err(_) => return Ok(false),
+            };
 
             if unsealed != proof.nodes[i].data {
                 return Ok(false);
@@ -466,6 +776,29 @@ where
 
         Ok(true)
     }
+
+    fn check_requirements(
+        pub_inputs: &Self::PublicInputs,
+        requirements: &Self::Requirements,
+    ) -> Result<()> {
+        if let Some(expected) = requirements.expected_replica_id {
+            ensure!(
+                pub_inputs.replica_id == Some(expected),
+                "replica_id mismatch: expected {:?}, got {:?}",
+                expected,
+                pub_inputs.replica_id
+            );
+        }
+
+        ensure!(
+            pub_inputs.challenges.len() >= requirements.minimum_challenges,
+            "too few challenges {} < {}",
+            pub_inputs.challenges.len(),
+            requirements.minimum_challenges
+        );
+
+        Ok(())
+    }
 }
 
 impl<'a, H, G> PoRep<'a, H, H> for DrgPoRep<'a, H, G>
@@ -485,53 +818,42 @@ where
         config: StoreConfig,
         replica_path: PathBuf,
     ) -> Result<(Tau<H::Domain>, ProverAux<H>)> {
-        use crate::cache_key::CacheKey;
+        // Fail before doing any work if the trees and the replica cannot be stored.
+        check_replication_space(pp, &config, &replica_path, data_tree.is_none())?;
 
-        use std::io::prelude::*;
-
-        let tree_d = match data_tree {
-            Some(tree) => tree,
-            None => pp.graph.merkle_tree(Some(config.clone()), data.as_ref())?,
+        let (tree_d, tree_d_config) = match data_tree {
+            Some(tree) => (tree, None),
+            None => (
+                pp.graph.merkle_tree(Some(config.clone()), data.as_ref())?,
+                Some(config.clone()),
+            ),
         };
 
         let graph = &pp.graph;
         // encode(&pp.graph, replica_id, data, None)?;
-        // Because a node always follows all of its parents in the data,
-        // the nodes are by definition already topologically sorted.
-        // Therefore, if we simply traverse the data in order, encoding each node in place,
-        // we can always get each parent's encodings with a simple lookup --
+        // Nodes are traversed in topological order, encoding each node in place,
+        // so we can always get each parent's encodings with a simple lookup --
         // since we will already have encoded the parent earlier in the traversal.
 
-        let mut parents = vec![0; graph.degree()];
-        for node in 0..graph.size() {
-            graph.parents(node, &mut parents)?;
-            let key = graph.create_key(replica_id, node, &parents, data.as_ref(), None)?;
+        graph.for_each_scheduled(|node, parents| {
+            let key = graph.create_key(replica_id, node, parents, data.as_ref(), None)?;
             let start = data_at_node_offset(node);
             let end = start + NODE_SIZE;
 
             let node_data = H::Domain::try_from_bytes(&data.as_ref()[start..end])?;
-            let encoded = H::sloth_encode(key.as_ref(), &node_data)?;
-
-            encoded.write_bytes(&mut data.as_mut()[start..end])?;
-        }
-
-        // Write out the encoded data here.
-        let mut f = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .open(&replica_path)?;
-        f.write_all(&data.as_ref())?;
-
-        let tree_r_last_config =
-            StoreConfig::from_config(&config, CacheKey::CommRLastTree.to_string(), None);
-        let tree_r: BinaryLCMerkleTree<_, _> =
-            pp.graph
-                .lcmerkle_tree(tree_r_last_config, &data.as_ref(), &replica_path)?;
-
-        let comm_d = tree_d.root();
-        let comm_r = tree_r.root();
-
-        Ok((Tau::new(comm_d, comm_r), ProverAux::new(tree_d, tree_r)))
+            let encoded = pp.encoding_op.encode::<H>(key.as_ref(), &node_data)?;
+
+            encoded.write_bytes(&mut data.as_mut()[start..end])
+        })?;
+
+        finish_replication(
+            pp,
+            tree_d,
+            tree_d_config,
+            data.as_ref(),
+            config,
+            replica_path,
+        )
     }
 
     fn extract_all<'b>(
@@ -540,7 +862,7 @@ where
         data: &'b [u8],
         _config: Option<StoreConfig>,
     ) -> Result<Vec<u8>> {
-        decode(&pp.graph, replica_id, data, None)
+        decode(&pp.graph, replica_id, data, None, pp.encoding_op)
     }
 
     fn extract(
@@ -550,8 +872,339 @@ where
         node: usize,
         _config: Option<StoreConfig>,
     ) -> Result<Vec<u8>> {
-        Ok(decode_block(&pp.graph, replica_id, data, None, node)?.into_bytes())
+        Ok(decode_block(&pp.graph, replica_id, data, None, node, pp.encoding_op)?.into_bytes())
+    }
+}
+
+impl<'a, H, G> DrgPoRep<'a, H, G>
+where
+    H: 'a + Hasher,
+    G::Key: AsRef<H::Domain>,
+    G: 'a + Graph<H> + ParameterSetMetadata + Sync + Send,
+{
+    /// Replicates the data read node by node from `source`, with the same result as
+    /// `replicate` on the flat data. The original data is never held in memory as a whole, only
+    /// the replica is.
+    #[cfg(not(feature = "verify-only"))]
+    pub fn replicate_from_source<S: DataSource<H> + ?Sized>(
+        pp: &PublicParams<H, G>,
+        replica_id: &H::Domain,
+        source: &S,
+        config: StoreConfig,
+        replica_path: PathBuf,
+    ) -> Result<(Tau<H::Domain>, ProverAux<H>)> {
+        let graph = &pp.graph;
+        let nodes = graph.size();
+        ensure!(
+            source.nodes() == nodes,
+            "data source has {} nodes, expected {}",
+            source.nodes(),
+            nodes
+        );
+
+        // Fail before doing any work if the trees and the replica cannot be stored.
+        check_replication_space(pp, &config, &replica_path, true)?;
+
+        let tree_d = BinaryMerkleTree::<H::Domain, H::Function>::try_from_iter_with_config(
+            (0..nodes).map(|node| source.read_node(node)),
+            config.clone(),
+        )?;
+
+        // Parents are encoded before their children, so the keys are derived from the
+        // replica in the same way as when encoding in place.
+        let mut replica = vec![0u8; graph.expected_size()];
+        graph.for_each_scheduled(|node, parents| {
+            let key = graph.create_key(replica_id, node, parents, &replica, None)?;
+            let start = data_at_node_offset(node);
+
+            let encoded = pp
+                .encoding_op
+                .encode::<H>(key.as_ref(), &source.read_node(node)?)?;
+            encoded.write_bytes(&mut replica[start..start + NODE_SIZE])
+        })?;
+
+        let tree_d_config = Some(config.clone());
+        finish_replication(pp, tree_d, tree_d_config, &replica, config, replica_path)
+    }
+
+    /// Checks the encoding of a single node, the vanilla analog of the encoding constraint of
+    /// the circuit: derives the key of `node_index` from `replica_id` and the replica values of
+    /// its `parents`, and checks that encoding `data_node` with it gives `replica_node`.
+    ///
+    /// `parents` holds the index and replica value of every parent, in the order returned by
+    /// `Graph::parents`. Returns false if they are not the parents of `node_index`.
+    pub fn verify_node_encoding(
+        pp: &PublicParams<H, G>,
+        replica_id: &H::Domain,
+        node_index: usize,
+        replica_node: &H::Domain,
+        parents: &[(u32, H::Domain)],
+        data_node: &H::Domain,
+    ) -> Result<bool> {
+        let graph = &pp.graph;
+        ensure!(
+            node_index < graph.size(),
+            Error::OutOfBounds(node_index, graph.size())
+        );
+
+        let mut expected_parents = vec![0; graph.degree()];
+        graph.parents(node_index, &mut expected_parents)?;
+        if parents.len() != expected_parents.len()
+            || parents
+                .iter()
+                .zip(&expected_parents)
+                .any(|((actual, _), expected)| actual != expected)
+        {
+            return Ok(false);
+        }
+
+        // The key only depends on the values of the parents, in order, so they are laid out back
+        // to back and addressed by their position. A node without parents is its own first
+        // parent, which `create_key` detects from the indices, so those are kept as they are.
+        let parents_data: Vec<u8> = parents
+            .iter()
+            .flat_map(|(_, parent)| parent.into_bytes())
+            .collect();
+        let positions: Vec<u32> = if node_index == expected_parents[0] as usize {
+            expected_parents
+        } else {
+            (0..parents.len() as u32).collect()
+        };
+
+        let key = graph.create_key(replica_id, node_index, &positions, &parents_data, None)?;
+        let encoded = pp.encoding_op.encode::<H>(key.as_ref(), data_node)?;
+
+        Ok(encoded == *replica_node)
+    }
+}
+
+/// Proves a single challenge, returning the proofs of the replica node, its parents and the data
+/// node. Replica nodes are taken from `remote_nodes` if set, see
+/// `PrivateInputs::fetch_remote_nodes`.
+#[cfg(not(feature = "verify-only"))]
+#[allow(clippy::type_complexity)]
+fn prove_challenge<H, G>(
+    pub_params: &PublicParams<H, G>,
+    pub_inputs: &PublicInputs<H::Domain>,
+    priv_inputs: &PrivateInputs<'_, H>,
+    remote_nodes: Option<&BTreeMap<usize, H::Domain>>,
+    challenge: usize,
+) -> Result<(
+    DataProof<H, typenum::U2>,
+    ReplicaParents<H>,
+    DataProof<H, typenum::U2>,
+)>
+where
+    H: Hasher,
+    G: Graph<H> + ParameterSetMetadata,
+{
+    let challenge = challenge % pub_params.graph.size();
+    ensure!(challenge != 0, "cannot prove the first node");
+
+    let tree_d = &priv_inputs.tree_d;
+    let tree_r = &priv_inputs.tree_r;
+    let tree_r_config_levels = priv_inputs.tree_r_config_levels;
+
+    let read_replica_node = |node: usize| match remote_nodes {
+        Some(nodes) => nodes
+            .get(&node)
+            .copied()
+            .with_context(|| format!("replica node {} was not fetched", node)),
+        None => priv_inputs.read_replica_node(node),
+    };
+
+    let data = read_replica_node(challenge)?;
+
+    let tree_proof = {
+        if tree_r_config_levels == 0 {
+            tree_r.gen_proof(challenge)
+        } else {
+            tree_r.gen_cached_proof(challenge, tree_r_config_levels)
+        }
+    }?;
+    let replica_node = DataProof {
+        proof: MerkleProof::new_from_proof(&tree_proof),
+        data,
+    };
+
+    let mut parents = vec![0; pub_params.graph.degree()];
+    pub_params.graph.parents(challenge, &mut parents)?;
+    let mut replica_parentsi = Vec::with_capacity(parents.len());
+
+    for p in &parents {
+        replica_parentsi.push((*p, {
+            let proof = {
+                if tree_r_config_levels == 0 {
+                    tree_r.gen_proof(*p as usize)
+                } else {
+                    tree_r.gen_cached_proof(*p as usize, tree_r_config_levels)
+                }
+            }?;
+            DataProof {
+                proof: MerkleProof::new_from_proof(&proof),
+                data: read_replica_node(*p as usize)?,
+            }
+        }));
+    }
+
+    let node_proof = tree_d.gen_proof(challenge)?;
+
+    {
+        // TODO: use this again, I can't make lifetimes work though atm and I do not know why
+        // let extracted = Self::extract(
+        //     pub_params,
+        //     &pub_inputs.replica_id.into_bytes(),
+        //     &replica,
+        //     challenge,
+        // )?;
+
+        let replica_id = pub_inputs.replica_id.context("missing replica_id")?;
+        let op = pub_params.encoding_op;
+        let extracted = match priv_inputs.replica {
+            Some(ref replica) => {
+                decode_laid_out_block::<H>(&replica_id, replica, challenge, data, &parents, op)?
+            }
+            None => decode_domain_block::<H>(&replica_id, tree_r, challenge, data, &parents, op)?,
+        };
+        let data_node = DataProof {
+            data: extracted,
+            proof: MerkleProof::new_from_proof(&node_proof),
+        };
+
+        Ok((replica_node, replica_parentsi, data_node))
+    }
+}
+
+/// Checks, before proving, that every challenged node and its parents can be read from
+/// `replica`, e.g. to catch a truncated replica up front. Challenges must be node indices. The
+/// error names the first node which cannot be read, and the challenge it is needed for. The
+/// nodes of a remote replica are fetched in coalesced ranges, see `read_remote_nodes`, and only
+/// read one by one to locate a failure.
+pub fn validate_challenges<H, G>(
+    pub_params: &PublicParams<H, G>,
+    replica: &ReplicaSource<'_>,
+    challenges: &[usize],
+) -> Result<()>
+where
+    H: Hasher,
+    G: Graph<H> + ParameterSetMetadata,
+{
+    let graph = &pub_params.graph;
+    let mut parents = vec![0; graph.degree()];
+
+    let mut nodes = Vec::with_capacity(challenges.len() * (graph.degree() + 1));
+    for &challenge in challenges {
+        ensure!(
+            challenge < graph.size(),
+            "challenge {} is out of range for {} nodes",
+            challenge,
+            graph.size()
+        );
+        graph.parents(challenge, &mut parents)?;
+
+        nodes.push((challenge, challenge));
+        nodes.extend(parents.iter().map(|parent| (challenge, *parent as usize)));
+    }
+
+    let read_node = |node: usize| -> Result<H::Domain> {
+        match replica {
+            ReplicaSource::Local(replica) => {
+                replica.read_node(node).and_then(H::Domain::try_from_bytes)
+            }
+            ReplicaSource::Remote(reader) => read_remote_nodes::<H>(reader.as_ref(), &[node])?
+                .remove(&node)
+                .context("remote read returned no data"),
+        }
+    };
+
+    if let ReplicaSource::Remote(reader) = replica {
+        let all_nodes: Vec<usize> = nodes.iter().map(|(_, node)| *node).collect();
+        if read_remote_nodes::<H>(reader.as_ref(), &all_nodes).is_ok() {
+            return Ok(());
+        }
+    }
+
+    for (challenge, node) in nodes {
+        read_node(node).with_context(|| {
+            format!(
+                "challenge {}: replica node {} is not readable",
+                challenge, node
+            )
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Checks that there is space for the replica and the trees built during replication, where
+/// tree_d is only built if `build_tree_d` is set.
+fn check_replication_space<H, G>(
+    pp: &PublicParams<H, G>,
+    config: &StoreConfig,
+    replica_path: &Path,
+    build_tree_d: bool,
+) -> Result<()>
+where
+    H: Hasher,
+    G: Graph<H> + ParameterSetMetadata,
+{
+    let mut tree_bytes = config.required_bytes(pp.graph.size(), BINARY_ARITY)?;
+    if build_tree_d {
+        tree_bytes *= 2;
     }
+    check_available_space(&config.path, tree_bytes)?;
+    if let Some(replica_dir) = replica_path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+    {
+        check_available_space(replica_dir, pp.graph.expected_size() as u64)?;
+    }
+
+    Ok(())
+}
+
+/// Writes the encoded `replica` to `replica_path` and builds tree_r over it. `tree_d_config` is
+/// the store of `tree_d`, if it was built during replication.
+fn finish_replication<H, G>(
+    pp: &PublicParams<H, G>,
+    tree_d: BinaryMerkleTree<H::Domain, H::Function>,
+    tree_d_config: Option<StoreConfig>,
+    replica: &[u8],
+    config: StoreConfig,
+    replica_path: PathBuf,
+) -> Result<(Tau<H::Domain>, ProverAux<H>)>
+where
+    H: Hasher,
+    G: Graph<H> + ParameterSetMetadata,
+{
+    use crate::cache_key::CacheKey;
+
+    use std::io::prelude::*;
+
+    // Write out the encoded data here.
+    let mut f = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .open(&replica_path)?;
+    f.write_all(replica)?;
+
+    let tree_r_last_config =
+        StoreConfig::from_config(&config, CacheKey::CommRLastTree.to_string(), None);
+    let tree_r: BinaryLCMerkleTree<_, _> =
+        pp.graph
+            .lcmerkle_tree(tree_r_last_config.clone(), replica, &replica_path)?;
+
+    let comm_d = tree_d.root();
+    let comm_r = tree_r.root();
+
+    let mut aux = ProverAux::new(tree_d, tree_r);
+    aux.stores = tree_d_config.map(|tree_d_config| AuxStores {
+        tree_d_config,
+        tree_r_config: tree_r_last_config,
+        replica_path,
+    });
+
+    Ok((Tau::new(comm_d, comm_r), aux))
 }
 
 pub fn decode<'a, H, G>(
@@ -559,23 +1212,20 @@ pub fn decode<'a, H, G>(
     replica_id: &'a H::Domain,
     data: &'a [u8],
     exp_parents_data: Option<&'a [u8]>,
+    encoding_op: EncodingOp,
 ) -> Result<Vec<u8>>
 where
     H: Hasher,
     G::Key: AsRef<H::Domain>,
     G: Graph<H> + Sync,
 {
-    // TODO: proper error handling
-    let result = (0..graph.size())
+    // Decoding with `EncodingOp::Xor` fails for nodes which are not fr32 padded.
+    let blocks = (0..graph.size())
         .into_par_iter()
-        .flat_map(|i| {
-            decode_block(graph, replica_id, data, exp_parents_data, i)
-                .unwrap()
-                .into_bytes()
-        })
-        .collect();
+        .map(|i| decode_block(graph, replica_id, data, exp_parents_data, i, encoding_op))
+        .collect::<Result<Vec<_>>>()?;
 
-    Ok(result)
+    Ok(blocks.iter().flat_map(|block| block.into_bytes()).collect())
 }
 
 pub fn decode_block<'a, H, G>(
@@ -584,6 +1234,7 @@ pub fn decode_block<'a, H, G>(
     data: &'a [u8],
     exp_parents_data: Option<&'a [u8]>,
     v: usize,
+    encoding_op: EncodingOp,
 ) -> Result<H::Domain>
 where
     H: Hasher,
@@ -595,7 +1246,7 @@ where
     let key = graph.create_key(replica_id, v, &parents, &data, exp_parents_data)?;
     let node_data = H::Domain::try_from_bytes(&data_at_node(data, v)?)?;
 
-    Ok(encode::decode(*key.as_ref(), node_data))
+    encoding_op.decode::<H>(key.as_ref(), &node_data)
 }
 
 pub fn decode_domain_block<H>(
@@ -604,25 +1255,64 @@ pub fn decode_domain_block<H>(
     node: usize,
     node_data: <H as Hasher>::Domain,
     parents: &[u32],
+    encoding_op: EncodingOp,
 ) -> Result<H::Domain>
 where
     H: Hasher,
 {
     let key = create_key_from_tree::<H, _>(replica_id, node, parents, tree)?;
 
-    Ok(encode::decode(key, node_data))
+    encoding_op.decode::<H>(&key, &node_data)
 }
 
-/// Creates the encoding key from a `MerkleTree`.
-/// The algorithm for that is `Blake2s(id | encodedParentNode1 | encodedParentNode1 | ...)`.
-/// It is only public so that it can be used for benchmarking
-pub fn create_key_from_tree<H: Hasher, U: typenum::Unsigned>(
-    id: &H::Domain,
+pub fn decode_laid_out_block<H>(
+    replica_id: &H::Domain,
+    replica: &LaidOutReplica<'_>,
     node: usize,
+    node_data: <H as Hasher>::Domain,
     parents: &[u32],
-    tree: &LCMerkleTree<H::Domain, H::Function, U>,
-) -> Result<H::Domain> {
-    let mut hasher = Sha256::new();
+    encoding_op: EncodingOp,
+) -> Result<H::Domain>
+where
+    H: Hasher,
+{
+    let key = create_key_from_laid_out_replica::<H>(replica_id, node, parents, replica)?;
+
+    encoding_op.decode::<H>(&key, &node_data)
+}
+
+/// Creates the encoding key from the parents' data in a `LaidOutReplica`.
+/// The algorithm for that is `Sha256(id | encodedParentNode1 | encodedParentNode1 | ...)`.
+pub fn create_key_from_laid_out_replica<H: Hasher>(
+    id: &H::Domain,
+    node: usize,
+    parents: &[u32],
+    replica: &LaidOutReplica<'_>,
+) -> Result<H::Domain> {
+    let mut hasher = Sha256::new();
+    hasher.input(AsRef::<[u8]>::as_ref(id));
+
+    // The hash is about the parents, hence skip if a node doesn't have any parents
+    if node != parents[0] as usize {
+        for parent in parents.iter() {
+            hasher.input(replica.read_node(*parent as usize)?);
+        }
+    }
+
+    let hash = hasher.result();
+    Ok(bytes_into_fr_repr_safe(hash.as_ref()).into())
+}
+
+/// Creates the encoding key from a `MerkleTree`.
+/// The algorithm for that is `Blake2s(id | encodedParentNode1 | encodedParentNode1 | ...)`.
+/// It is only public so that it can be used for benchmarking
+pub fn create_key_from_tree<H: Hasher, U: typenum::Unsigned>(
+    id: &H::Domain,
+    node: usize,
+    parents: &[u32],
+    tree: &LCMerkleTree<H::Domain, H::Function, U>,
+) -> Result<H::Domain> {
+    let mut hasher = Sha256::new();
     hasher.input(AsRef::<[u8]>::as_ref(&id));
 
     // The hash is about the parents, hence skip if a node doesn't have any parents
@@ -656,14 +1346,17 @@ mod tests {
     use paired::bls12_381::{Bls12, Fr};
     use rand::SeedableRng;
     use rand_xorshift::XorShiftRng;
+    use std::collections::BTreeSet;
     use std::fs::File;
     use std::io::Write;
+    use std::sync::Arc;
     use tempfile;
 
     use crate::cache_key::CacheKey;
     use crate::drgraph::{new_seed, BucketGraph, BASE_DEGREE};
     use crate::fr32::fr_into_bytes;
     use crate::hasher::{Blake2sHasher, PedersenHasher, Sha256Hasher};
+    use crate::porep::drg::{Identity, InMemoryRangeReader, NodeLayout, TestReplica};
     use crate::porep::stacked::BINARY_ARITY;
     use crate::util::data_at_node;
 
@@ -758,6 +1451,167 @@ mod tests {
         test_extract_all::<Blake2sHasher>();
     }
 
+    fn test_xor_encoding<H: Hasher>() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let nodes = 8;
+        let replica_id: H::Domain = H::Domain::random(rng);
+        // fr32 padded nodes, as required by `EncodingOp::Xor`.
+        let data: Vec<u8> = (0..nodes * 32).map(|i| (i % 64) as u8).collect();
+
+        let sp = SetupParams {
+            drg: DrgParams {
+                nodes,
+                degree: BASE_DEGREE,
+                expansion_degree: 0,
+                seed: new_seed(),
+            },
+            private: false,
+            challenges_count: 2,
+        };
+        let add = DrgPoRep::<H, BucketGraph<H>>::setup(&sp).expect("setup failed");
+        let xor = add.clone().with_encoding_op(EncodingOp::Xor);
+        assert_ne!(add.identifier(), xor.identifier());
+
+        let add_replica = TestReplica::with_data(&add, replica_id, data.clone());
+        let xor_replica = TestReplica::with_data(&xor, replica_id, data.clone());
+
+        assert_eq!(add_replica.tau.comm_d, xor_replica.tau.comm_d);
+        assert_ne!(add_replica.tau.comm_r, xor_replica.tau.comm_r);
+        assert_ne!(add_replica.replica, xor_replica.replica);
+
+        let extracted =
+            DrgPoRep::extract_all(&xor, &replica_id, &xor_replica.replica, None).unwrap();
+        assert_eq!(extracted, data);
+
+        let pub_inputs = xor_replica.public_inputs(vec![2, 5]);
+        let priv_inputs = xor_replica.private_inputs();
+        let proof = DrgPoRep::prove(&xor, &pub_inputs, &priv_inputs).expect("proving failed");
+
+        assert!(DrgPoRep::verify(&xor, &pub_inputs, &proof).expect("verification failed"));
+        assert!(!DrgPoRep::verify(&add, &pub_inputs, &proof).expect("verification failed"));
+    }
+
+    #[test]
+    fn xor_encoding_pedersen() {
+        test_xor_encoding::<PedersenHasher>();
+    }
+
+    #[test]
+    fn xor_encoding_sha256() {
+        test_xor_encoding::<Sha256Hasher>();
+    }
+
+    /// Produces nodes on demand from a closure, standing in for e.g. decompression.
+    #[cfg(not(feature = "verify-only"))]
+    struct ClosureSource<F> {
+        nodes: usize,
+        read: F,
+    }
+
+    #[cfg(not(feature = "verify-only"))]
+    impl<H: Hasher, F: Fn(usize) -> Result<H::Domain>> DataSource<H> for ClosureSource<F> {
+        fn nodes(&self) -> usize {
+            self.nodes
+        }
+
+        fn read_node(&self, index: usize) -> Result<H::Domain> {
+            (self.read)(index)
+        }
+    }
+
+    #[cfg(not(feature = "verify-only"))]
+    fn test_replicate_from_source<H: Hasher>() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let replica_id: H::Domain = H::Domain::random(rng);
+        let nodes = 8;
+        let data: Vec<u8> = (0..nodes)
+            .flat_map(|_| fr_into_bytes::<Bls12>(&Fr::random(rng)))
+            .collect();
+
+        let sp = SetupParams {
+            drg: DrgParams {
+                nodes,
+                degree: BASE_DEGREE,
+                expansion_degree: 0,
+                seed: new_seed(),
+            },
+            private: false,
+            challenges_count: 1,
+        };
+        let pp = DrgPoRep::<H, BucketGraph<H>>::setup(&sp).expect("setup failed");
+
+        let replicate_into = |dir: &std::path::Path, source: Option<&dyn DataSource<H>>| {
+            let config = StoreConfig::new(
+                dir,
+                CacheKey::CommDTree.to_string(),
+                StoreConfig::default_cached_above_base_layer(nodes, BINARY_ARITY),
+            );
+            let replica_path = dir.join("replica-path");
+
+            let (tau, _) = match source {
+                Some(source) => DrgPoRep::replicate_from_source(
+                    &pp,
+                    &replica_id,
+                    source,
+                    config,
+                    replica_path.clone(),
+                ),
+                None => DrgPoRep::replicate(
+                    &pp,
+                    &replica_id,
+                    data.clone().as_mut_slice().into(),
+                    None,
+                    config,
+                    replica_path.clone(),
+                ),
+            }
+            .expect("replication failed");
+
+            (tau, std::fs::read(replica_path).unwrap())
+        };
+
+        let flat_dir = tempfile::tempdir().unwrap();
+        let (flat_tau, flat_replica) = replicate_into(flat_dir.path(), None);
+
+        let source = ClosureSource {
+            nodes,
+            read: |index| H::Domain::try_from_bytes(data_at_node(&data, index)?),
+        };
+        let source_dir = tempfile::tempdir().unwrap();
+        let (source_tau, source_replica) = replicate_into(source_dir.path(), Some(&source));
+
+        assert_eq!(source_tau.comm_d, flat_tau.comm_d);
+        assert_eq!(source_tau.comm_r, flat_tau.comm_r);
+        assert_eq!(source_replica, flat_replica);
+
+        let short = ClosureSource {
+            nodes: nodes - 1,
+            read: |index| H::Domain::try_from_bytes(data_at_node(&data, index)?),
+        };
+        assert!(DrgPoRep::replicate_from_source(
+            &pp,
+            &replica_id,
+            &short,
+            StoreConfig::new(source_dir.path(), CacheKey::CommDTree.to_string(), 0),
+            source_dir.path().join("short-replica-path"),
+        )
+        .is_err());
+    }
+
+    #[cfg(not(feature = "verify-only"))]
+    #[test]
+    fn replicate_from_source_pedersen() {
+        test_replicate_from_source::<PedersenHasher>();
+    }
+
+    #[cfg(not(feature = "verify-only"))]
+    #[test]
+    fn replicate_from_source_sha256() {
+        test_replicate_from_source::<Sha256Hasher>();
+    }
+
     fn test_extract<H: Hasher>() {
         let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
 
@@ -913,7 +1767,7 @@ mod tests {
 
             let pub_inputs = PublicInputs::<H::Domain> {
                 replica_id: Some(replica_id),
-                challenges: vec![challenge, challenge],
+                challenges: vec![challenge, challenge].into(),
                 tau: Some(tau.clone().into()),
             };
 
@@ -924,6 +1778,7 @@ mod tests {
                     nodes,
                     BINARY_ARITY,
                 ),
+                replica: None,
             };
 
             let real_proof =
@@ -1002,7 +1857,7 @@ mod tests {
             if use_wrong_challenge {
                 let pub_inputs_with_wrong_challenge_for_proof = PublicInputs::<H::Domain> {
                     replica_id: Some(replica_id),
-                    challenges: vec![if challenge == 1 { 2 } else { 1 }],
+                    challenges: vec![if challenge == 1 { 2 } else { 1 }].into(),
                     tau: Some(tau.into()),
                 };
                 let verified = DrgPoRep::<H, _>::verify(
@@ -1061,6 +1916,631 @@ mod tests {
         }
     }
 
+    fn serialized_size_matches<H: Hasher>() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let nodes = 8;
+        let sp = SetupParams {
+            drg: DrgParams {
+                nodes,
+                degree: BASE_DEGREE,
+                expansion_degree: 0,
+                seed: new_seed(),
+            },
+            private: false,
+            challenges_count: 2,
+        };
+
+        let pp = DrgPoRep::<H, BucketGraph<_>>::setup(&sp).expect("setup failed");
+
+        let replica = TestReplica::new(&pp, rng);
+        let pub_inputs = replica.public_inputs(vec![3, 5]);
+        let priv_inputs = replica.private_inputs();
+
+        let proof =
+            DrgPoRep::<H, _>::prove(&pp, &pub_inputs, &priv_inputs).expect("proving failed");
+
+        assert_eq!(proof.serialized_size(), proof.serialize().len());
+    }
+
+    fn prover_aux_persist_load<H: Hasher>() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let nodes = 8;
+        let sp = SetupParams {
+            drg: DrgParams {
+                nodes,
+                degree: BASE_DEGREE,
+                expansion_degree: 0,
+                seed: new_seed(),
+            },
+            private: false,
+            challenges_count: 2,
+        };
+
+        let pp = DrgPoRep::<H, BucketGraph<_>>::setup(&sp).expect("setup failed");
+
+        let replica = TestReplica::new(&pp, rng);
+
+        let aux_path = replica.cache_dir.path().join("prover-aux.json");
+        replica.aux.persist(&aux_path).expect("persist failed");
+        let loaded = ProverAux::<H>::load(&aux_path, &pp).expect("load failed");
+        assert_eq!(loaded.tree_d.root(), replica.tau.comm_d);
+        assert_eq!(loaded.tree_r.root(), replica.tau.comm_r);
+
+        let pub_inputs = replica.public_inputs(vec![3, 5]);
+        let prove = |aux: &ProverAux<H>| {
+            let priv_inputs = PrivateInputs::<H> {
+                tree_d: &aux.tree_d,
+                tree_r: &aux.tree_r,
+                tree_r_config_levels: replica.tree_r_config_levels,
+                replica: None,
+            };
+            DrgPoRep::<H, _>::prove(&pp, &pub_inputs, &priv_inputs).expect("proving failed")
+        };
+
+        let proof = prove(&loaded);
+        assert!(DrgPoRep::<H, _>::verify(&pp, &pub_inputs, &proof).expect("verification failed"));
+        assert_eq!(
+            serde_json::to_vec(&proof).unwrap(),
+            serde_json::to_vec(&prove(&replica.aux)).unwrap()
+        );
+    }
+
+    #[test]
+    fn prover_aux_persist_load_pedersen() {
+        prover_aux_persist_load::<PedersenHasher>();
+    }
+
+    #[test]
+    fn prover_aux_persist_load_sha256() {
+        prover_aux_persist_load::<Sha256Hasher>();
+    }
+
+    fn verify_node_encoding<H: Hasher>() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let nodes = 8;
+        let sp = SetupParams {
+            drg: DrgParams {
+                nodes,
+                degree: BASE_DEGREE,
+                expansion_degree: 0,
+                seed: new_seed(),
+            },
+            private: false,
+            challenges_count: 1,
+        };
+        let pp = DrgPoRep::<H, BucketGraph<_>>::setup(&sp).expect("setup failed");
+
+        let replica = TestReplica::new(&pp, rng);
+
+        let node_at = |bytes: &[u8], node: usize| {
+            H::Domain::try_from_bytes(data_at_node(bytes, node).unwrap()).unwrap()
+        };
+
+        for node in 0..nodes {
+            let mut parent_indices = vec![0; pp.graph.degree()];
+            pp.graph.parents(node, &mut parent_indices).unwrap();
+            let parents: Vec<_> = parent_indices
+                .iter()
+                .map(|p| (*p, node_at(&replica.replica, *p as usize)))
+                .collect();
+
+            let verify = |replica_node: &H::Domain, parents: &[(u32, H::Domain)]| {
+                DrgPoRep::<H, _>::verify_node_encoding(
+                    &pp,
+                    &replica.replica_id,
+                    node,
+                    replica_node,
+                    parents,
+                    &node_at(&replica.data, node),
+                )
+                .expect("verification failed")
+            };
+
+            let replica_node = node_at(&replica.replica, node);
+            assert!(verify(&replica_node, &parents), "node {}", node);
+
+            let tampered = H::Domain::random(rng);
+            assert!(!verify(&tampered, &parents), "tampered node {}", node);
+
+            let mut wrong_parents = parents.clone();
+            wrong_parents[0].0 = (node + 1) as u32;
+            assert!(!verify(&replica_node, &wrong_parents), "node {}", node);
+        }
+
+        assert!(DrgPoRep::<H, _>::verify_node_encoding(
+            &pp,
+            &replica.replica_id,
+            nodes,
+            &H::Domain::default(),
+            &[],
+            &H::Domain::default(),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn verify_node_encoding_pedersen() {
+        verify_node_encoding::<PedersenHasher>();
+    }
+
+    #[test]
+    fn verify_node_encoding_sha256() {
+        verify_node_encoding::<Sha256Hasher>();
+    }
+
+    fn prove_parallel_matches_sequential<H: Hasher>() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let nodes = 32;
+        let sp = SetupParams {
+            drg: DrgParams {
+                nodes,
+                degree: BASE_DEGREE,
+                expansion_degree: 0,
+                seed: new_seed(),
+            },
+            private: false,
+            challenges_count: 8,
+        };
+
+        let pp = DrgPoRep::<H, BucketGraph<_>>::setup(&sp).expect("setup failed");
+
+        let replica = TestReplica::new(&pp, rng);
+        let pub_inputs = replica.public_inputs(vec![31, 3, 17, 5, 3, 22, 9, 30]);
+        let priv_inputs = replica.private_inputs();
+
+        let proof =
+            DrgPoRep::<H, _>::prove(&pp, &pub_inputs, &priv_inputs).expect("proving failed");
+
+        let mut replica_nodes = Vec::new();
+        let mut replica_parents = Vec::new();
+        let mut data_nodes = Vec::new();
+        for challenge in pub_inputs.challenges.iter() {
+            let (replica_node, replica_parentsi, data_node) =
+                prove_challenge(&pp, &pub_inputs, &priv_inputs, None, challenge)
+                    .expect("proving failed");
+            replica_nodes.push(replica_node);
+            replica_parents.push(replica_parentsi);
+            data_nodes.push(data_node);
+        }
+        let sequential_proof = Proof::new(replica_nodes, replica_parents, data_nodes);
+
+        assert_eq!(proof.serialize(), sequential_proof.serialize());
+        assert_eq!(
+            serde_json::to_vec(&proof).unwrap(),
+            serde_json::to_vec(&sequential_proof).unwrap()
+        );
+    }
+
+    #[test]
+    fn prove_parallel_matches_sequential_pedersen() {
+        prove_parallel_matches_sequential::<PedersenHasher>();
+    }
+
+    #[test]
+    fn prove_parallel_matches_sequential_sha256() {
+        prove_parallel_matches_sequential::<Sha256Hasher>();
+    }
+
+    fn prove_bitmap_challenges<H: Hasher>() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let nodes = 32;
+        let sp = SetupParams {
+            drg: DrgParams {
+                nodes,
+                degree: BASE_DEGREE,
+                expansion_degree: 0,
+                seed: new_seed(),
+            },
+            private: false,
+            challenges_count: 3,
+        };
+
+        let pp = DrgPoRep::<H, BucketGraph<_>>::setup(&sp).expect("setup failed");
+
+        let replica = TestReplica::new(&pp, rng);
+
+        let priv_inputs = replica.private_inputs();
+
+        let indices = replica.public_inputs(ChallengeSet::Indices(vec![3, 5, 17]));
+        let bitmap = replica.public_inputs(ChallengeSet::bitmap(nodes, &[17, 3, 5]).unwrap());
+
+        let indices_proof =
+            DrgPoRep::<H, _>::prove(&pp, &indices, &priv_inputs).expect("proving failed");
+        let bitmap_proof =
+            DrgPoRep::<H, _>::prove(&pp, &bitmap, &priv_inputs).expect("proving failed");
+
+        assert_eq!(indices_proof.serialize(), bitmap_proof.serialize());
+        assert!(DrgPoRep::<H, _>::verify(&pp, &bitmap, &bitmap_proof).expect("verification failed"));
+        assert!(
+            DrgPoRep::<H, _>::verify(&pp, &indices, &bitmap_proof).expect("verification failed")
+        );
+    }
+
+    #[test]
+    fn prove_bitmap_challenges_pedersen() {
+        prove_bitmap_challenges::<PedersenHasher>();
+    }
+
+    #[test]
+    fn prove_bitmap_challenges_sha256() {
+        prove_bitmap_challenges::<Sha256Hasher>();
+    }
+
+    /// Stores the nodes of a replica in reverse order.
+    #[derive(Debug)]
+    struct Reversed {
+        nodes: usize,
+    }
+
+    impl NodeLayout for Reversed {
+        fn disk_offset(&self, node_index: usize) -> u64 {
+            data_at_node_offset(self.nodes - 1 - node_index) as u64
+        }
+    }
+
+    fn prove_with_reversed_layout<H: Hasher>() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let nodes = 8;
+        let sp = SetupParams {
+            drg: DrgParams {
+                nodes,
+                degree: BASE_DEGREE,
+                expansion_degree: 0,
+                seed: new_seed(),
+            },
+            private: false,
+            challenges_count: 2,
+        };
+
+        let pp = DrgPoRep::<H, BucketGraph<_>>::setup(&sp).expect("setup failed");
+
+        let replica = TestReplica::new(&pp, rng);
+
+        let reversed_replica: Vec<u8> = (0..nodes)
+            .rev()
+            .flat_map(|i| data_at_node(&replica.replica, i).unwrap().to_vec())
+            .collect();
+        let layout = Reversed { nodes };
+
+        let pub_inputs = replica.public_inputs(vec![3, 5]);
+
+        let identity_inputs = replica.private_inputs();
+        let reversed_inputs = PrivateInputs::<H> {
+            replica: Some(ReplicaSource::Local(LaidOutReplica::new(
+                &reversed_replica,
+                &layout,
+            ))),
+            ..replica.private_inputs()
+        };
+
+        let identity_proof =
+            DrgPoRep::<H, _>::prove(&pp, &pub_inputs, &identity_inputs).expect("proving failed");
+        let reversed_proof =
+            DrgPoRep::<H, _>::prove(&pp, &pub_inputs, &reversed_inputs).expect("proving failed");
+
+        assert_eq!(identity_proof.serialize(), reversed_proof.serialize());
+        assert!(DrgPoRep::<H, _>::verify(&pp, &pub_inputs, &reversed_proof)
+            .expect("verification failed"));
+    }
+
+    #[test]
+    fn prove_with_reversed_layout_pedersen() {
+        prove_with_reversed_layout::<PedersenHasher>();
+    }
+
+    #[test]
+    fn prove_with_reversed_layout_sha256() {
+        prove_with_reversed_layout::<Sha256Hasher>();
+    }
+
+    fn validate_challenges_truncated<H: Hasher>() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let nodes = 8;
+        let sp = SetupParams {
+            drg: DrgParams {
+                nodes,
+                degree: BASE_DEGREE,
+                expansion_degree: 0,
+                seed: new_seed(),
+            },
+            private: false,
+            challenges_count: 2,
+        };
+        let pp = DrgPoRep::<H, BucketGraph<_>>::setup(&sp).expect("setup failed");
+
+        let replica: Vec<u8> = (0..nodes)
+            .flat_map(|_| fr_into_bytes::<Bls12>(&Fr::random(rng)))
+            .collect();
+        fn local(data: &[u8]) -> ReplicaSource<'_> {
+            ReplicaSource::Local(LaidOutReplica::new(data, &Identity))
+        }
+
+        validate_challenges(&pp, &local(&replica), &[3, 7]).unwrap();
+
+        // The last node of a truncated replica is missing.
+        let truncated = &replica[..(nodes - 1) * NODE_SIZE];
+        validate_challenges(&pp, &local(truncated), &[3, 6]).unwrap();
+        let err = validate_challenges(&pp, &local(truncated), &[3, 7]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "challenge 7: replica node 7 is not readable"
+        );
+
+        // In reverse order, truncating the replica drops the lowest nodes instead, so that the
+        // smallest parent of the challenge is missing while the challenge itself is readable.
+        let challenge = 6;
+        let mut parents = vec![0; pp.graph.degree()];
+        pp.graph.parents(challenge, &mut parents).unwrap();
+        let parent = *parents.iter().min().unwrap() as usize;
+        assert!(parent < challenge);
+
+        let layout = Reversed { nodes };
+        let reversed_truncated = &replica[..(nodes - 1 - parent) * NODE_SIZE];
+        let err = validate_challenges(
+            &pp,
+            &ReplicaSource::Local(LaidOutReplica::new(reversed_truncated, &layout)),
+            &[challenge],
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "challenge {}: replica node {} is not readable",
+                challenge, parent
+            )
+        );
+
+        let remote = ReplicaSource::Remote(Box::new(InMemoryRangeReader::new(truncated.to_vec())));
+        validate_challenges(&pp, &remote, &[3, 6]).unwrap();
+        let err = validate_challenges(&pp, &remote, &[3, 7]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "challenge 7: replica node 7 is not readable"
+        );
+
+        let err = validate_challenges(&pp, &local(&replica), &[nodes]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("challenge {} is out of range for {} nodes", nodes, nodes)
+        );
+    }
+
+    #[test]
+    fn validate_challenges_truncated_pedersen() {
+        validate_challenges_truncated::<PedersenHasher>();
+    }
+
+    #[test]
+    fn validate_challenges_truncated_sha256() {
+        validate_challenges_truncated::<Sha256Hasher>();
+    }
+
+    fn prove_with_remote_replica<H: Hasher>() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let nodes = 16;
+        let sp = SetupParams {
+            drg: DrgParams {
+                nodes,
+                degree: BASE_DEGREE,
+                expansion_degree: 0,
+                seed: new_seed(),
+            },
+            private: false,
+            challenges_count: 4,
+        };
+
+        let pp = DrgPoRep::<H, BucketGraph<_>>::setup(&sp).expect("setup failed");
+
+        let replica = TestReplica::new(&pp, rng);
+
+        let challenges = vec![3, 4, 5, 12];
+        let pub_inputs = replica.public_inputs(challenges.clone());
+
+        let local_inputs = replica.private_inputs();
+        let reader = InMemoryRangeReader::new(replica.replica.clone());
+        let reads = Arc::clone(&reader.reads);
+        let remote_inputs = PrivateInputs::<H> {
+            replica: Some(ReplicaSource::Remote(Box::new(reader))),
+            ..replica.private_inputs()
+        };
+
+        let local_proof =
+            DrgPoRep::<H, _>::prove(&pp, &pub_inputs, &local_inputs).expect("proving failed");
+        let remote_proof =
+            DrgPoRep::<H, _>::prove(&pp, &pub_inputs, &remote_inputs).expect("proving failed");
+
+        assert_eq!(local_proof.serialize(), remote_proof.serialize());
+        assert!(
+            DrgPoRep::<H, _>::verify(&pp, &pub_inputs, &remote_proof).expect("verification failed")
+        );
+
+        // Every needed node is fetched exactly once, and the adjacent challenges 3, 4 and 5 are
+        // fetched together.
+        let mut needed = BTreeSet::new();
+        let mut parents = vec![0; pp.graph.degree()];
+        for challenge in &challenges {
+            pp.graph.parents(*challenge, &mut parents).unwrap();
+            needed.insert(*challenge);
+            needed.extend(parents.iter().map(|parent| *parent as usize));
+        }
+        let reads = reads.lock().unwrap();
+        assert_eq!(
+            reads.iter().map(|(_, len)| len).sum::<usize>(),
+            needed.len() * NODE_SIZE
+        );
+        assert!(reads.len() < needed.len(), "{:?}", reads);
+        assert!(
+            reads
+                .iter()
+                .any(|(offset, len)| *offset <= 3 * NODE_SIZE as u64
+                    && *offset as usize + len >= 6 * NODE_SIZE),
+            "{:?}",
+            reads
+        );
+    }
+
+    #[test]
+    fn prove_with_remote_replica_pedersen() {
+        prove_with_remote_replica::<PedersenHasher>();
+    }
+
+    #[test]
+    fn prove_with_remote_replica_sha256() {
+        prove_with_remote_replica::<Sha256Hasher>();
+    }
+
+    #[test]
+    fn serialized_size_matches_pedersen() {
+        serialized_size_matches::<PedersenHasher>();
+    }
+
+    #[test]
+    fn serialized_size_matches_sha256() {
+        serialized_size_matches::<Sha256Hasher>();
+    }
+
+    fn check_requirements_replica_id<H: Hasher>() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let replica_id: H::Domain = H::Domain::random(rng);
+        let other_replica_id: H::Domain = H::Domain::random(rng);
+        assert_ne!(replica_id, other_replica_id);
+
+        let pub_inputs = PublicInputs::<H::Domain> {
+            replica_id: Some(replica_id),
+            challenges: vec![1].into(),
+            tau: None,
+        };
+
+        DrgPoRep::<H, BucketGraph<H>>::check_requirements(&pub_inputs, &Requirements::default())
+            .expect("no expected replica_id must always pass");
+
+        let matching = Requirements {
+            expected_replica_id: Some(replica_id),
+            ..Default::default()
+        };
+        DrgPoRep::<H, BucketGraph<H>>::check_requirements(&pub_inputs, &matching)
+            .expect("matching replica_id must pass");
+
+        let mismatched = Requirements {
+            expected_replica_id: Some(other_replica_id),
+            ..Default::default()
+        };
+        assert!(
+            DrgPoRep::<H, BucketGraph<H>>::check_requirements(&pub_inputs, &mismatched).is_err(),
+            "mismatched replica_id must be rejected"
+        );
+    }
+
+    #[test]
+    fn check_requirements_replica_id_pedersen() {
+        check_requirements_replica_id::<PedersenHasher>();
+    }
+
+    #[test]
+    fn check_requirements_replica_id_sha256() {
+        check_requirements_replica_id::<Sha256Hasher>();
+    }
+
+    fn check_requirements_minimum_challenges<H: Hasher>() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let pub_inputs = PublicInputs::<H::Domain> {
+            replica_id: Some(H::Domain::random(rng)),
+            challenges: vec![1, 2].into(),
+            tau: None,
+        };
+
+        DrgPoRep::<H, BucketGraph<H>>::check_requirements(&pub_inputs, &Requirements::default())
+            .expect("default requirements must not enforce a minimum");
+
+        let enough = Requirements {
+            minimum_challenges: 2,
+            ..Default::default()
+        };
+        DrgPoRep::<H, BucketGraph<H>>::check_requirements(&pub_inputs, &enough)
+            .expect("enough challenges must pass");
+
+        let too_many = Requirements {
+            minimum_challenges: 3,
+            ..Default::default()
+        };
+        assert!(
+            DrgPoRep::<H, BucketGraph<H>>::check_requirements(&pub_inputs, &too_many).is_err(),
+            "too few challenges must be rejected"
+        );
+    }
+
+    #[test]
+    fn check_requirements_minimum_challenges_pedersen() {
+        check_requirements_minimum_challenges::<PedersenHasher>();
+    }
+
+    #[test]
+    fn check_requirements_minimum_challenges_sha256() {
+        check_requirements_minimum_challenges::<Sha256Hasher>();
+    }
+
+    #[test]
+    fn public_params_diff() {
+        let seed = new_seed();
+        let sp = |challenges_count| SetupParams {
+            drg: DrgParams {
+                nodes: 8,
+                degree: BASE_DEGREE,
+                expansion_degree: 0,
+                seed,
+            },
+            private: false,
+            challenges_count,
+        };
+
+        let pp = DrgPoRep::<PedersenHasher, BucketGraph<_>>::setup(&sp(2)).unwrap();
+        let same = DrgPoRep::<PedersenHasher, BucketGraph<_>>::setup(&sp(2)).unwrap();
+        let other = DrgPoRep::<PedersenHasher, BucketGraph<_>>::setup(&sp(3)).unwrap();
+
+        assert!(pp.diff(&same).is_empty());
+
+        let diffs = pp.diff(&other);
+        assert_eq!(
+            diffs,
+            vec![ParamDiff {
+                field: "challenges_count",
+                left: "2".into(),
+                right: "3".into(),
+            }]
+        );
+        assert_eq!(diffs[0].to_string(), "challenges_count: 2 != 3");
+    }
+
+    #[test]
+    fn public_inputs_new_checks_private_state() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let replica_id = Some(<PedersenHasher as Hasher>::Domain::random(rng));
+        let tau = Some(Tau::new(Domain::random(rng), Domain::random(rng)));
+
+        let public = PublicInputs::new(replica_id, vec![1].into(), tau, false).unwrap();
+        assert_eq!(public.challenges, ChallengeSet::from(vec![1]));
+        assert!(PublicInputs::new(replica_id, vec![1].into(), None, true).is_ok());
+
+        for (tau, private) in &[(tau, true), (None, false)] {
+            let err = PublicInputs::new(replica_id, vec![1].into(), *tau, *private).unwrap_err();
+            assert!(
+                err.to_string().contains("inconsistent private state"),
+                "{}",
+                err
+            );
+        }
+    }
+
     #[test]
     fn test_drgporep_verifies_using_challenge() {
         prove_verify_wrong_challenge(8, 1);
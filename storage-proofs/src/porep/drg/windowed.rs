@@ -0,0 +1,507 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::marker::PhantomData;
+use std::path::PathBuf;
+
+use anyhow::ensure;
+use merkletree::store::StoreConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::drgraph::Graph;
+use crate::error::Result;
+use crate::hasher::{Domain, HashFunction, Hasher};
+use crate::merkle::BinaryMerkleTree;
+use crate::parameter_cache::ParameterSetMetadata;
+use crate::porep::PoRep;
+use crate::proof::ProofScheme;
+use crate::util::NODE_SIZE;
+use crate::Data;
+
+use super::vanilla::{
+    decode, decode_block, DrgPoRep, PrivateInputs, Proof, ProverAux, PublicInputs, PublicParams,
+    Requirements, SetupParams, Tau,
+};
+
+/// A `DrgPoRep` over a sector that is split into equally sized windows.
+///
+/// Every window is replicated and proven as an independent DRG PoRep, using a replica id derived
+/// from the sector replica id and the window index (see `window_replica_id`). Because the window
+/// index is bound into the replica id, the public inputs of each per-window proof, and therefore
+/// the existing DRG circuit, reference the window they belong to. The per-window commitments are
+/// bound together by the roots of Merkle trees over the window `comm_d`s and `comm_r`s.
+#[derive(Debug, Default)]
+pub struct WindowedDrgPoRep<'a, H, G>
+where
+    H: 'a + Hasher,
+    G: 'a + Graph<H>,
+{
+    _h: PhantomData<&'a H>,
+    _g: PhantomData<G>,
+}
+
+#[derive(Debug, Clone)]
+pub struct WindowedSetupParams {
+    /// Parameters of a single window.
+    pub window: SetupParams,
+    /// Number of windows, a power of two no smaller than 2.
+    pub windows: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct WindowedPublicParams<H, G>
+where
+    H: Hasher,
+    G: Graph<H> + ParameterSetMetadata,
+{
+    pub window: PublicParams<H, G>,
+    pub windows: usize,
+}
+
+impl<H, G> WindowedPublicParams<H, G>
+where
+    H: Hasher,
+    G: Graph<H> + ParameterSetMetadata,
+{
+    /// Number of nodes in a single window.
+    pub fn window_nodes(&self) -> usize {
+        self.window.graph.size()
+    }
+
+    /// Number of bytes in a single window.
+    pub fn window_bytes(&self) -> usize {
+        self.window_nodes() * NODE_SIZE
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowedTau<T> {
+    /// Root of the tree over the `comm_d` of all windows.
+    pub comm_d: T,
+    /// Root of the tree over the `comm_r` of all windows.
+    pub comm_r: T,
+    pub windows: Vec<Tau<T>>,
+}
+
+impl<T: Domain> WindowedTau<T> {
+    /// Builds the combined commitments over the given per-window taus.
+    pub fn new<H: Hasher<Domain = T>>(windows: Vec<Tau<T>>) -> Result<Self> {
+        let comm_d = combine_window_commitments::<H>(windows.iter().map(|tau| tau.comm_d))?;
+        let comm_r = combine_window_commitments::<H>(windows.iter().map(|tau| tau.comm_r))?;
+
+        Ok(WindowedTau {
+            comm_d,
+            comm_r,
+            windows,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WindowedPublicInputs<T: Domain> {
+    pub replica_id: T,
+    /// Challenged nodes, relative to the start of each window. Every window is challenged at the
+    /// same nodes.
+    pub challenges: Vec<usize>,
+    pub tau: WindowedTau<T>,
+}
+
+#[derive(Debug)]
+pub struct WindowedPrivateInputs<'a, H: 'a + Hasher> {
+    pub windows: Vec<PrivateInputs<'a, H>>,
+}
+
+/// Derives the replica id of window `window` from the sector replica id.
+pub fn window_replica_id<H: Hasher>(replica_id: &H::Domain, window: usize) -> H::Domain {
+    let mut to_hash = [0; 64];
+    to_hash[..32].copy_from_slice(AsRef::<[u8]>::as_ref(replica_id));
+    to_hash[32..40].copy_from_slice(&(window as u64).to_le_bytes());
+
+    H::Function::hash_leaf(&to_hash)
+}
+
+/// Returns the root of a binary Merkle tree with the given commitments as leaves.
+pub fn combine_window_commitments<H: Hasher>(
+    comms: impl Iterator<Item = H::Domain>,
+) -> Result<H::Domain> {
+    let tree = BinaryMerkleTree::<H::Domain, H::Function>::try_from_iter(comms.map(Ok))?;
+
+    Ok(tree.root())
+}
+
+impl<'a, H, G> WindowedDrgPoRep<'a, H, G>
+where
+    H: 'a + Hasher,
+    G: 'a + Graph<H> + ParameterSetMetadata,
+{
+    /// Returns the public inputs of the single-window DRG proof of window `window`.
+    pub fn window_public_inputs(
+        pub_inputs: &WindowedPublicInputs<H::Domain>,
+        window: usize,
+    ) -> Result<PublicInputs<H::Domain>> {
+        ensure!(
+            window < pub_inputs.tau.windows.len(),
+            "window {} out of range {}",
+            window,
+            pub_inputs.tau.windows.len()
+        );
+
+        Ok(PublicInputs {
+            replica_id: Some(window_replica_id::<H>(&pub_inputs.replica_id, window)),
+            challenges: pub_inputs.challenges.clone().into(),
+            tau: Some(pub_inputs.tau.windows[window]),
+        })
+    }
+}
+
+/// Returns true if every Merkle proof in `proof` is against the roots in `tau`.
+fn proof_matches_tau<H: Hasher>(proof: &Proof<H>, tau: &Tau<H::Domain>) -> bool {
+    let replica_roots = proof
+        .replica_nodes
+        .iter()
+        .chain(proof.replica_parents.iter().flatten().map(|(_, p)| p))
+        .all(|p| *p.proof.root() == tau.comm_r);
+    let data_roots = proof.nodes.iter().all(|p| *p.proof.root() == tau.comm_d);
+
+    replica_roots && data_roots
+}
+
+impl<'a, H, G> ProofScheme<'a> for WindowedDrgPoRep<'a, H, G>
+where
+    H: 'a + Hasher,
+    G: 'a + Graph<H> + ParameterSetMetadata + Sync,
+{
+    type PublicParams = WindowedPublicParams<H, G>;
+    type SetupParams = WindowedSetupParams;
+    type PublicInputs = WindowedPublicInputs<H::Domain>;
+    type PrivateInputs = WindowedPrivateInputs<'a, H>;
+    type Proof = Vec<Proof<H>>;
+    type Requirements = Requirements<H::Domain>;
+
+    fn setup(sp: &Self::SetupParams) -> Result<Self::PublicParams> {
+        ensure!(
+            sp.windows >= 2 && sp.windows.is_power_of_two(),
+            "number of windows must be a power of two no smaller than 2, got {}",
+            sp.windows
+        );
+
+        Ok(WindowedPublicParams {
+            window: DrgPoRep::<H, G>::setup(&sp.window)?,
+            windows: sp.windows,
+        })
+    }
+
+    #[cfg(not(feature = "verify-only"))]
+    fn prove<'b>(
+        pub_params: &'b Self::PublicParams,
+        pub_inputs: &'b Self::PublicInputs,
+        priv_inputs: &'b Self::PrivateInputs,
+    ) -> Result<Self::Proof> {
+        ensure!(
+            priv_inputs.windows.len() == pub_params.windows,
+            "expected private inputs for {} windows, got {}",
+            pub_params.windows,
+            priv_inputs.windows.len()
+        );
+
+        priv_inputs
+            .windows
+            .iter()
+            .enumerate()
+            .map(|(window, window_priv_inputs)| {
+                let window_pub_inputs = Self::window_public_inputs(pub_inputs, window)?;
+                DrgPoRep::<H, G>::prove(&pub_params.window, &window_pub_inputs, window_priv_inputs)
+            })
+            .collect()
+    }
+
+    fn verify(
+        pub_params: &Self::PublicParams,
+        pub_inputs: &Self::PublicInputs,
+        proof: &Self::Proof,
+    ) -> Result<bool> {
+        let tau = &pub_inputs.tau;
+        if tau.windows.len() != pub_params.windows || proof.len() != pub_params.windows {
+            return Ok(false);
+        }
+
+        let expected = WindowedTau::new::<H>(tau.windows.clone())?;
+        if expected.comm_d != tau.comm_d || expected.comm_r != tau.comm_r {
+            return Ok(false);
+        }
+
+        for (window, window_proof) in proof.iter().enumerate() {
+            if !proof_matches_tau(window_proof, &tau.windows[window]) {
+                return Ok(false);
+            }
+
+            let window_pub_inputs = Self::window_public_inputs(pub_inputs, window)?;
+            if !DrgPoRep::<H, G>::verify(&pub_params.window, &window_pub_inputs, window_proof)? {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn check_requirements(
+        pub_inputs: &Self::PublicInputs,
+        requirements: &Self::Requirements,
+    ) -> Result<()> {
+        let drg_pub_inputs = PublicInputs {
+            replica_id: Some(pub_inputs.replica_id),
+            challenges: pub_inputs.challenges.clone().into(),
+            tau: None,
+        };
+
+        DrgPoRep::<H, G>::check_requirements(&drg_pub_inputs, requirements)
+    }
+}
+
+impl<'a, H, G> PoRep<'a, H, H> for WindowedDrgPoRep<'a, H, G>
+where
+    H: 'a + Hasher,
+    G::Key: AsRef<H::Domain>,
+    G: 'a + Graph<H> + ParameterSetMetadata + Sync + Send,
+{
+    type Tau = WindowedTau<H::Domain>;
+    type ProverAux = Vec<ProverAux<H>>;
+
+    /// Replicates each window in place. `config` must be sized for a single window; the trees of
+    /// window `i` are stored in the `window-i` subdirectory of `config.path`, and its replica next
+    /// to `replica_path`, with a `.window-i` suffix. The full replica is written to `replica_path`.
+    fn replicate(
+        pp: &'a Self::PublicParams,
+        replica_id: &H::Domain,
+        mut data: Data<'a>,
+        data_tree: Option<BinaryMerkleTree<H::Domain, H::Function>>,
+        config: StoreConfig,
+        replica_path: PathBuf,
+    ) -> Result<(Self::Tau, Self::ProverAux)> {
+        ensure!(
+            data_tree.is_none(),
+            "windowed replication builds one data tree per window"
+        );
+        ensure!(
+            data.len() == pp.windows * pp.window_bytes(),
+            "expected {} bytes of data, got {}",
+            pp.windows * pp.window_bytes(),
+            data.len()
+        );
+
+        data.ensure_data()?;
+
+        let mut taus = Vec::with_capacity(pp.windows);
+        let mut aux = Vec::with_capacity(pp.windows);
+
+        for (window, window_data) in data.as_mut().chunks_mut(pp.window_bytes()).enumerate() {
+            let mut window_config = config.clone();
+            window_config.path = config.path.join(format!("window-{}", window));
+            fs::create_dir_all(&window_config.path)?;
+
+            let window_replica_path =
+                PathBuf::from(format!("{}.window-{}", replica_path.display(), window));
+
+            let (tau, window_aux) = DrgPoRep::<H, G>::replicate(
+                &pp.window,
+                &window_replica_id::<H>(replica_id, window),
+                window_data.into(),
+                None,
+                window_config,
+                window_replica_path,
+            )?;
+
+            taus.push(tau);
+            aux.push(window_aux);
+        }
+
+        let mut f = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .open(&replica_path)?;
+        f.write_all(data.as_ref())?;
+
+        Ok((WindowedTau::new::<H>(taus)?, aux))
+    }
+
+    fn extract_all<'b>(
+        pp: &'b Self::PublicParams,
+        replica_id: &'b H::Domain,
+        data: &'b [u8],
+        _config: Option<StoreConfig>,
+    ) -> Result<Vec<u8>> {
+        ensure!(
+            data.len() == pp.windows * pp.window_bytes(),
+            "expected {} bytes of replica, got {}",
+            pp.windows * pp.window_bytes(),
+            data.len()
+        );
+
+        let mut extracted = Vec::with_capacity(data.len());
+        for (window, window_data) in data.chunks(pp.window_bytes()).enumerate() {
+            let id = window_replica_id::<H>(replica_id, window);
+            extracted.extend(decode(
+                &pp.window.graph,
+                &id,
+                window_data,
+                None,
+                pp.window.encoding_op,
+            )?);
+        }
+
+        Ok(extracted)
+    }
+
+    fn extract(
+        pp: &Self::PublicParams,
+        replica_id: &H::Domain,
+        data: &[u8],
+        node: usize,
+        _config: Option<StoreConfig>,
+    ) -> Result<Vec<u8>> {
+        let window = node / pp.window_nodes();
+        ensure!(window < pp.windows, "node {} out of range", node);
+
+        let start = window * pp.window_bytes();
+        let end = start + pp.window_bytes();
+        ensure!(
+            end <= data.len(),
+            "replica is too short for window {}",
+            window
+        );
+
+        let id = window_replica_id::<H>(replica_id, window);
+        let block = decode_block(
+            &pp.window.graph,
+            &id,
+            &data[start..end],
+            None,
+            node % pp.window_nodes(),
+            pp.window.encoding_op,
+        )?;
+
+        Ok(block.into_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ff::Field;
+    use paired::bls12_381::{Bls12, Fr};
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::cache_key::CacheKey;
+    use crate::drgraph::{new_seed, BucketGraph, BASE_DEGREE};
+    use crate::fr32::fr_into_bytes;
+    use crate::hasher::PedersenHasher;
+    use crate::porep::drg::DrgParams;
+    use crate::porep::stacked::BINARY_ARITY;
+
+    #[test]
+    fn windowed_prove_verify_two_windows() {
+        type H = PedersenHasher;
+
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let window_nodes = 8;
+        let windows = 2;
+
+        let replica_id: <H as Hasher>::Domain = Domain::random(rng);
+        let data: Vec<u8> = (0..window_nodes * windows)
+            .flat_map(|_| fr_into_bytes::<Bls12>(&Fr::random(rng)))
+            .collect();
+        let mut replica = data.clone();
+
+        let sp = WindowedSetupParams {
+            window: SetupParams {
+                drg: DrgParams {
+                    nodes: window_nodes,
+                    degree: BASE_DEGREE,
+                    expansion_degree: 0,
+                    seed: new_seed(),
+                },
+                private: false,
+                challenges_count: 2,
+            },
+            windows,
+        };
+        let pp = WindowedDrgPoRep::<H, BucketGraph<H>>::setup(&sp).expect("setup failed");
+
+        let cache_dir = tempfile::tempdir().unwrap();
+        let levels = StoreConfig::default_cached_above_base_layer(window_nodes, BINARY_ARITY);
+        let config = StoreConfig::new(cache_dir.path(), CacheKey::CommDTree.to_string(), levels);
+        let replica_path = cache_dir.path().join("replica-path");
+
+        let (tau, aux) = WindowedDrgPoRep::<H, BucketGraph<H>>::replicate(
+            &pp,
+            &replica_id,
+            replica.as_mut_slice().into(),
+            None,
+            config,
+            replica_path,
+        )
+        .expect("replication failed");
+
+        assert_eq!(tau.windows.len(), windows);
+        assert_ne!(tau.windows[0].comm_r, tau.windows[1].comm_r);
+        assert_eq!(
+            tau.comm_r,
+            combine_window_commitments::<H>(tau.windows.iter().map(|t| t.comm_r)).unwrap()
+        );
+
+        let extracted =
+            WindowedDrgPoRep::<H, BucketGraph<H>>::extract_all(&pp, &replica_id, &replica, None)
+                .unwrap();
+        assert_eq!(extracted, data);
+
+        let pub_inputs = WindowedPublicInputs {
+            replica_id,
+            challenges: vec![3, 5],
+            tau: tau.clone(),
+        };
+        let priv_inputs = WindowedPrivateInputs::<H> {
+            windows: aux
+                .iter()
+                .map(|window_aux| PrivateInputs {
+                    tree_d: &window_aux.tree_d,
+                    tree_r: &window_aux.tree_r,
+                    tree_r_config_levels: levels,
+                    replica: None,
+                })
+                .collect(),
+        };
+
+        let proof = WindowedDrgPoRep::<H, BucketGraph<H>>::prove(&pp, &pub_inputs, &priv_inputs)
+            .expect("proving failed");
+        assert_eq!(proof.len(), windows);
+        assert!(
+            WindowedDrgPoRep::<H, BucketGraph<H>>::verify(&pp, &pub_inputs, &proof).unwrap(),
+            "failed to verify"
+        );
+
+        // Each window proof also verifies on its own, against its window's public inputs.
+        for (window, window_proof) in proof.iter().enumerate() {
+            let window_pub_inputs =
+                WindowedDrgPoRep::<H, BucketGraph<H>>::window_public_inputs(&pub_inputs, window)
+                    .unwrap();
+            assert!(DrgPoRep::<H, BucketGraph<H>>::verify(
+                &pp.window,
+                &window_pub_inputs,
+                window_proof
+            )
+            .unwrap());
+        }
+
+        // Swapping the window proofs breaks the binding to the window commitments.
+        let swapped = vec![proof[1].clone(), proof[0].clone()];
+        assert!(
+            !WindowedDrgPoRep::<H, BucketGraph<H>>::verify(&pp, &pub_inputs, &swapped).unwrap()
+        );
+
+        // A combined commitment that does not match the windows is rejected.
+        let mut bad_inputs = pub_inputs.clone();
+        bad_inputs.tau.comm_r = Domain::random(rng);
+        assert!(!WindowedDrgPoRep::<H, BucketGraph<H>>::verify(&pp, &bad_inputs, &proof).unwrap());
+    }
+}
@@ -11,6 +11,10 @@ use crate::Data;
 pub mod drg;
 pub mod stacked;
 
+mod scoped;
+
+pub use self::scoped::ScopedReplication;
+
 pub trait PoRep<'a, H: Hasher, G: Hasher>: ProofScheme<'a> {
     type Tau;
     type ProverAux;
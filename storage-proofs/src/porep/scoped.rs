@@ -0,0 +1,172 @@
+use std::path::{Path, PathBuf};
+
+use merkletree::store::StoreConfig;
+
+use crate::cache_key::CacheKey;
+use crate::error::Result;
+use crate::hasher::Hasher;
+use crate::merkle::BinaryMerkleTree;
+use crate::porep::stacked::BINARY_ARITY;
+use crate::porep::PoRep;
+use crate::util::NODE_SIZE;
+use crate::Data;
+
+/// Runs `PoRep::replicate` against a temporary cache directory, which is removed again once
+/// the `ScopedReplication` goes out of scope. The replica itself is written to the path given
+/// by the caller and is never removed.
+///
+/// The trees in `aux` are backed by the cache directory, so `tau` and `aux` are owned by this
+/// struct and are dropped before the directory is cleaned up.
+#[derive(Debug)]
+pub struct ScopedReplication<T, A> {
+    pub tau: T,
+    pub aux: A,
+    replica_path: PathBuf,
+    cache_path: PathBuf,
+    // Declared last, so it is dropped after `aux`.
+    cache_dir: Option<tempfile::TempDir>,
+}
+
+impl<T, A> ScopedReplication<T, A> {
+    pub fn replicate<'a, H, G, P>(
+        pub_params: &'a P::PublicParams,
+        replica_id: &H::Domain,
+        data: Data<'a>,
+        data_tree: Option<BinaryMerkleTree<G::Domain, G::Function>>,
+        replica_path: PathBuf,
+    ) -> Result<Self>
+    where
+        H: Hasher,
+        G: Hasher,
+        P: PoRep<'a, H, G, Tau = T, ProverAux = A>,
+    {
+        let cache_dir = tempfile::tempdir()?;
+        let cache_path = cache_dir.path().to_path_buf();
+
+        // MT for original data is always named tree-d, and it will be
+        // referenced later in the process as such.
+        let nodes = data.len() / NODE_SIZE;
+        let config = StoreConfig::new(
+            &cache_path,
+            CacheKey::CommDTree.to_string(),
+            StoreConfig::default_cached_above_base_layer(nodes, BINARY_ARITY),
+        );
+
+        let (tau, aux) = P::replicate(
+            pub_params,
+            replica_id,
+            data,
+            data_tree,
+            config,
+            replica_path.clone(),
+        )?;
+
+        Ok(ScopedReplication {
+            tau,
+            aux,
+            replica_path,
+            cache_path,
+            cache_dir: Some(cache_dir),
+        })
+    }
+
+    pub fn replica_path(&self) -> &Path {
+        &self.replica_path
+    }
+
+    pub fn cache_path(&self) -> &Path {
+        &self.cache_path
+    }
+
+    /// Keeps the cache directory on disk after this struct is dropped, and returns its path.
+    pub fn persist_cache_dir(&mut self) -> PathBuf {
+        if let Some(cache_dir) = self.cache_dir.take() {
+            cache_dir.into_path();
+        }
+
+        self.cache_path.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ff::Field;
+    use paired::bls12_381::{Bls12, Fr};
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::drgraph::{new_seed, BucketGraph, BASE_DEGREE};
+    use crate::fr32::fr_into_bytes;
+    use crate::hasher::{Domain, PedersenHasher};
+    use crate::porep::drg;
+    use crate::proof::ProofScheme;
+
+    fn scoped_replication(persist: bool) {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let nodes = 8;
+        let replica_id = <PedersenHasher as Hasher>::Domain::random(rng);
+        let mut data: Vec<u8> = (0..nodes)
+            .flat_map(|_| fr_into_bytes::<Bls12>(&Fr::random(rng)))
+            .collect();
+
+        let sp = drg::SetupParams {
+            drg: drg::DrgParams {
+                nodes,
+                degree: BASE_DEGREE,
+                expansion_degree: 0,
+                seed: new_seed(),
+            },
+            private: false,
+            challenges_count: 1,
+        };
+        let pp = drg::DrgPoRep::<PedersenHasher, BucketGraph<_>>::setup(&sp).expect("setup failed");
+
+        let replica_dir = tempfile::tempdir().unwrap();
+        let replica_path = replica_dir.path().join("replica-path");
+
+        let cache_path = {
+            let mut scoped = ScopedReplication::replicate::<
+                PedersenHasher,
+                PedersenHasher,
+                drg::DrgPoRep<PedersenHasher, _>,
+            >(
+                &pp,
+                &replica_id,
+                (&mut data[..]).into(),
+                None,
+                replica_path.clone(),
+            )
+            .expect("replication failed");
+
+            assert_eq!(scoped.replica_path(), replica_path.as_path());
+            assert_eq!(scoped.tau.comm_d, scoped.aux.tree_d.root());
+            assert!(scoped.cache_path().read_dir().unwrap().next().is_some());
+
+            if persist {
+                scoped.persist_cache_dir()
+            } else {
+                scoped.cache_path().to_path_buf()
+            }
+        };
+
+        assert!(replica_path.exists(), "replica must never be removed");
+        assert_eq!(cache_path.exists(), persist);
+
+        if persist {
+            std::fs::remove_dir_all(&cache_path).unwrap();
+        }
+    }
+
+    #[test]
+    fn scoped_replication_removes_cache_dir() {
+        scoped_replication(false);
+    }
+
+    #[test]
+    fn scoped_replication_persists_cache_dir() {
+        scoped_replication(true);
+    }
+}
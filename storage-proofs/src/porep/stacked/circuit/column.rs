@@ -16,11 +16,9 @@ pub struct Column {
 
 impl<H: Hasher> From<VanillaColumn<H>> for Column {
     fn from(other: VanillaColumn<H>) -> Self {
-        let VanillaColumn { index, rows, .. } = other;
-
         Column {
-            index: Some(index),
-            rows: rows.into_iter().map(|r| Some(r.into())).collect(),
+            index: Some(other.index()),
+            rows: other.rows().iter().map(|r| Some((*r).into())).collect(),
         }
     }
 }
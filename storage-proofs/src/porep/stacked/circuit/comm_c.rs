@@ -0,0 +1,142 @@
+use bellperson::gadgets::num;
+use bellperson::{Circuit, ConstraintSystem, SynthesisError};
+use fil_sapling_crypto::jubjub::JubjubEngine;
+use paired::bls12_381::{Bls12, Fr};
+
+use super::column_proof::ColumnProof;
+
+use crate::hasher::Hasher;
+use crate::porep::stacked::ColumnProof as VanillaColumnProof;
+
+/// Binds a public `comm_c` to the columns of tree_c: every column is hashed in the circuit, and
+/// the inclusion path of the resulting leaf must resolve to `comm_c`. This keeps a prover from
+/// claiming a `comm_c` which is unrelated to the columns it opens.
+///
+/// The only public input is `comm_c`, see `generate_public_inputs`.
+pub struct CommCCircuit<'a, H: Hasher> {
+    params: &'a <Bls12 as JubjubEngine>::Params,
+    comm_c: Option<H::Domain>,
+    column_proofs: Vec<ColumnProof<H>>,
+}
+
+impl<'a, H: Hasher> CommCCircuit<'a, H> {
+    pub fn new(
+        params: &'a <Bls12 as JubjubEngine>::Params,
+        comm_c: Option<H::Domain>,
+        column_proofs: Vec<VanillaColumnProof<H>>,
+    ) -> Self {
+        CommCCircuit {
+            params,
+            comm_c,
+            column_proofs: column_proofs.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Returns the public inputs of the circuit proving that columns are in the tree_c with
+    /// root `comm_c`.
+    pub fn generate_public_inputs(comm_c: &H::Domain) -> Vec<Fr> {
+        vec![(*comm_c).into()]
+    }
+}
+
+impl<'a, H: Hasher> Circuit<Bls12> for CommCCircuit<'a, H> {
+    fn synthesize<CS: ConstraintSystem<Bls12>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let CommCCircuit {
+            params,
+            comm_c,
+            column_proofs,
+        } = self;
+
+        let comm_c_num = num::AllocatedNum::alloc(cs.namespace(|| "comm_c"), || {
+            comm_c
+                .map(Into::into)
+                .ok_or_else(|| SynthesisError::AssignmentMissing)
+        })?;
+        comm_c_num.inputize(cs.namespace(|| "comm_c_input"))?;
+
+        for (i, proof) in column_proofs.into_iter().enumerate() {
+            proof.synthesize(
+                cs.namespace(|| format!("column_proof_{}", i)),
+                params,
+                &comm_c_num,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::crypto::pedersen::JJ_PARAMS;
+    use crate::gadgets::TestConstraintSystem;
+    use crate::hasher::{Domain, PedersenHasher, PoseidonHasher};
+    use crate::porep::stacked::{Column, OctTree};
+
+    fn comm_c_circuit<H: Hasher>() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let nodes = 64;
+        let layers = 2;
+
+        let columns: Vec<Column<H>> = (0..nodes)
+            .map(|index| {
+                let rows = (0..layers).map(|_| H::Domain::random(rng)).collect();
+                Column::new(index, rows).unwrap()
+            })
+            .collect();
+        let tree_c =
+            OctTree::<H>::try_from_iter(columns.iter().map(|column| column.hash().map(Into::into)))
+                .unwrap();
+        let comm_c = tree_c.root();
+
+        let proofs = |challenges: &[usize]| -> Vec<VanillaColumnProof<H>> {
+            challenges
+                .iter()
+                .map(|challenge| columns[*challenge].clone().into_proof(&tree_c).unwrap())
+                .collect()
+        };
+        let synthesize = |comm_c: H::Domain, challenges: &[usize]| {
+            let mut cs = TestConstraintSystem::<Bls12>::new();
+            CommCCircuit::<H>::new(&*JJ_PARAMS, Some(comm_c), proofs(challenges))
+                .synthesize(&mut cs)
+                .expect("failed to synthesize circuit");
+            cs
+        };
+
+        let challenges = [1, 8, 63];
+        let cs = synthesize(comm_c, &challenges);
+        assert!(cs.is_satisfied(), "{:?}", cs.which_is_unsatisfied());
+        assert!(cs.verify(&CommCCircuit::<H>::generate_public_inputs(&comm_c)));
+
+        // Every column proof costs the same, on top of the single constraint exposing comm_c.
+        let single = synthesize(comm_c, &challenges[..1]);
+        assert_eq!(
+            cs.num_constraints() - 1,
+            challenges.len() * (single.num_constraints() - 1)
+        );
+
+        // A comm_c which is not the root of tree_c is rejected, in the circuit and by the
+        // vanilla verification.
+        let other = H::Domain::random(rng);
+        assert!(!synthesize(other, &challenges).is_satisfied());
+        for (proof, challenge) in proofs(&challenges).iter().zip(&challenges) {
+            assert!(proof.verify(*challenge as u32, &comm_c));
+            assert!(!proof.verify(*challenge as u32, &other));
+        }
+    }
+
+    #[test]
+    fn comm_c_circuit_pedersen() {
+        comm_c_circuit::<PedersenHasher>();
+    }
+
+    #[test]
+    fn comm_c_circuit_poseidon() {
+        comm_c_circuit::<PoseidonHasher>();
+    }
+}
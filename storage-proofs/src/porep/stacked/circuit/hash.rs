@@ -4,6 +4,11 @@ use generic_array::typenum;
 use neptune::circuit::poseidon_hash;
 use paired::bls12_381::{Bls12, Fr};
 
+use crate::hasher::types::{
+    POSEIDON_CONSTANTS_1, POSEIDON_CONSTANTS_11, POSEIDON_CONSTANTS_16, POSEIDON_CONSTANTS_2,
+    POSEIDON_CONSTANTS_24, POSEIDON_CONSTANTS_36, POSEIDON_CONSTANTS_4, POSEIDON_CONSTANTS_8,
+};
+
 /// Hash a list of bits.
 pub fn hash_single_column<CS>(
     mut cs: CS,
@@ -22,22 +27,22 @@ where
         })
         .collect::<Result<Vec<_>, _>>()?;
 
+    macro_rules! poseidon {
+        ($arity:ty, $constants:ident) => {
+            poseidon_hash::<CS, Bls12, $arity>(cs, column, &*$constants)
+        };
+    }
+
+    // Must support the same column sizes as the vanilla `hash_single_column`.
     match column.len() {
-        1 => poseidon_hash::<CS, Bls12, typenum::U1>(
-            cs,
-            column,
-            &*crate::hasher::types::POSEIDON_CONSTANTS_1,
-        ),
-        2 => poseidon_hash::<CS, Bls12, typenum::U2>(
-            cs,
-            column,
-            &*crate::hasher::types::POSEIDON_CONSTANTS_2,
-        ),
-        11 => poseidon_hash::<CS, Bls12, typenum::U11>(
-            cs,
-            column,
-            &*crate::hasher::types::POSEIDON_CONSTANTS_11,
-        ),
+        1 => poseidon!(typenum::U1, POSEIDON_CONSTANTS_1),
+        2 => poseidon!(typenum::U2, POSEIDON_CONSTANTS_2),
+        4 => poseidon!(typenum::U4, POSEIDON_CONSTANTS_4),
+        8 => poseidon!(typenum::U8, POSEIDON_CONSTANTS_8),
+        11 => poseidon!(typenum::U11, POSEIDON_CONSTANTS_11),
+        16 => poseidon!(typenum::U16, POSEIDON_CONSTANTS_16),
+        24 => poseidon!(typenum::U24, POSEIDON_CONSTANTS_24),
+        36 => poseidon!(typenum::U36, POSEIDON_CONSTANTS_36),
         _ => panic!("unsupported column size: {}", column.len()),
     }
 }
@@ -115,7 +120,7 @@ mod tests {
             assert!(cs.is_satisfied(), "constraints not satisfied");
             assert_eq!(cs.num_constraints(), 601);
 
-            let expected: Fr = vanilla_hash_single_column(&vals).into();
+            let expected: Fr = vanilla_hash_single_column(&vals).unwrap().into();
 
             assert_eq!(
                 expected,
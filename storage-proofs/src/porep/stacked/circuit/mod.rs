@@ -1,5 +1,6 @@
 mod column;
 mod column_proof;
+mod comm_c;
 mod create_label;
 mod encoding_proof;
 mod hash;
@@ -7,5 +8,6 @@ mod labeling_proof;
 mod params;
 mod proof;
 
+pub use self::comm_c::CommCCircuit;
 pub use self::create_label::*;
 pub use self::proof::{StackedCircuit, StackedCompound};
@@ -1,6 +1,6 @@
 use std::marker::PhantomData;
 
-use anyhow::ensure;
+use anyhow::{ensure, Context};
 use bellperson::gadgets::num;
 use bellperson::{Circuit, ConstraintSystem, SynthesisError};
 use fil_sapling_crypto::jubjub::JubjubEngine;
@@ -16,7 +16,7 @@ use crate::error::Result;
 use crate::fr32::fr_into_bytes;
 use crate::gadgets::constraint;
 use crate::gadgets::por::PoRCompound;
-use crate::hasher::{HashFunction, Hasher};
+use crate::hasher::{Domain, Hasher};
 use crate::parameter_cache::{CacheableParameters, ParameterSetMetadata};
 use crate::por;
 use crate::porep::stacked::StackedDrg;
@@ -139,9 +139,9 @@ impl<'a, H: Hasher, G: Hasher> Circuit<Bls12> for StackedCircuit<'a, Bls12, H, G
                 .ok_or_else(|| SynthesisError::AssignmentMissing)
         })?;
 
-        // Verify comm_r = H(comm_c || comm_r_last)
+        // Verify comm_r = H(comm_c || comm_r_last), or the configured binding of both
         {
-            let hash_num = H::Function::hash2_circuit(
+            let hash_num = public_params.comm_r_binding.bind_circuit::<_, H, _>(
                 cs.namespace(|| "H_comm_c_comm_r_last"),
                 &comm_c_num,
                 &comm_r_last_num,
@@ -202,10 +202,10 @@ impl<'a, H: 'static + Hasher, G: 'static + Hasher>
         let mut inputs = Vec::new();
 
         let comm_d = pub_in.tau.as_ref().expect("missing tau").comm_d;
-        inputs.push(comm_d.into());
+        inputs.push(comm_d.try_into_fr().context("invalid comm_d")?);
 
         let comm_r = pub_in.tau.as_ref().expect("missing tau").comm_r;
-        inputs.push(comm_r.into());
+        inputs.push(comm_r.try_into_fr().context("invalid comm_r")?);
 
         let por_params = por::PoR::<H, typenum::U2>::setup(&por::SetupParams {
             leaves: graph.size(),
@@ -251,7 +251,7 @@ impl<'a, H: 'static + Hasher, G: 'static + Hasher>
 
                 // exp parents
                 let mut exp_parents = vec![0; graph.expansion_degree()];
-                graph.expanded_parents(challenge, &mut exp_parents);
+                graph.expanded_parents(challenge, &mut exp_parents)?;
                 for parent in exp_parents.into_iter() {
                     inputs.extend(generate_inclusion_inputs(parent as usize)?);
                 }
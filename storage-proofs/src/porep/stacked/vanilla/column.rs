@@ -1,13 +1,45 @@
+use anyhow::ensure;
+use merkletree::store::{DiskStore, Store};
 use paired::bls12_381::Fr;
 use serde::{Deserialize, Serialize};
 use std::marker::PhantomData;
+use zeroize::Zeroize;
 
+#[cfg(not(feature = "verify-only"))]
+use super::lazy_tree_c::UpperLevelCache;
 use super::{column_proof::ColumnProof, hash::hash_single_column, params::OctTree};
 
+#[cfg(not(feature = "verify-only"))]
+use crate::error::Error;
 use crate::error::Result;
 use crate::hasher::Hasher;
 use crate::merkle::MerkleProof;
 
+/// The number of nodes transposed at a time by `Column::build_all_from_layers`, so that the rows
+/// being filled stay in cache while each layer is read sequentially.
+const TRANSPOSE_BLOCK_NODES: usize = 64;
+
+/// The labels of a single layer, from which the rows of columns are read.
+pub trait LayerStore<H: Hasher> {
+    /// Returns the number of nodes in the layer.
+    fn nodes(&self) -> usize;
+
+    /// Reads the label of node `index`.
+    fn read_node(&self, index: usize) -> Result<H::Domain>;
+}
+
+impl<H: Hasher> LayerStore<H> for DiskStore<H::Domain> {
+    fn nodes(&self) -> usize {
+        self.len()
+    }
+
+    fn read_node(&self, index: usize) -> Result<H::Domain> {
+        self.read_at(index)
+    }
+}
+
+/// The nodes of all layers at one index. The rows are derived from the sector data, so they are
+/// zeroized when the column is dropped, including during unwinding.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Column<H: Hasher> {
     pub(crate) index: u32,
@@ -24,6 +56,59 @@ impl<H: Hasher> Column<H> {
         })
     }
 
+    /// Builds the column at `index` from the stores of all layers, the first layer first.
+    /// Fails if any of the layers does not contain the node.
+    pub fn from_layers<S: LayerStore<H>>(index: u32, layers: &[S]) -> Result<Self> {
+        let rows = layers
+            .iter()
+            .enumerate()
+            .map(|(row_index, layer)| {
+                ensure!(
+                    (index as usize) < layer.nodes(),
+                    "layer {} has {} nodes, cannot read node {}",
+                    row_index + 1,
+                    layer.nodes(),
+                    index
+                );
+                layer.read_node(index as usize)
+            })
+            .collect::<Result<_>>()?;
+
+        Column::new(index, rows)
+    }
+
+    /// Builds the columns of all nodes, in index order, from the labels of all layers held in
+    /// memory, the first layer first. Fails unless all layers have the same number of nodes.
+    pub fn build_all_from_layers(layers: &[Vec<H::Domain>]) -> Result<Vec<Self>> {
+        let nodes = layers.first().map(Vec::len).unwrap_or(0);
+        for (row_index, layer) in layers.iter().enumerate() {
+            ensure!(
+                layer.len() == nodes,
+                "layer {} has {} nodes, expected {}",
+                row_index + 1,
+                layer.len(),
+                nodes
+            );
+        }
+
+        let mut rows: Vec<Vec<H::Domain>> = (0..nodes)
+            .map(|_| Vec::with_capacity(layers.len()))
+            .collect();
+        for start in (0..nodes).step_by(TRANSPOSE_BLOCK_NODES) {
+            let end = std::cmp::min(start + TRANSPOSE_BLOCK_NODES, nodes);
+            for layer in layers {
+                for (node_rows, label) in rows[start..end].iter_mut().zip(&layer[start..end]) {
+                    node_rows.push(*label);
+                }
+            }
+        }
+
+        rows.into_iter()
+            .enumerate()
+            .map(|(index, rows)| Column::new(index as u32, rows))
+            .collect()
+    }
+
     pub fn with_capacity(index: u32, capacity: usize) -> Result<Self> {
         Column::new(index, Vec::with_capacity(capacity))
     }
@@ -36,8 +121,26 @@ impl<H: Hasher> Column<H> {
         self.index
     }
 
+    /// Returns the column of the same node with the rows of `other` appended to the rows of
+    /// `self`, e.g. to commit to the layers of two trees at once. Fails if the columns belong to
+    /// different nodes.
+    pub fn concat(&self, other: &Column<H>) -> Result<Self> {
+        ensure!(
+            self.index == other.index,
+            "cannot concatenate the columns of nodes {} and {}",
+            self.index,
+            other.index
+        );
+
+        let mut rows = Vec::with_capacity(self.rows.len() + other.rows.len());
+        rows.extend_from_slice(&self.rows);
+        rows.extend_from_slice(&other.rows);
+
+        Column::new(self.index, rows)
+    }
+
     /// Calculate the column hashes `C_i = H(E_i, O_i)` for the passed in column.
-    pub fn hash(&self) -> Fr {
+    pub fn hash(&self) -> Result<Fr> {
         hash_single_column(
             &self
                 .rows
@@ -61,4 +164,312 @@ impl<H: Hasher> Column<H> {
             MerkleProof::new_from_proof(&tree_c.gen_proof(self.index() as usize)?);
         ColumnProof::<H>::from_column(self, inclusion_proof)
     }
+
+    /// Creates the column proof and returns it with the column hash, which is the tree_c leaf the
+    /// proof opens, so the rows are not hashed again. Equivalent to calling `hash` and
+    /// `into_proof` for a column of `tree_c`.
+    pub fn hash_and_prove(self, tree_c: &OctTree<H>) -> Result<(H::Domain, ColumnProof<H>)> {
+        let proof = self.into_proof(tree_c)?;
+        let hash = proof.inclusion_proof.leaf();
+
+        Ok((hash, proof))
+    }
+
+    /// Create a column proof for this column without tree_c. The levels of tree_c below those
+    /// stored in `tree_c_cache` are rebuilt from the columns of `layers`, the first layer first,
+    /// which costs hashing `OCT_ARITY^rows_to_discard` columns. The proof is identical to the one
+    /// of `into_proof`.
+    #[cfg(not(feature = "verify-only"))]
+    pub fn into_proof_from_layers<S: LayerStore<H>>(
+        self,
+        layers: &[S],
+        tree_c_cache: &UpperLevelCache<H>,
+    ) -> Result<ColumnProof<H>> {
+        let index = self.index() as usize;
+        ensure!(
+            index < tree_c_cache.leafs(),
+            Error::OutOfBounds(index, tree_c_cache.leafs())
+        );
+
+        let subtree = index / tree_c_cache.subtree_leafs();
+        let subtree_levels = tree_c_cache.build_subtree(subtree, layers)?;
+        let inclusion_proof = tree_c_cache.proof(index, &subtree_levels);
+        let leaf: H::Domain = self.hash()?.into();
+        ensure!(
+            inclusion_proof.leaf() == leaf,
+            "column {} does not match the layers",
+            index
+        );
+
+        ColumnProof::<H>::from_column(self, inclusion_proof)
+    }
+}
+
+/// Recomputes the column hash `C_i` of node `index` from the layer stores, e.g. to spot-check a
+/// column proof without building tree_c. The hash is the tree_c leaf of the node.
+pub fn recompute_column_hash<H: Hasher, S: LayerStore<H>>(
+    index: u32,
+    layers: &[S],
+) -> Result<H::Domain> {
+    Ok(Column::<H>::from_layers(index, layers)?.hash()?.into())
+}
+
+impl<H: Hasher> Zeroize for Column<H> {
+    /// Overwrites all rows with zeros, keeping the number of rows.
+    fn zeroize(&mut self) {
+        self.rows.as_mut_slice().zeroize();
+    }
+}
+
+impl<H: Hasher> Drop for Column<H> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::hasher::{Domain, PedersenHasher};
+
+    #[test]
+    fn column_zeroize() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let rows = (0..4)
+            .map(|_| <PedersenHasher as Hasher>::Domain::random(rng))
+            .collect();
+        let mut column = Column::<PedersenHasher>::new(3, rows).unwrap();
+        assert!(column
+            .rows()
+            .iter()
+            .all(|row| *row != <PedersenHasher as Hasher>::Domain::default()));
+
+        column.zeroize();
+
+        assert_eq!(column.index(), 3);
+        assert_eq!(column.rows().len(), 4);
+        assert!(column
+            .rows()
+            .iter()
+            .all(|row| row.into_bytes().iter().all(|b| *b == 0)));
+    }
+
+    struct MockLayer(Vec<<PedersenHasher as Hasher>::Domain>);
+
+    impl LayerStore<PedersenHasher> for MockLayer {
+        fn nodes(&self) -> usize {
+            self.0.len()
+        }
+
+        fn read_node(&self, index: usize) -> Result<<PedersenHasher as Hasher>::Domain> {
+            Ok(self.0[index])
+        }
+    }
+
+    #[test]
+    fn column_from_layers() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let layers: Vec<MockLayer> = (0..4)
+            .map(|_| {
+                MockLayer(
+                    (0..8)
+                        .map(|_| <PedersenHasher as Hasher>::Domain::random(rng))
+                        .collect(),
+                )
+            })
+            .collect();
+
+        let column = Column::<PedersenHasher>::from_layers(5, &layers).unwrap();
+        let expected =
+            Column::<PedersenHasher>::new(5, layers.iter().map(|layer| layer.0[5]).collect())
+                .unwrap();
+        assert_eq!(column, expected);
+        assert_eq!(column.get_node_at_layer(1).unwrap(), &layers[0].0[5]);
+
+        let mut short_layers = layers;
+        short_layers[2].0.truncate(5);
+        let err = Column::<PedersenHasher>::from_layers(5, &short_layers).unwrap_err();
+        assert!(err.to_string().contains("layer 3"), "{}", err);
+    }
+
+    #[test]
+    fn column_concat() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let mut rows = |n| {
+            (0..n)
+                .map(|_| <PedersenHasher as Hasher>::Domain::random(rng))
+                .collect::<Vec<_>>()
+        };
+        let first_rows = rows(2);
+        let second_rows = rows(2);
+
+        let first = Column::<PedersenHasher>::new(3, first_rows.clone()).unwrap();
+        let second = Column::<PedersenHasher>::new(3, second_rows.clone()).unwrap();
+        let combined = first.concat(&second).unwrap();
+
+        assert_eq!(combined.index(), 3);
+        assert_eq!(combined.rows(), &[first_rows, second_rows].concat()[..]);
+        assert_eq!(combined.get_node_at_layer(3).unwrap(), &second.rows()[0]);
+        assert_ne!(combined.hash().unwrap(), first.hash().unwrap());
+
+        let other_node = Column::<PedersenHasher>::new(4, second.rows().to_vec()).unwrap();
+        assert!(first.concat(&other_node).is_err());
+    }
+
+    #[test]
+    fn build_all_columns_from_layers() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        // Not a multiple of the block size, so that the last block is partial.
+        let nodes = 2 * TRANSPOSE_BLOCK_NODES + 5;
+        let layers: Vec<Vec<_>> = (0..3)
+            .map(|_| {
+                (0..nodes)
+                    .map(|_| <PedersenHasher as Hasher>::Domain::random(rng))
+                    .collect()
+            })
+            .collect();
+
+        let columns = Column::<PedersenHasher>::build_all_from_layers(&layers).unwrap();
+        assert_eq!(columns.len(), nodes);
+        for (index, column) in columns.iter().enumerate() {
+            let expected = Column::<PedersenHasher>::new(
+                index as u32,
+                layers.iter().map(|layer| layer[index]).collect(),
+            )
+            .unwrap();
+            assert_eq!(column, &expected);
+        }
+
+        assert!(Column::<PedersenHasher>::build_all_from_layers(&[])
+            .unwrap()
+            .is_empty());
+
+        let mut uneven = layers;
+        uneven[1].pop();
+        let err = Column::<PedersenHasher>::build_all_from_layers(&uneven).unwrap_err();
+        assert!(err.to_string().contains("layer 2"), "{}", err);
+    }
+
+    #[test]
+    fn recompute_column_hash_matches_column() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let layers: Vec<MockLayer> = (0..2)
+            .map(|_| {
+                MockLayer(
+                    (0..8)
+                        .map(|_| <PedersenHasher as Hasher>::Domain::random(rng))
+                        .collect(),
+                )
+            })
+            .collect();
+
+        for index in 0..8 {
+            let expected: <PedersenHasher as Hasher>::Domain =
+                Column::<PedersenHasher>::from_layers(index, &layers)
+                    .unwrap()
+                    .hash()
+                    .unwrap()
+                    .into();
+            assert_eq!(
+                recompute_column_hash::<PedersenHasher, _>(index, &layers).unwrap(),
+                expected
+            );
+        }
+
+        assert!(recompute_column_hash::<PedersenHasher, _>(8, &layers).is_err());
+    }
+
+    #[test]
+    fn column_hash_and_prove_matches_two_steps() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let nodes = 64;
+        let layers: Vec<MockLayer> = (0..2)
+            .map(|_| {
+                MockLayer(
+                    (0..nodes)
+                        .map(|_| <PedersenHasher as Hasher>::Domain::random(rng))
+                        .collect(),
+                )
+            })
+            .collect();
+
+        let column = |index| Column::<PedersenHasher>::from_layers(index, &layers).unwrap();
+        let tree_c = OctTree::<PedersenHasher>::try_from_iter(
+            (0..nodes as u32).map(|index| column(index).hash().map(Into::into)),
+        )
+        .unwrap();
+
+        for index in &[0, 7, 8, 63] {
+            let expected_hash: <PedersenHasher as Hasher>::Domain =
+                column(*index).hash().unwrap().into();
+            let expected_proof = column(*index).into_proof(&tree_c).unwrap();
+
+            let (hash, proof) = column(*index).hash_and_prove(&tree_c).unwrap();
+
+            assert_eq!(hash, expected_hash);
+            assert_eq!(proof.column, expected_proof.column);
+            assert_eq!(
+                proof.inclusion_proof.as_pairs(),
+                expected_proof.inclusion_proof.as_pairs()
+            );
+            assert_eq!(proof.inclusion_proof.leaf(), hash);
+            assert!(proof.verify(*index, &tree_c.root()));
+        }
+    }
+
+    #[cfg(not(feature = "verify-only"))]
+    #[test]
+    fn column_proof_from_layers_matches_tree_c() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let nodes = 64;
+        let layers: Vec<MockLayer> = (0..2)
+            .map(|_| {
+                MockLayer(
+                    (0..nodes)
+                        .map(|_| <PedersenHasher as Hasher>::Domain::random(rng))
+                        .collect(),
+                )
+            })
+            .collect();
+
+        let column = |index| Column::<PedersenHasher>::from_layers(index, &layers).unwrap();
+        let tree_c = OctTree::<PedersenHasher>::try_from_iter(
+            (0..nodes as u32).map(|index| column(index).hash().map(Into::into)),
+        )
+        .unwrap();
+        let comm_c = tree_c.root();
+
+        for rows_to_discard in 0..3 {
+            let cache = UpperLevelCache::new(&layers, rows_to_discard).unwrap();
+            assert_eq!(cache.root(), comm_c);
+
+            for index in &[0, 1, 8, 35, 63] {
+                let expected = column(*index).into_proof(&tree_c).unwrap();
+                let actual = column(*index)
+                    .into_proof_from_layers(&layers, &cache)
+                    .unwrap();
+
+                assert_eq!(actual.column, expected.column);
+                assert_eq!(
+                    actual.inclusion_proof.as_pairs(),
+                    expected.inclusion_proof.as_pairs()
+                );
+                assert!(actual.verify(*index, &comm_c));
+            }
+
+            let tampered = Column::<PedersenHasher>::new(
+                3,
+                vec![<PedersenHasher as Hasher>::Domain::random(rng); 2],
+            )
+            .unwrap();
+            assert!(tampered.into_proof_from_layers(&layers, &cache).is_err());
+            assert!(column(0)
+                .into_proof_from_layers(&layers[..1], &cache)
+                .is_err());
+        }
+    }
 }
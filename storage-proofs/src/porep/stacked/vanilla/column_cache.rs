@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use paired::bls12_381::Fr;
+
+use super::column::Column;
+
+use crate::error::Result;
+use crate::hasher::Hasher;
+
+/// Memoizes columns, and their hashes, by node index for the duration of a single proof
+/// generation. Challenges and their parents share many columns, so this avoids reading the
+/// same rows from the layer stores over and over again.
+///
+/// The cache is shared between the parallel challenge provers. Columns are built outside of the
+/// lock, so two provers racing for the same missing column may both build it.
+#[derive(Debug)]
+pub struct ColumnCache<H: Hasher> {
+    columns: Mutex<HashMap<u32, Column<H>>>,
+    hashes: Mutex<HashMap<u32, Fr>>,
+    builds: AtomicUsize,
+}
+
+impl<H: Hasher> Default for ColumnCache<H> {
+    fn default() -> Self {
+        ColumnCache {
+            columns: Mutex::new(HashMap::new()),
+            hashes: Mutex::new(HashMap::new()),
+            builds: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl<H: Hasher> ColumnCache<H> {
+    pub fn new() -> Self {
+        ColumnCache::default()
+    }
+
+    /// Returns the column at `index`, calling `build` only if it has not been built before.
+    pub fn column<F>(&self, index: u32, build: F) -> Result<Column<H>>
+    where
+        F: FnOnce(u32) -> Result<Column<H>>,
+    {
+        if let Some(column) = self.columns.lock().unwrap().get(&index) {
+            return Ok(column.clone());
+        }
+
+        let column = build(index)?;
+        self.builds.fetch_add(1, Ordering::Relaxed);
+        self.columns.lock().unwrap().insert(index, column.clone());
+
+        Ok(column)
+    }
+
+    /// Returns the hash of the column at `index`, building the column first if needed.
+    pub fn column_hash<F>(&self, index: u32, build: F) -> Result<Fr>
+    where
+        F: FnOnce(u32) -> Result<Column<H>>,
+    {
+        if let Some(hash) = self.hashes.lock().unwrap().get(&index) {
+            return Ok(*hash);
+        }
+
+        let hash = self.column(index, build)?.hash()?;
+        self.hashes.lock().unwrap().insert(index, hash);
+
+        Ok(hash)
+    }
+
+    /// Returns how many columns were actually built, rather than served from the cache.
+    pub fn builds(&self) -> usize {
+        self.builds.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of distinct columns in the cache.
+    pub fn len(&self) -> usize {
+        self.columns.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::Cell;
+
+    use ff::Field;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::hasher::PoseidonHasher;
+
+    #[test]
+    fn column_cache_builds_each_column_once() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let layers = 2;
+        let nodes = 16;
+
+        let rows: Vec<Vec<<PoseidonHasher as Hasher>::Domain>> = (0..nodes)
+            .map(|_| (0..layers).map(|_| Fr::random(rng).into()).collect())
+            .collect();
+
+        let built = Cell::new(0);
+        let build = |index: u32| {
+            built.set(built.get() + 1);
+            Column::<PoseidonHasher>::new(index, rows[index as usize].clone())
+        };
+
+        // Challenges together with parents, which overlap heavily.
+        let requested = vec![3, 0, 1, 5, 0, 1, 7, 0, 3, 5, 9, 0];
+        let distinct = 6;
+
+        let cache = ColumnCache::<PoseidonHasher>::new();
+        for index in &requested {
+            let column = cache.column(*index, build).unwrap();
+            assert_eq!(column.rows(), &rows[*index as usize][..]);
+
+            let hash = cache.column_hash(*index, build).unwrap();
+            assert_eq!(hash, column.hash().unwrap());
+        }
+
+        assert_eq!(built.get(), distinct);
+        assert_eq!(cache.builds(), distinct);
+        assert_eq!(cache.len(), distinct);
+        assert!(cache.builds() < requested.len());
+    }
+}
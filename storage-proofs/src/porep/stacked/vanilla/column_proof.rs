@@ -1,6 +1,8 @@
+use anyhow::ensure;
 use generic_array::typenum;
 use log::trace;
 use paired::bls12_381::Fr;
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 
 use super::column::Column;
@@ -51,12 +53,15 @@ impl<H: Hasher> ColumnProof<H> {
         IncludedNode::new(*value)
     }
 
-    pub fn column_hash(&self) -> Fr {
+    pub fn column_hash(&self) -> Result<Fr> {
         self.column.hash()
     }
 
     pub fn verify(&self, challenge: u32, expected_root: &H::Domain) -> bool {
-        let c_i = self.column_hash();
+        let c_i = match self.column_hash() {
+            Ok(c_i) => c_i,
+            Err(_) => return false,
+        };
 
         check_eq!(self.inclusion_proof.root(), expected_root);
         check!(self.inclusion_proof.validate_data(c_i.into()));
@@ -64,4 +69,98 @@ impl<H: Hasher> ColumnProof<H> {
 
         true
     }
+
+    /// Verifies `proofs[i]` for `challenges[i]` against the same tree_c root `comm_c`, in
+    /// parallel. The results are in the order of the proofs, and equal those of `verify`.
+    pub fn verify_batch(
+        proofs: &[ColumnProof<H>],
+        challenges: &[u32],
+        comm_c: &H::Domain,
+    ) -> Result<Vec<bool>> {
+        ensure!(
+            proofs.len() == challenges.len(),
+            "got {} column proofs for {} challenges",
+            proofs.len(),
+            challenges.len()
+        );
+
+        Ok(proofs
+            .par_iter()
+            .zip(challenges.par_iter())
+            .map(|(proof, challenge)| {
+                // Comparing the roots is cheap, only hash the columns of matching proofs.
+                proof.root() == comm_c && proof.verify(*challenge, comm_c)
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::hasher::{Domain, PedersenHasher, PoseidonHasher};
+
+    use super::super::params::OctTree;
+
+    fn test_verify_batch<H: Hasher>() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let nodes = 64;
+        let layers = 2;
+
+        let columns: Vec<Column<H>> = (0..nodes)
+            .map(|index| {
+                let rows = (0..layers).map(|_| H::Domain::random(rng)).collect();
+                Column::new(index, rows).unwrap()
+            })
+            .collect();
+        let tree_c =
+            OctTree::<H>::try_from_iter(columns.iter().map(|column| column.hash().map(Into::into)))
+                .unwrap();
+        let comm_c = tree_c.root();
+
+        let challenges = vec![1, 7, 8, 33, 63];
+        let mut proofs: Vec<_> = challenges
+            .iter()
+            .map(|challenge| {
+                columns[*challenge as usize]
+                    .clone()
+                    .into_proof(&tree_c)
+                    .unwrap()
+            })
+            .collect();
+
+        // Tamper with the column of the third proof.
+        proofs[2].column.rows[1] = H::Domain::random(rng);
+
+        let results = ColumnProof::verify_batch(&proofs, &challenges, &comm_c).unwrap();
+        assert_eq!(results, vec![true, true, false, true, true]);
+
+        let expected: Vec<bool> = proofs
+            .iter()
+            .zip(&challenges)
+            .map(|(proof, challenge)| proof.verify(*challenge, &comm_c))
+            .collect();
+        assert_eq!(results, expected);
+
+        // A different root fails all proofs.
+        let other_root = H::Domain::random(rng);
+        let results = ColumnProof::verify_batch(&proofs, &challenges, &other_root).unwrap();
+        assert!(results.iter().all(|valid| !valid));
+
+        assert!(ColumnProof::verify_batch(&proofs, &challenges[1..], &comm_c).is_err());
+    }
+
+    #[test]
+    fn verify_batch_pedersen() {
+        test_verify_batch::<PedersenHasher>();
+    }
+
+    #[test]
+    fn verify_batch_poseidon() {
+        test_verify_batch::<PoseidonHasher>();
+    }
 }
@@ -0,0 +1,217 @@
+use bellperson::gadgets::num;
+use bellperson::{ConstraintSystem, SynthesisError};
+use ff::PrimeField;
+use fil_sapling_crypto::jubjub::JubjubEngine;
+use generic_array::typenum;
+use paired::bls12_381::Fr;
+
+use crate::hasher::{HashFunction, Hasher, PoseidonEngine};
+
+/// The tag hashed into `comm_c` by `TaggedBinding`, the ascii encoding of "comm_r".
+const COMM_R_TAG: u64 = 0x636f_6d6d_5f72;
+
+/// Binds the two replica commitments, `comm_c` and `comm_r_last`, into `comm_r`.
+///
+/// The native and the circuit binding must agree, as the circuit enforces that the public
+/// `comm_r` is the binding of the private `comm_c` and `comm_r_last`.
+pub trait CommRBinding {
+    fn bind<H: Hasher>(comm_c: &H::Domain, comm_r_last: &H::Domain) -> H::Domain;
+
+    fn bind_circuit<E, H, CS>(
+        cs: CS,
+        comm_c: &num::AllocatedNum<E>,
+        comm_r_last: &num::AllocatedNum<E>,
+        params: &E::Params,
+    ) -> Result<num::AllocatedNum<E>, SynthesisError>
+    where
+        E: JubjubEngine + PoseidonEngine<typenum::U2>,
+        H: Hasher,
+        CS: ConstraintSystem<E>;
+}
+
+/// `comm_r = H(comm_c || comm_r_last)`.
+#[derive(Debug, Clone, Copy)]
+pub struct Hash2Binding;
+
+impl CommRBinding for Hash2Binding {
+    fn bind<H: Hasher>(comm_c: &H::Domain, comm_r_last: &H::Domain) -> H::Domain {
+        H::Function::hash2(comm_c, comm_r_last)
+    }
+
+    fn bind_circuit<E, H, CS>(
+        cs: CS,
+        comm_c: &num::AllocatedNum<E>,
+        comm_r_last: &num::AllocatedNum<E>,
+        params: &E::Params,
+    ) -> Result<num::AllocatedNum<E>, SynthesisError>
+    where
+        E: JubjubEngine + PoseidonEngine<typenum::U2>,
+        H: Hasher,
+        CS: ConstraintSystem<E>,
+    {
+        H::Function::hash2_circuit(cs, comm_c, comm_r_last, params)
+    }
+}
+
+/// `comm_r = H(H(tag || comm_c) || comm_r_last)`, separating `comm_r` from any other
+/// `H(comm_c || comm_r_last)` at the cost of a second hash.
+#[derive(Debug, Clone, Copy)]
+pub struct TaggedBinding;
+
+impl TaggedBinding {
+    fn tag<F: PrimeField>() -> F {
+        F::from_repr(F::Repr::from(COMM_R_TAG)).expect("tag is in the field")
+    }
+}
+
+impl CommRBinding for TaggedBinding {
+    fn bind<H: Hasher>(comm_c: &H::Domain, comm_r_last: &H::Domain) -> H::Domain {
+        let tagged = H::Function::hash2(&Self::tag::<Fr>().into(), comm_c);
+        H::Function::hash2(&tagged, comm_r_last)
+    }
+
+    fn bind_circuit<E, H, CS>(
+        mut cs: CS,
+        comm_c: &num::AllocatedNum<E>,
+        comm_r_last: &num::AllocatedNum<E>,
+        params: &E::Params,
+    ) -> Result<num::AllocatedNum<E>, SynthesisError>
+    where
+        E: JubjubEngine + PoseidonEngine<typenum::U2>,
+        H: Hasher,
+        CS: ConstraintSystem<E>,
+    {
+        let tag = Self::tag::<E::Fr>();
+        let tag_num = num::AllocatedNum::alloc(cs.namespace(|| "tag"), || Ok(tag))?;
+        cs.enforce(
+            || "tag is constant",
+            |lc| lc + tag_num.get_variable(),
+            |lc| lc + CS::one(),
+            |lc| lc + (tag, CS::one()),
+        );
+
+        let tagged =
+            H::Function::hash2_circuit(cs.namespace(|| "tagged_comm_c"), &tag_num, comm_c, params)?;
+        H::Function::hash2_circuit(cs.namespace(|| "comm_r"), &tagged, comm_r_last, params)
+    }
+}
+
+/// Selects the `CommRBinding` of the stacked `PublicParams`.
+///
+/// The binding changes `comm_r`, so it is part of the identifier of the parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommRBindingKind {
+    /// `Hash2Binding`.
+    Hash2,
+    /// `TaggedBinding`.
+    Tagged,
+}
+
+impl Default for CommRBindingKind {
+    fn default() -> Self {
+        CommRBindingKind::Hash2
+    }
+}
+
+impl CommRBindingKind {
+    pub fn bind<H: Hasher>(self, comm_c: &H::Domain, comm_r_last: &H::Domain) -> H::Domain {
+        match self {
+            CommRBindingKind::Hash2 => Hash2Binding::bind::<H>(comm_c, comm_r_last),
+            CommRBindingKind::Tagged => TaggedBinding::bind::<H>(comm_c, comm_r_last),
+        }
+    }
+
+    pub fn bind_circuit<E, H, CS>(
+        self,
+        cs: CS,
+        comm_c: &num::AllocatedNum<E>,
+        comm_r_last: &num::AllocatedNum<E>,
+        params: &E::Params,
+    ) -> Result<num::AllocatedNum<E>, SynthesisError>
+    where
+        E: JubjubEngine + PoseidonEngine<typenum::U2>,
+        H: Hasher,
+        CS: ConstraintSystem<E>,
+    {
+        match self {
+            CommRBindingKind::Hash2 => {
+                Hash2Binding::bind_circuit::<_, H, _>(cs, comm_c, comm_r_last, params)
+            }
+            CommRBindingKind::Tagged => {
+                TaggedBinding::bind_circuit::<_, H, _>(cs, comm_c, comm_r_last, params)
+            }
+        }
+    }
+
+    /// Returns the suffix of the parameter identifier for this binding. It is empty for the
+    /// default, so that the identifiers of existing parameters do not change.
+    pub fn identifier(self) -> &'static str {
+        match self {
+            CommRBindingKind::Hash2 => "",
+            CommRBindingKind::Tagged => ", comm_r_binding: tagged",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use paired::bls12_381::Bls12;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::crypto::pedersen::JJ_PARAMS;
+    use crate::gadgets::TestConstraintSystem;
+    use crate::hasher::{Domain, PedersenHasher, PoseidonHasher};
+
+    fn comm_r_binding<H: Hasher>() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let comm_c = H::Domain::random(rng);
+        let comm_r_last = H::Domain::random(rng);
+
+        let default = CommRBindingKind::default().bind::<H>(&comm_c, &comm_r_last);
+        assert_eq!(default, H::Function::hash2(&comm_c, &comm_r_last));
+
+        let tagged = CommRBindingKind::Tagged.bind::<H>(&comm_c, &comm_r_last);
+        assert_ne!(tagged, default);
+        assert_eq!(
+            tagged,
+            CommRBindingKind::Tagged.bind::<H>(&comm_c, &comm_r_last)
+        );
+
+        for kind in &[CommRBindingKind::Hash2, CommRBindingKind::Tagged] {
+            let mut cs = TestConstraintSystem::<Bls12>::new();
+            let comm_c_num =
+                num::AllocatedNum::alloc(cs.namespace(|| "comm_c"), || Ok(comm_c.into())).unwrap();
+            let comm_r_last_num =
+                num::AllocatedNum::alloc(cs.namespace(|| "comm_r_last"), || Ok(comm_r_last.into()))
+                    .unwrap();
+
+            let comm_r_num = kind
+                .bind_circuit::<_, H, _>(
+                    cs.namespace(|| "bind"),
+                    &comm_c_num,
+                    &comm_r_last_num,
+                    &*JJ_PARAMS,
+                )
+                .unwrap();
+
+            assert!(cs.is_satisfied(), "{:?}", cs.which_is_unsatisfied());
+            assert_eq!(
+                comm_r_num.get_value().unwrap(),
+                kind.bind::<H>(&comm_c, &comm_r_last).into()
+            );
+        }
+    }
+
+    #[test]
+    fn comm_r_binding_pedersen() {
+        comm_r_binding::<PedersenHasher>();
+    }
+
+    #[test]
+    fn comm_r_binding_poseidon() {
+        comm_r_binding::<PoseidonHasher>();
+    }
+}
@@ -13,7 +13,7 @@ use sha2raw::Sha256;
 
 use crate::crypto::feistel::{self, FeistelPrecomputed};
 use crate::drgraph::BASE_DEGREE;
-use crate::drgraph::{BucketGraph, Graph};
+use crate::drgraph::{BucketGraph, Graph, Seed};
 use crate::error::Result;
 use crate::hasher::Hasher;
 use crate::parameter_cache::ParameterSetMetadata;
@@ -67,8 +67,7 @@ impl ParentCache {
                 graph.generate_expanded_parents(
                     node,
                     &mut entry[base_degree..base_degree + exp_degree],
-                );
-                Ok(())
+                )
             })?;
 
         info!("cache filled");
@@ -96,6 +95,7 @@ where
     expansion_degree: usize,
     base_graph: G,
     feistel_precomputed: FeistelPrecomputed,
+    max_resamples: usize,
     id: String,
     cache: Option<&'static ParentCache>,
     _h: PhantomData<H>,
@@ -111,6 +111,7 @@ where
             .field("expansion_degree", &self.expansion_degree)
             .field("base_graph", &self.base_graph)
             .field("feistel_precomputed", &self.feistel_precomputed)
+            .field("max_resamples", &self.max_resamples)
             .field("id", &self.id)
             .field("cache", &self.cache)
             .finish()
@@ -148,7 +149,7 @@ where
         nodes: usize,
         base_degree: usize,
         expansion_degree: usize,
-        seed: [u8; 28],
+        seed: Seed,
     ) -> Result<Self> {
         assert_eq!(base_degree, BASE_DEGREE);
         assert_eq!(expansion_degree, EXP_DEGREE);
@@ -171,6 +172,7 @@ where
             expansion_degree,
             cache: None,
             feistel_precomputed: feistel::precompute((expansion_degree * nodes) as feistel::Index),
+            max_resamples: feistel::DEFAULT_MAX_RESAMPLES,
             _h: PhantomData,
         };
 
@@ -328,6 +330,10 @@ where
         self.base_graph.degree() + self.expansion_degree
     }
 
+    fn expansion_degree(&self) -> usize {
+        self.expansion_degree
+    }
+
     #[inline]
     fn parents(&self, node: usize, parents: &mut [u32]) -> Result<()> {
         if let Some(cache) = self.cache {
@@ -342,21 +348,22 @@ where
                 node,
                 &mut parents[self.base_graph().degree()
                     ..self.base_graph().degree() + self.expansion_degree()],
-            );
+            )?;
         }
         Ok(())
     }
 
-    fn seed(&self) -> [u8; 28] {
+    // Expansion parents are labels of the previous layer, so only the base parents have to
+    // precede their node.
+    fn validate_acyclic(&self) -> Result<()> {
+        self.base_graph().validate_acyclic()
+    }
+
+    fn seed(&self) -> Seed {
         self.base_graph().seed()
     }
 
-    fn new(
-        nodes: usize,
-        base_degree: usize,
-        expansion_degree: usize,
-        seed: [u8; 28],
-    ) -> Result<Self> {
+    fn new(nodes: usize, base_degree: usize, expansion_degree: usize, seed: Seed) -> Result<Self> {
         Self::new_stacked(nodes, base_degree, expansion_degree, seed)
     }
 
@@ -379,8 +386,7 @@ where
 {
     /// Assign one parent to `node` using a Chung's construction with a reversible
     /// permutation function from a Feistel cipher (controlled by `invert_permutation`).
-    fn correspondent(&self, node: usize, i: usize) -> u32 {
-        return 0;
+    fn correspondent(&self, node: usize, i: usize) -> Result<u32> {
         // We can't just generate random values between `[0, size())`, we need to
         // expand the search space (domain) to accommodate every unique parent assignment
         // generated here. This can be visualized more clearly as a matrix where the each
@@ -407,30 +413,33 @@ where
         // inverse), will be removed.
         let a = (node * self.expansion_degree) as feistel::Index + i as feistel::Index;
 
-        let transformed = feistel::permute(
+        let transformed = feistel::permute_bounded(
             self.size() as feistel::Index * self.expansion_degree as feistel::Index,
             a,
             &FEISTEL_KEYS,
             self.feistel_precomputed,
-        );
-        transformed as u32 / self.expansion_degree as u32
+            self.max_resamples,
+        )?;
+
         // Collapse the output in the matrix search space to the row of the corresponding
         // node (losing the column information, that will be regenerated later when calling
         // back this function in the `reversed` direction).
+        Ok(transformed as u32 / self.expansion_degree as u32)
     }
 
-    fn generate_expanded_parents(&self, node: usize, expanded_parents: &mut [u32]) {
+    fn generate_expanded_parents(&self, node: usize, expanded_parents: &mut [u32]) -> Result<()> {
         debug_assert_eq!(expanded_parents.len(), self.expansion_degree);
         for (i, el) in expanded_parents.iter_mut().enumerate() {
-            *el = self.correspondent(node, i);
+            *el = self.correspondent(node, i)?;
         }
+        Ok(())
     }
 
     pub fn new_stacked(
         nodes: usize,
         base_degree: usize,
         expansion_degree: usize,
-        seed: [u8; 28],
+        seed: Seed,
     ) -> Result<Self> {
         Self::new(None, nodes, base_degree, expansion_degree, seed)
     }
@@ -443,6 +452,13 @@ where
         self.expansion_degree
     }
 
+    /// Sets the bound on the re-encodings of the permutation sampling a single expansion parent.
+    /// Parents which are already in the parents cache are not affected.
+    pub fn with_max_resamples(mut self, max_resamples: usize) -> Self {
+        self.max_resamples = max_resamples;
+        self
+    }
+
     pub fn base_parents(&self, node: usize, parents: &mut [u32]) -> Result<()> {
         if let Some(cache) = self.cache {
             // Read from the cache
@@ -459,14 +475,15 @@ where
     /// that is applied one way for the forward layers and one way for the reversed
     /// ones.
     #[inline]
-    pub fn expanded_parents(&self, node: usize, parents: &mut [u32]) {
+    pub fn expanded_parents(&self, node: usize, parents: &mut [u32]) -> Result<()> {
         if let Some(cache) = self.cache {
             // Read from the cache
             let cache_parents = cache.read(node as u32);
             parents.copy_from_slice(&cache_parents[self.base_graph().degree()..]);
+            Ok(())
         } else {
             // No cache usage, generate on demand.
-            self.generate_expanded_parents(node, parents);
+            self.generate_expanded_parents(node, parents)
         }
     }
 }
@@ -494,6 +511,9 @@ mod tests {
 
     use std::collections::HashSet;
 
+    use crate::drgraph::new_seed;
+    use crate::hasher::PedersenHasher;
+
     // Test that 3 (or more) rounds of the Feistel cipher can be used
     // as a pseudorandom permutation, that is, each input will be mapped
     // to a unique output (and though not test here, since the cipher
@@ -532,4 +552,48 @@ mod tests {
         // have skipped as duplicates).
         assert_eq!(shuffled.len(), (n * d) as usize);
     }
+
+    #[test]
+    fn expanded_parents_max_resamples() {
+        let nodes = 64;
+        let graph = StackedBucketGraph::<PedersenHasher>::new_stacked(
+            nodes,
+            BASE_DEGREE,
+            EXP_DEGREE,
+            new_seed(),
+        )
+        .unwrap();
+        let bounded = graph.clone().with_max_resamples(0);
+
+        let n = (nodes * EXP_DEGREE) as feistel::Index;
+        let precomputed = feistel::precompute(n);
+
+        // The permutation encodes 1024 indexes, of which only `n` are in range, so many
+        // expansion parents need at least one re-encoding.
+        let mut parents = vec![0; EXP_DEGREE];
+        let mut bounded_parents = vec![0; EXP_DEGREE];
+        let mut distinct = HashSet::new();
+        let mut resampled = 0;
+        for node in 0..nodes {
+            graph.expanded_parents(node, &mut parents).unwrap();
+            for (i, parent) in parents.iter().enumerate() {
+                let index = (node * EXP_DEGREE + i) as feistel::Index;
+                let expected = feistel::permute(n, index, &FEISTEL_KEYS, precomputed)
+                    / EXP_DEGREE as feistel::Index;
+                assert_eq!(u64::from(*parent), expected, "node {}", node);
+            }
+            distinct.extend(parents.iter().copied());
+
+            match bounded.expanded_parents(node, &mut bounded_parents) {
+                Ok(()) => assert_eq!(bounded_parents, parents, "node {}", node),
+                Err(err) => {
+                    assert!(err.to_string().contains("within 0 resamples"), "{}", err);
+                    resampled += 1;
+                }
+            }
+        }
+
+        assert!(resampled > 0);
+        assert!(distinct.len() > nodes / 2, "{:?}", distinct);
+    }
 }
@@ -1,22 +1,84 @@
-use crate::hasher::types::{POSEIDON_CONSTANTS_1, POSEIDON_CONSTANTS_11, POSEIDON_CONSTANTS_2};
+use anyhow::bail;
 use neptune::poseidon::Poseidon;
 use paired::bls12_381::Fr;
 
-/// Hash all elements in the given column.
-pub fn hash_single_column(column: &[Fr]) -> Fr {
-    match column.len() {
-        1 => {
-            let mut hasher = Poseidon::new_with_preimage(column, &*POSEIDON_CONSTANTS_1);
-            hasher.hash()
-        }
-        2 => {
-            let mut hasher = Poseidon::new_with_preimage(column, &*POSEIDON_CONSTANTS_2);
-            hasher.hash()
+use crate::error::Result;
+use crate::hasher::types::{
+    POSEIDON_CONSTANTS_1, POSEIDON_CONSTANTS_11, POSEIDON_CONSTANTS_16, POSEIDON_CONSTANTS_2,
+    POSEIDON_CONSTANTS_24, POSEIDON_CONSTANTS_36, POSEIDON_CONSTANTS_4, POSEIDON_CONSTANTS_8,
+};
+
+/// Column sizes, that is layer counts, for which Poseidon constants are instantiated.
+pub const SUPPORTED_COLUMN_SIZES: [usize; 8] = [1, 2, 4, 8, 11, 16, 24, 36];
+
+/// Returns true if columns of `size` rows can be hashed.
+pub fn is_supported_column_size(size: usize) -> bool {
+    SUPPORTED_COLUMN_SIZES.contains(&size)
+}
+
+/// Hash all elements in the given column, selecting the Poseidon width from its length.
+pub fn hash_single_column(column: &[Fr]) -> Result<Fr> {
+    macro_rules! poseidon {
+        ($constants:ident) => {
+            Poseidon::new_with_preimage(column, &*$constants).hash()
+        };
+    }
+
+    let hash = match column.len() {
+        1 => poseidon!(POSEIDON_CONSTANTS_1),
+        2 => poseidon!(POSEIDON_CONSTANTS_2),
+        4 => poseidon!(POSEIDON_CONSTANTS_4),
+        8 => poseidon!(POSEIDON_CONSTANTS_8),
+        11 => poseidon!(POSEIDON_CONSTANTS_11),
+        16 => poseidon!(POSEIDON_CONSTANTS_16),
+        24 => poseidon!(POSEIDON_CONSTANTS_24),
+        36 => poseidon!(POSEIDON_CONSTANTS_36),
+        size => bail!(
+            "unsupported column size: {}, expected one of {:?}",
+            size,
+            SUPPORTED_COLUMN_SIZES
+        ),
+    };
+
+    Ok(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ff::Field;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    #[test]
+    fn hash_single_column_supported_sizes() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        for size in SUPPORTED_COLUMN_SIZES.iter() {
+            let column: Vec<Fr> = (0..*size).map(|_| Fr::random(rng)).collect();
+            let hash = hash_single_column(&column).unwrap();
+
+            // Deterministic, and sensitive to the last row.
+            assert_eq!(hash, hash_single_column(&column).unwrap());
+            let mut changed = column.clone();
+            changed[size - 1] = Fr::random(rng);
+            assert_ne!(hash, hash_single_column(&changed).unwrap(), "size {}", size);
         }
-        11 => {
-            let mut hasher = Poseidon::new_with_preimage(column, &*POSEIDON_CONSTANTS_11);
-            hasher.hash()
+    }
+
+    #[test]
+    fn hash_single_column_unsupported_sizes() {
+        for size in &[0, 3, 5, 10, 12, 37] {
+            assert!(!is_supported_column_size(*size));
+
+            let column = vec![Fr::one(); *size];
+            let err = hash_single_column(&column).unwrap_err();
+            assert!(
+                err.to_string().contains("unsupported column size"),
+                "{}",
+                err
+            );
         }
-        _ => panic!("unsupported column size: {}", column.len()),
     }
 }
@@ -0,0 +1,196 @@
+use std::marker::PhantomData;
+
+use anyhow::ensure;
+use merkletree::merkle::FromIndexedParallelIterator;
+use merkletree::store::StoreConfig;
+use paired::bls12_381::Fr;
+use rayon::prelude::*;
+use zeroize::Zeroize;
+
+use super::{
+    hash::{hash_single_column, is_supported_column_size, SUPPORTED_COLUMN_SIZES},
+    params::OctTree,
+};
+
+use crate::error::Result;
+use crate::hasher::Hasher;
+
+/// A tree_c which is built from the labels of each layer as soon as the layer is complete,
+/// instead of reading all layers back from their stores at the end of replication.
+///
+/// A column hash depends on all rows of the column, so each column is hashed as soon as its last
+/// row arrives, i.e. while the last layer is pushed. Until then the rows are held in memory, one
+/// label per node and layer, i.e. the sector size times the number of layers. Only use it for
+/// sectors small enough for that, larger sectors build tree_c from the label stores. The rows
+/// are derived from the sector data, so they are zeroized once the columns are hashed, or when
+/// the tree is dropped.
+#[derive(Debug)]
+pub struct IncrementalTreeC<H: Hasher> {
+    nodes: usize,
+    layers: usize,
+    /// The rows pushed so far, by column: the rows of node `i` start at `i * layers`.
+    rows: Vec<H::Domain>,
+    /// The column hashes, filled in when the last layer is pushed.
+    hashes: Vec<H::Domain>,
+    pushed_layers: usize,
+    _h: PhantomData<H>,
+}
+
+impl<H: Hasher> IncrementalTreeC<H> {
+    pub fn new(nodes: usize, layers: usize) -> Result<Self> {
+        ensure!(nodes > 0, "tree_c needs at least one leaf");
+        ensure!(
+            is_supported_column_size(layers),
+            "unsupported number of layers {}, column hashing supports {:?}",
+            layers,
+            SUPPORTED_COLUMN_SIZES
+        );
+
+        Ok(IncrementalTreeC {
+            nodes,
+            layers,
+            rows: vec![H::Domain::default(); nodes * layers],
+            hashes: Vec::new(),
+            pushed_layers: 0,
+            _h: PhantomData,
+        })
+    }
+
+    /// Adds the labels of the next layer, starting with the first layer.
+    pub fn push_layer(&mut self, labels: &[H::Domain]) -> Result<()> {
+        ensure!(
+            self.pushed_layers < self.layers,
+            "all {} layers have already been pushed",
+            self.layers
+        );
+        ensure!(
+            labels.len() == self.nodes,
+            "layer {} has {} labels, expected {}",
+            self.pushed_layers + 1,
+            labels.len(),
+            self.nodes
+        );
+
+        let layers = self.layers;
+        let row = self.pushed_layers;
+        let columns = self.rows.par_chunks_mut(layers).zip(labels.par_iter());
+        if row + 1 == layers {
+            self.hashes = columns
+                .map(|(column, label)| {
+                    column[row] = *label;
+                    let column: Vec<Fr> = column.iter().copied().map(Into::into).collect();
+                    Ok(hash_single_column(&column)?.into())
+                })
+                .collect::<Result<Vec<H::Domain>>>()?;
+            self.rows.zeroize();
+            self.rows = Vec::new();
+        } else {
+            columns.for_each(|(column, label)| column[row] = *label);
+        }
+        self.pushed_layers += 1;
+
+        Ok(())
+    }
+
+    /// Returns the number of layers pushed so far.
+    pub fn pushed_layers(&self) -> usize {
+        self.pushed_layers
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.pushed_layers == self.layers
+    }
+
+    /// Builds tree_c from the column hashes, which is identical to the tree built from the layer
+    /// stores once replication is done. Fails unless all layers have been pushed.
+    pub fn finalize(mut self, config: Option<StoreConfig>) -> Result<OctTree<H>> {
+        ensure!(
+            self.is_complete(),
+            "only {} of {} layers have been pushed",
+            self.pushed_layers,
+            self.layers
+        );
+
+        let hashes = std::mem::replace(&mut self.hashes, Vec::new()).into_par_iter();
+        match config {
+            Some(config) => OctTree::<H>::from_par_iter_with_config(hashes, config),
+            None => OctTree::<H>::from_par_iter(hashes),
+        }
+    }
+}
+
+impl<H: Hasher> Drop for IncrementalTreeC<H> {
+    fn drop(&mut self) {
+        self.rows.zeroize();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::hasher::{Domain, PedersenHasher, PoseidonHasher};
+
+    fn test_incremental_tree_c_matches_batch<H: Hasher>() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let nodes = 64;
+        let layers = 4;
+
+        let labels: Vec<Vec<H::Domain>> = (0..layers)
+            .map(|_| (0..nodes).map(|_| H::Domain::random(rng)).collect())
+            .collect();
+
+        let column_hashes = (0..nodes)
+            .map(|node| {
+                let column: Vec<Fr> = labels.iter().map(|layer| layer[node].into()).collect();
+                Ok(hash_single_column(&column)?.into())
+            })
+            .collect::<Result<Vec<H::Domain>>>()
+            .unwrap();
+        let batch = OctTree::<H>::try_from_iter(column_hashes.into_iter().map(Ok)).unwrap();
+
+        let mut incremental = IncrementalTreeC::<H>::new(nodes, layers).unwrap();
+        assert!(incremental.push_layer(&labels[0][1..]).is_err());
+        for (layer, layer_labels) in labels.iter().enumerate() {
+            assert!(!incremental.is_complete());
+            incremental.push_layer(layer_labels).unwrap();
+            assert_eq!(incremental.pushed_layers(), layer + 1);
+        }
+        assert!(incremental.is_complete());
+        assert!(incremental.push_layer(&labels[0]).is_err());
+
+        let cache_dir = tempfile::tempdir().unwrap();
+        let config = StoreConfig::new(cache_dir.path(), "tree-c", 0);
+        let tree_c = incremental.finalize(Some(config)).unwrap();
+        assert_eq!(tree_c.root(), batch.root());
+        assert_eq!(tree_c.len(), batch.len());
+    }
+
+    #[test]
+    fn incremental_tree_c_matches_batch_pedersen() {
+        test_incremental_tree_c_matches_batch::<PedersenHasher>();
+    }
+
+    #[test]
+    fn incremental_tree_c_matches_batch_poseidon() {
+        test_incremental_tree_c_matches_batch::<PoseidonHasher>();
+    }
+
+    #[test]
+    fn incremental_tree_c_requires_all_layers() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let nodes = 8;
+
+        let mut incremental = IncrementalTreeC::<PoseidonHasher>::new(nodes, 2).unwrap();
+        let labels: Vec<_> = (0..nodes)
+            .map(|_| <PoseidonHasher as Hasher>::Domain::random(rng))
+            .collect();
+        incremental.push_layer(&labels).unwrap();
+
+        assert!(incremental.finalize(None).is_err());
+        assert!(IncrementalTreeC::<PoseidonHasher>::new(nodes, 3).is_err());
+    }
+}
@@ -0,0 +1,326 @@
+use std::sync::Mutex;
+
+use anyhow::ensure;
+use generic_array::typenum;
+use merkletree::hash::Algorithm;
+
+use super::{
+    column::{Column, LayerStore},
+    params::LabelsCache,
+    OCT_ARITY,
+};
+
+use crate::error::{Error, Result};
+use crate::hasher::Hasher;
+use crate::merkle::MerkleProof;
+
+/// A tree_c that is not held in memory.
+///
+/// Only the levels of the tree at and above `rows_to_discard` are stored. The levels below are
+/// rebuilt from the layer stores when a proof is generated: a proof for a leaf requires hashing
+/// the columns of the `OCT_ARITY^rows_to_discard` leaves in its subtree. The most recently
+/// rebuilt subtree is kept, so proofs for nearby leaves are cheap.
+#[derive(Debug)]
+pub struct LazyTreeC<H: Hasher> {
+    labels: LabelsCache<H>,
+    upper: UpperLevelCache<H>,
+    /// The index and levels of the last rebuilt subtree.
+    subtree: Mutex<Option<(usize, Vec<Vec<H::Domain>>)>>,
+}
+
+impl<H: Hasher> LazyTreeC<H> {
+    /// Builds the stored levels of the tree over the columns of `labels`, hashing each column
+    /// once.
+    pub fn new(labels: LabelsCache<H>, rows_to_discard: usize) -> Result<Self> {
+        let upper = UpperLevelCache::new(&labels.labels, rows_to_discard)?;
+
+        Ok(LazyTreeC {
+            labels,
+            upper,
+            subtree: Mutex::new(None),
+        })
+    }
+
+    pub fn root(&self) -> H::Domain {
+        self.upper.root()
+    }
+
+    pub fn leafs(&self) -> usize {
+        self.upper.leafs()
+    }
+
+    /// Generates the inclusion proof of the column hash at `index`, identical to the proof of a
+    /// fully materialized tree_c.
+    pub fn gen_proof(&self, index: usize) -> Result<MerkleProof<H, typenum::U8>> {
+        ensure!(
+            index < self.leafs(),
+            Error::OutOfBounds(index, self.leafs())
+        );
+
+        let subtree = index / self.upper.subtree_leafs();
+
+        let mut cached = self.subtree.lock().unwrap();
+        let is_cached = match *cached {
+            Some((cached_subtree, _)) => cached_subtree == subtree,
+            None => false,
+        };
+        if !is_cached {
+            *cached = Some((
+                subtree,
+                self.upper.build_subtree(subtree, &self.labels.labels)?,
+            ));
+        }
+        let subtree_levels = &cached.as_ref().expect("subtree was just built").1;
+
+        Ok(self.upper.proof(index, subtree_levels))
+    }
+}
+
+/// The levels of a tree_c at and above `rows_to_discard`, up to and including the root.
+///
+/// Together with the layer stores this is enough to generate any inclusion proof of tree_c, see
+/// `Column::into_proof_from_layers`, while only holding `1 / OCT_ARITY^rows_to_discard` of the
+/// tree.
+#[derive(Debug, Clone)]
+pub struct UpperLevelCache<H: Hasher> {
+    leafs: usize,
+    rows_to_discard: usize,
+    /// The stored levels, starting at `rows_to_discard`, up to and including the root.
+    levels: Vec<Vec<H::Domain>>,
+}
+
+impl<H: Hasher> UpperLevelCache<H> {
+    /// Builds the stored levels of the tree over the columns of `layers`, the first layer first,
+    /// hashing each column once.
+    pub fn new<S: LayerStore<H>>(layers: &[S], rows_to_discard: usize) -> Result<Self> {
+        ensure!(!layers.is_empty(), "no layers to build tree_c from");
+
+        let leafs = layers[0].nodes();
+        let height = tree_height(leafs)?;
+        ensure!(
+            rows_to_discard < height,
+            "cannot discard {} rows of a tree with {} rows",
+            rows_to_discard,
+            height
+        );
+
+        let mut cache = UpperLevelCache {
+            leafs,
+            rows_to_discard,
+            levels: Vec::new(),
+        };
+
+        let subtree_roots = (0..cache.subtree_count())
+            .map(|subtree| {
+                let levels = cache.build_subtree(subtree, layers)?;
+                Ok(levels[rows_to_discard][0])
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        cache.levels = build_levels::<H>(subtree_roots, rows_to_discard);
+
+        Ok(cache)
+    }
+
+    pub fn root(&self) -> H::Domain {
+        self.levels[self.levels.len() - 1][0]
+    }
+
+    pub fn leafs(&self) -> usize {
+        self.leafs
+    }
+
+    pub fn rows_to_discard(&self) -> usize {
+        self.rows_to_discard
+    }
+
+    pub(crate) fn subtree_leafs(&self) -> usize {
+        OCT_ARITY.pow(self.rows_to_discard as u32)
+    }
+
+    fn subtree_count(&self) -> usize {
+        self.leafs / self.subtree_leafs()
+    }
+
+    /// Hashes the columns of subtree `subtree` and builds all of its levels.
+    pub(crate) fn build_subtree<S: LayerStore<H>>(
+        &self,
+        subtree: usize,
+        layers: &[S],
+    ) -> Result<Vec<Vec<H::Domain>>> {
+        let subtree_leafs = self.subtree_leafs();
+        let start = subtree * subtree_leafs;
+
+        let leafs = (start..start + subtree_leafs)
+            .map(|node| {
+                let column = Column::<H>::from_layers(node as u32, layers)?;
+                Ok(column.hash()?.into())
+            })
+            .collect::<Result<Vec<H::Domain>>>()?;
+
+        Ok(build_levels::<H>(leafs, 0))
+    }
+
+    /// Assembles the inclusion proof of leaf `index` from the levels of its subtree, as built by
+    /// `build_subtree`, and the stored levels.
+    pub(crate) fn proof(
+        &self,
+        index: usize,
+        subtree_levels: &[Vec<H::Domain>],
+    ) -> MerkleProof<H, typenum::U8> {
+        let subtree_leafs = self.subtree_leafs();
+        let subtree = index / subtree_leafs;
+
+        let mut path = Vec::with_capacity(self.levels.len() + self.rows_to_discard - 1);
+        let lower = subtree_levels[..self.rows_to_discard]
+            .iter()
+            .enumerate()
+            .map(|(row, level)| (level, (index % subtree_leafs) >> (3 * row)));
+        let upper = self.levels[..self.levels.len() - 1]
+            .iter()
+            .enumerate()
+            .map(|(row, level)| (level, subtree >> (3 * row)));
+
+        for (level, position) in lower.chain(upper) {
+            let start = position - position % OCT_ARITY;
+            let siblings = level[start..start + OCT_ARITY]
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| start + i != position)
+                .map(|(_, node)| *node)
+                .collect();
+            path.push((siblings, position % OCT_ARITY));
+        }
+
+        let leaf = subtree_levels[0][index % subtree_leafs];
+
+        MerkleProof::new_from_parts(self.root(), leaf, path)
+    }
+}
+
+fn tree_height(leafs: usize) -> Result<usize> {
+    let mut height = 1;
+    let mut width = leafs;
+    while width > 1 {
+        ensure!(
+            width % OCT_ARITY == 0,
+            "tree_c needs a power of {} leafs, got {}",
+            OCT_ARITY,
+            leafs
+        );
+        width /= OCT_ARITY;
+        height += 1;
+    }
+
+    Ok(height)
+}
+
+/// Builds the levels of an oct tree over `leafs`, which sit at row `row` of the full tree.
+fn build_levels<H: Hasher>(leafs: Vec<H::Domain>, row: usize) -> Vec<Vec<H::Domain>> {
+    let mut hasher = H::Function::default();
+    let mut levels = vec![leafs];
+
+    while levels[levels.len() - 1].len() > 1 {
+        let height = row + levels.len() - 1;
+        let next = levels[levels.len() - 1]
+            .chunks(OCT_ARITY)
+            .map(|nodes| {
+                hasher.reset();
+                hasher.multi_node(nodes, height)
+            })
+            .collect();
+        levels.push(next);
+    }
+
+    levels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::marker::PhantomData;
+
+    use merkletree::store::{DiskStore, Store, StoreConfig};
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::hasher::{Domain, PedersenHasher, PoseidonHasher};
+    use crate::merkle::OctMerkleTree;
+
+    fn labels_cache<H: Hasher>(
+        dir: &std::path::Path,
+        nodes: usize,
+        layers: &[Vec<u8>],
+    ) -> LabelsCache<H> {
+        let labels = layers
+            .iter()
+            .enumerate()
+            .map(|(layer, data)| {
+                let config = StoreConfig::new(dir, format!("layer-{}", layer), 0);
+                DiskStore::new_from_slice_with_config(nodes, OCT_ARITY, data, config).unwrap()
+            })
+            .collect();
+
+        LabelsCache {
+            labels,
+            _h: PhantomData,
+        }
+    }
+
+    fn test_lazy_tree_c_matches_eager<H: Hasher>() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let nodes = 64;
+        let cache_dir = tempfile::tempdir().unwrap();
+
+        let layers: Vec<Vec<u8>> = (0..2)
+            .map(|_| {
+                (0..nodes)
+                    .flat_map(|_| H::Domain::random(rng).into_bytes())
+                    .collect()
+            })
+            .collect();
+
+        let labels = labels_cache::<H>(cache_dir.path(), nodes, &layers);
+        let column_hashes = (0..nodes)
+            .map(|node| Ok(labels.column(node as u32)?.hash()?.into()))
+            .collect::<Result<Vec<H::Domain>>>()
+            .unwrap();
+        let eager = OctMerkleTree::<H::Domain, H::Function>::try_from_iter(
+            column_hashes.into_iter().map(Ok),
+        )
+        .unwrap();
+
+        for rows_to_discard in 0..3 {
+            let labels = labels_cache::<H>(cache_dir.path(), nodes, &layers);
+            let lazy = LazyTreeC::new(labels, rows_to_discard).unwrap();
+            assert_eq!(lazy.root(), eager.root());
+
+            for index in &[0, 1, 7, 8, 35, 63, 62, 9] {
+                let expected = MerkleProof::<H, typenum::U8>::new_from_proof(
+                    &eager.gen_proof(*index).unwrap(),
+                );
+                let actual = lazy.gen_proof(*index).unwrap();
+
+                assert_eq!(actual.root(), expected.root());
+                assert_eq!(actual.leaf(), expected.leaf());
+                assert_eq!(actual.as_pairs(), expected.as_pairs());
+                assert!(actual.validate(*index));
+            }
+
+            assert!(lazy.gen_proof(nodes).is_err());
+        }
+
+        assert!(LazyTreeC::new(labels_cache::<H>(cache_dir.path(), nodes, &layers), 3).is_err());
+    }
+
+    #[test]
+    fn lazy_tree_c_matches_eager_pedersen() {
+        test_lazy_tree_c_matches_eager::<PedersenHasher>();
+    }
+
+    #[test]
+    fn lazy_tree_c_matches_eager_poseidon() {
+        test_lazy_tree_c_matches_eager::<PoseidonHasher>();
+    }
+}
@@ -3,23 +3,33 @@ mod macros;
 
 mod challenges;
 mod column;
+mod column_cache;
 mod column_proof;
+mod comm_r;
 mod create_label;
 mod encoding_proof;
 mod graph;
 pub(crate) mod hash;
+mod incremental_tree_c;
 mod labeling_proof;
+#[cfg(not(feature = "verify-only"))]
+mod lazy_tree_c;
 mod params;
 mod porep;
 mod proof;
 mod proof_scheme;
 
 pub use self::challenges::{ChallengeRequirements, LayerChallenges};
-pub use self::column::Column;
+pub use self::column::{recompute_column_hash, Column, LayerStore};
+pub use self::column_cache::ColumnCache;
 pub use self::column_proof::ColumnProof;
+pub use self::comm_r::{CommRBinding, CommRBindingKind, Hash2Binding, TaggedBinding};
 pub use self::create_label::*;
 pub use self::encoding_proof::EncodingProof;
 pub use self::graph::{StackedBucketGraph, StackedGraph, EXP_DEGREE};
+pub use self::incremental_tree_c::IncrementalTreeC;
 pub use self::labeling_proof::LabelingProof;
+#[cfg(not(feature = "verify-only"))]
+pub use self::lazy_tree_c::{LazyTreeC, UpperLevelCache};
 pub use self::params::*;
 pub use self::proof::{StackedDrg, TOTAL_PARENTS};
@@ -2,7 +2,7 @@ use std::collections::HashMap;
 use std::marker::PhantomData;
 use std::path::{Path, PathBuf};
 
-use anyhow::Context;
+use anyhow::{ensure, Context};
 use generic_array::typenum;
 use log::trace;
 use merkletree::merkle::get_merkle_tree_leafs;
@@ -10,13 +10,12 @@ use merkletree::store::{DiskStore, Store, StoreConfig};
 use serde::{Deserialize, Serialize};
 
 use super::{
-    column::Column, column_proof::ColumnProof, graph::StackedBucketGraph, EncodingProof,
-    LabelingProof, LayerChallenges,
+    column::Column, column_proof::ColumnProof, comm_r::CommRBindingKind, graph::StackedBucketGraph,
+    EncodingProof, LabelingProof, LayerChallenges,
 };
 
-use crate::drgraph::Graph;
-use crate::error::Result;
-use crate::fr32::bytes_into_fr_repr_safe;
+use crate::drgraph::{Graph, Seed};
+use crate::error::{Error, Result};
 use crate::hasher::{Domain, Hasher};
 use crate::merkle::{open_lcmerkle_tree, LCMerkleTree, MerkleProof, MerkleTree};
 use crate::parameter_cache::ParameterSetMetadata;
@@ -41,7 +40,7 @@ pub struct SetupParams {
     pub expansion_degree: usize,
 
     // Random seed
-    pub seed: [u8; 28],
+    pub seed: Seed,
 
     pub layer_challenges: LayerChallenges,
 }
@@ -53,6 +52,8 @@ where
 {
     pub graph: StackedBucketGraph<H>,
     pub layer_challenges: LayerChallenges,
+    /// How `comm_r` binds `comm_c` and `comm_r_last`.
+    pub comm_r_binding: CommRBindingKind,
     _h: PhantomData<H>,
 }
 
@@ -64,9 +65,16 @@ where
         PublicParams {
             graph,
             layer_challenges,
+            comm_r_binding: CommRBindingKind::default(),
             _h: PhantomData,
         }
     }
+
+    /// Returns these parameters with `comm_r` computed by `comm_r_binding`.
+    pub fn with_comm_r_binding(mut self, comm_r_binding: CommRBindingKind) -> Self {
+        self.comm_r_binding = comm_r_binding;
+        self
+    }
 }
 
 impl<H> ParameterSetMetadata for PublicParams<H>
@@ -75,9 +83,10 @@ where
 {
     fn identifier(&self) -> String {
         format!(
-            "layered_drgporep::PublicParams{{ graph: {}, challenges: {:?} }}",
+            "layered_drgporep::PublicParams{{ graph: {}, challenges: {:?}{} }}",
             self.graph.identifier(),
             self.layer_challenges,
+            self.comm_r_binding.identifier(),
         )
     }
 
@@ -92,6 +101,7 @@ where
 {
     fn from(other: &PublicParams<H>) -> PublicParams<H> {
         PublicParams::new(other.graph.clone(), other.layer_challenges.clone())
+            .with_comm_r_binding(other.comm_r_binding)
     }
 }
 
@@ -311,6 +321,9 @@ pub struct TemporaryAux<H: Hasher, G: Hasher> {
     pub tree_d_config: StoreConfig,
     pub tree_r_last_config: StoreConfig,
     pub tree_c_config: StoreConfig,
+    /// If set, `clear_temp` leaves the label stores on disk, so the layers can be inspected.
+    #[serde(default)]
+    pub retain_layers: bool,
     pub _g: PhantomData<G>,
 }
 
@@ -338,7 +351,7 @@ impl<H: Hasher, G: Hasher> TemporaryAux<H, G> {
     }
 
     // 'clear_temp' will discard all persisted merkle and layer data
-    // that is no longer required.
+    // that is no longer required. Layer data is kept if 'retain_layers' is set.
     pub fn clear_temp(t_aux: TemporaryAux<H, G>) -> Result<()> {
         let cached = |config: &StoreConfig| {
             Path::new(&StoreConfig::data_path(&config.path, &config.id)).exists()
@@ -378,6 +391,11 @@ impl<H: Hasher, G: Hasher> TemporaryAux<H, G> {
             trace!("tree c deleted");
         }
 
+        if t_aux.retain_layers {
+            trace!("retaining {} layers", t_aux.labels.len());
+            return Ok(());
+        }
+
         for i in 0..t_aux.labels.labels.len() {
             let cur_config = t_aux.labels.labels[i].clone();
             if cached(&cur_config) {
@@ -477,6 +495,34 @@ impl<H: Hasher, G: Hasher> TemporaryAuxCache<H, G> {
     pub fn column(&self, column_index: u32) -> Result<Column<H>> {
         self.labels.column(column_index)
     }
+
+    /// Returns the inclusion proof of `leaf` in tree_r_last, the tree of `comm_r_last`, making
+    /// use of the levels cached on disk.
+    pub fn tree_r_proof(&self, leaf: usize) -> Result<MerkleProof<H, typenum::U8>> {
+        ensure!(
+            leaf < self.tree_r_last.leafs(),
+            Error::OutOfBounds(leaf, self.tree_r_last.leafs())
+        );
+
+        let proof = if self.tree_r_last_config_levels == 0 {
+            self.tree_r_last.gen_proof(leaf)
+        } else {
+            self.tree_r_last
+                .gen_cached_proof(leaf, self.tree_r_last_config_levels)
+        }?;
+
+        Ok(MerkleProof::new_from_proof(&proof))
+    }
+
+    /// Returns the inclusion proof of `leaf` in tree_c, the tree of `comm_c`.
+    pub fn tree_c_proof(&self, leaf: usize) -> Result<MerkleProof<H, typenum::U8>> {
+        ensure!(
+            leaf < self.tree_c.leafs(),
+            Error::OutOfBounds(leaf, self.tree_c.leafs())
+        );
+
+        Ok(MerkleProof::new_from_proof(&self.tree_c.gen_proof(leaf)?))
+    }
 }
 
 type VerifyCallback = fn(&StoreConfig, usize) -> Result<()>;
@@ -541,17 +587,16 @@ impl<H: Hasher> Labels<H> {
 
     /// Build the column for the given node.
     pub fn column(&self, node: u32) -> Result<Column<H>> {
-        let rows = self
+        let stores = self
             .labels
             .iter()
             .map(|label| {
                 assert!(label.size.is_some());
-                let store = DiskStore::new_from_disk(label.size.unwrap(), OCT_ARITY, &label)?;
-                store.read_at(node as usize)
+                DiskStore::new_from_disk(label.size.unwrap(), OCT_ARITY, &label)
             })
-            .collect::<Result<_>>()?;
+            .collect::<Result<Vec<DiskStore<H::Domain>>>>()?;
 
-        Column::new(node, rows)
+        Column::from_layers(node, &stores)
     }
 
     /// Update all configs to the new passed in root cache path.
@@ -614,13 +659,7 @@ impl<H: Hasher> LabelsCache<H> {
 
     /// Build the column for the given node.
     pub fn column(&self, node: u32) -> Result<Column<H>> {
-        let rows = self
-            .labels
-            .iter()
-            .map(|labels| labels.read_at(node as usize))
-            .collect::<Result<_>>()?;
-
-        Column::new(node, rows)
+        Column::from_layers(node, &self.labels)
     }
 }
 
@@ -635,14 +674,11 @@ pub fn generate_replica_id<H: Hasher, T: AsRef<[u8]>>(
     ticket: &[u8; 32],
     comm_d: T,
 ) -> H::Domain {
-    use sha2::{Digest, Sha256};
-
-    let hash = Sha256::new()
-        .chain(prover_id)
-        .chain(&sector_id.to_be_bytes()[..])
-        .chain(ticket)
-        .chain(AsRef::<[u8]>::as_ref(&comm_d))
-        .result();
+    let mut message = Vec::with_capacity(32 + 8 + 32 + 32);
+    message.extend_from_slice(prover_id);
+    message.extend_from_slice(&sector_id.to_be_bytes()[..]);
+    message.extend_from_slice(ticket);
+    message.extend_from_slice(comm_d.as_ref());
 
-    bytes_into_fr_repr_safe(hash.as_ref()).into()
+    H::hash_bytes(&message)
 }
@@ -24,17 +24,7 @@ impl<'a, 'c, H: 'static + Hasher, G: 'static + Hasher> PoRep<'a, H, G> for Stack
         config: StoreConfig,
         replica_path: PathBuf,
     ) -> Result<(Self::Tau, Self::ProverAux)> {
-        let (tau, p_aux, t_aux) = Self::transform_and_replicate_layers(
-            &pp.graph,
-            &pp.layer_challenges,
-            replica_id,
-            data,
-            data_tree,
-            config,
-            replica_path,
-        )?;
-
-        Ok((tau, (p_aux, t_aux)))
+        Self::replicate_with_options(pp, replica_id, data, data_tree, config, replica_path, false)
     }
 
     fn extract_all<'b>(
@@ -1,7 +1,9 @@
+#[cfg(not(feature = "verify-only"))]
 use std::collections::HashMap;
 use std::marker::PhantomData;
 use std::path::PathBuf;
 
+use anyhow::ensure;
 use log::{info, trace};
 use merkletree::merkle::{
     get_merkle_tree_len, is_merkle_tree_size_valid, FromIndexedParallelIterator,
@@ -11,15 +13,20 @@ use rayon::prelude::*;
 
 use super::{
     challenges::LayerChallenges,
-    column::Column,
     create_label, create_label_exp,
     graph::StackedBucketGraph,
-    hash::hash_single_column,
+    hash::{hash_single_column, is_supported_column_size, SUPPORTED_COLUMN_SIZES},
+    incremental_tree_c::IncrementalTreeC,
     params::{
-        get_node, BinaryTree, Labels, LabelsCache, PersistentAux, Proof, PublicInputs,
-        PublicParams, ReplicaColumnProof, Tau, TemporaryAux, TemporaryAuxCache, TransformedLayers,
-        BINARY_ARITY, OCT_ARITY,
+        get_node, BinaryTree, Labels, LabelsCache, PersistentAux, PublicParams, Tau, TemporaryAux,
+        TransformedLayers, BINARY_ARITY, OCT_ARITY,
     },
+};
+#[cfg(not(feature = "verify-only"))]
+use super::{
+    column::Column,
+    column_cache::ColumnCache,
+    params::{Proof, PublicInputs, ReplicaColumnProof, TemporaryAuxCache},
     EncodingProof, LabelingProof,
 };
 
@@ -28,12 +35,14 @@ use crate::data::Data;
 use crate::drgraph::Graph;
 use crate::encode::{decode, encode};
 use crate::error::Result;
-use crate::hasher::{Domain, HashFunction, Hasher};
+use crate::hasher::{Domain, Hasher};
 use crate::measurements::{
     measure_op,
     Operation::{CommD, EncodeWindowTimeAll, GenerateTreeC, GenerateTreeRLast},
 };
-use crate::merkle::{MerkleProof, MerkleTree, OctLCMerkleTree, OctMerkleTree, Store};
+#[cfg(not(feature = "verify-only"))]
+use crate::merkle::MerkleProof;
+use crate::merkle::{MerkleTree, OctLCMerkleTree, OctMerkleTree, Store};
 use crate::porep::PoRep;
 use crate::util::NODE_SIZE;
 pub const TOTAL_PARENTS: usize = 37;
@@ -45,6 +54,7 @@ pub struct StackedDrg<'a, H: 'a + Hasher, G: 'a + Hasher> {
 }
 
 impl<'a, H: 'static + Hasher, G: 'static + Hasher> StackedDrg<'a, H, G> {
+    #[cfg(not(feature = "verify-only"))]
     #[allow(clippy::too_many_arguments)]
     pub(crate) fn prove_layers(
         graph: &StackedBucketGraph<H>,
@@ -67,6 +77,11 @@ impl<'a, H: 'static + Hasher, G: 'static + Hasher> StackedDrg<'a, H, G> {
         assert_eq!(p_aux.comm_r_last, t_aux.tree_r_last.root());
         assert_eq!(p_aux.comm_c, t_aux.tree_c.root());
 
+        // Columns are shared between challenges and their parents, so only build each once.
+        let column_cache = ColumnCache::<H>::new();
+        let get_column =
+            |x: u32| -> Result<Column<H>> { column_cache.column(x, |index| t_aux.column(index)) };
+
         let get_drg_parents_columns = |x: usize| -> Result<Vec<Column<H>>> {
             let base_degree = graph.base_graph().degree();
 
@@ -76,7 +91,7 @@ impl<'a, H: 'static + Hasher, G: 'static + Hasher> StackedDrg<'a, H, G> {
             graph.base_parents(x, &mut parents)?;
 
             for parent in &parents {
-                columns.push(t_aux.column(*parent)?);
+                columns.push(get_column(*parent)?);
             }
 
             debug_assert!(columns.len() == base_degree);
@@ -86,9 +101,9 @@ impl<'a, H: 'static + Hasher, G: 'static + Hasher> StackedDrg<'a, H, G> {
 
         let get_exp_parents_columns = |x: usize| -> Result<Vec<Column<H>>> {
             let mut parents = vec![0; graph.expansion_degree()];
-            graph.expanded_parents(x, &mut parents);
+            graph.expanded_parents(x, &mut parents)?;
 
-            parents.iter().map(|parent| t_aux.column(*parent)).collect()
+            parents.iter().map(|parent| get_column(*parent)).collect()
         };
 
         (0..partition_count)
@@ -116,7 +131,7 @@ impl<'a, H: 'static + Hasher, G: 'static + Hasher> StackedDrg<'a, H, G> {
                         let rpc = {
                             // All labels in C_X
                             trace!("  c_x");
-                            let c_x = t_aux.column(challenge as u32)?.into_proof(&t_aux.tree_c)?;
+                            let c_x = get_column(challenge as u32)?.into_proof(&t_aux.tree_c)?;
 
                             // All labels in the DRG parents.
                             trace!("  drg_parents");
@@ -141,16 +156,7 @@ impl<'a, H: 'static + Hasher, G: 'static + Hasher> StackedDrg<'a, H, G> {
 
                         // Final replica layer openings
                         trace!("final replica layer openings");
-                        let tree_r_last_proof = {
-                            if t_aux.tree_r_last_config_levels == 0 {
-                                t_aux.tree_r_last.gen_proof(challenge)
-                            } else {
-                                t_aux
-                                    .tree_r_last
-                                    .gen_cached_proof(challenge, t_aux.tree_r_last_config_levels)
-                            }
-                        }?;
-                        let comm_r_last_proof = MerkleProof::new_from_proof(&tree_r_last_proof);
+                        let comm_r_last_proof = t_aux.tree_r_proof(challenge)?;
                         assert!(comm_r_last_proof.validate(challenge));
 
                         // Labeling Proofs Layer 1..l
@@ -239,9 +245,15 @@ impl<'a, H: 'static + Hasher, G: 'static + Hasher> StackedDrg<'a, H, G> {
 
         let layers = layer_challenges.layers();
         assert!(layers > 0);
+        ensure!(
+            is_supported_column_size(layers),
+            "unsupported number of layers {}, column hashing supports {:?}",
+            layers,
+            SUPPORTED_COLUMN_SIZES
+        );
 
         // generate labels
-        let (labels, _) = Self::generate_labels(graph, layer_challenges, replica_id, config)?;
+        let (labels, _) = Self::generate_labels(graph, layer_challenges, replica_id, config, None)?;
 
         let last_layer_labels = labels.labels_for_last_layer()?;
         let size = merkletree::store::Store::len(last_layer_labels);
@@ -261,12 +273,15 @@ impl<'a, H: 'static + Hasher, G: 'static + Hasher> StackedDrg<'a, H, G> {
         Ok(())
     }
 
+    /// Generates the labels of all layers into their stores. If `tree_c` is given, the labels of
+    /// each layer are also pushed to it as soon as the layer is complete.
     #[allow(clippy::type_complexity)]
     fn generate_labels(
         graph: &StackedBucketGraph<H>,
         layer_challenges: &LayerChallenges,
         replica_id: &<H as Hasher>::Domain,
         config: StoreConfig,
+        mut tree_c: Option<&mut IncrementalTreeC<H>>,
     ) -> Result<(LabelsCache<H>, Labels<H>)> {
         info!("generate labels");
 
@@ -294,6 +309,15 @@ impl<'a, H: 'static + Hasher, G: 'static + Hasher> StackedDrg<'a, H, G> {
                 }
             }
 
+            if let Some(tree_c) = tree_c.as_mut() {
+                info!("  pushing layer to tree_c");
+                let layer_labels = labels_buffer[..layer_size]
+                    .par_chunks(NODE_SIZE)
+                    .map(H::Domain::try_from_bytes)
+                    .collect::<Result<Vec<_>>>()?;
+                tree_c.push_layer(&layer_labels)?;
+            }
+
             info!("  setting exp parents");
             labels_buffer.copy_within(..layer_size, layer_size);
 
@@ -355,41 +379,63 @@ impl<'a, H: 'static + Hasher, G: 'static + Hasher> StackedDrg<'a, H, G> {
         )
     }
 
+    /// Generates the layers and replicates. If `incremental_tree_c` is set, tree_c is built in
+    /// memory while the layers are generated, see `IncrementalTreeC`, otherwise it is built from
+    /// the label stores afterwards.
     pub(crate) fn transform_and_replicate_layers(
-        graph: &StackedBucketGraph<H>,
-        layer_challenges: &LayerChallenges,
+        pp: &PublicParams<H>,
         replica_id: &<H as Hasher>::Domain,
         data: Data,
         data_tree: Option<BinaryTree<G>>,
         config: StoreConfig,
         replica_path: PathBuf,
+        incremental_tree_c: bool,
     ) -> Result<TransformedLayers<H, G>> {
+        let mut tree_c = if incremental_tree_c {
+            Some(IncrementalTreeC::<H>::new(
+                pp.graph.size(),
+                pp.layer_challenges.layers(),
+            )?)
+        } else {
+            None
+        };
+
         // Generate key layers.
         let (_, labels) = measure_op(EncodeWindowTimeAll, || {
-            Self::generate_labels(graph, layer_challenges, replica_id, config.clone())
+            Self::generate_labels(
+                &pp.graph,
+                &pp.layer_challenges,
+                replica_id,
+                config.clone(),
+                tree_c.as_mut(),
+            )
         })?;
 
         Self::transform_and_replicate_layers_inner(
-            graph,
-            layer_challenges,
+            pp,
             data,
             data_tree,
             config,
             replica_path,
             labels,
+            tree_c,
         )
     }
 
+    /// Transforms and replicates the layers whose labels are in the stores of `label_configs`.
+    /// tree_c is finalized from `tree_c` if given, and built from the label stores otherwise.
     pub(crate) fn transform_and_replicate_layers_inner(
-        graph: &StackedBucketGraph<H>,
-        layer_challenges: &LayerChallenges,
+        pp: &PublicParams<H>,
         mut data: Data,
         data_tree: Option<BinaryTree<G>>,
         config: StoreConfig,
         replica_path: PathBuf,
         label_configs: Labels<H>,
+        tree_c: Option<IncrementalTreeC<H>>,
     ) -> Result<TransformedLayers<H, G>> {
         trace!("transform_and_replicate_layers");
+        let graph = &pp.graph;
+        let layer_challenges = &pp.layer_challenges;
         let nodes_count = graph.size();
 
         assert_eq!(data.len(), nodes_count * NODE_SIZE);
@@ -441,6 +487,11 @@ impl<'a, H: 'static + Hasher, G: 'static + Hasher> StackedDrg<'a, H, G> {
 
         // Build the tree for CommC
         let tree_c = measure_op(GenerateTreeC, || {
+            if let Some(tree_c) = tree_c {
+                info!("finalizing tree_c");
+                return tree_c.finalize(Some(tree_c_config.clone()));
+            }
+
             info!("Building column hashes");
 
             let gsize = graph.size();
@@ -470,7 +521,9 @@ impl<'a, H: 'static + Hasher, G: 'static + Hasher> StackedDrg<'a, H, G> {
                                 })
                                 .collect();
 
-                            *hash = hash_single_column(&data).into();
+                            *hash = hash_single_column(&data)
+                                .expect("column size was checked")
+                                .into();
                         }
                     });
                 }
@@ -555,8 +608,9 @@ impl<'a, H: 'static + Hasher, G: 'static + Hasher> StackedDrg<'a, H, G> {
 
         data.drop_data();
 
-        // comm_r = H(comm_c || comm_r_last)
-        let comm_r: H::Domain = H::Function::hash2(&tree_c.root(), &tree_r_last.root());
+        let comm_r: H::Domain = pp
+            .comm_r_binding
+            .bind::<H>(&tree_c.root(), &tree_r_last.root());
 
         Ok((
             Tau {
@@ -572,11 +626,69 @@ impl<'a, H: 'static + Hasher, G: 'static + Hasher> StackedDrg<'a, H, G> {
                 tree_d_config,
                 tree_r_last_config,
                 tree_c_config,
+                retain_layers: false,
                 _g: PhantomData,
             },
         ))
     }
 
+    /// Replicates like `PoRep::replicate`. If `retain_layers` is set, the label stores of all
+    /// layers are kept on disk when the returned aux is cleared, for debugging replication.
+    #[allow(clippy::type_complexity)]
+    pub fn replicate_with_options(
+        pp: &'a PublicParams<H>,
+        replica_id: &H::Domain,
+        data: Data<'a>,
+        data_tree: Option<BinaryTree<G>>,
+        config: StoreConfig,
+        replica_path: PathBuf,
+        retain_layers: bool,
+    ) -> Result<(
+        <Self as PoRep<'a, H, G>>::Tau,
+        <Self as PoRep<'a, H, G>>::ProverAux,
+    )> {
+        let (tau, p_aux, mut t_aux) = Self::transform_and_replicate_layers(
+            pp,
+            replica_id,
+            data,
+            data_tree,
+            config,
+            replica_path,
+            false,
+        )?;
+        t_aux.retain_layers = retain_layers;
+
+        Ok((tau, (p_aux, t_aux)))
+    }
+
+    /// Replicates like `PoRep::replicate`, building tree_c while the layers are generated
+    /// instead of reading the columns back from the label stores. This holds the labels of all
+    /// layers in memory, so it is only suited to small sectors, see `IncrementalTreeC`.
+    #[allow(clippy::type_complexity)]
+    pub fn replicate_with_incremental_tree_c(
+        pp: &'a PublicParams<H>,
+        replica_id: &H::Domain,
+        data: Data<'a>,
+        data_tree: Option<BinaryTree<G>>,
+        config: StoreConfig,
+        replica_path: PathBuf,
+    ) -> Result<(
+        <Self as PoRep<'a, H, G>>::Tau,
+        <Self as PoRep<'a, H, G>>::ProverAux,
+    )> {
+        let (tau, p_aux, t_aux) = Self::transform_and_replicate_layers(
+            pp,
+            replica_id,
+            data,
+            data_tree,
+            config,
+            replica_path,
+            true,
+        )?;
+
+        Ok((tau, (p_aux, t_aux)))
+    }
+
     /// Phase1 of replication.
     pub fn replicate_phase1(
         pp: &'a PublicParams<H>,
@@ -586,7 +698,7 @@ impl<'a, H: 'static + Hasher, G: 'static + Hasher> StackedDrg<'a, H, G> {
         info!("replicate_phase1");
 
         let (_, labels) = measure_op(EncodeWindowTimeAll, || {
-            Self::generate_labels(&pp.graph, &pp.layer_challenges, replica_id, config)
+            Self::generate_labels(&pp.graph, &pp.layer_challenges, replica_id, config, None)
         })?;
 
         Ok(labels)
@@ -609,13 +721,13 @@ impl<'a, H: 'static + Hasher, G: 'static + Hasher> StackedDrg<'a, H, G> {
         info!("replicate_phase2");
 
         let (tau, paux, taux) = Self::transform_and_replicate_layers_inner(
-            &pp.graph,
-            &pp.layer_challenges,
+            pp,
             data,
             Some(data_tree),
             config,
             replica_path,
             labels,
+            None,
         )?;
 
         Ok((tau, (paux, taux)))
@@ -626,6 +738,8 @@ impl<'a, H: 'static + Hasher, G: 'static + Hasher> StackedDrg<'a, H, G> {
 mod tests {
     use super::*;
 
+    use std::path::Path;
+
     use ff::Field;
     use paired::bls12_381::{Bls12, Fr};
     use rand::{Rng, SeedableRng};
@@ -633,8 +747,11 @@ mod tests {
 
     use crate::drgraph::{new_seed, BASE_DEGREE};
     use crate::fr32::fr_into_bytes;
-    use crate::hasher::{Blake2sHasher, PedersenHasher, PoseidonHasher, Sha256Hasher};
-    use crate::porep::stacked::{PrivateInputs, SetupParams, EXP_DEGREE};
+    use crate::hasher::{
+        Blake2sHasher, HashFunction, PedersenHasher, PoseidonHasher, Sha256Hasher,
+    };
+    use crate::parameter_cache::ParameterSetMetadata;
+    use crate::porep::stacked::{CommRBindingKind, PrivateInputs, SetupParams, EXP_DEGREE};
     use crate::porep::PoRep;
     use crate::proof::ProofScheme;
 
@@ -737,6 +854,202 @@ mod tests {
         assert_eq!(data, decoded_data);
     }
 
+    #[test]
+    fn replicate_retain_layers() {
+        type H = PoseidonHasher;
+
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let replica_id: <H as Hasher>::Domain = Domain::random(rng);
+        let nodes = 64;
+        let layers = 2;
+
+        let data: Vec<u8> = (0..nodes)
+            .flat_map(|_| fr_into_bytes::<Bls12>(&Fr::random(rng)))
+            .collect();
+
+        let sp = SetupParams {
+            nodes,
+            degree: BASE_DEGREE,
+            expansion_degree: EXP_DEGREE,
+            seed: new_seed(),
+            layer_challenges: LayerChallenges::new(layers, 1),
+        };
+        let pp = StackedDrg::<H, Blake2sHasher>::setup(&sp).expect("setup failed");
+
+        for retain_layers in &[false, true] {
+            let cache_dir = tempfile::tempdir().unwrap();
+            let config = StoreConfig::new(
+                cache_dir.path(),
+                CacheKey::CommDTree.to_string(),
+                StoreConfig::default_cached_above_base_layer(nodes, BINARY_ARITY),
+            );
+            let replica_path = cache_dir.path().join("replica-path");
+
+            let mut data_copy = data.clone();
+            let (_, (_, t_aux)) = StackedDrg::<H, Blake2sHasher>::replicate_with_options(
+                &pp,
+                &replica_id,
+                (&mut data_copy[..]).into(),
+                None,
+                config,
+                replica_path,
+                *retain_layers,
+            )
+            .expect("replication failed");
+
+            assert_eq!(t_aux.labels.len(), layers);
+            TemporaryAux::<H, Blake2sHasher>::clear_temp(t_aux.clone())
+                .expect("t_aux delete failed");
+
+            for (layer, label) in t_aux.labels.labels.iter().enumerate() {
+                let exists = Path::new(&StoreConfig::data_path(&label.path, &label.id)).exists();
+                assert_eq!(exists, *retain_layers, "layer {}", layer + 1);
+
+                if *retain_layers {
+                    let store = t_aux
+                        .labels_for_layer(layer + 1)
+                        .expect("failed to open retained layer");
+                    assert_eq!(Store::len(&store), nodes);
+                    store
+                        .read_at(nodes - 1)
+                        .expect("failed to read retained layer");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn replicate_comm_r_binding() {
+        type H = PoseidonHasher;
+
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let replica_id: <H as Hasher>::Domain = Domain::random(rng);
+        let nodes = 64;
+
+        let data: Vec<u8> = (0..nodes)
+            .flat_map(|_| fr_into_bytes::<Bls12>(&Fr::random(rng)))
+            .collect();
+
+        let sp = SetupParams {
+            nodes,
+            degree: BASE_DEGREE,
+            expansion_degree: EXP_DEGREE,
+            seed: new_seed(),
+            layer_challenges: LayerChallenges::new(2, 1),
+        };
+        let pp = StackedDrg::<H, Blake2sHasher>::setup(&sp).expect("setup failed");
+
+        let replicate = |pp: &PublicParams<H>| {
+            let cache_dir = tempfile::tempdir().unwrap();
+            let config = StoreConfig::new(
+                cache_dir.path(),
+                CacheKey::CommDTree.to_string(),
+                StoreConfig::default_cached_above_base_layer(nodes, BINARY_ARITY),
+            );
+
+            let mut data_copy = data.clone();
+            let (tau, (p_aux, _)) = StackedDrg::<H, Blake2sHasher>::replicate(
+                pp,
+                &replica_id,
+                (&mut data_copy[..]).into(),
+                None,
+                config,
+                cache_dir.path().join("replica-path"),
+            )
+            .expect("replication failed");
+            (tau.comm_r, p_aux)
+        };
+
+        // The default binding is the plain hash of both commitments.
+        let (comm_r, p_aux) = replicate(&pp);
+        assert_eq!(
+            comm_r,
+            <H as Hasher>::Function::hash2(&p_aux.comm_c, &p_aux.comm_r_last)
+        );
+
+        let tagged_pp = PublicParams::from(&pp).with_comm_r_binding(CommRBindingKind::Tagged);
+        assert_ne!(pp.identifier(), tagged_pp.identifier());
+
+        let (tagged_comm_r, tagged_p_aux) = replicate(&tagged_pp);
+        assert_eq!(tagged_p_aux.comm_c, p_aux.comm_c);
+        assert_eq!(tagged_p_aux.comm_r_last, p_aux.comm_r_last);
+        assert_ne!(tagged_comm_r, comm_r);
+        assert_eq!(replicate(&tagged_pp).0, tagged_comm_r);
+    }
+
+    #[test]
+    fn replicate_incremental_tree_c() {
+        type H = PoseidonHasher;
+        type G = Blake2sHasher;
+
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let replica_id: <H as Hasher>::Domain = Domain::random(rng);
+        let nodes = 64;
+
+        let data: Vec<u8> = (0..nodes)
+            .flat_map(|_| fr_into_bytes::<Bls12>(&Fr::random(rng)))
+            .collect();
+
+        let sp = SetupParams {
+            nodes,
+            degree: BASE_DEGREE,
+            expansion_degree: EXP_DEGREE,
+            seed: new_seed(),
+            layer_challenges: LayerChallenges::new(2, 1),
+        };
+        let pp = StackedDrg::<H, G>::setup(&sp).expect("setup failed");
+
+        let cache_dir = tempfile::tempdir().unwrap();
+        let config = StoreConfig::new(
+            cache_dir.path(),
+            CacheKey::CommDTree.to_string(),
+            StoreConfig::default_cached_above_base_layer(nodes, BINARY_ARITY),
+        );
+        let mut data_copy = data.clone();
+        let (tau, (p_aux, _)) = StackedDrg::<H, G>::replicate_with_incremental_tree_c(
+            &pp,
+            &replica_id,
+            (&mut data_copy[..]).into(),
+            None,
+            config,
+            cache_dir.path().join("replica-path"),
+        )
+        .expect("replication failed");
+
+        // The second phase reads the columns back from the label stores of the first.
+        let cache_dir = tempfile::tempdir().unwrap();
+        let config = StoreConfig::new(
+            cache_dir.path(),
+            CacheKey::CommDTree.to_string(),
+            StoreConfig::default_cached_above_base_layer(nodes, BINARY_ARITY),
+        );
+        let labels = StackedDrg::<H, G>::replicate_phase1(&pp, &replica_id, config.clone())
+            .expect("phase1 failed");
+        let data_tree = StackedDrg::<H, G>::build_binary_tree::<G>(
+            &data,
+            StoreConfig::from_config(
+                &config,
+                "data-tree".to_string(),
+                Some(get_merkle_tree_len(nodes, BINARY_ARITY).unwrap()),
+            ),
+        )
+        .expect("failed to build tree_d");
+        let mut data_copy = data.clone();
+        let (phased_tau, (phased_p_aux, _)) = StackedDrg::<H, G>::replicate_phase2(
+            &pp,
+            labels,
+            (&mut data_copy[..]).into(),
+            data_tree,
+            config,
+            cache_dir.path().join("replica-path"),
+        )
+        .expect("phase2 failed");
+
+        assert_eq!(p_aux.comm_c, phased_p_aux.comm_c);
+        assert_eq!(p_aux.comm_r_last, phased_p_aux.comm_r_last);
+        assert_eq!(tau.comm_r, phased_tau.comm_r);
+    }
+
     fn prove_verify_fixed(n: usize) {
         let challenges = LayerChallenges::new(DEFAULT_STACKED_LAYERS, 5);
 
@@ -839,6 +1152,69 @@ mod tests {
         assert!(proofs_are_valid);
     }
 
+    fn test_aux_cache_tree_proofs<H: 'static + Hasher>() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let nodes = 64;
+        let replica_id: H::Domain = H::Domain::random(rng);
+        let mut data: Vec<u8> = (0..nodes)
+            .flat_map(|_| fr_into_bytes::<Bls12>(&Fr::random(rng)))
+            .collect();
+
+        let sp = SetupParams {
+            nodes,
+            degree: BASE_DEGREE,
+            expansion_degree: EXP_DEGREE,
+            seed: new_seed(),
+            layer_challenges: LayerChallenges::new(DEFAULT_STACKED_LAYERS, 5),
+        };
+
+        let cache_dir = tempfile::tempdir().unwrap();
+        let config = StoreConfig::new(
+            cache_dir.path(),
+            CacheKey::CommDTree.to_string(),
+            StoreConfig::default_cached_above_base_layer(nodes, BINARY_ARITY),
+        );
+        let replica_path = cache_dir.path().join("replica-path");
+
+        let pp = StackedDrg::<H, Blake2sHasher>::setup(&sp).expect("setup failed");
+        let (_, (p_aux, t_aux)) = StackedDrg::<H, Blake2sHasher>::replicate(
+            &pp,
+            &replica_id,
+            (&mut data[..]).into(),
+            None,
+            config,
+            replica_path.clone(),
+        )
+        .expect("replication failed");
+
+        let t_aux = TemporaryAuxCache::<H, Blake2sHasher>::new(&t_aux, replica_path)
+            .expect("failed to restore contents of t_aux");
+
+        for leaf in &[0, 1, 31, nodes - 1] {
+            let tree_r_proof = t_aux.tree_r_proof(*leaf).unwrap();
+            assert!(tree_r_proof.validate(*leaf));
+            assert_eq!(tree_r_proof.root(), &p_aux.comm_r_last);
+
+            let tree_c_proof = t_aux.tree_c_proof(*leaf).unwrap();
+            assert!(tree_c_proof.validate(*leaf));
+            assert_eq!(tree_c_proof.root(), &p_aux.comm_c);
+        }
+
+        assert!(t_aux.tree_r_proof(nodes).is_err());
+        assert!(t_aux.tree_c_proof(nodes).is_err());
+    }
+
+    #[test]
+    fn aux_cache_tree_proofs_pedersen() {
+        test_aux_cache_tree_proofs::<PedersenHasher>();
+    }
+
+    #[test]
+    fn aux_cache_tree_proofs_poseidon() {
+        test_aux_cache_tree_proofs::<PoseidonHasher>();
+    }
+
     table_tests! {
         prove_verify_fixed {
            prove_verify_fixed_64_64(64);
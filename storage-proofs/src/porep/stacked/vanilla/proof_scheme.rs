@@ -1,3 +1,4 @@
+#[cfg(not(feature = "verify-only"))]
 use anyhow::ensure;
 use log::trace;
 use rayon::prelude::*;
@@ -11,7 +12,7 @@ use super::{
 
 use crate::drgraph::Graph;
 use crate::error::Result;
-use crate::hasher::{HashFunction, Hasher};
+use crate::hasher::Hasher;
 use crate::proof::ProofScheme;
 
 impl<'a, 'c, H: 'static + Hasher, G: 'static + Hasher> ProofScheme<'a> for StackedDrg<'c, H, G> {
@@ -33,6 +34,7 @@ impl<'a, 'c, H: 'static + Hasher, G: 'static + Hasher> ProofScheme<'a> for Stack
         Ok(PublicParams::new(graph, sp.layer_challenges.clone()))
     }
 
+    #[cfg(not(feature = "verify-only"))]
     fn prove<'b>(
         pub_params: &'b Self::PublicParams,
         pub_inputs: &'b Self::PublicInputs,
@@ -54,6 +56,7 @@ impl<'a, 'c, H: 'static + Hasher, G: 'static + Hasher> ProofScheme<'a> for Stack
         Ok(proofs[k].to_owned())
     }
 
+    #[cfg(not(feature = "verify-only"))]
     fn prove_all_partitions<'b>(
         pub_params: &'b Self::PublicParams,
         pub_inputs: &'b Self::PublicInputs,
@@ -102,7 +105,7 @@ impl<'a, 'c, H: 'static + Hasher, G: 'static + Hasher> ProofScheme<'a> for Stack
             let actual_comm_r: H::Domain = {
                 let comm_c = proofs[0].comm_c();
                 let comm_r_last = proofs[0].comm_r_last();
-                H::Function::hash2(comm_c, comm_r_last)
+                pub_params.comm_r_binding.bind::<H>(comm_c, comm_r_last)
             };
 
             if expected_comm_r != &actual_comm_r {
@@ -15,6 +15,7 @@ use crate::gadgets::variables::Root;
 use crate::hasher::{
     HashFunction, Hasher, PoseidonArity, PoseidonEngine, PoseidonFunction, PoseidonMDArity,
 };
+use crate::porep::stacked::CommRBindingKind;
 
 /// This is the `ElectionPoSt` circuit.
 pub struct ElectionPoStCircuit<'a, E: JubjubEngine, H: Hasher> {
@@ -30,6 +31,8 @@ pub struct ElectionPoStCircuit<'a, E: JubjubEngine, H: Hasher> {
     pub randomness: Option<E::Fr>,
     pub prover_id: Option<E::Fr>,
     pub sector_id: Option<E::Fr>,
+    /// How `comm_r` binds `comm_c` and `comm_r_last`.
+    pub comm_r_binding: CommRBindingKind,
     pub _h: PhantomData<H>,
 }
 
@@ -62,6 +65,7 @@ where
         let randomness = self.randomness;
         let prover_id = self.prover_id;
         let sector_id = self.sector_id;
+        let comm_r_binding = self.comm_r_binding;
 
         assert_eq!(paths.len(), leafs.len());
 
@@ -87,9 +91,9 @@ where
 
         comm_r_num.inputize(cs.namespace(|| "comm_r_input"))?;
 
-        // Verify H(Comm_C || comm_r_last) == comm_r
+        // Verify that comm_r binds comm_c and comm_r_last
         {
-            let hash_num = H::Function::hash2_circuit(
+            let hash_num = comm_r_binding.bind_circuit::<_, H, _>(
                 cs.namespace(|| "H_comm_c_comm_r_last"),
                 &comm_c_num,
                 &comm_r_last_num,
@@ -204,7 +208,7 @@ mod tests {
     use crate::drgraph::{new_seed, BucketGraph, Graph, BASE_DEGREE};
     use crate::fr32::fr_into_bytes;
     use crate::gadgets::TestConstraintSystem;
-    use crate::hasher::{Domain, HashFunction, Hasher, PedersenHasher, PoseidonHasher};
+    use crate::hasher::{Domain, Hasher, PedersenHasher, PoseidonHasher};
     use crate::merkle::OctLCMerkleTree;
     use crate::porep::stacked::OCT_ARITY;
     use crate::post::election::{self, ElectionPoSt, ElectionPoStCompound};
@@ -214,15 +218,31 @@ mod tests {
 
     #[test]
     fn test_election_post_circuit_pedersen() {
-        test_election_post_circuit::<PedersenHasher>(389_883);
+        assert_eq!(
+            test_election_post_circuit::<PedersenHasher>(CommRBindingKind::default()),
+            389_883,
+            "wrong number of constraints"
+        );
     }
 
     #[test]
     fn test_election_post_circuit_poseidon() {
-        test_election_post_circuit::<PoseidonHasher>(24_426);
+        assert_eq!(
+            test_election_post_circuit::<PoseidonHasher>(CommRBindingKind::default()),
+            24_426,
+            "wrong number of constraints"
+        );
     }
 
-    fn test_election_post_circuit<H: Hasher>(expected_constraints: usize) {
+    #[test]
+    fn test_election_post_circuit_tagged_comm_r_poseidon() {
+        let default = test_election_post_circuit::<PoseidonHasher>(CommRBindingKind::default());
+        let tagged = test_election_post_circuit::<PoseidonHasher>(CommRBindingKind::Tagged);
+        assert!(tagged > default, "the tagged binding hashes twice");
+    }
+
+    /// Proves and checks an `ElectionPoSt` circuit, returning its number of constraints.
+    fn test_election_post_circuit<H: Hasher>(comm_r_binding: CommRBindingKind) -> usize {
         use std::fs::File;
         use std::io::prelude::*;
 
@@ -238,6 +258,7 @@ mod tests {
             sector_size: sector_size as u64,
             challenge_count: 20,
             challenged_nodes: 1,
+            comm_r_binding,
         };
 
         let mut sectors: Vec<SectorId> = Vec::new();
@@ -284,7 +305,7 @@ mod tests {
         let tree = trees.remove(&candidate.sector_id).unwrap();
         let comm_r_last = tree.root();
         let comm_c = H::Domain::random(rng);
-        let comm_r = H::Function::hash2(&comm_c, &comm_r_last);
+        let comm_r = comm_r_binding.bind::<H>(&comm_c, &comm_r_last);
 
         let pub_inputs = election::PublicInputs {
             randomness,
@@ -339,6 +360,7 @@ mod tests {
             randomness: Some(randomness.into()),
             prover_id: Some(prover_id.into()),
             sector_id: Some(candidate.sector_id.into()),
+            comm_r_binding,
             _h: PhantomData,
         };
 
@@ -349,11 +371,6 @@ mod tests {
         assert!(cs.is_satisfied(), "constraints not satisfied");
 
         assert_eq!(cs.num_inputs(), 23, "wrong number of inputs");
-        assert_eq!(
-            cs.num_constraints(),
-            expected_constraints,
-            "wrong number of constraints"
-        );
         assert_eq!(cs.get_input(0, "ONE"), Fr::one());
 
         let generated_inputs =
@@ -372,5 +389,7 @@ mod tests {
             expected_inputs.len() - 1,
             "inputs are not the same length"
         );
+
+        cs.num_constraints()
     }
 }
@@ -1,5 +1,6 @@
 use std::marker::PhantomData;
 
+use anyhow::Context;
 use bellperson::Circuit;
 use fil_sapling_crypto::jubjub::JubjubEngine;
 use generic_array::typenum;
@@ -11,7 +12,7 @@ use crate::crypto::pedersen::JJ_PARAMS;
 use crate::drgraph;
 use crate::error::Result;
 use crate::gadgets::por::PoRCompound;
-use crate::hasher::Hasher;
+use crate::hasher::{Domain, Hasher};
 use crate::parameter_cache::{CacheableParameters, ParameterSetMetadata};
 use crate::por;
 use crate::post::election::{self, ElectionPoSt, ElectionPoStCircuit};
@@ -50,9 +51,9 @@ where
             private: true,
         };
 
-        // 1. Inputs for verifying comm_r = H(comm_c || comm_r_last)
+        // 1. Inputs for verifying that comm_r binds comm_c and comm_r_last
 
-        inputs.push(pub_inputs.comm_r.into());
+        inputs.push(pub_inputs.comm_r.try_into_fr().context("invalid comm_r")?);
 
         // 2. Inputs for verifying inclusion paths
 
@@ -88,7 +89,7 @@ where
         pub_in: &<ElectionPoSt<'a, H> as ProofScheme<'a>>::PublicInputs,
         _priv_in: <ElectionPoStCircuit<'a, Bls12, H> as CircuitComponent>::ComponentPrivateInputs,
         vanilla_proof: &<ElectionPoSt<'a, H> as ProofScheme<'a>>::Proof,
-        _pub_params: &<ElectionPoSt<'a, H> as ProofScheme<'a>>::PublicParams,
+        pub_params: &<ElectionPoSt<'a, H> as ProofScheme<'a>>::PublicParams,
     ) -> Result<ElectionPoStCircuit<'a, Bls12, H>> {
         let comm_r = pub_in.comm_r.into();
         let comm_c = vanilla_proof.comm_c.into();
@@ -126,6 +127,7 @@ where
             randomness: Some(pub_in.randomness.into()),
             prover_id: Some(pub_in.prover_id.into()),
             sector_id: Some(pub_in.sector_id.into()),
+            comm_r_binding: pub_params.comm_r_binding,
             _h: PhantomData,
         })
     }
@@ -154,6 +156,7 @@ where
             randomness: None,
             prover_id: None,
             sector_id: None,
+            comm_r_binding: pub_params.comm_r_binding,
             _h: PhantomData,
         }
     }
@@ -174,7 +177,7 @@ mod tests {
     use crate::drgraph::{new_seed, BucketGraph, Graph, BASE_DEGREE};
     use crate::fr32::fr_into_bytes;
     use crate::gadgets::{MetricCS, TestConstraintSystem};
-    use crate::hasher::{Domain, HashFunction, Hasher, PedersenHasher, PoseidonHasher};
+    use crate::hasher::{Domain, Hasher, PedersenHasher, PoseidonHasher};
     use crate::merkle::OctLCMerkleTree;
     use crate::porep::stacked::OCT_ARITY;
     use crate::post::election;
@@ -260,7 +263,10 @@ mod tests {
         let tree = trees.remove(&candidate.sector_id).unwrap();
         let comm_r_last = tree.root();
         let comm_c = H::Domain::random(rng);
-        let comm_r = H::Function::hash2(&comm_c, &comm_r_last);
+        let comm_r = pub_params
+            .vanilla_params
+            .comm_r_binding
+            .bind::<H>(&comm_c, &comm_r_last);
 
         let pub_inputs = election::PublicInputs {
             randomness,
@@ -5,7 +5,9 @@ use std::marker::PhantomData;
 use anyhow::{bail, ensure, Context};
 use byteorder::{ByteOrder, LittleEndian};
 use generic_array::typenum;
+#[cfg(not(feature = "verify-only"))]
 use log::trace;
+#[cfg(not(feature = "verify-only"))]
 use merkletree::store::StoreConfig;
 use paired::bls12_381::{Bls12, Fr};
 use rayon::prelude::*;
@@ -22,6 +24,8 @@ use crate::hasher::{
 use crate::measurements::{measure_op, Operation};
 use crate::merkle::{MerkleProof, OctLCMerkleTree};
 use crate::parameter_cache::ParameterSetMetadata;
+use crate::porep::stacked::CommRBindingKind;
+#[cfg(not(feature = "verify-only"))]
 use crate::porep::stacked::OCT_ARITY;
 use crate::proof::{NoRequirements, ProofScheme};
 use crate::sector::*;
@@ -41,15 +45,27 @@ pub struct PublicParams {
     pub sector_size: u64,
     pub challenge_count: usize,
     pub challenged_nodes: usize,
+    /// How the sector's `comm_r` binds `comm_c` and `comm_r_last`. It must match the binding
+    /// the sector was sealed with.
+    pub comm_r_binding: CommRBindingKind,
+}
+
+impl PublicParams {
+    /// Returns these parameters with `comm_r` checked against `comm_r_binding`.
+    pub fn with_comm_r_binding(mut self, comm_r_binding: CommRBindingKind) -> Self {
+        self.comm_r_binding = comm_r_binding;
+        self
+    }
 }
 
 impl ParameterSetMetadata for PublicParams {
     fn identifier(&self) -> String {
         format!(
-            "ElectionPoSt::PublicParams{{sector_size: {}, count: {}, nodes: {}}}",
+            "ElectionPoSt::PublicParams{{sector_size: {}, count: {}, nodes: {}{}}}",
             self.sector_size(),
             self.challenge_count,
             self.challenged_nodes,
+            self.comm_r_binding.identifier(),
         )
     }
 
@@ -324,9 +340,11 @@ impl<'a, H: 'a + Hasher> ProofScheme<'a> for ElectionPoSt<'a, H> {
             sector_size: sp.sector_size,
             challenge_count: sp.challenge_count,
             challenged_nodes: sp.challenged_nodes,
+            comm_r_binding: CommRBindingKind::default(),
         })
     }
 
+    #[cfg(not(feature = "verify-only"))]
     fn prove<'b>(
         pub_params: &'b Self::PublicParams,
         pub_inputs: &'b Self::PublicInputs,
@@ -392,13 +410,13 @@ impl<'a, H: 'a + Hasher> ProofScheme<'a> for ElectionPoSt<'a, H> {
         pub_inputs: &Self::PublicInputs,
         proof: &Self::Proof,
     ) -> Result<bool> {
-        // verify that H(Comm_c || Comm_r_last) == Comm_R
+        // verify that comm_r binds comm_c and comm_r_last
         // comm_r_last is the root of the proof
         let comm_r_last = proof.inclusion_proofs[0].root();
         let comm_c = proof.comm_c;
         let comm_r = &pub_inputs.comm_r;
 
-        if AsRef::<[u8]>::as_ref(&H::Function::hash2(&comm_c, comm_r_last))
+        if AsRef::<[u8]>::as_ref(&pub_params.comm_r_binding.bind::<H>(&comm_c, comm_r_last))
             != AsRef::<[u8]>::as_ref(comm_r)
         {
             return Ok(false);
@@ -452,7 +470,7 @@ mod tests {
     use crate::fr32::fr_into_bytes;
     use crate::hasher::{PedersenHasher, PoseidonHasher};
 
-    fn test_election_post<H: Hasher>() {
+    fn test_election_post<H: Hasher>(comm_r_binding: CommRBindingKind) {
         let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
 
         let leaves = 64;
@@ -462,6 +480,7 @@ mod tests {
             sector_size: sector_size as u64,
             challenge_count: 40,
             challenged_nodes: 1,
+            comm_r_binding,
         };
 
         let randomness = H::Domain::random(rng);
@@ -505,7 +524,7 @@ mod tests {
         let tree = trees.remove(&candidate.sector_id).unwrap();
         let comm_r_last = tree.root();
         let comm_c = H::Domain::random(rng);
-        let comm_r = H::Function::hash2(&comm_c, &comm_r_last);
+        let comm_r = comm_r_binding.bind::<H>(&comm_c, &comm_r_last);
 
         let pub_inputs = PublicInputs {
             randomness,
@@ -529,15 +548,31 @@ mod tests {
             .expect("verification failed");
 
         assert!(is_valid);
+
+        let other_binding = match comm_r_binding {
+            CommRBindingKind::Hash2 => CommRBindingKind::Tagged,
+            CommRBindingKind::Tagged => CommRBindingKind::Hash2,
+        };
+        let other_params = pub_params.clone().with_comm_r_binding(other_binding);
+        assert!(
+            !ElectionPoSt::<H>::verify(&other_params, &pub_inputs, &proof)
+                .expect("verification failed"),
+            "a proof must not verify under another comm_r binding"
+        );
     }
 
     #[test]
     fn election_post_pedersen() {
-        test_election_post::<PedersenHasher>();
+        test_election_post::<PedersenHasher>(CommRBindingKind::default());
     }
 
     #[test]
     fn election_post_poseidon() {
-        test_election_post::<PoseidonHasher>();
+        test_election_post::<PoseidonHasher>(CommRBindingKind::default());
+    }
+
+    #[test]
+    fn election_post_tagged_comm_r_poseidon() {
+        test_election_post::<PoseidonHasher>(CommRBindingKind::Tagged);
     }
 }
@@ -10,7 +10,8 @@ use crate::error::Result;
 use crate::gadgets::constraint;
 use crate::gadgets::por::PoRCircuit;
 use crate::gadgets::variables::Root;
-use crate::hasher::{HashFunction, Hasher, PoseidonArity, PoseidonEngine};
+use crate::hasher::{Hasher, PoseidonArity, PoseidonEngine};
+use crate::porep::stacked::CommRBindingKind;
 
 /// This is the `RationalPoSt` circuit.
 pub struct RationalPoStCircuit<'a, E: JubjubEngine, H: Hasher> {
@@ -22,6 +23,8 @@ pub struct RationalPoStCircuit<'a, E: JubjubEngine, H: Hasher> {
     pub leafs: Vec<Option<E::Fr>>,
     #[allow(clippy::type_complexity)]
     pub paths: Vec<Vec<(Vec<Option<E::Fr>>, Option<usize>)>>,
+    /// How each `comm_r` binds its `comm_c` and `comm_r_last`.
+    pub comm_r_binding: CommRBindingKind,
     pub _h: PhantomData<H>,
 }
 
@@ -44,6 +47,7 @@ where
         let comm_r_lasts = self.comm_r_lasts;
         let leafs = self.leafs;
         let paths = self.paths;
+        let comm_r_binding = self.comm_r_binding;
 
         assert_eq!(paths.len(), leafs.len());
         assert_eq!(paths.len(), comm_rs.len());
@@ -79,9 +83,9 @@ where
 
             comm_r_num.inputize(cs.namespace(|| format!("comm_r_{}_input", i)))?;
 
-            // Verify H(Comm_C || comm_r_last) == comm_r
+            // Verify that comm_r binds comm_c and comm_r_last
             {
-                let hash_num = H::Function::hash2_circuit(
+                let hash_num = comm_r_binding.bind_circuit::<_, H, _>(
                     cs.namespace(|| format!("H_comm_c_comm_r_last_{}", i)),
                     &comm_c_num,
                     &comm_r_last_num,
@@ -127,7 +131,7 @@ mod tests {
     use crate::drgraph::{new_seed, BucketGraph, Graph, BASE_DEGREE};
     use crate::fr32::fr_into_bytes;
     use crate::gadgets::TestConstraintSystem;
-    use crate::hasher::{Domain, HashFunction, Hasher, PedersenHasher, PoseidonHasher};
+    use crate::hasher::{Domain, Hasher, PedersenHasher, PoseidonHasher};
     use crate::post::rational::{self, derive_challenges, RationalPoSt, RationalPoStCompound};
     use crate::proof::ProofScheme;
     use crate::sector::OrderedSectorSet;
@@ -135,15 +139,31 @@ mod tests {
 
     #[test]
     fn test_rational_post_circuit_pedersen() {
-        test_rational_post_circuit::<PedersenHasher>(16_490);
+        assert_eq!(
+            test_rational_post_circuit::<PedersenHasher>(CommRBindingKind::default()),
+            16_490,
+            "wrong number of constraints"
+        );
     }
 
     #[test]
     fn test_rational_post_circuit_poseidon() {
-        test_rational_post_circuit::<PoseidonHasher>(3_806);
+        assert_eq!(
+            test_rational_post_circuit::<PoseidonHasher>(CommRBindingKind::default()),
+            3_806,
+            "wrong number of constraints"
+        );
     }
 
-    fn test_rational_post_circuit<H: Hasher>(expected_constraints: usize) {
+    #[test]
+    fn test_rational_post_circuit_tagged_comm_r_poseidon() {
+        let default = test_rational_post_circuit::<PoseidonHasher>(CommRBindingKind::default());
+        let tagged = test_rational_post_circuit::<PoseidonHasher>(CommRBindingKind::Tagged);
+        assert!(tagged > default, "the tagged binding hashes twice");
+    }
+
+    /// Proves and checks a `RationalPoSt` circuit, returning its number of constraints.
+    fn test_rational_post_circuit<H: Hasher>(comm_r_binding: CommRBindingKind) -> usize {
         let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
 
         let leaves = 32;
@@ -153,6 +173,7 @@ mod tests {
         let pub_params = rational::PublicParams {
             sector_size,
             challenges_count,
+            comm_r_binding,
         };
 
         let data1: Vec<u8> = (0..leaves)
@@ -191,7 +212,7 @@ mod tests {
         let comm_rs: Vec<_> = comm_cs
             .iter()
             .zip(comm_r_lasts.iter())
-            .map(|(comm_c, comm_r_last)| H::Function::hash2(comm_c, comm_r_last))
+            .map(|(comm_c, comm_r_last)| comm_r_binding.bind::<H>(comm_c, comm_r_last))
             .collect();
 
         let pub_inputs = rational::PublicInputs {
@@ -244,6 +265,7 @@ mod tests {
             comm_rs: comm_rs.iter().copied().map(|c| Some(c.into())).collect(),
             comm_cs: comm_cs.into_iter().map(|c| Some(c.into())).collect(),
             comm_r_lasts: comm_r_lasts.into_iter().map(|c| Some(c.into())).collect(),
+            comm_r_binding,
             _h: PhantomData,
         };
 
@@ -254,11 +276,6 @@ mod tests {
         assert!(cs.is_satisfied(), "constraints not satisfied");
 
         assert_eq!(cs.num_inputs(), 5, "wrong number of inputs");
-        assert_eq!(
-            cs.num_constraints(),
-            expected_constraints,
-            "wrong number of constraints"
-        );
         assert_eq!(cs.get_input(0, "ONE"), Fr::one());
 
         let generated_inputs =
@@ -277,5 +294,7 @@ mod tests {
             expected_inputs.len() - 1,
             "inputs are not the same length"
         );
+
+        cs.num_constraints()
     }
 }
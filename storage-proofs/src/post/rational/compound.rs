@@ -1,6 +1,6 @@
 use std::marker::PhantomData;
 
-use anyhow::ensure;
+use anyhow::{ensure, Context};
 use bellperson::{Circuit, ConstraintSystem, SynthesisError};
 use fil_sapling_crypto::jubjub::JubjubEngine;
 use generic_array::typenum;
@@ -11,9 +11,10 @@ use crate::crypto::pedersen::JJ_PARAMS;
 use crate::drgraph;
 use crate::error::Result;
 use crate::gadgets::por::PoRCompound;
-use crate::hasher::{Hasher, PoseidonArity, PoseidonEngine};
+use crate::hasher::{Domain, Hasher, PoseidonArity, PoseidonEngine};
 use crate::parameter_cache::{CacheableParameters, ParameterSetMetadata};
 use crate::por;
+use crate::porep::stacked::CommRBindingKind;
 use crate::post::rational::{RationalPoSt, RationalPoStCircuit};
 use crate::proof::ProofScheme;
 use crate::util::NODE_SIZE;
@@ -56,7 +57,7 @@ where
         );
 
         for (challenge, comm_r) in pub_in.challenges.iter().zip(pub_in.comm_rs.iter()) {
-            inputs.push((*comm_r).into());
+            inputs.push(comm_r.try_into_fr().context("invalid comm_r")?);
 
             let por_pub_inputs = por::PublicInputs {
                 commitment: None,
@@ -78,7 +79,7 @@ where
         pub_in: &<RationalPoSt<'a, H> as ProofScheme<'a>>::PublicInputs,
         _priv_in: <RationalPoStCircuit<'a, Bls12, H> as CircuitComponent>::ComponentPrivateInputs,
         vanilla_proof: &<RationalPoSt<'a, H> as ProofScheme<'a>>::Proof,
-        _pub_params: &<RationalPoSt<'a, H> as ProofScheme<'a>>::PublicParams,
+        pub_params: &<RationalPoSt<'a, H> as ProofScheme<'a>>::PublicParams,
     ) -> Result<RationalPoStCircuit<'a, Bls12, H>> {
         let comm_rs: Vec<_> = pub_in.comm_rs.iter().map(|c| Some((*c).into())).collect();
         let comm_cs: Vec<_> = vanilla_proof
@@ -121,6 +122,7 @@ where
             comm_cs,
             comm_r_lasts,
             paths,
+            comm_r_binding: pub_params.comm_r_binding,
             _h: PhantomData,
         })
     }
@@ -145,6 +147,7 @@ where
             comm_r_lasts,
             leafs,
             paths,
+            comm_r_binding: pub_params.comm_r_binding,
             _h: PhantomData,
         }
     }
@@ -163,6 +166,7 @@ where
         comm_cs: Vec<Option<E::Fr>>,
         comm_r_lasts: Vec<Option<E::Fr>>,
         paths: Vec<Vec<(Vec<Option<E::Fr>>, Option<usize>)>>,
+        comm_r_binding: CommRBindingKind,
     ) -> Result<(), SynthesisError> {
         Self {
             params,
@@ -171,6 +175,7 @@ where
             comm_cs,
             comm_r_lasts,
             paths,
+            comm_r_binding,
             _h: PhantomData,
         }
         .synthesize(cs)
@@ -191,7 +196,7 @@ mod tests {
     use crate::drgraph::{new_seed, BucketGraph, Graph, BASE_DEGREE};
     use crate::fr32::fr_into_bytes;
     use crate::gadgets::TestConstraintSystem;
-    use crate::hasher::{Domain, HashFunction, Hasher, PedersenHasher, PoseidonHasher};
+    use crate::hasher::{Domain, Hasher, PedersenHasher, PoseidonHasher};
     use crate::post::rational::{self, derive_challenges};
     use crate::proof::NoRequirements;
     use crate::sector::OrderedSectorSet;
@@ -263,7 +268,12 @@ mod tests {
         let comm_rs: Vec<_> = comm_cs
             .iter()
             .zip(comm_r_lasts.iter())
-            .map(|(comm_c, comm_r_last)| H::Function::hash2(comm_c, comm_r_last))
+            .map(|(comm_c, comm_r_last)| {
+                pub_params
+                    .vanilla_params
+                    .comm_r_binding
+                    .bind::<H>(comm_c, comm_r_last)
+            })
             .collect();
 
         let pub_inputs = rational::PublicInputs {
@@ -1,16 +1,19 @@
 use std::collections::{BTreeMap, HashSet};
 use std::marker::PhantomData;
 
-use anyhow::{bail, ensure, Context};
+#[cfg(not(feature = "verify-only"))]
+use anyhow::bail;
+use anyhow::{ensure, Context};
 use byteorder::{ByteOrder, LittleEndian};
 use generic_array::typenum;
 use serde::{Deserialize, Serialize};
 
 use crate::drgraph::graph_height;
 use crate::error::{Error, Result};
-use crate::hasher::{Domain, HashFunction, Hasher};
+use crate::hasher::{Domain, Hasher};
 use crate::merkle::{BinaryMerkleTree, MerkleProof};
 use crate::parameter_cache::ParameterSetMetadata;
+use crate::porep::stacked::CommRBindingKind;
 use crate::proof::{NoRequirements, ProofScheme};
 use crate::sector::*;
 use crate::util::NODE_SIZE;
@@ -30,14 +33,26 @@ pub struct PublicParams {
     pub sector_size: u64,
     /// How many challenges there are in total.
     pub challenges_count: usize,
+    /// How each sector's `comm_r` binds `comm_c` and `comm_r_last`. It must match the binding
+    /// the sectors were sealed with.
+    pub comm_r_binding: CommRBindingKind,
+}
+
+impl PublicParams {
+    /// Returns these parameters with `comm_r` checked against `comm_r_binding`.
+    pub fn with_comm_r_binding(mut self, comm_r_binding: CommRBindingKind) -> Self {
+        self.comm_r_binding = comm_r_binding;
+        self
+    }
 }
 
 impl ParameterSetMetadata for PublicParams {
     fn identifier(&self) -> String {
         format!(
-            "RationalPoSt::PublicParams{{sector_size: {} challenges_count: {}}}",
+            "RationalPoSt::PublicParams{{sector_size: {} challenges_count: {}{}}}",
             self.sector_size(),
             self.challenges_count,
+            self.comm_r_binding.identifier(),
         )
     }
 
@@ -115,9 +130,11 @@ impl<'a, H: 'a + Hasher> ProofScheme<'a> for RationalPoSt<'a, H> {
         Ok(PublicParams {
             sector_size: sp.sector_size,
             challenges_count: sp.challenges_count,
+            comm_r_binding: CommRBindingKind::default(),
         })
     }
 
+    #[cfg(not(feature = "verify-only"))]
     fn prove<'b>(
         _pub_params: &'b Self::PublicParams,
         pub_inputs: &'b Self::PublicInputs,
@@ -188,11 +205,11 @@ impl<'a, H: 'a + Hasher> ProofScheme<'a> for RationalPoSt<'a, H> {
         {
             let challenged_leaf = challenge.leaf;
 
-            // verify that H(Comm_c || Comm_r_last) == Comm_R
+            // verify that comm_r binds comm_c and comm_r_last
             // comm_r_last is the root of the proof
             let comm_r_last = merkle_proof.root();
 
-            if AsRef::<[u8]>::as_ref(&H::Function::hash2(comm_c, &comm_r_last))
+            if AsRef::<[u8]>::as_ref(&pub_params.comm_r_binding.bind::<H>(comm_c, &comm_r_last))
                 != AsRef::<[u8]>::as_ref(&comm_r)
             {
                 return Ok(false);
@@ -298,7 +315,7 @@ mod tests {
     use crate::hasher::{Blake2sHasher, PedersenHasher, PoseidonHasher, Sha256Hasher};
     use crate::merkle::make_proof_for_test;
 
-    fn test_rational_post<H: Hasher>() {
+    fn test_rational_post<H: Hasher>(comm_r_binding: CommRBindingKind) {
         let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
 
         let leaves = 64;
@@ -308,6 +325,7 @@ mod tests {
         let pub_params = PublicParams {
             sector_size,
             challenges_count,
+            comm_r_binding,
         };
 
         let data1: Vec<u8> = (0..leaves)
@@ -358,7 +376,7 @@ mod tests {
         let comm_rs: Vec<H::Domain> = comm_cs
             .iter()
             .zip(comm_r_lasts.iter())
-            .map(|(comm_c, comm_r_last)| H::Function::hash2(comm_c, comm_r_last))
+            .map(|(comm_c, comm_r_last)| pub_params.comm_r_binding.bind::<H>(comm_c, comm_r_last))
             .collect();
 
         let pub_inputs = PublicInputs {
@@ -380,26 +398,42 @@ mod tests {
             .expect("verification failed");
 
         assert!(is_valid);
+
+        let other_binding = match comm_r_binding {
+            CommRBindingKind::Hash2 => CommRBindingKind::Tagged,
+            CommRBindingKind::Tagged => CommRBindingKind::Hash2,
+        };
+        let other_params = pub_params.clone().with_comm_r_binding(other_binding);
+        assert!(
+            !RationalPoSt::<H>::verify(&other_params, &pub_inputs, &proof)
+                .expect("verification failed"),
+            "a proof must not verify under another comm_r binding"
+        );
     }
 
     #[test]
     fn rational_post_pedersen() {
-        test_rational_post::<PedersenHasher>();
+        test_rational_post::<PedersenHasher>(CommRBindingKind::default());
     }
 
     #[test]
     fn rational_post_sha256() {
-        test_rational_post::<Sha256Hasher>();
+        test_rational_post::<Sha256Hasher>(CommRBindingKind::default());
     }
 
     #[test]
     fn rational_post_blake2s() {
-        test_rational_post::<Blake2sHasher>();
+        test_rational_post::<Blake2sHasher>(CommRBindingKind::default());
     }
 
     #[test]
     fn rational_post_poseidon() {
-        test_rational_post::<PoseidonHasher>();
+        test_rational_post::<PoseidonHasher>(CommRBindingKind::default());
+    }
+
+    #[test]
+    fn rational_post_tagged_comm_r_poseidon() {
+        test_rational_post::<PoseidonHasher>(CommRBindingKind::Tagged);
     }
 
     // Construct a proof that satisfies a cursory validation:
@@ -429,6 +463,7 @@ mod tests {
         let pub_params = PublicParams {
             sector_size,
             challenges_count,
+            comm_r_binding: CommRBindingKind::default(),
         };
 
         let data: Vec<u8> = (0..leaves)
@@ -453,7 +488,7 @@ mod tests {
         let comm_rs: Vec<H::Domain> = comm_cs
             .iter()
             .zip(comm_r_lasts.iter())
-            .map(|(comm_c, comm_r_last)| H::Function::hash2(comm_c, comm_r_last))
+            .map(|(comm_c, comm_r_last)| pub_params.comm_r_binding.bind::<H>(comm_c, comm_r_last))
             .collect();
 
         let pub_inputs = PublicInputs::<H::Domain> {
@@ -507,6 +542,7 @@ mod tests {
         let pub_params = PublicParams {
             sector_size,
             challenges_count,
+            comm_r_binding: CommRBindingKind::default(),
         };
 
         let data: Vec<u8> = (0..leaves)
@@ -537,7 +573,7 @@ mod tests {
         let comm_rs: Vec<H::Domain> = comm_cs
             .iter()
             .zip(comm_r_lasts.iter())
-            .map(|(comm_c, comm_r_last)| H::Function::hash2(comm_c, comm_r_last))
+            .map(|(comm_c, comm_r_last)| pub_params.comm_r_binding.bind::<H>(comm_c, comm_r_last))
             .collect();
 
         let pub_inputs = PublicInputs {
@@ -565,7 +601,7 @@ mod tests {
         let comm_rs: Vec<H::Domain> = comm_cs
             .iter()
             .zip(comm_r_lasts.iter())
-            .map(|(comm_c, comm_r_last)| H::Function::hash2(comm_c, comm_r_last))
+            .map(|(comm_c, comm_r_last)| pub_params.comm_r_binding.bind::<H>(comm_c, comm_r_last))
             .collect();
 
         let different_pub_inputs = PublicInputs {
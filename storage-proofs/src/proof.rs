@@ -1,5 +1,7 @@
+#[cfg(not(feature = "verify-only"))]
 use std::time::Instant;
 
+#[cfg(not(feature = "verify-only"))]
 use log::info;
 use serde::de::DeserializeOwned;
 use serde::ser::Serialize;
@@ -7,6 +9,8 @@ use serde::ser::Serialize;
 use crate::error::Result;
 
 /// The ProofScheme trait provides the methods that any proof scheme needs to implement.
+///
+/// The proving methods are left out by the `verify-only` feature.
 pub trait ProofScheme<'a> {
     type PublicParams: Clone;
     type SetupParams: Clone;
@@ -19,13 +23,21 @@ pub trait ProofScheme<'a> {
     /// a ProofScheme to the specific parameters required by a consumer.
     fn setup(_: &Self::SetupParams) -> Result<Self::PublicParams>;
 
+    /// validate_setup_params checks the setup parameters for combinations which `setup` or
+    /// proving would only reject later, or not at all. Errors should name the offending field.
+    fn validate_setup_params(_: &Self::SetupParams) -> Result<()> {
+        Ok(())
+    }
+
     /// prove generates and returns a proof from public parameters, public inputs, and private inputs.
+    #[cfg(not(feature = "verify-only"))]
     fn prove(
         _: &Self::PublicParams,
         _: &Self::PublicInputs,
         _: &Self::PrivateInputs,
     ) -> Result<Self::Proof>;
 
+    #[cfg(not(feature = "verify-only"))]
     fn prove_all_partitions(
         pub_params: &Self::PublicParams,
         pub_in: &Self::PublicInputs,
@@ -97,6 +109,16 @@ pub trait ProofScheme<'a> {
     ) -> bool {
         true
     }
+
+    /// check_requirements validates the public inputs against the supplied requirements.
+    /// It is called before any proof verification takes place, so implementations should
+    /// return an error as soon as the inputs are found to be unacceptable.
+    fn check_requirements(
+        _pub_inputs: &Self::PublicInputs,
+        _requirements: &Self::Requirements,
+    ) -> Result<()> {
+        Ok(())
+    }
 }
 
 #[derive(Default)]
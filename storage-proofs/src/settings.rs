@@ -4,6 +4,9 @@ use config::{Config, ConfigError, Environment, File};
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
 
+use crate::crypto::pedersen::DEFAULT_PEDERSEN_WINDOW_SIZE;
+use crate::hasher::blake2s::Blake2sPacking;
+
 lazy_static! {
     pub static ref SETTINGS: Mutex<Settings> =
         Mutex::new(Settings::new().expect("invalid configuration"));
@@ -16,13 +19,15 @@ const SETTINGS_PATH: &str = "./rust-fil-proofs.config.toml";
 pub struct Settings {
     pub maximize_caching: bool,
     pub pedersen_hash_exp_window_size: u32,
+    pub blake2s_packing: Blake2sPacking,
 }
 
 impl Default for Settings {
     fn default() -> Self {
         Settings {
             maximize_caching: false,
-            pedersen_hash_exp_window_size: 16,
+            pedersen_hash_exp_window_size: DEFAULT_PEDERSEN_WINDOW_SIZE,
+            blake2s_packing: Blake2sPacking::default(),
         }
     }
 }
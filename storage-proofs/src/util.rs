@@ -1,9 +1,13 @@
 use anyhow::ensure;
 use bellperson::gadgets::boolean::{self, AllocatedBit, Boolean};
 use bellperson::{ConstraintSystem, SynthesisError};
+use byteorder::{ByteOrder, LittleEndian};
+use ff::PrimeField;
+use paired::bls12_381::{Fr, FrRepr};
 use paired::Engine;
 
 use crate::error;
+use crate::hasher::Hasher;
 
 pub const NODE_SIZE: usize = 32;
 
@@ -24,6 +28,27 @@ pub fn data_at_node(data: &[u8], v: usize) -> error::Result<&[u8]> {
     Ok(&data[offset..offset + NODE_SIZE])
 }
 
+/// Constructs a domain element directly from a borrowed node, e.g. a node inside an mmap'd
+/// replica, without copying it into an intermediate buffer first.
+///
+/// Fails if the bytes are not the canonical little-endian encoding of an `Fr`, i.e. if their
+/// value is not strictly less than the field modulus.
+///
+/// The node is read as four little-endian `u64` limbs using unaligned loads, so `bytes` has no
+/// alignment requirement beyond that of `u8`. Any offset into a mapped replica is valid, though
+/// reads at `NODE_SIZE` boundaries (as returned by `data_at_node`) are the common case.
+pub fn domain_from_bytes_ref<H: Hasher>(bytes: &[u8; NODE_SIZE]) -> error::Result<H::Domain> {
+    let repr = FrRepr([
+        LittleEndian::read_u64(&bytes[0..8]),
+        LittleEndian::read_u64(&bytes[8..16]),
+        LittleEndian::read_u64(&bytes[16..24]),
+        LittleEndian::read_u64(&bytes[24..32]),
+    ]);
+    ensure!(Fr::from_repr(repr).is_ok(), error::Error::BadFrBytes);
+
+    Ok(repr.into())
+}
+
 /// Converts bytes into their bit representation, in little endian format.
 pub fn bytes_into_bits(bytes: &[u8]) -> Vec<bool> {
     bytes
@@ -129,7 +154,10 @@ pub fn bits_to_bytes(bits: &[bool]) -> Vec<u8> {
 mod tests {
     use super::*;
 
+    use crate::fr32::fr_into_bytes;
     use crate::gadgets::TestConstraintSystem;
+    use crate::hasher::{Blake2sHasher, Domain, PedersenHasher, PoseidonHasher, Sha256Hasher};
+    use ff::Field;
     use paired::bls12_381::*;
     use rand::{Rng, SeedableRng};
     use rand_xorshift::XorShiftRng;
@@ -158,6 +186,54 @@ mod tests {
         }
     }
 
+    fn domain_from_bytes_ref_matches_copy<H: Hasher>() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let data: Vec<u8> = (0..16)
+            .flat_map(|_| fr_into_bytes::<Bls12>(&Fr::random(rng)))
+            .collect();
+
+        for i in 0..16 {
+            let node = data_at_node(&data, i).unwrap();
+            let mut node_ref = [0u8; NODE_SIZE];
+            node_ref.copy_from_slice(node);
+
+            let copied = H::Domain::try_from_bytes(node).unwrap();
+            let borrowed = domain_from_bytes_ref::<H>(&node_ref).unwrap();
+            assert_eq!(copied, borrowed);
+        }
+
+        // All bits set is far above the modulus.
+        assert!(domain_from_bytes_ref::<H>(&[0xff; NODE_SIZE]).is_err());
+
+        // The modulus itself is the smallest non-canonical value.
+        let mut modulus = [0u8; NODE_SIZE];
+        for (limb, chunk) in Fr::char().as_ref().iter().zip(modulus.chunks_mut(8)) {
+            LittleEndian::write_u64(chunk, *limb);
+        }
+        assert!(domain_from_bytes_ref::<H>(&modulus).is_err());
+    }
+
+    #[test]
+    fn test_domain_from_bytes_ref_pedersen() {
+        domain_from_bytes_ref_matches_copy::<PedersenHasher>();
+    }
+
+    #[test]
+    fn test_domain_from_bytes_ref_poseidon() {
+        domain_from_bytes_ref_matches_copy::<PoseidonHasher>();
+    }
+
+    #[test]
+    fn test_domain_from_bytes_ref_sha256() {
+        domain_from_bytes_ref_matches_copy::<Sha256Hasher>();
+    }
+
+    #[test]
+    fn test_domain_from_bytes_ref_blake2s() {
+        domain_from_bytes_ref_matches_copy::<Blake2sHasher>();
+    }
+
     #[test]
     fn test_bool_to_u8() {
         assert_eq!(bool_to_u8(false, 2), 0b0000_0000);